@@ -0,0 +1,85 @@
+use crate::{
+    crypto::{
+        elgamal::CompressedCiphertext,
+        hash,
+        Hash,
+        PublicKey,
+        HASH_SIZE
+    },
+    serializer::{Serializer, Writer}
+};
+
+// Path from a leaf up to the root of a merkle tree
+// Each step is the sibling hash, flag is set when the sibling is on the left side
+pub type MerklePath = Vec<(bool, Hash)>;
+
+// Leaf of the balances merkle tree: the encrypted balance of an account for an asset
+pub fn balance_leaf_hash(key: &PublicKey, asset: &Hash, ciphertext: &CompressedCiphertext) -> Hash {
+    let mut writer = Writer::new();
+    key.write(&mut writer);
+    asset.write(&mut writer);
+    ciphertext.write(&mut writer);
+    hash(writer.as_bytes())
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = [0u8; HASH_SIZE * 2];
+    bytes[..HASH_SIZE].copy_from_slice(left.as_bytes());
+    bytes[HASH_SIZE..].copy_from_slice(right.as_bytes());
+    hash(&bytes)
+}
+
+// Build the root of the merkle tree and the path of the leaf at this index
+// Like the merkle builder of the daemon, a node without sibling is hashed with itself
+// Returns None if there is no leaf at this index
+pub fn build_merkle_path(mut level: Vec<Hash>, mut index: usize) -> Option<(Hash, MerklePath)> {
+    if index >= level.len() {
+        return None
+    }
+
+    let mut path = Vec::new();
+    while level.len() > 1 {
+        let sibling = (index ^ 1).min(level.len() - 1);
+        path.push((sibling < index, level[sibling].clone()));
+
+        level = level.chunks(2)
+            .map(|pair| node_hash(&pair[0], &pair[pair.len() - 1]))
+            .collect();
+        index /= 2;
+    }
+
+    Some((level.remove(0), path))
+}
+
+// Compute the root of the merkle tree from a leaf and its path
+pub fn compute_merkle_root(leaf: Hash, path: &[(bool, Hash)]) -> Hash {
+    path.iter().fold(leaf, |current, (left, sibling)| if *left {
+        node_hash(sibling, &current)
+    } else {
+        node_hash(&current, sibling)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_path() {
+        for count in 1..=7u8 {
+            let leaves: Vec<Hash> = (0..count).map(|i| Hash::new([i; 32])).collect();
+            let (root, _) = build_merkle_path(leaves.clone(), 0).unwrap();
+            for (index, leaf) in leaves.iter().enumerate() {
+                let (leaf_root, path) = build_merkle_path(leaves.clone(), index).unwrap();
+                assert_eq!(leaf_root, root);
+                assert_eq!(compute_merkle_root(leaf.clone(), &path), root);
+            }
+            assert!(build_merkle_path(leaves, count as usize).is_none());
+        }
+
+        // Single leaf is the root
+        let (root, path) = build_merkle_path(vec![Hash::zero()], 0).unwrap();
+        assert_eq!(root, Hash::zero());
+        assert!(path.is_empty());
+    }
+}
@@ -1,10 +1,12 @@
 mod balance;
+mod merkle;
 mod nonce;
 
 use std::borrow::Cow;
 use std::fmt::{self, Display, Formatter};
 
 pub use balance::{VersionedBalance, BalanceType};
+pub use merkle::{balance_leaf_hash, build_merkle_path, compute_merkle_root, MerklePath};
 pub use nonce::VersionedNonce;
 use serde::{Serialize, Deserialize};
 use crate::crypto::elgamal::{Ciphertext, CompressedCiphertext, DecompressionError, RISTRETTO_COMPRESSED_SIZE};
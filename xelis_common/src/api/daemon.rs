@@ -6,7 +6,7 @@ use std::{
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize, Serializer, Deserializer, de::Error};
 use crate::{
-    account::{CiphertextCache, VersionedBalance, VersionedNonce},
+    account::{CiphertextCache, MerklePath, VersionedBalance, VersionedNonce},
     block::EXTRA_NONCE_SIZE,
     crypto::{Address, Hash},
     difficulty::{CumulativeDifficulty, Difficulty},
@@ -137,6 +137,28 @@ pub struct GetBalanceAtTopoHeightParams<'a> {
     pub topoheight: u64
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetBalancesMerkleHashParams {
+    pub topoheight: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetBalanceMerkleProofParams<'a> {
+    pub address: Cow<'a, Address>,
+    pub asset: Cow<'a, Hash>,
+    pub topoheight: u64
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetBalanceMerkleProofResult {
+    // Balance version in effect at the requested topoheight
+    pub version: VersionedBalance,
+    // Path from the balance leaf up to the root
+    pub path: MerklePath,
+    // Root of the balances merkle tree at the requested topoheight
+    pub merkle_hash: Hash
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetNonceParams<'a> {
     pub address: Cow<'a, Address>
@@ -90,7 +90,8 @@ pub struct SplitAddressResult {
 
 #[derive(Serialize, Deserialize)]
 pub struct RescanParams {
-    pub until_topoheight: Option<u64>
+    pub until_topoheight: Option<u64>,
+    pub asset: Option<Hash>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -114,6 +115,16 @@ pub struct BalanceChanged {
     pub balance: u64
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SupplyEvent {
+    pub asset: Hash,
+    // Last supply known by the wallet
+    pub old_supply: u64,
+    // Supply observed at the topoheight below
+    pub new_supply: u64,
+    pub topoheight: u64
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetValueFromKeyParams {
     pub tree: String,
@@ -177,6 +188,12 @@ pub enum NotifyEvent {
     Online,
     // Same here
     Offline,
+    // When a tracked asset supply increased above the configured rate
+    // Contains a SupplyEvent as value
+    SupplyAlert,
+    // When a block has been processed while syncing the history
+    // Contains its topoheight as value
+    SyncProgress,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -233,4 +250,13 @@ pub struct TransactionEntry {
     pub topoheight: u64,
     #[serde(flatten)]
     pub entry: EntryType,
+}
+
+// Transaction entry from a merged history of two wallets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergedTransactionEntry {
+    #[serde(flatten)]
+    pub entry: TransactionEntry,
+    // Set when the transaction is present in both histories
+    pub in_both: bool
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use curve25519_dalek::{
     Scalar
 };
 use rand::rngs::OsRng;
+use sha3::{Digest, Sha3_512};
 use zeroize::Zeroize;
 use crate::{
     api::DataElement,
@@ -25,6 +26,9 @@ use super::{
     H
 };
 
+// Domain separator used to derive child private keys
+const CHILD_KEY_DOMAIN: &[u8] = b"xelis-child-key";
+
 #[derive(Clone)]
 pub struct PublicKey(RistrettoPoint);
 
@@ -109,6 +113,29 @@ impl PrivateKey {
         &self.0
     }
 
+    // Derive deterministically the child private key at this index
+    // Only hardened derivation is possible: the parent private key is required
+    // Index 0 is the parent key itself so existing wallets keep their address
+    // Returns None if the derived scalar is zero, this index must be skipped
+    pub fn derive_child(&self, index: u32) -> Option<PrivateKey> {
+        if index == 0 {
+            return Some(self.clone())
+        }
+
+        let mut hasher = Sha3_512::new();
+        hasher.update(CHILD_KEY_DOMAIN);
+        hasher.update(self.0.as_bytes());
+        hasher.update(index.to_be_bytes());
+
+        let hash = hasher.finalize();
+        let scalar = Scalar::from_bytes_mod_order_wide(&hash.try_into().unwrap());
+        if scalar == Scalar::ZERO {
+            return None
+        }
+
+        Some(Self(scalar))
+    }
+
     // Decrypt a Ciphertext to a point
     pub fn decrypt_to_point(&self, ciphertext: &Ciphertext) -> RistrettoPoint {
         let commitment = ciphertext.commitment().as_point();
@@ -218,6 +245,25 @@ mod tests {
         assert!(signature.verify(message, public_key));
     }
 
+    #[test]
+    fn test_derive_child() {
+        let keypair = KeyPair::new();
+        let private_key = keypair.get_private_key();
+
+        // Index 0 is the parent key
+        assert_eq!(private_key.derive_child(0).unwrap().as_scalar(), private_key.as_scalar());
+
+        // Deterministic and different for each index
+        let first = private_key.derive_child(1).unwrap();
+        assert_eq!(first.as_scalar(), private_key.derive_child(1).unwrap().as_scalar());
+        assert_ne!(first.as_scalar(), private_key.as_scalar());
+        assert_ne!(first.as_scalar(), private_key.derive_child(2).unwrap().as_scalar());
+
+        // Different parent keys derive different children
+        let other = KeyPair::new();
+        assert_ne!(first.as_scalar(), other.get_private_key().derive_child(1).unwrap().as_scalar());
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let keypair = KeyPair::new();
@@ -24,6 +24,7 @@ use super::{
         RISTRETTO_COMPRESSED_SIZE,
         SCALAR_SIZE
     },
+    Hash,
     ProtocolTranscript,
     TranscriptError
 };
@@ -355,6 +356,38 @@ impl CiphertextValidityProof {
     }
 }
 
+/// Proof that a ciphertext encrypts a known amount to a public key.
+// This is a commitment equality proof against the commitment of the amount without blinding,
+// so anyone knowing the amount can rebuild it and verify the proof without the private key
+#[derive(Clone, Debug)]
+pub struct AmountProof(CommitmentEqProof);
+
+impl AmountProof {
+    // Context is hashed in the transcript, the proof is only valid for it
+    pub fn new(keypair: &KeyPair, ciphertext: &Ciphertext, amount: u64, context: &Hash) -> Self {
+        let mut transcript = Self::transcript(keypair.get_public_key(), ciphertext, amount, context);
+        let opening = PedersenOpening::from_scalar(Scalar::ZERO);
+        Self(CommitmentEqProof::new(keypair, ciphertext, &opening, amount, &mut transcript))
+    }
+
+    pub fn verify(&self, public_key: &PublicKey, ciphertext: &Ciphertext, amount: u64, context: &Hash) -> Result<(), ProofVerificationError> {
+        let mut transcript = Self::transcript(public_key, ciphertext, amount, context);
+        let commitment = PedersenCommitment::new_with_opening(amount, &PedersenOpening::from_scalar(Scalar::ZERO));
+        let mut batch_collector = BatchCollector::default();
+        self.0.pre_verify(public_key, ciphertext, &commitment, &mut transcript, &mut batch_collector)?;
+        batch_collector.verify().map_err(|_| ProofVerificationError::GenericProof)
+    }
+
+    fn transcript(public_key: &PublicKey, ciphertext: &Ciphertext, amount: u64, context: &Hash) -> Transcript {
+        let mut transcript = Transcript::new(b"amount_proof");
+        transcript.append_public_key(b"public_key", &public_key.compress());
+        transcript.append_ciphertext(b"ciphertext", &ciphertext.compress());
+        transcript.append_u64(b"amount", amount);
+        transcript.append_hash(b"context", context);
+        transcript
+    }
+}
+
 #[allow(non_snake_case)]
 impl Serializer for CommitmentEqProof {
     fn write(&self, writer: &mut Writer) {
@@ -439,6 +472,20 @@ impl Serializer for RangeProof {
     }
 }
 
+impl Serializer for AmountProof {
+    fn write(&self, writer: &mut Writer) {
+        self.0.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(Self(CommitmentEqProof::read(reader)?))
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -512,4 +559,26 @@ mod tests {
         assert!(result.is_ok());
         assert!(batch_collector.verify().is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_amount_proof() {
+        let keypair = KeyPair::new();
+        let ciphertext = keypair.get_public_key().encrypt(100u64);
+        let context = Hash::new([1u8; 32]);
+
+        let proof = AmountProof::new(&keypair, &ciphertext, 100, &context);
+        assert!(proof.verify(keypair.get_public_key(), &ciphertext, 100, &context).is_ok());
+        let decoded = AmountProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(decoded.verify(keypair.get_public_key(), &ciphertext, 100, &context).is_ok());
+
+        // Another amount, ciphertext, key or context is rejected
+        assert!(proof.verify(keypair.get_public_key(), &ciphertext, 101, &context).is_err());
+        assert!(proof.verify(keypair.get_public_key(), &keypair.get_public_key().encrypt(100u64), 100, &context).is_err());
+        assert!(proof.verify(KeyPair::new().get_public_key(), &ciphertext, 100, &context).is_err());
+        assert!(proof.verify(keypair.get_public_key(), &ciphertext, 100, &Hash::zero()).is_err());
+
+        // A proof can't be built for an amount the ciphertext doesn't hold
+        let proof = AmountProof::new(&keypair, &ciphertext, 50, &context);
+        assert!(proof.verify(keypair.get_public_key(), &ciphertext, 50, &context).is_err());
+    }
+}
@@ -186,12 +186,9 @@ impl<T: Serializer + std::hash::Hash + Ord> Serializer for BTreeSet<T> {
         }
     }
 
+    // Elements may have different sizes (strings), so each one is counted
     fn size(&self) -> usize {
-        match self.first() {
-            Some(first) => 2 + self.len() * first.size(),
-            // If the set is empty, we still need to write the size (u16)
-            None => 2
-        }
+        2 + self.iter().map(Serializer::size).sum::<usize>()
     }
 }
 
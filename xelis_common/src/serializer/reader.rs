@@ -15,6 +15,8 @@ pub enum ReaderError {
     InvalidHex,
     #[error("Error on try into")]
     ErrorTryInto,
+    #[error("Checksum mismatch")]
+    ChecksumMismatch,
     #[error(transparent)]
     TryFromSliceError(#[from] TryFromSliceError),
     #[error(transparent)]
@@ -35,6 +35,15 @@ pub struct CipherFormatError;
 /// We never use a key twice, then. We can reuse the same nonce everytime.
 const NONCE: &[u8; 12] = b"xelis-crypto";
 
+/// Size of the Poly1305 tag appended to the encrypted data.
+pub const TAG_SIZE: usize = 16;
+
+/// Serialized size of the cipher for a plaintext of this size.
+/// It includes the u16 length prefix and the tag, this is what counts against the extra data limit.
+pub fn cipher_size(plaintext_size: usize) -> usize {
+    2 + plaintext_size + TAG_SIZE
+}
+
 /// This is the encrypted data, which is the result of the encryption process.
 /// It is a simple wrapper around a vector of bytes.
 /// This doesn't contain the nonce, which is always the same.
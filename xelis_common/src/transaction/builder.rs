@@ -44,7 +44,7 @@ use crate::{
 };
 use thiserror::Error;
 use super::{
-    aead::{derive_aead_key_from_opening, PlaintextData},
+    aead::{cipher_size, derive_aead_key_from_opening, PlaintextData},
     BurnPayload,
     Reference,
     Role,
@@ -74,6 +74,8 @@ pub enum GenerationError<T> {
     InvalidNetwork,
     #[error("Extra data was provied with an integrated address")]
     ExtraDataAndIntegratedAddress,
+    #[error("Nonce overflow")]
+    NonceOverflow,
     #[error("Proof generation error: {0}")]
     Proof(#[from] ProofGenerationError),
 }
@@ -295,8 +297,8 @@ impl TransactionBuilder {
                     + 1;
 
                     if let Some(extra_data) = &transfer.extra_data {
-                        // 2 represents u16 length
-                        size += 2 + extra_data.size();
+                        // u16 length, encrypted data and its tag
+                        size += cipher_size(extra_data.size());
                     }
                 }
                 transfers.len()
@@ -413,7 +415,9 @@ impl TransactionBuilder {
 
         // Get the nonce
         let nonce = state.get_nonce().map_err(GenerationError::State)?;
-        state.update_nonce(nonce + 1).map_err(GenerationError::State)?;
+        // Never wrap the nonce, it would reuse already used nonces
+        let next_nonce = nonce.checked_add(1).ok_or(GenerationError::NonceOverflow)?;
+        state.update_nonce(next_nonce).map_err(GenerationError::State)?;
 
         // 0.a Create the commitments
 
@@ -449,8 +453,9 @@ impl TransactionBuilder {
                     transfer.extra_data = Some(extra_data);
                 }
 
+                // Limit is verified on the encrypted extra data, so count its overhead
                 if let Some(extra_data) = &transfer.extra_data {
-                    extra_data_size += extra_data.size();
+                    extra_data_size += cipher_size(extra_data.size());
                 }
             }
 
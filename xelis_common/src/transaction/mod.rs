@@ -225,6 +225,11 @@ impl Transaction {
         &self.reference
     }
 
+    // Exact size in bytes of the transaction once serialized
+    pub fn serialized_size(&self) -> usize {
+        self.size()
+    }
+
     pub fn consume(self) -> (CompressedPublicKey, TransactionType) {
         (self.source, self.data)
     }
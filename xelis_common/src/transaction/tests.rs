@@ -12,10 +12,11 @@ use crate::{
         PublicKey
     },
     serializer::Serializer,
-    transaction::{TransactionType, MAX_TRANSFER_COUNT}
+    transaction::{TransactionType, EXTRA_DATA_LIMIT_SIZE, MAX_TRANSFER_COUNT}
 };
 use super::{
     aead::{
+        cipher_size,
         derive_aead_key_from_ct,
         derive_aead_key_from_opening,
         PlaintextData
@@ -24,6 +25,7 @@ use super::{
         AccountState,
         FeeBuilder,
         FeeHelper,
+        GenerationError,
         TransactionBuilder,
         TransactionTypeBuilder,
         TransferBuilder
@@ -329,6 +331,112 @@ async fn test_max_transfers() {
     assert!(tx.verify(&mut state).await.is_ok());
 }
 
+#[test]
+fn test_nonce_overflow() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+    alice.nonce = u64::MAX;
+
+    let mut state = AccountStateImpl {
+        balances: alice.balances.clone(),
+        nonce: alice.nonce,
+        reference: Reference {
+            topoheight: 0,
+            hash: Hash::zero(),
+        },
+    };
+
+    let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        amount: 1,
+        destination: bob.address(),
+        asset: XELIS_ASSET,
+        extra_data: None,
+    }]);
+    let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
+    assert!(matches!(builder.build(&mut state, &alice.keypair), Err(GenerationError::NonceOverflow)));
+
+    // Nonce must not wrap to 0
+    assert_eq!(state.nonce, u64::MAX);
+}
+
+#[test]
+fn test_extra_data_limit() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    // Extra data with the exact serialized size requested
+    let extra_data = |size: usize| {
+        let mut values = vec![DataElement::Value(DataValue::String("a".repeat(200))); 4];
+        values.push(DataElement::Value(DataValue::String(String::new())));
+        let padding = size - DataElement::Array(values.clone()).size();
+        values.pop();
+        values.push(DataElement::Value(DataValue::String("a".repeat(padding))));
+        let extra_data = DataElement::Array(values);
+        assert_eq!(extra_data.size(), size);
+        extra_data
+    };
+
+    let build = |extra_data: DataElement| {
+        let mut state = AccountStateImpl {
+            balances: alice.balances.clone(),
+            nonce: alice.nonce,
+            reference: Reference {
+                topoheight: 0,
+                hash: Hash::zero(),
+            },
+        };
+
+        let data = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            amount: 1,
+            destination: bob.address(),
+            asset: XELIS_ASSET,
+            extra_data: Some(extra_data),
+        }]);
+        let builder = TransactionBuilder::new(0, alice.keypair.get_public_key().compress(), data, FeeBuilder::Multiplier(1f64));
+        builder.build(&mut state, &alice.keypair)
+    };
+
+    // Plaintext fits but not its cipher, rejected before encryption
+    assert!(matches!(build(extra_data(EXTRA_DATA_LIMIT_SIZE)), Err(GenerationError::ExtraDataTooLarge)));
+
+    // Cipher fits exactly
+    let max_plaintext = EXTRA_DATA_LIMIT_SIZE - cipher_size(0);
+    let tx = build(extra_data(max_plaintext)).unwrap();
+    let TransactionType::Transfers(transfers) = tx.get_data() else {
+        unreachable!()
+    };
+    assert_eq!(transfers[0].get_extra_data().as_ref().unwrap().size(), EXTRA_DATA_LIMIT_SIZE);
+}
+
+#[test]
+fn test_extra_data_round_trip() {
+    let mut alice = Account::new();
+    let bob = Account::new();
+    alice.set_balance(XELIS_ASSET, 100 * COIN_VALUE);
+
+    let payload = DataElement::Array(vec![
+        DataElement::Value(DataValue::String("invoice".to_owned())),
+        DataElement::Value(DataValue::U64(42)),
+    ]);
+    let tx = create_tx_for(alice, bob.address(), 1, Some(payload.clone()));
+    let TransactionType::Transfers(transfers) = tx.get_data() else {
+        unreachable!()
+    };
+
+    // Only the length prefix and the tag are added by the encryption
+    let cipher = transfers[0].get_extra_data().clone().unwrap();
+    assert_eq!(cipher.size(), cipher_size(payload.size()));
+
+    // Receiver recovers the exact plaintext
+    let bob_ct = transfers[0].get_ciphertext(Role::Receiver).decompress().unwrap();
+    let key = derive_aead_key_from_ct(&bob.keypair.get_private_key(), &bob_ct);
+    let plaintext = cipher.decrypt_in_place(&key).unwrap();
+    assert_eq!(DataElement::from_bytes(&plaintext.0).unwrap(), payload);
+}
+
 #[async_trait]
 impl<'a> BlockchainVerificationState<'a, ()> for ChainState {
 
@@ -10,6 +10,7 @@ use xelis_common::{
     network::Network,
     time::TimestampSeconds,
 };
+use crate::p2p::packet::capabilities::PeerCapabilities;
 
 // In case of potential forks, have a unique network id to not connect to others compatible chains
 pub const NETWORK_ID_SIZE: usize = 16;
@@ -130,10 +131,30 @@ pub const PEER_TEMP_BAN_TIME: u64 = 15 * 60;
 pub const PEER_TIMEOUT_REQUEST_OBJECT: u64 = 15000;
 // millis until we timeout during a bootstrap request
 pub const PEER_TIMEOUT_BOOTSTRAP_STEP: u64 = 60000;
+// attempts for a bootstrap request before switching to another peer
+pub const PEER_BOOTSTRAP_STEP_MAX_ATTEMPTS: u32 = 3;
+// millis to wait before retrying a bootstrap request, doubled at each retry
+pub const PEER_BOOTSTRAP_STEP_RETRY_DELAY: u64 = 2000;
+// balances requests in flight during a bootstrap sync
+pub const PEER_BOOTSTRAP_MAX_BALANCES_REQUESTS: usize = 4;
+// consecutive failed fast syncs before falling back on the full chain sync
+pub const PEER_BOOTSTRAP_MAX_CONSECUTIVE_FAILURES: u32 = 5;
+// millis on the full chain sync before trying to fast sync again, doubled at each new fallback
+pub const PEER_BOOTSTRAP_FALLBACK_RETRY_DELAY: u64 = 300000;
+// additional peers fetching the balances pages in parallel during a bootstrap sync
+pub const PEER_BOOTSTRAP_MAX_HELPERS: usize = 3;
 // millis until we timeout during a handshake
 pub const PEER_TIMEOUT_INIT_CONNECTION: u64 = 5000;
 // 16 additional bytes are for AEAD from ChaCha20Poly1305
 pub const PEER_MAX_PACKET_SIZE: u32 = MAX_BLOCK_SIZE as u32 + 16;
+// Optional features announced to our peers in the capabilities packet
+pub const P2P_CAPABILITIES: PeerCapabilities = PeerCapabilities::COMPRESSION
+    .union(PeerCapabilities::CHECKSUM)
+    .union(PeerCapabilities::CHAIN_DIAGNOSTIC)
+    .union(PeerCapabilities::BALANCES_PAGES);
+// First daemon version able to read the capabilities packet
+// Older nodes close the connection on any unknown packet, so it's never sent to them
+pub const P2P_CAPABILITIES_MIN_VERSION: [u64; 3] = [1, 10, 0];
 // Peer TX cache size
 // This is how many elements are stored in the LRU cache at maximum
 pub const PEER_TX_CACHE_SIZE: usize = 10240;
@@ -53,11 +53,13 @@ use crate::{
         BLOCK_TIME_MILLIS, CHAIN_SYNC_RESPONSE_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS,
         DEFAULT_CACHE_SIZE, DEFAULT_P2P_BIND_ADDRESS, DEFAULT_RPC_BIND_ADDRESS, DEV_FEES,
         DEV_PUBLIC_KEY, EMISSION_SPEED_FACTOR, GENESIS_BLOCK_DIFFICULTY, MAX_BLOCK_SIZE,
-        MILLIS_PER_SECOND, P2P_DEFAULT_MAX_PEERS, SIDE_BLOCK_REWARD_MAX_BLOCKS, PRUNE_SAFETY_LIMIT,
+        MILLIS_PER_SECOND, P2P_DEFAULT_MAX_PEERS, PEER_BOOTSTRAP_STEP_MAX_ATTEMPTS, PEER_TIMEOUT_BOOTSTRAP_STEP,
+        SIDE_BLOCK_REWARD_MAX_BLOCKS, PRUNE_SAFETY_LIMIT,
         SIDE_BLOCK_REWARD_PERCENT, SIDE_BLOCK_REWARD_MIN_PERCENT, STABLE_LIMIT, TIMESTAMP_IN_FUTURE_LIMIT
     },
     core::{
         blockdag,
+        checkpoints::{parse_checkpoint, Checkpoints},
         difficulty,
         error::BlockchainError,
         mempool::Mempool,
@@ -67,7 +69,11 @@ use crate::{
         tx_selector::{TxSelector, TxSelectorEntry},
         state::{ChainState, ApplicableChainState},
     },
-    p2p::P2pServer,
+    p2p::{
+        bootstrap_retry::BootstrapRetryPolicy,
+        packet::bootstrap_chain::TrustedCheckpoint,
+        P2pServer
+    },
     rpc::{
         rpc::{
             get_block_type_for_block,
@@ -91,7 +97,7 @@ use std::{
         atomic::{AtomicU64, Ordering},
         Arc
     },
-    time::Instant
+    time::{Duration, Instant}
 };
 use tokio::sync::{Mutex, RwLock};
 use log::{info, error, debug, warn, trace};
@@ -170,6 +176,31 @@ pub struct Config {
     /// and for high-end devices who want to (or help others to) sync faster.
     #[clap(long)]
     pub max_chain_response_size: Option<usize>,
+    /// Timeout in milliseconds for each fast sync step request.
+    /// 
+    /// A peer not answering in time is asked again after an exponential backoff delay.
+    #[clap(long)]
+    pub fast_sync_step_timeout: Option<u64>,
+    /// Maximum attempts for each fast sync step request.
+    /// 
+    /// Once exhausted, the fast sync continues with another peer.
+    #[clap(long)]
+    pub fast_sync_step_max_attempts: Option<u32>,
+    /// Maximum balances requests in flight during a fast sync.
+    /// 
+    /// Each request covers a chunk of accounts for one asset.
+    #[clap(long)]
+    pub fast_sync_max_balances_requests: Option<usize>,
+    /// Consecutive failed fast syncs before falling back on the full chain sync.
+    /// 
+    /// Any fully validated step resets the failures counter.
+    #[clap(long)]
+    pub fast_sync_max_failures: Option<u32>,
+    /// Additional peers used to fetch the balances in parallel during a fast sync.
+    /// 
+    /// Only peers agreeing on the stable block are used, set it to 0 to sync from a single peer.
+    #[clap(long)]
+    pub fast_sync_max_helpers: Option<usize>,
     /// Ask peers to not share our IP to others and/or through API.
     /// 
     /// This is useful for people that don't want that their IP is revealed in RPC API
@@ -182,7 +213,19 @@ pub struct Config {
     /// 
     /// This is useful for seed nodes under heavy load or for nodes that don't want to connect to others.
     #[clap(long, default_value = "false")]
-    pub disable_outgoing_connections: bool
+    pub disable_outgoing_connections: bool,
+    /// Trusted checkpoints in the format topoheight:hash.
+    /// 
+    /// Blocks at or below the highest checkpoint are synced with a relaxed verification (no PoW check).
+    /// 
+    /// A block with a different hash at a checkpoint topoheight will abort the sync.
+    #[clap(long, value_parser = parse_checkpoint)]
+    pub checkpoints: Vec<(u64, Hash)>,
+    /// Trusted stable block to fast sync from, in the format topoheight:hash.
+    /// 
+    /// Peers that don't agree with it are rejected instead of negotiating a common point.
+    #[clap(long, value_parser = parse_checkpoint)]
+    pub fast_sync_checkpoint: Option<(u64, Hash)>
 }
 
 pub struct Blockchain<S: Storage> {
@@ -218,7 +261,9 @@ pub struct Blockchain<S: Storage> {
     // using base hash, current tip hash and base height, this cache is used to store the DAG order
     full_order_cache: Mutex<LruCache<(Hash, Hash, u64), IndexSet<Hash>>>,
     // auto prune mode if enabled, will delete all blocks every N and keep only N top blocks (topoheight based)
-    auto_prune_keep_n_blocks: Option<u64>
+    auto_prune_keep_n_blocks: Option<u64>,
+    // trusted checkpoints used to relax the verification during sync
+    checkpoints: Checkpoints
 }
 
 impl<S: Storage> Blockchain<S> {
@@ -275,7 +320,8 @@ impl<S: Storage> Blockchain<S> {
             tip_base_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             tip_work_score_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             full_order_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
-            auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks
+            auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks,
+            checkpoints: Checkpoints::new(config.checkpoints)
         };
 
         // include genesis block
@@ -318,7 +364,22 @@ impl<S: Storage> Blockchain<S> {
                 exclusive_nodes.push(addr);
             }
 
-            match P2pServer::new(config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_outgoing_connections) {
+            let mut bootstrap_retry_policy = BootstrapRetryPolicy::new(
+                Duration::from_millis(config.fast_sync_step_timeout.unwrap_or(PEER_TIMEOUT_BOOTSTRAP_STEP)),
+                config.fast_sync_step_max_attempts.unwrap_or(PEER_BOOTSTRAP_STEP_MAX_ATTEMPTS).max(1)
+            );
+            if let Some(max_balances_requests) = config.fast_sync_max_balances_requests {
+                bootstrap_retry_policy.max_balances_requests = max_balances_requests.max(1);
+            }
+            if let Some(max_failures) = config.fast_sync_max_failures {
+                bootstrap_retry_policy.max_consecutive_failures = max_failures.max(1);
+            }
+            if let Some(max_helpers) = config.fast_sync_max_helpers {
+                bootstrap_retry_policy.max_helpers = max_helpers;
+            }
+            let trusted_checkpoint = config.fast_sync_checkpoint.map(|(topoheight, hash)| TrustedCheckpoint::new(topoheight, hash));
+
+            match P2pServer::new(config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_outgoing_connections, bootstrap_retry_policy, trusted_checkpoint) {
                 Ok(p2p) => {
                     // connect to priority nodes
                     for addr in config.priority_nodes {
@@ -599,6 +660,11 @@ impl<S: Storage> Blockchain<S> {
         self.stable_topoheight.load(Ordering::Acquire)
     }
 
+    // Get the trusted checkpoints configured
+    pub fn get_checkpoints(&self) -> &Checkpoints {
+        &self.checkpoints
+    }
+
     // Get the network on which this chain is running
     pub fn get_network(&self) -> &Network {
         &self.network
@@ -1505,6 +1571,19 @@ impl<S: Storage> Blockchain<S> {
 
     // Add a new block in chain using the requested storage
     pub async fn add_new_block_for_storage(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
+        self.add_new_block_with_verification(storage, block, broadcast, mining, true).await
+    }
+
+    // Add a checkpoint block or one of its ancestors proven through the block tips
+    // Its PoW is not verified
+    pub async fn add_new_trusted_block(&self, block: Block) -> Result<(), BlockchainError> {
+        let mut storage = self.storage.write().await;
+        self.add_new_block_with_verification(&mut storage, block, false, false, false).await
+    }
+
+    // Add a new block in chain using the requested storage
+    // PoW verification can only be skipped for blocks covered by a trusted checkpoint
+    async fn add_new_block_with_verification(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool, verify_pow: bool) -> Result<(), BlockchainError> {
         let start = Instant::now();
 
         // Verify that the block is on the correct version
@@ -1614,10 +1693,16 @@ impl<S: Storage> Blockchain<S> {
         }
 
         // verify PoW and get difficulty for this block based on tips
-        let pow_hash = block.get_pow_hash()?;
-        debug!("POW hash: {}", pow_hash);
-        let (difficulty, p) = self.verify_proof_of_work(storage, &pow_hash, block.get_tips().iter()).await?;
-        debug!("PoW is valid for difficulty {}", difficulty);
+        let (difficulty, p) = if verify_pow {
+            let pow_hash = block.get_pow_hash()?;
+            debug!("POW hash: {}", pow_hash);
+            let (difficulty, p) = self.verify_proof_of_work(storage, &pow_hash, block.get_tips().iter()).await?;
+            debug!("PoW is valid for difficulty {}", difficulty);
+            (difficulty, p)
+        } else {
+            debug!("Skipping PoW verification for trusted block {}", block_hash);
+            self.get_difficulty_at_tips(storage, block.get_tips().iter()).await?
+        };
 
         let mut current_topoheight = self.get_topo_height();
         // Transaction verification
@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use indexmap::IndexSet;
+use log::{debug, error};
+use xelis_common::{crypto::Hash, serializer::Serializer};
+use super::error::BlockchainError;
+
+// Trusted checkpoints configured by the node operator
+// A block at a checkpoint topoheight must have the checkpoint hash
+// Such block and its ancestors are synced with a relaxed verification
+#[derive(Debug, Default, Clone)]
+pub struct Checkpoints {
+    checkpoints: BTreeMap<u64, Hash>
+}
+
+impl Checkpoints {
+    pub fn new(checkpoints: Vec<(u64, Hash)>) -> Self {
+        Self {
+            checkpoints: checkpoints.into_iter().collect()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    // Verify the block hash at this topoheight against the checkpoints
+    // Returns true if the block is the one of a checkpoint
+    // A different hash at a checkpoint topoheight is a hard error
+    pub fn verify(&self, topoheight: u64, hash: &Hash) -> Result<bool, BlockchainError> {
+        match self.checkpoints.get(&topoheight) {
+            Some(expected) if expected != hash => {
+                error!("Block {} at topoheight {} doesn't match checkpoint {}", hash, topoheight, expected);
+                Err(BlockchainError::CheckpointMismatch(topoheight, expected.clone(), hash.clone()))
+            },
+            Some(_) => {
+                debug!("Block {} matches checkpoint at topoheight {}", hash, topoheight);
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    }
+}
+
+// Blocks that can be added without verifying their PoW
+// These are the checkpoint blocks and their ancestors reached through the tips of each block,
+// so a peer can't make us skip the PoW of blocks that aren't part of the checkpointed chain
+// `tips` contains the tips of every block that may be trusted
+pub fn trusted_ancestors(checkpoint_blocks: Vec<Hash>, tips: &HashMap<Hash, IndexSet<Hash>>) -> HashSet<Hash> {
+    let mut trusted = HashSet::new();
+    let mut pending = checkpoint_blocks;
+    while let Some(hash) = pending.pop() {
+        if trusted.contains(&hash) {
+            continue;
+        }
+
+        if let Some(block_tips) = tips.get(&hash) {
+            pending.extend(block_tips.iter().filter(|tip| tips.contains_key(*tip)).cloned());
+        }
+        trusted.insert(hash);
+    }
+
+    trusted
+}
+
+// Parse a checkpoint from the format "topoheight:hash"
+pub fn parse_checkpoint(value: &str) -> Result<(u64, Hash), String> {
+    let (topoheight, hash) = value.split_once(':')
+        .ok_or_else(|| format!("Invalid checkpoint '{}', expected format is topoheight:hash", value))?;
+
+    let topoheight = topoheight.parse::<u64>()
+        .map_err(|e| format!("Invalid checkpoint topoheight '{}': {}", topoheight, e))?;
+    let hash = Hash::from_hex(hash.to_owned())
+        .map_err(|e| format!("Invalid checkpoint hash '{}': {}", hash, e))?;
+
+    Ok((topoheight, hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoints() {
+        let checkpoint = Hash::new([1u8; 32]);
+        let checkpoints = Checkpoints::new(vec![(100, checkpoint.clone())]);
+
+        // Matching checkpoint
+        assert!(checkpoints.verify(100, &checkpoint).unwrap());
+        // Below or above the checkpoint, the block must be proven to be an ancestor of it
+        assert!(!checkpoints.verify(50, &Hash::new([2u8; 32])).unwrap());
+        assert!(!checkpoints.verify(101, &Hash::new([2u8; 32])).unwrap());
+
+        // Mismatch aborts
+        assert!(matches!(checkpoints.verify(100, &Hash::new([2u8; 32])), Err(BlockchainError::CheckpointMismatch(100, _, _))));
+
+        // No checkpoint configured
+        assert!(!Checkpoints::default().verify(0, &checkpoint).unwrap());
+    }
+
+    #[test]
+    fn test_trusted_ancestors() {
+        let hash = |i: u8| Hash::new([i; 32]);
+        // Checkpoint block 1 has 2 as tip, which has 3 as tip
+        let mut tips = HashMap::new();
+        tips.insert(hash(1), IndexSet::from([hash(2)]));
+        tips.insert(hash(2), IndexSet::from([hash(3)]));
+        tips.insert(hash(3), IndexSet::from([hash(9)]));
+
+        // Fake chain sent below the checkpoint, not referenced by it
+        tips.insert(hash(4), IndexSet::from([hash(5)]));
+        tips.insert(hash(5), IndexSet::from([hash(3)]));
+
+        let trusted = trusted_ancestors(vec![hash(1)], &tips);
+        assert_eq!(trusted, HashSet::from([hash(1), hash(2), hash(3)]));
+
+        // Without a checkpoint block in the chain, nothing is trusted
+        assert!(trusted_ancestors(Vec::new(), &tips).is_empty());
+    }
+
+    #[test]
+    fn test_parse_checkpoint() {
+        let hash = Hash::new([1u8; 32]);
+        assert_eq!(parse_checkpoint(&format!("100:{}", hash)).unwrap(), (100, hash));
+        assert!(parse_checkpoint("100").is_err());
+        assert!(parse_checkpoint("abc:00").is_err());
+    }
+}
@@ -172,6 +172,8 @@ pub enum BlockchainError {
     ConfigMaxChainResponseSize,
     #[error("Invalid config sync mode")]
     ConfigSyncMode,
+    #[error("Block at topoheight {} doesn't match checkpoint, expected {}, got {}", _0, _1, _2)]
+    CheckpointMismatch(u64, Hash, Hash),
     #[error("Expected at least one tips")]
     ExpectedTips,
     #[error("Block {0} has invalid tips count: {1}")]
@@ -1,6 +1,12 @@
 use std::borrow::Cow;
 
-use xelis_common::{crypto::{hash, Hash, HASH_SIZE}, serializer::Serializer};
+use xelis_common::{
+    account::balance_leaf_hash,
+    crypto::{hash, Hash, PublicKey, HASH_SIZE},
+    serializer::Serializer
+};
+use crate::p2p::packet::bootstrap_chain::MAX_ITEMS_PER_PAGE;
+use super::{error::BlockchainError, storage::Storage};
 
 // This builder is used to build a merkle tree from a list of hashes
 // It uses a bottom-up approach to build the tree
@@ -79,4 +85,78 @@ impl<'a> MerkleBuilder<'a> {
     pub fn verify(&mut self, root: &Hash) -> bool {
         self.build() == *root
     }
-}
\ No newline at end of file
+}
+
+// Leaf of the balances merkle tree with the account and asset it belongs to
+pub type BalanceLeaf = (PublicKey, Hash, Hash);
+
+// Leaves of the balances merkle tree at the topoheight, sorted by account then asset
+// Each leaf is the balance version in effect at the topoheight, collected by pages
+// like the Assets, Keys and Balances steps of the fast sync
+pub async fn get_balances_merkle_leaves<S: Storage>(storage: &S, topoheight: u64) -> Result<Vec<BalanceLeaf>, BlockchainError> {
+    let mut assets = Vec::new();
+    loop {
+        let page = storage.get_partial_assets(MAX_ITEMS_PER_PAGE, assets.len(), 0, topoheight).await?;
+        let last = page.len() < MAX_ITEMS_PER_PAGE;
+        assets.extend(page.into_iter().map(|asset| asset.get_asset().clone()));
+        if last {
+            break;
+        }
+    }
+
+    let mut keys = Vec::new();
+    loop {
+        let page = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, keys.len(), 0, topoheight).await?;
+        let last = page.len() < MAX_ITEMS_PER_PAGE;
+        keys.extend(page);
+        if last {
+            break;
+        }
+    }
+
+    let mut leaves = Vec::new();
+    for asset in assets {
+        for page in keys.chunks(MAX_ITEMS_PER_PAGE) {
+            let balances = storage.get_versioned_balances(&asset, page.iter(), topoheight).await?;
+            for (key, balance) in page.iter().zip(balances) {
+                if let Some(balance) = balance {
+                    let leaf = balance_leaf_hash(key, &asset, &balance.get_balance().compress());
+                    leaves.push((key.clone(), asset.clone(), leaf));
+                }
+            }
+        }
+    }
+    leaves.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()).then_with(|| a.1.cmp(&b.1)));
+
+    Ok(leaves)
+}
+
+// Root of the balances merkle tree, zero if there is no balance
+pub fn build_balances_merkle_hash(leaves: &[BalanceLeaf]) -> Hash {
+    if leaves.is_empty() {
+        return Hash::zero()
+    }
+
+    MerkleBuilder::from_iter(leaves.iter().map(|(_, _, leaf)| leaf)).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{account::{build_merkle_path, compute_merkle_root}, crypto::KeyPair};
+    use super::*;
+
+    #[test]
+    fn test_balances_merkle_hash() {
+        assert_eq!(build_balances_merkle_hash(&[]), Hash::zero());
+
+        let leaves: Vec<BalanceLeaf> = (0..5u8).map(|i| (KeyPair::new().get_public_key().compress(), Hash::new([i; 32]), Hash::new([i + 10; 32]))).collect();
+        let root = build_balances_merkle_hash(&leaves);
+        let hashes: Vec<Hash> = leaves.iter().map(|(_, _, leaf)| leaf.clone()).collect();
+        // Paths given to the wallets lead to the same root
+        for (index, leaf) in hashes.iter().enumerate() {
+            let (path_root, path) = build_merkle_path(hashes.clone(), index).unwrap();
+            assert_eq!(path_root, root);
+            assert_eq!(compute_merkle_root(leaf.clone(), &path), root);
+        }
+    }
+}
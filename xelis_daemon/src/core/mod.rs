@@ -8,4 +8,5 @@ pub mod simulator;
 pub mod nonce_checker;
 pub mod tx_selector;
 pub mod state;
-pub mod merkle;
\ No newline at end of file
+pub mod merkle;
+pub mod checkpoints;
\ No newline at end of file
@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use indexmap::IndexSet;
+use log::trace;
+use xelis_common::serializer::Serializer;
+use crate::{
+    core::{
+        error::BlockchainError,
+        storage::{sled::BLOCKS_METADATA, SledStorage}
+    },
+    p2p::packet::bootstrap_chain::BlockMetadata
+};
+
+// Blocks metadata series saved by the metadata only sync of audit nodes
+#[async_trait]
+pub trait BlocksMetadataProvider {
+    // get the last blocks metadata series synced, stable block first
+    async fn get_blocks_metadata(&self) -> Result<Option<IndexSet<BlockMetadata>>, BlockchainError>;
+
+    // set the blocks metadata series on disk, it replaces the previous one
+    async fn set_blocks_metadata(&mut self, blocks: &IndexSet<BlockMetadata>) -> Result<(), BlockchainError>;
+}
+
+#[async_trait]
+impl BlocksMetadataProvider for SledStorage {
+    async fn get_blocks_metadata(&self) -> Result<Option<IndexSet<BlockMetadata>>, BlockchainError> {
+        trace!("get blocks metadata");
+        self.load_optional_from_disk(&self.extra, BLOCKS_METADATA)
+    }
+
+    async fn set_blocks_metadata(&mut self, blocks: &IndexSet<BlockMetadata>) -> Result<(), BlockchainError> {
+        trace!("set blocks metadata ({} blocks)", blocks.len());
+        self.extra.insert(BLOCKS_METADATA, blocks.to_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::serializer::Serializer;
+use crate::{
+    core::{
+        error::BlockchainError,
+        storage::{sled::BOOTSTRAP_PROGRESS, SledStorage}
+    },
+    p2p::packet::bootstrap_chain::BootstrapProgress
+};
+
+// Fast sync progress, saved after each page to resume it after a restart
+#[async_trait]
+pub trait BootstrapProgressProvider {
+    // get the fast sync progress if one is in progress
+    async fn get_bootstrap_progress(&self) -> Result<Option<BootstrapProgress>, BlockchainError>;
+
+    // set the fast sync progress on disk
+    async fn set_bootstrap_progress(&mut self, progress: &BootstrapProgress) -> Result<(), BlockchainError>;
+
+    // delete the fast sync progress once the sync is done
+    async fn delete_bootstrap_progress(&mut self) -> Result<(), BlockchainError>;
+}
+
+#[async_trait]
+impl BootstrapProgressProvider for SledStorage {
+    async fn get_bootstrap_progress(&self) -> Result<Option<BootstrapProgress>, BlockchainError> {
+        trace!("get bootstrap progress");
+        if !self.extra.contains_key(BOOTSTRAP_PROGRESS)? {
+            return Ok(None)
+        }
+
+        Ok(Some(self.load_from_disk(&self.extra, BOOTSTRAP_PROGRESS)?))
+    }
+
+    async fn set_bootstrap_progress(&mut self, progress: &BootstrapProgress) -> Result<(), BlockchainError> {
+        trace!("set bootstrap progress {:?}", progress);
+        self.extra.insert(BOOTSTRAP_PROGRESS, progress.to_bytes())?;
+        Ok(())
+    }
+
+    async fn delete_bootstrap_progress(&mut self) -> Result<(), BlockchainError> {
+        trace!("delete bootstrap progress");
+        self.extra.remove(BOOTSTRAP_PROGRESS)?;
+        Ok(())
+    }
+}
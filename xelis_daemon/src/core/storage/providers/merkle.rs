@@ -10,6 +10,9 @@ use crate::core::{error::BlockchainError, storage::SledStorage};
 // NOTE: We are based on the topoheight because of DAG reorgs as it's the main consensus
 #[async_trait]
 pub trait MerkleHashProvider {
+    // Check if the merkle hash was stored at a specific topoheight
+    async fn has_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError>;
+
     // Get the merkle hash at a specific topoheight
     async fn get_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError>;
 
@@ -19,6 +22,11 @@ pub trait MerkleHashProvider {
 
 #[async_trait]
 impl MerkleHashProvider for SledStorage {
+    async fn has_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError> {
+        trace!("has merkle hash at topoheight {}", topoheight);
+        Ok(self.merkle_hashes.contains_key(&topoheight.to_bytes())?)
+    }
+
     async fn get_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
         trace!("get merkle hash at topoheight {}", topoheight);
         self.load_from_disk(&self.merkle_hashes, &topoheight.to_bytes())
@@ -11,6 +11,8 @@ mod block;
 mod blockdag;
 mod merkle;
 mod account;
+mod bootstrap_progress;
+mod blocks_metadata;
 
 pub use asset::AssetProvider;
 pub use blocks_at_height::BlocksAtHeightProvider;
@@ -24,4 +26,6 @@ pub use transaction::TransactionProvider;
 pub use block::BlockProvider;
 pub use blockdag::BlockDagProvider;
 pub use merkle::MerkleHashProvider;
-pub use account::AccountProvider;
\ No newline at end of file
+pub use account::AccountProvider;
+pub use bootstrap_progress::BootstrapProgressProvider;
+pub use blocks_metadata::BlocksMetadataProvider;
\ No newline at end of file
@@ -46,6 +46,8 @@ const TOP_TOPO_HEIGHT: &[u8; 4] = b"TOPO";
 const TOP_HEIGHT: &[u8; 4] = b"TOPH";
 const NETWORK: &[u8] = b"NET";
 pub(super) const PRUNED_TOPOHEIGHT: &[u8; 4] = b"PRUN";
+pub(super) const BOOTSTRAP_PROGRESS: &[u8; 4] = b"BOOT";
+pub(super) const BLOCKS_METADATA: &[u8; 4] = b"BMTA";
 // Counters (prevent to perform a O(n))
 pub(super) const ACCOUNTS_COUNT: &[u8; 4] = b"CACC";
 pub(super) const TXS_COUNT: &[u8; 4] = b"CTXS";
@@ -455,6 +457,10 @@ impl Storage for SledStorage {
         let reward: u64 = self.delete_cacheable_data(&self.rewards, &None, &topoheight).await?;
         trace!("Reward for block {} was: {}", hash, reward);
 
+        // Balances merkle hash is only stored once requested at a stable topoheight
+        trace!("Deleting balances merkle hash");
+        self.merkle_hashes.remove(&topoheight.to_bytes())?;
+
         trace!("Deleting difficulty");
         let _: Difficulty = self.delete_cacheable_data(&self.difficulty, &None, &hash).await?;
 
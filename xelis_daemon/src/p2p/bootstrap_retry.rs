@@ -0,0 +1,349 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex
+    },
+    time::{Duration, Instant}
+};
+use log::{debug, info, warn};
+use tokio::{sync::Semaphore, time::sleep};
+use crate::config::{
+    PEER_BOOTSTRAP_FALLBACK_RETRY_DELAY,
+    PEER_BOOTSTRAP_MAX_BALANCES_REQUESTS,
+    PEER_BOOTSTRAP_MAX_CONSECUTIVE_FAILURES,
+    PEER_BOOTSTRAP_MAX_HELPERS,
+    PEER_BOOTSTRAP_STEP_MAX_ATTEMPTS,
+    PEER_BOOTSTRAP_STEP_RETRY_DELAY,
+    PEER_TIMEOUT_BOOTSTRAP_STEP
+};
+use super::{error::P2pError, packet::bootstrap_chain::StepKind};
+
+// Timeout and retries applied to each fast sync step request
+// A peer not answering in time is asked again after an exponential backoff
+// Once all attempts are exhausted, the sync is aborted with this peer so another one can be selected
+#[derive(Debug, Clone)]
+pub struct BootstrapRetryPolicy {
+    // timeout used for steps without a specific one
+    pub default_timeout: Duration,
+    // timeout for specific steps
+    pub step_timeouts: HashMap<StepKind, Duration>,
+    // maximum attempts for a step, including the first one
+    pub max_attempts: u32,
+    // delay before the first retry, doubled at each retry
+    pub initial_backoff: Duration,
+    // maximum balances requests in flight, each one covers up to MAX_BALANCES_PER_PAGE accounts
+    pub max_balances_requests: usize,
+    // consecutive failed fast syncs before falling back on the full chain sync
+    pub max_consecutive_failures: u32,
+    // time spent on the full chain sync before trying to fast sync again, doubled at each new fallback
+    pub fallback_retry_delay: Duration,
+    // additional peers agreeing on the stable block used to fetch the balances pages in parallel
+    pub max_helpers: usize
+}
+
+impl Default for BootstrapRetryPolicy {
+    fn default() -> Self {
+        Self {
+            default_timeout: Duration::from_millis(PEER_TIMEOUT_BOOTSTRAP_STEP),
+            step_timeouts: HashMap::new(),
+            max_attempts: PEER_BOOTSTRAP_STEP_MAX_ATTEMPTS,
+            initial_backoff: Duration::from_millis(PEER_BOOTSTRAP_STEP_RETRY_DELAY),
+            max_balances_requests: PEER_BOOTSTRAP_MAX_BALANCES_REQUESTS,
+            max_consecutive_failures: PEER_BOOTSTRAP_MAX_CONSECUTIVE_FAILURES,
+            fallback_retry_delay: Duration::from_millis(PEER_BOOTSTRAP_FALLBACK_RETRY_DELAY),
+            max_helpers: PEER_BOOTSTRAP_MAX_HELPERS
+        }
+    }
+}
+
+impl BootstrapRetryPolicy {
+    pub fn new(default_timeout: Duration, max_attempts: u32) -> Self {
+        Self {
+            default_timeout,
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    // Semaphore bounding the balances requests in flight
+    pub fn balances_requests_semaphore(&self) -> Semaphore {
+        Semaphore::new(self.max_balances_requests.max(1))
+    }
+
+    // Failures tracker deciding when to stop fast syncing
+    pub fn fast_sync_failures(&self) -> FastSyncFailures {
+        FastSyncFailures::new(self.max_consecutive_failures, self.fallback_retry_delay)
+    }
+
+    pub fn get_timeout(&self, kind: StepKind) -> Duration {
+        self.step_timeouts.get(&kind).copied().unwrap_or(self.default_timeout)
+    }
+
+    // Delay to wait before the retry following this failed attempt (starting at 0)
+    pub fn get_backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff.saturating_mul(2u32.saturating_pow(attempt))
+    }
+
+    // Execute the request with the step timeout until it succeeds or all attempts are exhausted
+    // Only timeouts are retried, any other error is returned directly
+    pub async fn execute<T, F, Fut>(&self, kind: StepKind, mut request: F) -> Result<T, P2pError>
+    where
+        F: FnMut(Duration) -> Fut,
+        Fut: Future<Output = Result<T, P2pError>>
+    {
+        let timeout = self.get_timeout(kind);
+        let mut attempt = 0;
+        loop {
+            match request(timeout).await {
+                Err(P2pError::AsyncTimeOut(_)) => {
+                    attempt += 1;
+                    if attempt >= self.max_attempts {
+                        warn!("Bootstrap step {:?} timed out after {} attempts", kind, attempt);
+                        return Err(P2pError::BootstrapStepRetriesExhausted(kind, attempt))
+                    }
+
+                    let backoff = self.get_backoff(attempt - 1);
+                    debug!("Bootstrap step {:?} timed out, retrying in {:?} ({}/{})", kind, backoff, attempt, self.max_attempts);
+                    sleep(backoff).await;
+                },
+                res => return res
+            }
+        }
+    }
+}
+
+// Execute the request once a permit is available
+// Used to bound the number of requests in flight to a peer
+pub async fn execute_limited<T, Fut>(semaphore: &Semaphore, request: Fut) -> Result<T, P2pError>
+where
+    Fut: Future<Output = Result<T, P2pError>>
+{
+    let _permit = semaphore.acquire().await?;
+    request.await
+}
+
+// Track the consecutive failed fast syncs, whatever the peer used
+// Past the threshold, the caller should switch to the full chain sync instead of retrying forever
+// The fast sync is tried again once the retry delay is elapsed, this delay is doubled at each new fallback
+// The counters are reset by any fully validated step
+pub struct FastSyncFailures {
+    // consecutive failures before falling back
+    threshold: u32,
+    // time on the full chain sync before the first retry
+    retry_delay: Duration,
+    failures: AtomicU32,
+    // consecutive fallbacks, used for the retry backoff
+    fallbacks: AtomicU32,
+    // when we fell back on the full chain sync
+    fallback_at: Mutex<Option<Instant>>
+}
+
+impl FastSyncFailures {
+    pub fn new(threshold: u32, retry_delay: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            retry_delay,
+            failures: AtomicU32::new(0),
+            fallbacks: AtomicU32::new(0),
+            fallback_at: Mutex::new(None)
+        }
+    }
+
+    // Record a failed fast sync
+    // Returns an error once the threshold is reached
+    pub fn record_failure(&self) -> Result<(), P2pError> {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst).saturating_add(1);
+        if failures >= self.threshold {
+            if failures == self.threshold {
+                self.fallbacks.fetch_add(1, Ordering::SeqCst);
+                *self.fallback_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+            }
+            return Err(P2pError::FallbackToFullSync(failures))
+        }
+
+        Ok(())
+    }
+
+    // A step was fully validated, the fast sync is making progress
+    pub fn record_success(&self) {
+        self.failures.store(0, Ordering::SeqCst);
+        self.fallbacks.store(0, Ordering::SeqCst);
+        *self.fallback_at.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    // Time to wait on the full chain sync for the current fallback
+    pub fn get_retry_delay(&self) -> Duration {
+        let fallbacks = self.fallbacks.load(Ordering::SeqCst).max(1);
+        // Capped to 16 times the initial delay
+        self.retry_delay.saturating_mul(1 << (fallbacks - 1).min(4))
+    }
+
+    // Should we stop fast syncing
+    // Once the retry delay is elapsed, the failures are cleared to try the fast sync again
+    pub fn should_fallback(&self) -> bool {
+        if self.failures.load(Ordering::SeqCst) < self.threshold {
+            return false
+        }
+
+        let mut fallback_at = self.fallback_at.lock().unwrap_or_else(|e| e.into_inner());
+        let retry_delay = self.get_retry_delay();
+        match *fallback_at {
+            Some(at) if at.elapsed() < retry_delay => true,
+            _ => {
+                info!("Trying to fast sync again after {:?} on the full chain sync", retry_delay);
+                *fallback_at = None;
+                self.failures.store(0, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+
+    pub fn get_failures(&self) -> u32 {
+        self.failures.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc
+    };
+    use tokio::{task::JoinSet, time::timeout};
+    use super::*;
+
+    // Mock transport answering after the delay for the attempt
+    async fn mock_request(delays: &[u64], attempts: &AtomicU32, limit: Duration) -> Result<u64, P2pError> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+        let delay = delays[attempt as usize];
+        timeout(limit, async move {
+            sleep(Duration::from_millis(delay)).await;
+            delay
+        }).await.map_err(P2pError::from)
+    }
+
+    fn policy() -> BootstrapRetryPolicy {
+        BootstrapRetryPolicy {
+            default_timeout: Duration::from_millis(50),
+            step_timeouts: HashMap::new(),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_balances_requests: 2,
+            max_consecutive_failures: 3,
+            fallback_retry_delay: Duration::from_millis(20),
+            max_helpers: 1
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_stall() {
+        // First attempt stalls, second one answers
+        let attempts = AtomicU32::new(0);
+        let res = policy().execute(StepKind::Balances, |limit| mock_request(&[1000, 0], &attempts, limit)).await;
+        assert_eq!(res.unwrap(), 0);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // Always stalling
+        let attempts = AtomicU32::new(0);
+        let res = policy().execute(StepKind::Balances, |limit| mock_request(&[1000, 1000, 1000], &attempts, limit)).await;
+        assert!(matches!(res, Err(P2pError::BootstrapStepRetriesExhausted(StepKind::Balances, 3))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let mut policy = BootstrapRetryPolicy::default();
+        assert_eq!(policy.get_backoff(0), Duration::from_secs(2));
+        assert_eq!(policy.get_backoff(1), Duration::from_secs(4));
+        assert_eq!(policy.get_backoff(2), Duration::from_secs(8));
+
+        policy.step_timeouts.insert(StepKind::Balances, Duration::from_secs(120));
+        assert_eq!(policy.get_timeout(StepKind::Balances), Duration::from_secs(120));
+        assert_eq!(policy.get_timeout(StepKind::Keys), policy.default_timeout);
+    }
+
+    // Mock transport tracking the requests in flight
+    async fn mock_balances_request(in_flight: &AtomicUsize, max_in_flight: &AtomicUsize) -> Result<(), P2pError> {
+        let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        max_in_flight.fetch_max(current, Ordering::SeqCst);
+        sleep(Duration::from_millis(10)).await;
+        in_flight.fetch_sub(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_balances_requests_limit() {
+        let policy = policy();
+        let semaphore = Arc::new(policy.balances_requests_semaphore());
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let mut requests = JoinSet::new();
+        for _ in 0..16 {
+            let semaphore = Arc::clone(&semaphore);
+            let in_flight = Arc::clone(&in_flight);
+            let max_in_flight = Arc::clone(&max_in_flight);
+            requests.spawn(async move {
+                execute_limited(&semaphore, mock_balances_request(&in_flight, &max_in_flight)).await
+            });
+        }
+
+        while let Some(res) = requests.join_next().await {
+            res.unwrap().unwrap();
+        }
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), policy.max_balances_requests);
+        assert_eq!(BootstrapRetryPolicy::default().max_balances_requests, PEER_BOOTSTRAP_MAX_BALANCES_REQUESTS);
+    }
+
+    #[test]
+    fn test_fallback_to_full_sync() {
+        let failures = policy().fast_sync_failures();
+        assert!(failures.record_failure().is_ok());
+        assert!(failures.record_failure().is_ok());
+        // A validated step resets the counter
+        failures.record_success();
+        assert_eq!(failures.get_failures(), 0);
+
+        // Simulated checksum errors, timeouts and kind mismatches from several peers
+        for _ in 0..2 {
+            assert!(failures.record_failure().is_ok());
+            assert!(!failures.should_fallback());
+        }
+        assert!(matches!(failures.record_failure(), Err(P2pError::FallbackToFullSync(3))));
+        assert!(failures.should_fallback());
+
+        assert_eq!(BootstrapRetryPolicy::default().max_consecutive_failures, PEER_BOOTSTRAP_MAX_CONSECUTIVE_FAILURES);
+    }
+
+    #[test]
+    fn test_fast_sync_retry_after_fallback() {
+        let failures = policy().fast_sync_failures();
+        for _ in 0..3 {
+            let _ = failures.record_failure();
+        }
+        assert!(failures.should_fallback());
+        assert_eq!(failures.get_retry_delay(), Duration::from_millis(20));
+
+        // Fast sync is tried again once the delay is elapsed
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!failures.should_fallback());
+        assert_eq!(failures.get_failures(), 0);
+
+        // Failing again doubles the delay
+        for _ in 0..3 {
+            let _ = failures.record_failure();
+        }
+        assert_eq!(failures.get_retry_delay(), Duration::from_millis(40));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(failures.should_fallback());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!failures.should_fallback());
+
+        // A validated step resets the backoff
+        failures.record_success();
+        assert_eq!(failures.get_retry_delay(), Duration::from_millis(20));
+    }
+}
@@ -313,6 +313,11 @@ impl<S: Storage> PrunedTopoheightProvider for ChainValidator<'_, S> {
 
 #[async_trait]
 impl<S: Storage> MerkleHashProvider for ChainValidator<'_, S> {
+    async fn has_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError> {
+        let storage = self.blockchain.get_storage().read().await;
+        storage.has_balances_merkle_hash_at_topoheight(topoheight).await
+    }
+
     async fn get_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
         let storage = self.blockchain.get_storage().read().await;
         storage.get_balances_merkle_hash_at_topoheight(topoheight).await
@@ -69,6 +69,8 @@ pub enum P2pError {
     InvalidHandshake,
     #[error("Expected Handshake packet")]
     ExpectedHandshake,
+    #[error("Expected Capabilities packet")]
+    ExpectedCapabilities,
     #[error("Invalid peer address, {}", _0)]
     InvalidPeerAddress(String), // peer address from handshake
     #[error("Invalid network")]
@@ -103,6 +105,12 @@ pub enum P2pError {
     RequestSyncChainTooFast,
     #[error(transparent)]
     AsyncTimeOut(#[from] Elapsed),
+    #[error("Bootstrap step {:?} timed out after {} attempts", _0, _1)]
+    BootstrapStepRetriesExhausted(StepKind, u32),
+    #[error("Fast sync failed {} times in a row, falling back on full sync", _0)]
+    FallbackToFullSync(u32),
+    #[error("No peer left to fetch {} remaining pages", _0)]
+    NoPeerForPages(u64),
     #[error("No response received from peer")]
     NoResponse,
     #[error("Invalid object hash, expected: {}, got: {}", _0, _1)]
@@ -139,6 +147,28 @@ pub enum P2pError {
     InvalidPeerlist,
     #[error("Invalid bootstrap chain step, expected {:?}, got {:?}", _0, _1)]
     InvalidBootstrapStep(StepKind, StepKind),
+    #[error("Bootstrap chain response for step {:?} is above its budget of {} bytes", _0, _1)]
+    StepResponseTooBig(StepKind, usize),
+    #[error("Stable block {} announced in chain info doesn't match block {} from blocks metadata", _0, _1)]
+    BootstrapStableBlockMismatch(Hash, Hash),
+    #[error("Peer can't continue the fast sync pinned on block {} at topoheight {}", _1, _0)]
+    BootstrapPivotMismatch(u64, Hash),
+    #[error("Peer doesn't agree with the trusted checkpoint {} at topoheight {}", _1, _0)]
+    CheckpointMismatch(u64, Hash),
+    #[error("No common point found with peer (genesis {}, lowest topoheight {})", _0, _1)]
+    NoCommonPoint(Hash, u64),
+    #[error("Too many pages announced for bootstrap step {:?}", kind)]
+    TooManyPages {
+        kind: StepKind
+    },
+    #[error("Bootstrap step {:?} (page {:?}) failed with peer {}: {}", kind, page, peer, source)]
+    BootstrapStep {
+        peer: u64,
+        kind: StepKind,
+        page: Option<u64>,
+        #[source]
+        source: Box<P2pError>
+    },
     #[error("Error while serde JSON: {}", _0)]
     JsonError(#[from] serde_json::Error),
     #[error(transparent)]
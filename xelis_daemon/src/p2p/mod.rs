@@ -4,6 +4,8 @@ pub mod error;
 pub mod packet;
 pub mod peer_list;
 pub mod chain_validator;
+pub mod bootstrap_retry;
+pub mod page_scheduler;
 mod tracker;
 mod encryption;
 
@@ -36,24 +38,51 @@ use crate::{
         CHAIN_SYNC_DEFAULT_RESPONSE_BLOCKS, CHAIN_SYNC_DELAY, CHAIN_SYNC_REQUEST_EXPONENTIAL_INDEX_START,
         CHAIN_SYNC_REQUEST_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS, CHAIN_SYNC_TOP_BLOCKS, PEER_MAX_PACKET_SIZE,
         MILLIS_PER_SECOND, NETWORK_ID, P2P_EXTEND_PEERLIST_DELAY, P2P_PING_DELAY, P2P_PING_PEER_LIST_DELAY, P2P_PING_PEER_LIST_LIMIT,
-        PEER_FAIL_LIMIT, PEER_TIMEOUT_INIT_CONNECTION, PRUNE_SAFETY_LIMIT, STABLE_LIMIT
+        PEER_FAIL_LIMIT, PEER_TIMEOUT_INIT_CONNECTION, P2P_CAPABILITIES, PRUNE_SAFETY_LIMIT, STABLE_LIMIT
     },
     core::{
         blockchain::Blockchain,
+        checkpoints::trusted_ancestors,
         error::BlockchainError,
         storage::Storage
     },
     p2p::{
+        bootstrap_retry::{execute_limited, BootstrapRetryPolicy, FastSyncFailures},
         chain_validator::ChainValidator,
         connection::ConnectionMessage,
+        page_scheduler::fetch_pages,
         packet::{
             bootstrap_chain::{
+                balances_page,
+                balances_page_size,
+                balances_pages,
+                is_block_hash_at_available,
+                step_error,
+                verify_next_page,
+                resume_step_request,
+                rewind_target,
+                validate_balances_response,
+                zip_nonces,
+                verify_stable_block,
+                BalanceEntry,
                 BlockMetadata,
+                BootstrapCursor,
+                ChainDiagnostic,
+                CommonPointCache,
                 BootstrapChainResponse,
+                CompressedBootstrapChainResponse,
+                StepKind,
+                SyncReport,
                 StepRequest,
                 StepResponse,
+                TrustedCheckpoint,
+                is_shortcut_accepted,
+                shortcut_chain_info_request,
+                sort_keys,
+                MAX_ACCOUNTS_PER_REQUEST,
                 MAX_ITEMS_PER_PAGE
             },
+            capabilities::{is_capabilities_supported, PeerCapabilities},
             chain::CommonPoint,
             inventory::{
                 NotifyInventoryRequest,
@@ -86,7 +115,8 @@ use tokio::{
             self, UnboundedSender, UnboundedReceiver,
             Sender, Receiver, unbounded_channel
         },
-        Mutex
+        Mutex,
+        Semaphore
     },
     select,
     task::JoinHandle,
@@ -101,7 +131,7 @@ use std::{
         Arc,
         atomic::{AtomicBool, Ordering}
     },
-    collections::{hash_map::Entry, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet},
     convert::TryInto,
     net::{IpAddr, SocketAddr},
     time::Duration,
@@ -163,10 +193,20 @@ pub struct P2pServer<S: Storage> {
     outgoing_connections_disabled: AtomicBool,
     // Are we syncing the chain with another peer
     is_syncing: AtomicBool,
+    // Timeout and retries for each fast sync step
+    bootstrap_retry_policy: BootstrapRetryPolicy,
+    // Bound the balances requests in flight during a fast sync
+    balances_requests: Semaphore,
+    // Consecutive failed fast syncs, used to fallback on the full chain sync
+    fast_sync_failures: FastSyncFailures,
+    // Last common point agreed with each peer during a fast sync
+    common_points: Mutex<CommonPointCache>,
+    // Stable block peers must agree with to fast sync with them
+    trusted_checkpoint: Option<TrustedCheckpoint>,
 }
 
 impl<S: Storage> P2pServer<S> {
-    pub fn new(dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool) -> Result<Arc<Self>, P2pError> {
+    pub fn new(dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool, bootstrap_retry_policy: BootstrapRetryPolicy, trusted_checkpoint: Option<TrustedCheckpoint>) -> Result<Arc<Self>, P2pError> {
         if let Some(tag) = &tag {
             debug_assert!(tag.len() > 0 && tag.len() <= 16);
         }
@@ -206,6 +246,11 @@ impl<S: Storage> P2pServer<S> {
             sharable,
             is_syncing: AtomicBool::new(false),
             outgoing_connections_disabled: AtomicBool::new(disable_outgoing_connections),
+            balances_requests: bootstrap_retry_policy.balances_requests_semaphore(),
+            fast_sync_failures: bootstrap_retry_policy.fast_sync_failures(),
+            bootstrap_retry_policy,
+            common_points: Mutex::new(CommonPointCache::new(NonZeroUsize::new(max_peers.max(1)).unwrap())),
+            trusted_checkpoint
         };
 
         let arc = Arc::new(server);
@@ -493,7 +538,7 @@ impl<S: Storage> P2pServer<S> {
         };
         trace!("received handshake packet!");
         connection.set_state(State::Handshake);
-        let peer = self.verify_handshake(connection, handshake, priority).await?;
+        let mut peer = self.verify_handshake(connection, handshake, priority).await?;
         trace!("Handshake has been verified");
         // if it's a outgoing connection, don't send the handshake back
         // because we have already sent it
@@ -502,6 +547,11 @@ impl<S: Storage> P2pServer<S> {
             self.send_handshake(peer.get_connection()).await?;
         }
 
+        // Older nodes can't read the capabilities packet, no optional feature is used with them
+        if is_capabilities_supported(peer.get_version()) {
+            self.exchange_capabilities(buf, &mut peer).await?;
+        }
+
         // if we reach here, handshake is all good, we can start listening this new peer
         // we can save the peer in our peerlist
         let peer_id = peer.get_id(); // keep in memory the peer_id outside connection (because of moved value)
@@ -566,6 +616,22 @@ impl<S: Storage> P2pServer<S> {
         Ok(connection)
     }
 
+    // Send our capabilities to the peer and wait on its own ones
+    // Both sides send them right after the handshake, before any other packet
+    async fn exchange_capabilities(&self, buf: &mut [u8], peer: &mut Peer) -> Result<(), P2pError> {
+        trace!("Sending capabilities to {}", peer);
+        peer.get_connection().send_bytes(&Packet::Capabilities(P2P_CAPABILITIES).to_bytes()).await?;
+
+        let capabilities = match timeout(Duration::from_millis(PEER_TIMEOUT_INIT_CONNECTION), peer.get_connection().read_packet(buf, buf.len() as u32)).await?? {
+            Packet::Capabilities(capabilities) => capabilities,
+            _ => return Err(P2pError::ExpectedCapabilities)
+        };
+        debug!("{} announced capabilities {:?}", peer, capabilities);
+        peer.set_capabilities(capabilities);
+
+        Ok(())
+    }
+
     // Send a handshake to a connection (this is used to determine if its a potential peer)
     // Handsake is sent only once, when we connect to a new peer, and we get it back from connection to make it a peer
     async fn send_handshake(&self, connection: &Connection) -> Result<(), P2pError> {
@@ -616,8 +682,33 @@ impl<S: Storage> P2pServer<S> {
     // he have the blocks we need
     async fn select_random_best_peer(&self, fast_sync: bool, previous_peer: Option<&(Arc<Peer>, bool)>) -> Result<Option<Arc<Peer>>, BlockchainError> {
         trace!("select random best peer");
+        let mut peers = self.find_sync_peers(fast_sync).await?;
+
+        // Try to not reuse the same peer between each sync
+        if let Some((previous_peer, err)) = previous_peer {
+            if peers.len() > 1 || *err {
+                debug!("removing previous peer {} from random selection, err: {}", previous_peer, err);
+                // We don't need to preserve the order
+                peers.swap_remove(previous_peer);
+            }
+        }
+
+        let count = peers.len();
+        trace!("peers available for random selection: {}", count);
+        if count == 0 {
+            return Ok(None)
+        }
+
+        let selected = rand::thread_rng().gen_range(0..count);
+        Ok(peers.swap_remove_index(selected))
+    }
+
+    // Search all the peers we can sync from, see select_random_best_peer for the rules applied
+    // Arcs are cloned to prevent the lock until the end of the sync request
+    async fn find_sync_peers(&self, fast_sync: bool) -> Result<IndexSet<Arc<Peer>>, BlockchainError> {
+        trace!("find sync peers");
         let peer_list = self.peer_list.read().await;
-        trace!("peer list locked for select random best peer");
+        trace!("peer list locked for find sync peers");
         let our_height = self.blockchain.get_height();
         let our_topoheight = self.blockchain.get_topo_height();
 
@@ -633,7 +724,7 @@ impl<S: Storage> P2pServer<S> {
         // and that are pruned but before our height so we can sync correctly
         let available_peers = peer_list.get_peers().values();
         // IndexSet is used to select by random index
-        let mut peers: IndexSet<&Arc<Peer>> = IndexSet::with_capacity(available_peers.len());
+        let mut peers: IndexSet<Arc<Peer>> = IndexSet::with_capacity(available_peers.len());
 
         for p in available_peers {
             // Avoid selecting peers that have a weaker cumulative difficulty than us
@@ -670,27 +761,10 @@ impl<S: Storage> P2pServer<S> {
                 continue;
             }
 
-            peers.insert(p);
-        }
-
-        // Try to not reuse the same peer between each sync
-        if let Some((previous_peer, err)) = previous_peer {
-            if peers.len() > 1 || *err {
-                debug!("removing previous peer {} from random selection, err: {}", previous_peer, err);
-                // We don't need to preserve the order
-                peers.swap_remove(previous_peer);
-            }
-        }
-
-        let count = peers.len();
-        trace!("peers available for random selection: {}", count);
-        if count == 0 {
-            return Ok(None)
+            peers.insert(Arc::clone(p));
         }
 
-        let selected = rand::thread_rng().gen_range(0..count);
-        // clone the Arc to prevent the lock until the end of the sync request
-        Ok(peers.swap_remove_index(selected).map(|p| Arc::clone(p)))
+        Ok(peers)
     }
 
     // Check if user has allowed fast sync mode
@@ -738,7 +812,7 @@ impl<S: Storage> P2pServer<S> {
             // first we have to check if we allow fast sync mode
             // and then we check if we have a potential peer above us to fast sync
             // otherwise we sync normally 
-            let fast_sync = if self.allow_fast_sync() {
+            let fast_sync = if self.allow_fast_sync() && !self.fast_sync_failures.should_fallback() {
                 trace!("locking peer list for fast sync check");
                 let peerlist = self.peer_list.read().await;
                 trace!("peer list locked for fast sync check");
@@ -767,8 +841,15 @@ impl<S: Storage> P2pServer<S> {
                 // check if we can maybe fast sync first
                 // otherwise, fallback on the normal chain sync
                 let err = if fast_sync {
-                    if let Err(e) = self.bootstrap_chain(&peer).await {
-                        warn!("Error occured while fast syncing with {}: {}", peer, e);
+                    // The other peers able to fast sync take over if the selected one fails
+                    let mut peers = vec![Arc::clone(&peer)];
+                    match self.find_sync_peers(true).await {
+                        Ok(others) => peers.extend(others.into_iter().filter(|p| p.get_id() != peer.get_id())),
+                        Err(e) => warn!("Error while searching failover peers for fast sync: {}", e)
+                    }
+
+                    if let Err(e) = self.bootstrap_chain_from_peers(&peers).await {
+                        warn!("Error occured while fast syncing: {}", e);
                         true
                     } else {
                         false
@@ -1146,6 +1227,11 @@ impl<S: Storage> P2pServer<S> {
                 peer.get_connection().close().await?;
                 return Err(P2pError::InvalidPacket)
             },
+            Packet::Capabilities(_) => {
+                error!("{} sent us capabilities packet after the connection setup (not valid!)", peer);
+                peer.get_connection().close().await?;
+                return Err(P2pError::InvalidPacket)
+            },
             Packet::KeyExchange(key) => {
                 trace!("{}: Rotate key packet", peer);
                 let key = key.into_owned();
@@ -1487,20 +1573,32 @@ impl<S: Storage> P2pServer<S> {
                 }
             },
             Packet::BootstrapChainRequest(request) => {
-                self.handle_bootstrap_chain_request(peer, request.step()).await?;
+                let step = request.decode(peer.supports(PeerCapabilities::BALANCES_PAGES))?;
+                self.handle_bootstrap_chain_request(peer, step).await?;
             },
             Packet::BootstrapChainResponse(response) => {
-                debug!("Received a bootstrap chain response ({:?}) from {}", response.kind(), peer);
-                if let Some(sender) = peer.get_bootstrap_chain_channel().lock().await.take() {
-                    trace!("Sending bootstrap chain response ({:?})", response.kind());
-                    let response = response.response();
-                    if let Err(e) = sender.send(response) {
-                        error!("Error while sending bootstrap response to channel: {:?}", e.kind());
-                    }
-                } else {
-                    debug!("{} send us a bootstrap chain response of step {:?} but we didn't asked it", peer, response.kind());
+                // The response is decoded within the budget of the step we requested
+                let Some(kind) = peer.get_requested_bootstrap_step().await else {
+                    debug!("{} send us a bootstrap chain response but we didn't asked it", peer);
+                    return Err(P2pError::UnrequestedBootstrapChainResponse)
+                };
+                let response = response.decode(kind, peer.supports(PeerCapabilities::CHECKSUM), peer.supports(PeerCapabilities::BALANCES_PAGES))?;
+                self.handle_bootstrap_chain_response(peer, response).await?;
+            },
+            Packet::CompressedBootstrapChainResponse(response) => {
+                if !peer.supports(PeerCapabilities::COMPRESSION) {
+                    debug!("{} send us a compressed bootstrap chain response but compression wasn't negotiated", peer);
                     return Err(P2pError::UnrequestedBootstrapChainResponse)
                 }
+
+                // The response is decompressed within the budget of the step we requested
+                let Some(kind) = peer.get_requested_bootstrap_step().await else {
+                    debug!("{} send us a compressed bootstrap chain response but we didn't asked it", peer);
+                    return Err(P2pError::UnrequestedBootstrapChainResponse)
+                };
+                let balances_pages = peer.supports(PeerCapabilities::BALANCES_PAGES);
+                let response = response.decompress(kind, balances_pages)?.decode(kind, peer.supports(PeerCapabilities::CHECKSUM), balances_pages)?;
+                self.handle_bootstrap_chain_response(peer, response).await?;
             },
             Packet::PeerDisconnected(packet) => {
                 // This packet is used to keep sync between peers being shared
@@ -1563,7 +1661,8 @@ impl<S: Storage> P2pServer<S> {
         };
 
         // Verify we have the same genesis block hash
-        if let Some(genesis_id) = blocks.last() {
+        // A single block id is a common point cached from a previous sync, it's verified below
+        if let Some(genesis_id) = blocks.last().filter(|_| blocks.len() > 1) {
             let our_genesis_hash = storage.get_hash_at_topo_height(0).await?;
             if *genesis_id.get_hash() != our_genesis_hash || genesis_id.get_topoheight() > start_topoheight {
                 warn!("Block id list has incorrect block genesis hash! Got {} at {}", genesis_id.get_hash(), genesis_id.get_topoheight());
@@ -1729,6 +1828,43 @@ impl<S: Storage> P2pServer<S> {
         let (mut blocks, top_blocks) = response.consume();
         debug!("handling chain response from {}, {} blocks, {} top blocks, pop count {}", peer, blocks.len(), top_blocks.len(), pop_count);
 
+        // Verify the blocks against our checkpoints before requesting any of them
+        // Blocks are ordered by topoheight starting after the common point
+        // Top blocks are not ordered and are always fully verified
+        let mut trusted_blocks = HashSet::new();
+        let checkpoints = self.blockchain.get_checkpoints();
+        if !checkpoints.is_empty() {
+            let mut checkpoint_blocks = Vec::new();
+            let mut last_checkpoint_index = None;
+            for (i, hash) in blocks.iter().enumerate() {
+                if checkpoints.verify(common_topoheight + 1 + i as u64, hash)? {
+                    checkpoint_blocks.push(hash.clone());
+                    last_checkpoint_index = Some(i);
+                }
+            }
+
+            // Only the ancestors of a checkpoint block can skip their PoW
+            // They are proven by walking back the tips of the block headers, each header being verified against its hash
+            if let Some(last) = last_checkpoint_index {
+                let mut tips = HashMap::new();
+                for hash in blocks.iter().take(last + 1) {
+                    if self.blockchain.has_block(hash).await? {
+                        continue;
+                    }
+
+                    let response = peer.request_blocking_object(ObjectRequest::BlockHeader(hash.clone())).await?;
+                    let OwnedObjectResponse::BlockHeader(header, hash) = response else {
+                        error!("{} sent us an invalid object response", peer);
+                        return Err(P2pError::ExpectedBlock.into())
+                    };
+                    tips.insert(hash, header.get_tips().clone());
+                }
+
+                trusted_blocks = trusted_ancestors(checkpoint_blocks, &tips);
+                debug!("{} blocks from {} are covered by a checkpoint", trusted_blocks.len(), peer);
+            }
+        }
+
         let our_previous_topoheight = self.blockchain.get_topo_height();
         let our_previous_height = self.blockchain.get_height();
         let top_len = top_blocks.len();
@@ -1863,7 +1999,11 @@ impl<S: Storage> P2pServer<S> {
                         let response = peer.request_blocking_object(ObjectRequest::Block(hash)).await?;
                         if let OwnedObjectResponse::Block(block, hash) = response {
                             trace!("Received block {} at height {} from {}", hash, block.get_height(), peer);
-                            self.blockchain.add_new_block(block, false, false).await?;
+                            if trusted_blocks.contains(&hash) {
+                                self.blockchain.add_new_trusted_block(block).await?;
+                            } else {
+                                self.blockchain.add_new_block(block, false, false).await?;
+                            }
                         } else {
                             error!("{} sent us an invalid block response", peer);
                             return Err(P2pError::ExpectedBlock.into())
@@ -2097,6 +2237,19 @@ impl<S: Storage> P2pServer<S> {
         peer_list.broadcast(packet).await;
     }
 
+    // Log why no common point was found with the peer using its chain diagnostic
+    fn diagnose_no_common_point(&self, peer: &Arc<Peer>, diagnostic: ChainDiagnostic, our_topoheight: u64) -> P2pError {
+        if diagnostic.is_other_network(get_genesis_block_hash(self.blockchain.get_network())) {
+            warn!("No common point with {}: its genesis block {} is not ours, it's on another network", peer, diagnostic.get_genesis_hash());
+        } else if diagnostic.is_pruned_past(our_topoheight) {
+            warn!("No common point with {}: it is pruned at topoheight {} while we are at {}", peer, diagnostic.get_lowest_topoheight(), our_topoheight);
+        } else {
+            warn!("No common point with {} ! Not same chain ?", peer);
+        }
+
+        diagnostic.into_error()
+    }
+
     // Handle a bootstrap chain request
     // We have differents steps available for a bootstrap sync
     // We verify that they are send in good order
@@ -2128,10 +2281,17 @@ impl<S: Storage> P2pServer<S> {
         let response = match request {
             StepRequest::ChainInfo(blocks) => {
                 let common_point = self.find_common_point(&*storage, blocks).await?;
-                let tips = storage.get_tips().await?;
-                let (hash, height) = self.blockchain.find_common_base::<S, _>(&storage, &tips).await?;
-                let stable_topo = storage.get_topo_height_for_hash(&hash).await?;
-                StepResponse::ChainInfo(common_point, stable_topo, height, hash)
+                if common_point.is_none() && peer.supports(PeerCapabilities::CHAIN_DIAGNOSTIC) {
+                    debug!("No common point found with {}, sending chain diagnostic", peer);
+                    let genesis_hash = get_genesis_block_hash(self.blockchain.get_network()).clone();
+                    let lowest_topoheight = storage.get_pruned_topoheight().await?.unwrap_or(0);
+                    StepResponse::NoCommonPoint(ChainDiagnostic::new(genesis_hash, lowest_topoheight))
+                } else {
+                    let tips = storage.get_tips().await?;
+                    let (hash, height) = self.blockchain.find_common_base::<S, _>(&storage, &tips).await?;
+                    let stable_topo = storage.get_topo_height_for_hash(&hash).await?;
+                    StepResponse::ChainInfo(common_point, stable_topo, height, hash)
+                }
             },
             StepRequest::Assets(min, max, page) => {
                 if min > max {
@@ -2148,14 +2308,30 @@ impl<S: Storage> P2pServer<S> {
                 };
                 StepResponse::Assets(assets, page)
             },
-            StepRequest::Balances(topoheight, asset, keys) => {
-                let balances = storage.get_versioned_balances(&asset, keys.iter(), topoheight).await?;
-                StepResponse::Balances(balances.into_iter().map(|v| {
-                    v.map(|v| {
-                        let (balance, output_balance, balance_type, _) = v.consume();
-                        (balance, output_balance, balance_type)
-                    })
-                }).collect())
+            StepRequest::Balances(topoheight, asset, keys, page) => {
+                let balances_pages = peer.supports(PeerCapabilities::BALANCES_PAGES);
+                let page_size = balances_page_size(balances_pages);
+                let Some((range, next_page)) = balances_page(keys.len(), page, page_size) else {
+                    warn!("Invalid balances page {:?} for {} keys", page, keys.len());
+                    return Err(P2pError::InvalidPacket.into())
+                };
+
+                let page_keys = keys.iter().skip(range.start).take(range.len());
+                let balances = storage.get_versioned_balances(&asset, page_keys.clone(), topoheight).await?;
+                let mut entries = Vec::with_capacity(balances.len());
+                for (key, balance) in page_keys.zip(balances) {
+                    let entry = match balance {
+                        Some(balance) => {
+                            let (balance, output_balance, balance_type, _) = balance.consume();
+                            BalanceEntry::Present(balance, output_balance, balance_type)
+                        },
+                        // registration at the requested topoheight is included
+                        None if storage.is_account_registered_below_topoheight(key, topoheight.saturating_add(1)).await? => BalanceEntry::Missing,
+                        None => BalanceEntry::unregistered(balances_pages)
+                    };
+                    entries.push(entry);
+                }
+                StepResponse::Balances(entries, next_page)
             },
             StepRequest::Nonces(topoheight, keys) => {
                 let mut nonces = Vec::with_capacity(keys.len());
@@ -2172,7 +2348,8 @@ impl<S: Storage> P2pServer<S> {
                 }
 
                 let page = page.unwrap_or(0);
-                let keys = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, page as usize * MAX_ITEMS_PER_PAGE, min, max).await?;
+                let mut keys = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, page as usize * MAX_ITEMS_PER_PAGE, min, max).await?;
+                sort_keys(&mut keys);
                 let page = if keys.len() == MAX_ITEMS_PER_PAGE {
                     Some(page + 1)
                 } else {
@@ -2201,8 +2378,45 @@ impl<S: Storage> P2pServer<S> {
                 }
                 StepResponse::BlocksMetadata(blocks)
             },
+            StepRequest::BlockHashAt(topoheight) => {
+                let stable_topoheight = self.blockchain.get_stable_topoheight();
+                let hash = if is_block_hash_at_available(topoheight, storage.get_pruned_topoheight().await?, stable_topoheight) {
+                    Some(storage.get_hash_at_topo_height(topoheight).await?)
+                } else {
+                    debug!("Requested block hash at topoheight {} is not in stable range (stable topoheight: {})", topoheight, stable_topoheight);
+                    None
+                };
+                StepResponse::BlockHashAt(hash)
+            },
+        };
+        let balances_pages = peer.supports(PeerCapabilities::BALANCES_PAGES);
+        let response = if peer.supports(PeerCapabilities::CHECKSUM) {
+            BootstrapChainResponse::with_checksum(&response, balances_pages)
+        } else {
+            BootstrapChainResponse::new(&response, balances_pages)
         };
-        peer.send_packet(Packet::BootstrapChainResponse(BootstrapChainResponse::new(response))).await?;
+        if peer.supports(PeerCapabilities::COMPRESSION) {
+            trace!("Sending compressed bootstrap chain response to {}", peer);
+            peer.send_packet(Packet::CompressedBootstrapChainResponse(CompressedBootstrapChainResponse::compress(&response)?)).await?;
+        } else {
+            peer.send_packet(Packet::BootstrapChainResponse(response)).await?;
+        }
+        Ok(())
+    }
+
+    // Forward the bootstrap chain response to the pending request of the peer
+    async fn handle_bootstrap_chain_response(&self, peer: &Arc<Peer>, response: StepResponse) -> Result<(), P2pError> {
+        debug!("Received a bootstrap chain response ({:?}) from {}", response.kind(), peer);
+        if let Some(sender) = peer.get_bootstrap_chain_channel().lock().await.take() {
+            trace!("Sending bootstrap chain response ({:?})", response.kind());
+            if let Err(e) = sender.send(response) {
+                error!("Error while sending bootstrap response to channel: {:?}", e.kind());
+            }
+        } else {
+            debug!("{} send us a bootstrap chain response of step {:?} but we didn't asked it", peer, response.kind());
+            return Err(P2pError::UnrequestedBootstrapChainResponse)
+        }
+
         Ok(())
     }
 
@@ -2236,21 +2450,81 @@ impl<S: Storage> P2pServer<S> {
         Ok(blocks)
     }
 
+    // Restore the fast sync progress saved before a restart
+    async fn load_bootstrap_cursor(&self) -> Result<BootstrapCursor, BlockchainError> {
+        let storage = self.blockchain.get_storage().read().await;
+        Ok(match storage.get_bootstrap_progress().await? {
+            Some(progress) => {
+                info!("Found fast sync progress at step {:?} for stable topoheight {}", progress.step, progress.pivot_topoheight);
+                BootstrapCursor::from_progress(progress, self.blockchain.get_topo_height())
+            },
+            None => BootstrapCursor::new()
+        })
+    }
+
+    // Fast sync using the peers in order, moving to the next one on any failure (timeout, invalid response...)
+    // The progress made with a failing peer is kept and the next peer continues from it
+    // Each failing peer counts as a failed fast sync, no more peer is tried once we must fall back on the full sync
+    pub async fn bootstrap_chain_from_peers(&self, peers: &[Arc<Peer>]) -> Result<SyncReport, BlockchainError> {
+        let mut cursor = self.load_bootstrap_cursor().await?;
+        let mut last_error = None;
+        for peer in peers {
+            match self.bootstrap_chain_with_cursor(peer, &mut cursor).await {
+                Ok(report) => return Ok(report),
+                Err(e) => {
+                    warn!("Fast sync with {} failed: {}, trying next peer", peer, e);
+                    last_error = Some(e);
+                    if let Err(e) = self.fast_sync_failures.record_failure() {
+                        warn!("{}", e);
+                        return Err(e.into())
+                    }
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or(BlockchainError::Unknown))
+    }
+
     // first, retrieve chain info of selected peer
     // We retrieve all assets through pagination,
     // then we fetch all keys with its nonces and its balances (also through pagination)
     // and for the last step, retrieve last STABLE TOPOHEIGHT - PRUNE_SAFETY_LIMIT blocks
     // reload blockchain cache from disk, and we're ready to sync the rest of the chain
     // NOTE: it could be even faster without retrieving each TXs, but we do it in case user don't enable pruning
-    async fn bootstrap_chain(&self, peer: &Arc<Peer>) -> Result<(), BlockchainError> {
+    // Fast sync with a peer, resuming from the cursor if it's on the same stable block
+    // Returns the report of the steps completed for the stable block
+    async fn bootstrap_chain_with_cursor(&self, peer: &Arc<Peer>, cursor: &mut BootstrapCursor) -> Result<SyncReport, BlockchainError> {
         info!("Starting fast sync with {}", peer);
 
         let mut our_topoheight = self.blockchain.get_topo_height();
 
         let mut stable_topoheight = 0;
-        let mut step: Option<StepRequest> = {
+        // Try first the common point agreed during a previous sync with this peer
+        let mut shortcut = {
             let storage = self.blockchain.get_storage().read().await;
-            Some(StepRequest::ChainInfo(self.build_list_of_blocks_id(&*storage).await?))
+            let top_block_hash = storage.get_top_block_hash().await?;
+            self.common_points.lock().await.get_shortcut(peer.get_id(), &top_block_hash)
+        };
+        // The peer must agree on the trusted checkpoint, it's verified once the common point is known
+        // If our chain contains it, we never rewind below it
+        let checkpoint_in_chain = match &self.trusted_checkpoint {
+            Some(checkpoint) => {
+                let storage = self.blockchain.get_storage().read().await;
+                checkpoint.topoheight <= our_topoheight
+                    && matches!(storage.get_hash_at_topo_height(checkpoint.topoheight).await, Ok(hash) if hash == checkpoint.hash)
+            },
+            None => false
+        };
+
+        let mut step: Option<StepRequest> = match &shortcut {
+            Some(common_point) => {
+                debug!("Using cached common point {} at topoheight {} with {}", common_point.get_hash(), common_point.get_topoheight(), peer);
+                Some(shortcut_chain_info_request(common_point))
+            },
+            None => {
+                let storage = self.blockchain.get_storage().read().await;
+                Some(StepRequest::ChainInfo(self.build_list_of_blocks_id(&*storage).await?))
+            }
         };
 
         // keep them in memory, we add them when we're syncing
@@ -2258,58 +2532,121 @@ impl<S: Storage> P2pServer<S> {
         let mut top_topoheight: u64 = 0;
         let mut top_height: u64 = 0;
         let mut top_block_hash: Option<Hash> = None;
+        // peers agreeing on the stable block, sharing the balances pages with the peer
+        let mut helpers: Vec<Arc<Peer>> = Vec::new();
 
         loop {
             let response = if let Some(step) = step.take() {
                 info!("Requesting step {:?}", step.kind());
                 // This will also verify that the received step is the requested one
-                peer.request_boostrap_chain(step).await?
+                self.request_bootstrap_step(peer, step).await?
             } else {
                 break;
             };
 
+            // Peer doesn't agree on the cached common point, fallback on the full block id list
+            if let Some(common_point) = shortcut.take() {
+                if !is_shortcut_accepted(&common_point, &response) {
+                    debug!("{} rejected the cached common point, sending the full block id list", peer);
+                    self.common_points.lock().await.remove(peer.get_id());
+                    let storage = self.blockchain.get_storage().read().await;
+                    step = Some(StepRequest::ChainInfo(self.build_list_of_blocks_id(&*storage).await?));
+                    continue;
+                }
+            }
+
             step = match response {
                 StepResponse::ChainInfo(common_point, topoheight, height, hash) => {
-                    // first, check the common point in case we deviated from the chain
-                    if let Some(common_point) = common_point {
+                    let Some(common_point) = common_point else {
+                        warn!("No common point with {} ! Not same chain ?", peer);
+                        return Err(BlockchainError::Unknown)
+                    };
+
+                    // Peer must agree on the trusted checkpoint, there is no fallback
+                    let mut agreed_checkpoint = None;
+                    if let Some(checkpoint) = &self.trusted_checkpoint {
+                        if checkpoint.needs_block_hash(checkpoint_in_chain, &common_point) {
+                            let hash_at_checkpoint = self.request_block_hash_at(peer, checkpoint.topoheight).await?;
+                            if let Err(e) = checkpoint.verify_block_hash(hash_at_checkpoint.as_ref()) {
+                                warn!("{} doesn't agree with the trusted checkpoint at topoheight {}", peer, checkpoint.topoheight);
+                                return Err(e.into())
+                            }
+                        }
+
+                        if checkpoint_in_chain {
+                            agreed_checkpoint = Some(checkpoint.topoheight);
+                        }
+                    }
+
+                    // check the common point in case we deviated from the chain
+                    {
                         let mut storage = self.blockchain.get_storage().write().await;
                         debug!("Unverified common point found at {} with hash {}", common_point.get_topoheight(), common_point.get_hash());
                         let hash_at_topo = storage.get_hash_at_topo_height(common_point.get_topoheight()).await?;
                         if hash_at_topo != *common_point.get_hash() {
-                            warn!("Common point is {} while our hash at topoheight {} is {}. Aborting", common_point.get_hash(), common_point.get_topoheight(), storage.get_hash_at_topo_height(common_point.get_topoheight()).await?);
+                            warn!("Common point is {} while our hash at topoheight {} is {}. Aborting", common_point.get_hash(), common_point.get_topoheight(), hash_at_topo);
                             return Err(BlockchainError::Unknown)
                         }
 
                         let top_block_hash = storage.get_top_block_hash().await?;
                         if *common_point.get_hash() != top_block_hash {
-                            let pruned_topoheight = storage.get_pruned_topoheight().await?.unwrap_or(0);
-                            
                             warn!("Common point is {} while our top block hash is {} !", common_point.get_hash(), top_block_hash);
-                            // Count how much blocks we need to pop
-                            let pop_count = if pruned_topoheight >= common_point.get_topoheight() {
-                                our_topoheight - pruned_topoheight
-                            } else {
-                                our_topoheight - common_point.get_topoheight()
-                            };
-                            warn!("We need to pop {} blocks for fast sync", pop_count);
-                            our_topoheight = self.blockchain.rewind_chain_for_storage(&mut *storage, pop_count, !peer.is_priority()).await?;
-                            debug!("New topoheight after rewind is now {}", our_topoheight);
+                            let pruned_topoheight = storage.get_pruned_topoheight().await?.unwrap_or(0);
+                            // Only pop the blocks the peer doesn't have
+                            let target = rewind_target(common_point.get_topoheight(), agreed_checkpoint, pruned_topoheight, self.blockchain.get_stable_topoheight());
+                            let pop_count = our_topoheight.saturating_sub(target);
+                            if pop_count > 0 {
+                                warn!("We need to pop {} blocks for fast sync", pop_count);
+                                our_topoheight = self.blockchain.rewind_chain_for_storage(&mut *storage, pop_count, !peer.is_priority()).await?;
+                                debug!("New topoheight after rewind is now {}", our_topoheight);
+                            }
                         }
-                    } else {
-                        warn!("No common point with {} ! Not same chain ?", peer);
-                        return Err(BlockchainError::Unknown)
                     }
 
-                    top_topoheight = topoheight;
-                    top_height = height;
-                    top_block_hash = Some(hash);
-                    stable_topoheight = topoheight;
+                    // A sync in progress stays on its pinned pivot, the peer must have it in its chain
+                    let (pivot_topoheight, pivot_height, pivot_hash) = match cursor.get_pinned_pivot() {
+                        Some((pinned_topoheight, pinned_height, pinned_hash)) if pinned_topoheight != topoheight || *pinned_hash != hash => {
+                            let hash_at_pivot = self.request_block_hash_at(peer, pinned_topoheight).await?;
+                            if hash_at_pivot.as_ref() != Some(pinned_hash) {
+                                warn!("{} doesn't have the fast sync pivot {} at topoheight {}", peer, pinned_hash, pinned_topoheight);
+                                return Err(P2pError::BootstrapPivotMismatch(pinned_topoheight, pinned_hash.clone()).into())
+                            }
+
+                            debug!("{} is at stable topoheight {}, continuing on the pivot at topoheight {}", peer, topoheight, pinned_topoheight);
+                            (pinned_topoheight, pinned_height, pinned_hash.clone())
+                        },
+                        _ => (topoheight, height, hash)
+                    };
+
+                    let (next_kind, page) = cursor.resume(our_topoheight, pivot_topoheight, pivot_height, &pivot_hash)?;
+                    if page.is_some() || next_kind != StepKind::Assets {
+                        info!("Resuming fast sync at step {:?} (page {:?})", next_kind, page);
+                    }
+
+                    // Pages can't be shared with a peer answering the whole accounts set at once
+                    if peer.supports(PeerCapabilities::BALANCES_PAGES) {
+                        helpers = self.select_fast_sync_helpers(peer, pivot_topoheight, &pivot_hash).await;
+                    }
+                    if !helpers.is_empty() {
+                        info!("Fast sync balances will be shared with {} other peers", helpers.len());
+                    }
+
+                    top_topoheight = pivot_topoheight;
+                    top_height = pivot_height;
+                    top_block_hash = Some(pivot_hash);
+                    stable_topoheight = pivot_topoheight;
+                    cursor.get_report_mut().record(StepKind::ChainInfo, 1);
 
-                    Some(StepRequest::Assets(our_topoheight, topoheight, None))
+                    Some(resume_step_request(next_kind, page, our_topoheight, pivot_topoheight))
+                },
+                StepResponse::NoCommonPoint(diagnostic) => {
+                    return Err(self.diagnose_no_common_point(peer, diagnostic, our_topoheight).into())
                 },
                 // fetch all assets from peer
                 StepResponse::Assets(assets, next_page) => {
+                    verify_next_page(StepKind::Assets, next_page, stable_topoheight)?;
                     let mut storage = self.blockchain.get_storage().write().await;
+                    cursor.get_report_mut().record(StepKind::Assets, assets.len() as u64);
                     for asset in assets {
                         let (asset, data) = asset.consume();
                         debug!("Saving asset {} at topoheight {}", asset, stable_topoheight);
@@ -2317,32 +2654,51 @@ impl<S: Storage> P2pServer<S> {
                     }
 
                     if next_page.is_some() {
+                        cursor.save(StepKind::Assets, next_page);
                         Some(StepRequest::Assets(our_topoheight, stable_topoheight, next_page))
                     } else {
                         // Go to next step
+                        cursor.save(StepKind::Keys, None);
                         Some(StepRequest::Keys(our_topoheight, stable_topoheight, None))
                     }
                 },
                 // fetch all new accounts
                 StepResponse::Keys(keys, next_page) => {
-                    debug!("Requesting nonces for keys");
-                    let StepResponse::Nonces(nonces) = peer.request_boostrap_chain(StepRequest::Nonces(stable_topoheight, Cow::Borrowed(&keys))).await? else {
-                        // shouldn't happen
-                        error!("Received an invalid StepResponse (how ?) while fetching nonces");
+                    verify_next_page(StepKind::Keys, next_page, stable_topoheight)?;
+                    // Keys are used as is for the balances and nonces requests
+                    if keys.len() > MAX_ACCOUNTS_PER_REQUEST {
+                        error!("Received {} keys while expecting at most {}", keys.len(), MAX_ACCOUNTS_PER_REQUEST);
                         return Err(P2pError::InvalidPacket.into())
+                    }
+
+                    debug!("Requesting nonces for keys");
+                    let request = StepRequest::Nonces(stable_topoheight, Cow::Borrowed(&keys));
+                    let response = self.request_bootstrap_step(peer, request.clone()).await?;
+
+                    // Nonces are common to all assets, each requested account must have exactly one
+                    let nonces = match zip_nonces(&request, response) {
+                        Ok(nonces) => nonces,
+                        Err(e) => {
+                            error!("Received an invalid nonces response from {}: {}", peer, e);
+                            return Err(step_error(peer.get_id(), &request, e).into())
+                        }
                     };
 
+                    let nonces_count = nonces.len() as u64;
                     {
                         let mut storage = self.blockchain.get_storage().write().await;
                         // save all nonces
-                        for (key, nonce) in keys.iter().zip(nonces) {
+                        for (key, nonce) in nonces {
                             debug!("Saving nonce {} for {}", nonce, key.as_address(self.blockchain.get_network().is_mainnet()));
-                            storage.set_last_nonce_to(key, stable_topoheight, &VersionedNonce::new(nonce, None)).await?;
-                            storage.set_account_registration_topoheight(key, stable_topoheight).await?;
+                            storage.set_last_nonce_to(&key, stable_topoheight, &VersionedNonce::new(nonce, None)).await?;
+                            storage.set_account_registration_topoheight(&key, stable_topoheight).await?;
                         }
                     }
 
+                    let mut balances_count = 0;
                     let mut page = 0;
+                    // Helpers are only selected if the peer answers by pages too
+                    let page_size = balances_page_size(peer.supports(PeerCapabilities::BALANCES_PAGES));
                     loop {
                         // Retrieve chunked assets
                         let assets = {
@@ -2358,17 +2714,38 @@ impl<S: Storage> P2pServer<S> {
                         // Request every asset balances
                         for asset in assets {
                             debug!("Request balances for asset {}", asset);
-                            let StepResponse::Balances(balances) = peer.request_boostrap_chain(StepRequest::Balances(stable_topoheight, Cow::Borrowed(&asset), Cow::Borrowed(&keys))).await? else {
-                                // shouldn't happen
-                                error!("Received an invalid StepResponse (how ?) while fetching balances");
+                            // Pages are requested in parallel to the peer and the helpers
+                            let mut peers = Vec::with_capacity(helpers.len() + 1);
+                            peers.push(Arc::clone(peer));
+                            peers.extend(helpers.iter().cloned());
+
+                            let pages = fetch_pages(&mut peers, balances_pages(keys.len(), page_size), |peer: Arc<Peer>, page| {
+                                let request = StepRequest::Balances(stable_topoheight, Cow::Borrowed(&asset), Cow::Borrowed(&keys), (page != 0).then_some(page));
+                                async move {
+                                    let response = execute_limited(&self.balances_requests, self.request_bootstrap_step(&peer, request.clone())).await?;
+                                    // The peer must answer exactly the requested page
+                                    validate_balances_response(&request, &response, page_size).map_err(|e| step_error(peer.get_id(), &request, e))?;
+                                    match response {
+                                        StepResponse::Balances(balances, _) => Ok(balances),
+                                        // shouldn't happen
+                                        _ => Err(P2pError::InvalidPacket)
+                                    }
+                                }
+                            }).await?;
+
+                            // The peer is used for the next steps, it can't be kept if it sent invalid balances
+                            if !peers.first().is_some_and(|p| p.get_id() == peer.get_id()) {
+                                error!("Received invalid balances from {}, aborting fast sync", peer);
                                 return Err(P2pError::InvalidPacket.into())
-                            };
-    
-                            // save all balances for this asset
+                            }
+                            // Dropped helpers are not used for the next assets
+                            helpers = peers.split_off(1);
+
+                            // save all balances for this asset, pages are in order and each one has an entry per account
                             let mut storage = self.blockchain.get_storage().write().await;
-                            for (key, balance) in keys.iter().zip(balances) {
+                            for (key, balance) in keys.iter().zip(pages.into_iter().flatten()) {
                                 // check that the account have balance for this asset
-                                if let Some((balance, output_balance, balance_type)) = balance {
+                                if let BalanceEntry::Present(balance, output_balance, balance_type) = balance {
                                     debug!("Saving balance {:?} for key {} at topoheight {}", balance, key.as_address(self.blockchain.get_network().is_mainnet()), stable_topoheight);
                                     let mut versioned_balance = storage.get_new_versioned_balance(key, &asset, stable_topoheight).await?;
                                     versioned_balance.set_balance(balance);
@@ -2376,15 +2753,23 @@ impl<S: Storage> P2pServer<S> {
                                     versioned_balance.set_balance_type(balance_type);
                                     versioned_balance.set_previous_topoheight(None);
                                     storage.set_last_balance_to(key, &asset, stable_topoheight, &versioned_balance).await?;
+                                    balances_count += 1;
                                 }
                             }
                         }
                     }
 
+                    let report = cursor.get_report_mut();
+                    report.record(StepKind::Keys, keys.len() as u64);
+                    report.record(StepKind::Balances, balances_count);
+                    report.record(StepKind::Nonces, nonces_count);
+
                     if next_page.is_some() {
+                        cursor.save(StepKind::Keys, next_page);
                         Some(StepRequest::Keys(our_topoheight, stable_topoheight, next_page))
                     } else {
                         // Go to next step
+                        cursor.save(StepKind::BlocksMetadata, None);
                         Some(StepRequest::BlocksMetadata(stable_topoheight))
                     }
                 },
@@ -2395,6 +2780,10 @@ impl<S: Storage> P2pServer<S> {
                         return Err(P2pError::InvalidPacket.into())
                     }
 
+                    // The stable block must be the one announced in the chain info
+                    verify_stable_block(top_block_hash.as_ref().ok_or(P2pError::InvalidPacket)?, &blocks)?;
+
+                    let blocks_count = blocks.len() as u64;
                     let mut lowest_topoheight = stable_topoheight;
                     for (i, metadata) in blocks.into_iter().enumerate() {
                         let topoheight = stable_topoheight - i as u64;
@@ -2461,7 +2850,12 @@ impl<S: Storage> P2pServer<S> {
                     storage.set_pruned_topoheight(lowest_topoheight).await?;
                     storage.set_top_topoheight(top_topoheight)?;
                     storage.set_top_height(top_height)?;
-                    storage.store_tips(&HashSet::from([top_block_hash.take().expect("Expected top block hash for fast sync")]))?;
+                    let top_hash = top_block_hash.take().expect("Expected top block hash for fast sync");
+                    storage.store_tips(&HashSet::from([top_hash.clone()]))?;
+                    cursor.get_report_mut().record(StepKind::BlocksMetadata, blocks_count);
+
+                    // Remember it to shortcut the next sync with this peer
+                    self.common_points.lock().await.insert(peer.get_id(), CommonPoint::new(top_hash, top_topoheight));
 
                     None
                 },
@@ -2470,11 +2864,130 @@ impl<S: Storage> P2pServer<S> {
                     return Err(P2pError::InvalidPacket.into());
                 }
             };
+
+            // Step is fully validated, the fast sync is making progress
+            self.fast_sync_failures.record_success();
+
+            // Persist the progress so the sync can be resumed after a restart
+            if step.is_some() {
+                if let Some(progress) = cursor.get_progress() {
+                    let mut storage = self.blockchain.get_storage().write().await;
+                    storage.set_bootstrap_progress(&progress).await?;
+                }
+            }
+        }
+
+        {
+            let mut storage = self.blockchain.get_storage().write().await;
+            storage.delete_bootstrap_progress().await?;
         }
         self.blockchain.reload_from_disk().await?;
         info!("Fast sync done with {}", peer);
 
-        Ok(())
+        let report = cursor.get_report_mut();
+        if !report.finish() {
+            warn!("Fast sync with {} didn't complete all steps, missing: {:?}", peer, report.get_missing_steps());
+        }
+
+        Ok(report.clone())
+    }
+
+    // Request a bootstrap step to the peer, retrying it on timeout using the configured policy
+    // A peer answering with another step than the requested one is misbehaving, it is temp banned
+    async fn request_bootstrap_step(&self, peer: &Arc<Peer>, step: StepRequest<'_>) -> Result<StepResponse, P2pError> {
+        let res = self.bootstrap_retry_policy.execute(step.kind(), |timeout| peer.request_boostrap_chain(step.clone(), timeout)).await;
+        if let Err(P2pError::InvalidBootstrapStep(expected, got)) = &res {
+            warn!("{} sent a step response {:?} while we requested {:?}, temp banning it", peer, got, expected);
+            if let Err(e) = peer.close_and_temp_ban().await {
+                error!("Error while temp banning {}: {}", peer, e);
+            }
+        }
+
+        res.map_err(|e| step_error(peer.get_id(), &step, e))
+    }
+
+    // Peers able to share the fast sync balances pages with the peer
+    // Only the ones having the same block hash at the stable topoheight are selected
+    async fn select_fast_sync_helpers(&self, peer: &Arc<Peer>, stable_topoheight: u64, stable_hash: &Hash) -> Vec<Arc<Peer>> {
+        let max_helpers = self.bootstrap_retry_policy.max_helpers;
+        if max_helpers == 0 {
+            return Vec::new()
+        }
+
+        let candidates: Vec<Arc<Peer>> = {
+            let peer_list = self.peer_list.read().await;
+            peer_list.get_peers().values()
+                .filter(|p| p.get_id() != peer.get_id() && p.supports(PeerCapabilities::BALANCES_PAGES) && p.get_topoheight() >= stable_topoheight)
+                .filter(|p| p.get_pruned_topoheight().map_or(true, |pruned| pruned <= stable_topoheight))
+                .cloned()
+                .collect()
+        };
+
+        let mut helpers = Vec::new();
+        for candidate in candidates {
+            if helpers.len() >= max_helpers {
+                break;
+            }
+
+            match self.request_block_hash_at(&candidate, stable_topoheight).await {
+                Ok(Some(hash)) if hash == *stable_hash => helpers.push(candidate),
+                Ok(_) => debug!("{} doesn't agree on the stable block at topoheight {}", candidate, stable_topoheight),
+                Err(e) => debug!("Couldn't verify the stable block with {}: {}", candidate, e)
+            }
+        }
+
+        helpers
+    }
+
+    // Request the block hash at a stable topoheight from a peer
+    // Returns None if the topoheight isn't in the stable range of the peer
+    pub async fn request_block_hash_at(&self, peer: &Arc<Peer>, topoheight: u64) -> Result<Option<Hash>, BlockchainError> {
+        debug!("Requesting block hash at topoheight {} from {}", topoheight, peer);
+        let StepResponse::BlockHashAt(hash) = self.request_bootstrap_step(peer, StepRequest::BlockHashAt(topoheight)).await? else {
+            // shouldn't happen, the response kind is verified
+            error!("Received an invalid StepResponse while requesting block hash at topoheight {}", topoheight);
+            return Err(P2pError::InvalidPacket.into())
+        };
+
+        Ok(hash)
+    }
+
+    // Lightweight sync for audit nodes: only ChainInfo and BlocksMetadata steps are requested
+    // Assets, keys, balances and nonces are skipped, and our chain isn't modified
+    // The blocks metadata series (stable block first) of the peer is saved in storage and returned from it
+    pub async fn sync_metadata_only(&self, peer: &Arc<Peer>) -> Result<IndexSet<BlockMetadata>, BlockchainError> {
+        info!("Starting metadata only sync with {}", peer);
+
+        let mut step: Option<StepRequest> = {
+            let storage = self.blockchain.get_storage().read().await;
+            Some(StepRequest::ChainInfo(self.build_list_of_blocks_id(&*storage).await?))
+        };
+
+        while let Some(request) = step.take() {
+            let kind = request.kind();
+            info!("Requesting step {:?}", kind);
+            // This will also verify that the received step is the requested one
+            let response = self.request_bootstrap_step(peer, request).await?;
+
+            step = match response {
+                StepResponse::ChainInfo(None, _, _, _) => {
+                    warn!("No common point with {} ! Not same chain ?", peer);
+                    return Err(BlockchainError::Unknown)
+                },
+                StepResponse::NoCommonPoint(diagnostic) => {
+                    let our_topoheight = self.blockchain.get_topo_height();
+                    return Err(self.diagnose_no_common_point(peer, diagnostic, our_topoheight).into())
+                },
+                response => {
+                    let mut storage = self.blockchain.get_storage().write().await;
+                    apply_metadata_only_step(&mut *storage, kind, response).await?
+                }
+            };
+        }
+        info!("Metadata only sync done with {}", peer);
+
+        let storage = self.blockchain.get_storage().read().await;
+        storage.get_blocks_metadata().await?.ok_or(BlockchainError::Unknown)
     }
 
     // Request the inventory of a peer
@@ -2522,6 +3035,32 @@ impl<S: Storage> P2pServer<S> {
     }
 }
 
+// Apply a step response of the metadata only sync and returns the next step to request
+// Only the blocks metadata series is written, no account data is requested nor stored
+async fn apply_metadata_only_step<S: Storage>(storage: &mut S, kind: StepKind, response: StepResponse) -> Result<Option<StepRequest>, BlockchainError> {
+    Ok(match response {
+        StepResponse::ChainInfo(Some(_), topoheight, _, _) => {
+            kind.next_metadata_only()
+                .filter(|next| *next == StepKind::BlocksMetadata)
+                .map(|_| StepRequest::BlocksMetadata(topoheight))
+        },
+        StepResponse::BlocksMetadata(blocks) => {
+            // Last N blocks + stable block
+            if blocks.len() != PRUNE_SAFETY_LIMIT as usize + 1 {
+                error!("Received {} blocks metadata while expecting {}", blocks.len(), PRUNE_SAFETY_LIMIT + 1);
+                return Err(P2pError::InvalidPacket.into())
+            }
+
+            storage.set_blocks_metadata(&blocks).await?;
+            None
+        },
+        response => {
+            error!("Received bootstrap chain response {:?} but didn't asked for it", response);
+            return Err(P2pError::InvalidPacket.into());
+        }
+    })
+}
+
 // Check if a socket address is a local address
 pub fn is_local_address(socket_addr: &SocketAddr) -> bool {
     match socket_addr.ip() {
@@ -2535,4 +3074,58 @@ pub fn is_local_address(socket_addr: &SocketAddr) -> bool {
             ipv6.is_loopback() // || ipv6.is_unique_local()
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        difficulty::{CumulativeDifficulty, Difficulty},
+        network::Network,
+        varuint::VarUint
+    };
+    use crate::core::storage::{AssetProvider, BlocksMetadataProvider, NonceProvider, SledStorage};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metadata_only_sync() {
+        let path = std::env::temp_dir().join(format!("xelis-metadata-only-{}", rand::random::<u64>()));
+        let mut storage = SledStorage::new(format!("{}/", path.display()), None, Network::Dev).unwrap();
+        let series = |count: u64| -> IndexSet<BlockMetadata> {
+            (0..count).map(|i| BlockMetadata {
+                hash: Hash::new([i as u8; 32]),
+                supply: i,
+                reward: i,
+                difficulty: Difficulty::from(i + 1),
+                cumulative_difficulty: CumulativeDifficulty::from(i + 1),
+                p: VarUint::from(i)
+            }).collect()
+        };
+
+        // Chain info leads directly to the blocks metadata
+        let chain_info = StepResponse::ChainInfo(Some(CommonPoint::new(Hash::zero(), 0)), 150, 140, Hash::new([1u8; 32]));
+        let next = apply_metadata_only_step(&mut storage, StepKind::ChainInfo, chain_info).await.unwrap();
+        assert!(matches!(next, Some(StepRequest::BlocksMetadata(150))));
+
+        // Incomplete series is rejected
+        let partial = StepResponse::BlocksMetadata(series(PRUNE_SAFETY_LIMIT));
+        assert!(apply_metadata_only_step(&mut storage, StepKind::BlocksMetadata, partial).await.is_err());
+        assert!(storage.get_blocks_metadata().await.unwrap().is_none());
+
+        let blocks = StepResponse::BlocksMetadata(series(PRUNE_SAFETY_LIMIT + 1));
+        let next = apply_metadata_only_step(&mut storage, StepKind::BlocksMetadata, blocks).await.unwrap();
+        assert!(next.is_none());
+        assert_eq!(storage.get_blocks_metadata().await.unwrap(), Some(series(PRUNE_SAFETY_LIMIT + 1)));
+
+        // Account steps are never answered during it
+        let balances = StepResponse::Balances(Vec::new(), None);
+        assert!(apply_metadata_only_step(&mut storage, StepKind::Balances, balances).await.is_err());
+
+        // Only the metadata is stored, no account data
+        assert_eq!(storage.count_assets().await.unwrap(), 0);
+        assert_eq!(storage.count_accounts().await.unwrap(), 0);
+        assert!(storage.get_assets().await.unwrap().is_empty());
+
+        drop(storage);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
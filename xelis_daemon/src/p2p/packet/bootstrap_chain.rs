@@ -1,14 +1,23 @@
 use std::{
     borrow::Cow,
-    hash::{Hash as StdHash, Hasher}
+    hash::{Hash as StdHash, Hasher},
+    io::{Read, Result as IOResult, Write},
+    num::NonZeroUsize,
+    ops::Range
+};
+use flate2::{
+    read::DeflateDecoder,
+    write::DeflateEncoder,
+    Compression
 };
 use indexmap::IndexSet;
 use log::debug;
+use lru::LruCache;
 use xelis_common::{
     account::{BalanceType, CiphertextCache},
     asset::AssetWithData,
     crypto::{
-        Hash, PublicKey
+        hash, Hash, PublicKey, HASH_SIZE
     },
     difficulty::{
         CumulativeDifficulty,
@@ -20,10 +29,14 @@ use xelis_common::{
         Serializer,
         Writer
     },
+    time::TimestampSeconds,
     varuint::VarUint
 };
 use super::chain::{BlockId, CommonPoint};
-use crate::config::CHAIN_SYNC_REQUEST_MAX_BLOCKS;
+use crate::{
+    config::{CHAIN_SYNC_REQUEST_MAX_BLOCKS, MAX_BLOCK_SIZE, PEER_MAX_PACKET_SIZE, PRUNE_SAFETY_LIMIT},
+    p2p::error::P2pError
+};
 
 // this file implements the protocol for the fast sync (bootstrapped chain)
 // You will have to request through StepRequest::FetchAssets all the registered assets
@@ -34,6 +47,383 @@ use crate::config::CHAIN_SYNC_REQUEST_MAX_BLOCKS;
 // how many items we can answer per request
 
 pub const MAX_ITEMS_PER_PAGE: usize = 1024;
+// Maximum accounts to look up in a balances or nonces request
+// Accounts are requested by pages of keys, so it's the same as the page size
+pub const MAX_ACCOUNTS_PER_REQUEST: usize = MAX_ITEMS_PER_PAGE;
+// Balances answered per page, a balance being way bigger than a key
+// the balances of an accounts set are split over several pages
+pub const MAX_BALANCES_PER_PAGE: usize = 256;
+
+// Hard ceiling of the pages of any paginated step, whatever the stable topoheight announced
+// It's above a billion assets or accounts with MAX_ITEMS_PER_PAGE items per page
+pub const MAX_STEP_PAGES: u64 = 1 << 20;
+
+// Maximum serialized size of a step response, whatever its step
+pub const MAX_STEP_RESPONSE_BYTES: usize = PEER_MAX_PACKET_SIZE as usize;
+
+// Upper bounds of the items serialized in step responses, used to compute the budget of each step
+const PUBLIC_KEY_SIZE: usize = 32;
+const CIPHERTEXT_SIZE: usize = 64;
+// Optional page number
+const PAGE_MAX_SIZE: usize = 1 + 8;
+// Length byte and up to 256 bits
+const VARUINT_MAX_SIZE: usize = 1 + 32;
+// asset hash, registration topoheight and decimals
+const ASSET_MAX_SIZE: usize = HASH_SIZE + 8 + 1;
+// optional flag, balance, optional output balance and balance type
+const BALANCE_MAX_SIZE: usize = 1 + CIPHERTEXT_SIZE + 1 + CIPHERTEXT_SIZE + 1;
+// hash, supply, reward, difficulty, cumulative difficulty and P
+const BLOCK_METADATA_MAX_SIZE: usize = HASH_SIZE + 8 + 8 + 3 * VARUINT_MAX_SIZE;
+
+// Verify the size of a step response against the budget of its step
+pub fn verify_step_response_size(kind: StepKind, size: usize, balances_pages: bool) -> Result<(), P2pError> {
+    let max = kind.max_response_size(balances_pages);
+    if size > max {
+        debug!("Step response {:?} of {} bytes is above its budget of {} bytes", kind, size, max);
+        return Err(P2pError::StepResponseTooBig(kind, max))
+    }
+
+    Ok(())
+}
+
+// Maximum pages of a paginated step up to the stable topoheight, never above MAX_STEP_PAGES
+// Assets and accounts are registered by blocks, so there can't be more of them than what the stable chain can contain:
+// each block registers at most its miner and one per hash fitting in it
+// Balances are requested for a page of keys, so their pages are bounded by the accounts per request
+pub fn max_pages(kind: StepKind, stable_topoheight: u64) -> u64 {
+    match kind {
+        StepKind::Assets | StepKind::Keys => {
+            let per_block = (MAX_BLOCK_SIZE / HASH_SIZE) as u64 + 1;
+            let max_items = stable_topoheight.saturating_add(1).saturating_mul(per_block);
+            max_items.div_ceil(MAX_ITEMS_PER_PAGE as u64).min(MAX_STEP_PAGES)
+        },
+        StepKind::Balances => balances_pages(MAX_ACCOUNTS_PER_REQUEST, MAX_BALANCES_PER_PAGE),
+        _ => 1
+    }
+}
+
+// Verify that the next page announced by a peer is below the ceiling of its step
+// Otherwise, a peer could keep the pagination going forever with tiny pages
+pub fn verify_next_page(kind: StepKind, next_page: Option<u64>, stable_topoheight: u64) -> Result<(), P2pError> {
+    if let Some(page) = next_page {
+        // pages start at 0
+        if page >= max_pages(kind, stable_topoheight) {
+            debug!("Next page {} is above the pages limit of step {:?}", page, kind);
+            return Err(P2pError::TooManyPages { kind })
+        }
+    }
+
+    Ok(())
+}
+
+// Attach the peer id and the step to an error of a fast sync request
+// An error already carrying its step context is returned as is
+pub fn step_error<E: Into<P2pError>>(peer: u64, request: &StepRequest, error: E) -> P2pError {
+    match error.into() {
+        e @ P2pError::BootstrapStep { .. } => e,
+        e => P2pError::BootstrapStep {
+            peer,
+            kind: request.kind(),
+            page: request.get_page(),
+            source: Box::new(e)
+        }
+    }
+}
+
+// Verify that the step response received is the one requested
+pub fn verify_step_kind(expected: StepKind, response: &StepResponse) -> Result<(), P2pError> {
+    let got = response.kind();
+    if got != expected {
+        debug!("Expected step response {:?}, got {:?}", expected, got);
+        return Err(P2pError::InvalidBootstrapStep(expected, got))
+    }
+
+    Ok(())
+}
+
+// Accounts answered per Balances step
+// Older peers don't support the pages and answer the whole accounts set at once
+pub fn balances_page_size(balances_pages: bool) -> usize {
+    if balances_pages {
+        MAX_BALANCES_PER_PAGE
+    } else {
+        MAX_ACCOUNTS_PER_REQUEST
+    }
+}
+
+// Balances of an accounts set are answered in chunks of page size accounts
+// Returns the range of accounts for the requested page and the next page if any
+// None is returned if the page is out of the accounts set
+pub fn balances_page(accounts: usize, page: Option<u64>, page_size: usize) -> Option<(Range<usize>, Option<u64>)> {
+    let page = page.unwrap_or(0);
+    let start = usize::try_from(page).ok()?.checked_mul(page_size)?;
+    if page != 0 && start >= accounts {
+        return None
+    }
+
+    let end = accounts.min(start + page_size);
+    let next_page = if end < accounts {
+        Some(page + 1)
+    } else {
+        None
+    };
+
+    Some((start..end, next_page))
+}
+
+// Number of pages needed to request the balances of an accounts set
+// An empty set is still answered with a first page
+pub fn balances_pages(accounts: usize, page_size: usize) -> u64 {
+    accounts.div_ceil(page_size).max(1) as u64
+}
+
+// Verify that a balances response matches its request
+// Balances are answered by position, so the response must contain exactly one entry per account of the requested page
+// and announce the same next page, otherwise balances would be attributed to the wrong accounts
+pub fn validate_balances_response(request: &StepRequest, response: &StepResponse, page_size: usize) -> Result<(), ReaderError> {
+    let (StepRequest::Balances(_, _, keys, page), StepResponse::Balances(balances, next_page)) = (request, response) else {
+        debug!("Expected a balances response for a balances request");
+        return Err(ReaderError::InvalidValue)
+    };
+
+    let (range, expected_next_page) = balances_page(keys.len(), *page, page_size).ok_or(ReaderError::InvalidValue)?;
+    if balances.len() != range.len() {
+        debug!("Received {} balances while expecting {}", balances.len(), range.len());
+        return Err(ReaderError::InvalidSize)
+    }
+
+    if *next_page != expected_next_page {
+        debug!("Received balances with next page {:?} while expecting {:?}", next_page, expected_next_page);
+        return Err(ReaderError::InvalidValue)
+    }
+
+    Ok(())
+}
+
+// Verify that a nonces response matches its request
+// Nonces are answered by position, so the response must contain exactly one nonce per requested account
+pub fn validate_nonces_response(request: &StepRequest, response: &StepResponse) -> Result<(), ReaderError> {
+    let (StepRequest::Nonces(_, keys), StepResponse::Nonces(nonces)) = (request, response) else {
+        debug!("Expected a nonces response for a nonces request");
+        return Err(ReaderError::InvalidValue)
+    };
+
+    if nonces.len() != keys.len() {
+        debug!("Received {} nonces while expecting {}", nonces.len(), keys.len());
+        return Err(ReaderError::InvalidSize)
+    }
+
+    Ok(())
+}
+
+// Pair each requested account with its nonce once the response is validated
+pub fn zip_nonces(request: &StepRequest, response: StepResponse) -> Result<Vec<(PublicKey, u64)>, ReaderError> {
+    validate_nonces_response(request, &response)?;
+    let (StepRequest::Nonces(_, keys), StepResponse::Nonces(nonces)) = (request, response) else {
+        return Err(ReaderError::InvalidValue)
+    };
+
+    Ok(keys.iter().cloned().zip(nonces).collect())
+}
+
+// Verify that the stable block of the blocks metadata is the one announced in the chain info
+// Blocks metadata are sent from the stable block to the lowest one
+pub fn verify_stable_block(stable_hash: &Hash, blocks: &IndexSet<BlockMetadata>) -> Result<(), P2pError> {
+    let Some(stable) = blocks.first() else {
+        debug!("No stable block in blocks metadata");
+        return Err(P2pError::InvalidPacket)
+    };
+
+    if stable.hash != *stable_hash {
+        return Err(P2pError::BootstrapStableBlockMismatch(stable_hash.clone(), stable.hash.clone()))
+    }
+
+    Ok(())
+}
+
+// Final integrity check of a fast sync between the chain info and the blocks metadata responses
+pub fn verify_top_block(chain_info: &StepResponse, blocks: &StepResponse) -> Result<(), P2pError> {
+    let (StepResponse::ChainInfo(_, _, _, stable_hash), StepResponse::BlocksMetadata(blocks)) = (chain_info, blocks) else {
+        debug!("Expected chain info and blocks metadata responses");
+        return Err(P2pError::InvalidPacket)
+    };
+
+    verify_stable_block(stable_hash, blocks)
+}
+
+// Check if the block hash at this topoheight can be shared
+// Only stable blocks that are not pruned are answered
+pub fn is_block_hash_at_available(topoheight: u64, pruned_topoheight: Option<u64>, stable_topoheight: u64) -> bool {
+    topoheight <= stable_topoheight && pruned_topoheight.map_or(true, |pruned| topoheight > pruned)
+}
+
+// Last common point agreed with each peer (by its id) during a fast sync
+// On the next sync with the same peer, it's sent alone as the chain info request
+// instead of the full block id list, as long as it's still our top block
+pub struct CommonPointCache {
+    points: LruCache<u64, CommonPoint>
+}
+
+impl CommonPointCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            points: LruCache::new(capacity)
+        }
+    }
+
+    pub fn insert(&mut self, peer_id: u64, common_point: CommonPoint) {
+        self.points.put(peer_id, common_point);
+    }
+
+    pub fn remove(&mut self, peer_id: u64) -> Option<CommonPoint> {
+        self.points.pop(&peer_id)
+    }
+
+    // Cached common point for this peer, dropped if it's no longer our top block
+    pub fn get_shortcut(&mut self, peer_id: u64, top_block_hash: &Hash) -> Option<CommonPoint> {
+        if self.points.get(&peer_id)?.get_hash() != top_block_hash {
+            debug!("Cached common point for peer {} is not our top block anymore", peer_id);
+            self.points.pop(&peer_id);
+            return None
+        }
+
+        self.points.peek(&peer_id).cloned()
+    }
+}
+
+// Sent instead of a ChainInfo when no common point was found
+// It lets the requester know if the peer is on another network or if it pruned past our blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainDiagnostic {
+    // Genesis block hash of the peer
+    genesis_hash: Hash,
+    // Lowest topoheight available on the peer (its pruned topoheight or 0)
+    lowest_topoheight: u64
+}
+
+impl ChainDiagnostic {
+    pub fn new(genesis_hash: Hash, lowest_topoheight: u64) -> Self {
+        Self {
+            genesis_hash,
+            lowest_topoheight
+        }
+    }
+
+    pub fn get_genesis_hash(&self) -> &Hash {
+        &self.genesis_hash
+    }
+
+    pub fn get_lowest_topoheight(&self) -> u64 {
+        self.lowest_topoheight
+    }
+
+    // Peer doesn't share our genesis block, it's not the same chain at all
+    pub fn is_other_network(&self, our_genesis_hash: &Hash) -> bool {
+        self.genesis_hash != *our_genesis_hash
+    }
+
+    // Peer deleted all the blocks we have in common
+    pub fn is_pruned_past(&self, our_topoheight: u64) -> bool {
+        self.lowest_topoheight > our_topoheight
+    }
+
+    pub fn into_error(self) -> P2pError {
+        P2pError::NoCommonPoint(self.genesis_hash, self.lowest_topoheight)
+    }
+}
+
+impl Serializer for ChainDiagnostic {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.genesis_hash);
+        writer.write_u64(&self.lowest_topoheight);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let genesis_hash = reader.read_hash()?;
+        let lowest_topoheight = reader.read_u64()?;
+        Ok(Self::new(genesis_hash, lowest_topoheight))
+    }
+
+    fn size(&self) -> usize {
+        self.genesis_hash.size() + self.lowest_topoheight.size()
+    }
+}
+
+// Keys in a page are sorted by their serialized bytes
+// so every honest peer sends the same page in the same order
+pub fn sort_keys(keys: &mut IndexSet<PublicKey>) {
+    keys.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+}
+
+// Check that a key comes strictly after the previous one in the canonical order
+fn is_after_key(previous: Option<&PublicKey>, key: &PublicKey) -> bool {
+    match previous {
+        Some(previous) => previous.as_bytes() < key.as_bytes(),
+        None => true
+    }
+}
+
+// Chain info request containing only the cached common point
+pub fn shortcut_chain_info_request(common_point: &CommonPoint) -> StepRequest<'static> {
+    StepRequest::ChainInfo(IndexSet::from([BlockId::new(common_point.get_hash().clone(), common_point.get_topoheight())]))
+}
+
+// Check if the peer agreed on the common point sent as a shortcut
+// Otherwise the full block id list must be sent
+pub fn is_shortcut_accepted(common_point: &CommonPoint, response: &StepResponse) -> bool {
+    matches!(response, StepResponse::ChainInfo(Some(point), _, _, _) if point == common_point)
+}
+
+// Stable block trusted by the node operator to start the fast sync from
+// Peers must agree with it, otherwise the sync is aborted with them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedCheckpoint {
+    pub topoheight: u64,
+    pub hash: Hash
+}
+
+impl TrustedCheckpoint {
+    pub fn new(topoheight: u64, hash: Hash) -> Self {
+        Self {
+            topoheight,
+            hash
+        }
+    }
+
+    // Check if the peer block hash at the checkpoint must be requested
+    // A common point at or above the checkpoint in our chain already proves the peer agrees with it
+    pub fn needs_block_hash(&self, in_our_chain: bool, common_point: &CommonPoint) -> bool {
+        !in_our_chain || common_point.get_topoheight() < self.topoheight
+    }
+
+    // Verify the block hash of the peer at the checkpoint topoheight
+    // None means the checkpoint topoheight isn't in the stable range of the peer
+    pub fn verify_block_hash(&self, hash: Option<&Hash>) -> Result<(), P2pError> {
+        match hash {
+            Some(hash) if *hash == self.hash => Ok(()),
+            _ => {
+                debug!("Peer has block {:?} at checkpoint topoheight {} instead of {}", hash, self.topoheight, self.hash);
+                Err(P2pError::CheckpointMismatch(self.topoheight, self.hash.clone()))
+            }
+        }
+    }
+}
+
+// Topoheight to rewind our chain to before a fast sync
+// Only the blocks above the common point with the peer are popped, but never a block of
+// a trusted checkpoint agreed by both sides or a block at or below our stable topoheight
+pub fn rewind_target(common_topoheight: u64, agreed_checkpoint: Option<u64>, pruned_topoheight: u64, stable_topoheight: u64) -> u64 {
+    common_topoheight
+        .max(agreed_checkpoint.unwrap_or(0))
+        .max(pruned_topoheight)
+        .max(stable_topoheight)
+}
+
+// Circulating supply emitted between two blocks
+// None if the later block has a lower supply than the earlier one
+pub fn supply_delta(earlier: &BlockMetadata, later: &BlockMetadata) -> Option<u64> {
+    later.supply.checked_sub(earlier.supply)
+}
 
 #[derive(Debug)]
 pub struct BlockMetadata {
@@ -103,17 +493,87 @@ impl Serializer for BlockMetadata {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd)]
+// Read a variable size field and decode it only once its bytes are consumed
+// This keep the reader aligned on the next field even if the value is invalid
+fn read_framed_varuint(reader: &mut Reader) -> Result<Option<VarUint>, ReaderError> {
+    let len = reader.read_u8()?;
+    let mut bytes = vec![len];
+    bytes.extend(reader.read_bytes::<Vec<u8>>(len as usize)?);
+    Ok(VarUint::from_bytes(&bytes).ok())
+}
+
+// Lenient version of the blocks metadata decoding
+// A malformed or duplicated block metadata is skipped instead of discarding the whole set
+// Returns the valid blocks metadata and the count of skipped entries
+// Entries can only be skipped if their framing is still readable, otherwise an error is returned
+// Strict decoding through the Serializer trait should be preferred
+pub fn read_blocks_metadata_lenient(reader: &mut Reader) -> Result<(IndexSet<BlockMetadata>, usize), ReaderError> {
+    let count = reader.read_u16()? as usize;
+    if count > MAX_ITEMS_PER_PAGE {
+        debug!("Invalid blocks metadata length ({})", count);
+        return Err(ReaderError::InvalidSize)
+    }
+
+    let mut blocks = IndexSet::with_capacity(count);
+    let mut skipped = 0;
+    for i in 0..count {
+        let hash = reader.read_hash()?;
+        let supply = reader.read_u64()?;
+        let reward = reader.read_u64()?;
+        let difficulty = read_framed_varuint(reader)?;
+        let cumulative_difficulty = read_framed_varuint(reader)?;
+        let p = read_framed_varuint(reader)?;
+
+        let (Some(difficulty), Some(cumulative_difficulty), Some(p)) = (difficulty, cumulative_difficulty, p) else {
+            debug!("Skipping malformed block metadata {} at index {}", hash, i);
+            skipped += 1;
+            continue;
+        };
+
+        let metadata = BlockMetadata {
+            hash,
+            supply,
+            reward,
+            difficulty,
+            cumulative_difficulty,
+            p
+        };
+
+        if !blocks.insert(metadata) {
+            debug!("Skipping duplicated block metadata at index {}", i);
+            skipped += 1;
+        }
+    }
+
+    Ok((blocks, skipped))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Ord, PartialOrd, Hash)]
 pub enum StepKind {
     ChainInfo,
     Assets,
     Keys,
     Balances,
     Nonces,
-    BlocksMetadata
+    // TODO: Smart Contracts step after Nonces, once deployed contracts and their storage are available in the daemon
+    BlocksMetadata,
+    // Standalone request, not part of the fast sync steps
+    BlockHashAt
 }
 
 impl StepKind {
+    // All the fast sync steps, in order
+    pub fn all() -> [Self; 6] {
+        [
+            Self::ChainInfo,
+            Self::Assets,
+            Self::Keys,
+            Self::Balances,
+            Self::Nonces,
+            Self::BlocksMetadata
+        ]
+    }
+
     pub fn next(&self) -> Option<Self> {
         Some(match self {
             Self::ChainInfo => Self::Assets,
@@ -121,12 +581,255 @@ impl StepKind {
             Self::Keys => Self::Balances,
             Self::Balances => Self::Nonces,
             Self::Nonces => Self::BlocksMetadata,
-            Self::BlocksMetadata => return None
+            Self::BlocksMetadata | Self::BlockHashAt => return None
+        })
+    }
+
+    // Maximum serialized size of a response for this step, checksum included
+    // Each step has its own budget as their responses have very different sizes
+    // Older peers not supporting the balances pages answer the whole accounts set at once
+    pub fn max_response_size(&self, balances_pages: bool) -> usize {
+        // 1 for the id, 2 for the items count
+        let size = match self {
+            Self::ChainInfo => 1 + (1 + HASH_SIZE + 8) + 8 + 8 + HASH_SIZE,
+            Self::Assets => 1 + 2 + MAX_ITEMS_PER_PAGE * ASSET_MAX_SIZE + PAGE_MAX_SIZE,
+            Self::Keys => 1 + 2 + MAX_ITEMS_PER_PAGE * PUBLIC_KEY_SIZE + PAGE_MAX_SIZE,
+            Self::Balances => 1 + 2 + balances_page_size(balances_pages) * BALANCE_MAX_SIZE + PAGE_MAX_SIZE,
+            Self::Nonces => 1 + 2 + MAX_ACCOUNTS_PER_REQUEST * 8,
+            Self::BlocksMetadata => 1 + 2 + (PRUNE_SAFETY_LIMIT as usize + 1) * BLOCK_METADATA_MAX_SIZE,
+            Self::BlockHashAt => 1 + 1 + HASH_SIZE
+        };
+
+        (size + HASH_SIZE).min(MAX_STEP_RESPONSE_BYTES)
+    }
+
+    // Steps traversal used by the metadata only sync
+    // No account data is requested, only the chain info and the blocks metadata
+    pub fn next_metadata_only(&self) -> Option<Self> {
+        match self {
+            Self::ChainInfo => Some(Self::BlocksMetadata),
+            _ => None
+        }
+    }
+}
+
+impl Serializer for StepKind {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(match reader.read_u8()? {
+            0 => Self::ChainInfo,
+            1 => Self::Assets,
+            2 => Self::Keys,
+            3 => Self::Balances,
+            4 => Self::Nonces,
+            5 => Self::BlocksMetadata,
+            6 => Self::BlockHashAt,
+            id => {
+                debug!("Received invalid value for StepKind: {}", id);
+                return Err(ReaderError::InvalidValue)
+            }
         })
     }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(match self {
+            Self::ChainInfo => 0,
+            Self::Assets => 1,
+            Self::Keys => 2,
+            Self::Balances => 3,
+            Self::Nonces => 4,
+            Self::BlocksMetadata => 5,
+            Self::BlockHashAt => 6
+        });
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
 }
 
-#[derive(Debug)]
+// Steps completed during a fast sync with the count of items processed for each
+// Pages of the same step are merged into one entry
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncReport {
+    pub steps: Vec<(StepKind, u64)>,
+    // true only if all the steps ran in order
+    pub completed: bool
+}
+
+impl SyncReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record the items processed for a step
+    pub fn record(&mut self, kind: StepKind, items: u64) {
+        match self.steps.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += items,
+            None => self.steps.push((kind, items))
+        }
+    }
+
+    // Verify that every step from first to last was honored in order
+    pub fn finish(&mut self) -> bool {
+        self.completed = self.steps.iter().map(|(kind, _)| *kind).eq(StepKind::all());
+        self.completed
+    }
+
+    // Steps that didn't run
+    pub fn get_missing_steps(&self) -> Vec<StepKind> {
+        StepKind::all().into_iter()
+            .filter(|kind| !self.steps.iter().any(|(k, _)| k == kind))
+            .collect()
+    }
+}
+
+// Build the request to continue a fast sync at this step and page
+// Steps that can't be resumed are restarted from the assets
+pub fn resume_step_request(kind: StepKind, page: Option<u64>, our_topoheight: u64, stable_topoheight: u64) -> StepRequest<'static> {
+    match kind {
+        StepKind::Keys => StepRequest::Keys(our_topoheight, stable_topoheight, page),
+        StepKind::BlocksMetadata => StepRequest::BlocksMetadata(stable_topoheight),
+        _ => StepRequest::Assets(our_topoheight, stable_topoheight, page)
+    }
+}
+
+// Fast sync progress persisted on disk to resume it after a restart
+// The pivot is the stable block used for the sync, it's kept to never mix data from two stable points
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootstrapProgress {
+    // step in progress
+    pub step: StepKind,
+    // last page fully processed for this step, None if no page was processed yet
+    pub last_page: Option<u64>,
+    pub pivot_topoheight: u64,
+    pub pivot_height: u64,
+    pub pivot_hash: Hash
+}
+
+impl BootstrapProgress {
+    // Create the progress from the next page to request
+    pub fn new(step: StepKind, next_page: Option<u64>, pivot_topoheight: u64, pivot_height: u64, pivot_hash: Hash) -> Self {
+        Self {
+            step,
+            last_page: next_page.map(|page| page.saturating_sub(1)),
+            pivot_topoheight,
+            pivot_height,
+            pivot_hash
+        }
+    }
+
+    // Page following the last processed one
+    pub fn next_page(&self) -> Option<u64> {
+        self.last_page.map(|page| page + 1)
+    }
+
+    pub fn next_step_request(&self, our_topoheight: u64) -> StepRequest<'static> {
+        resume_step_request(self.step, self.next_page(), our_topoheight, self.pivot_topoheight)
+    }
+}
+
+impl Serializer for BootstrapProgress {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let step = StepKind::read(reader)?;
+        let last_page = Option::read(reader)?;
+        let pivot_topoheight = reader.read_u64()?;
+        let pivot_height = reader.read_u64()?;
+        let pivot_hash = reader.read_hash()?;
+
+        Ok(Self {
+            step,
+            last_page,
+            pivot_topoheight,
+            pivot_height,
+            pivot_hash
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        self.step.write(writer);
+        self.last_page.write(writer);
+        writer.write_u64(&self.pivot_topoheight);
+        writer.write_u64(&self.pivot_height);
+        writer.write_hash(&self.pivot_hash);
+    }
+
+    fn size(&self) -> usize {
+        self.step.size() + self.last_page.size() + self.pivot_topoheight.size() + self.pivot_height.size() + self.pivot_hash.size()
+    }
+}
+
+// Progress of a fast sync, kept to resume it with another peer
+// Once a step is processed, the pivot is pinned: every next step is requested at its topoheight
+// and a peer that can't serve it is rejected instead of restarting the sync on another stable block
+#[derive(Debug, Default)]
+pub struct BootstrapCursor {
+    // our topoheight, pivot topoheight, height and hash used for the sync
+    pivot: Option<(u64, u64, u64, Hash)>,
+    // next step to request with its page
+    next: Option<(StepKind, Option<u64>)>,
+    // steps completed for this stable block
+    report: SyncReport
+}
+
+impl BootstrapCursor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Restore the cursor from the progress saved on disk
+    pub fn from_progress(progress: BootstrapProgress, our_topoheight: u64) -> Self {
+        let next_page = progress.next_page();
+        Self {
+            pivot: Some((our_topoheight, progress.pivot_topoheight, progress.pivot_height, progress.pivot_hash)),
+            next: Some((progress.step, next_page)),
+            report: SyncReport::new()
+        }
+    }
+
+    // Progress to persist on disk, None if nothing was processed yet
+    pub fn get_progress(&self) -> Option<BootstrapProgress> {
+        let (_, topoheight, height, hash) = self.pivot.as_ref()?;
+        let (step, page) = self.next?;
+        Some(BootstrapProgress::new(step, page, *topoheight, *height, hash.clone()))
+    }
+
+    // Pivot topoheight, height and hash pinned by the progress made, None if nothing was processed yet
+    pub fn get_pinned_pivot(&self) -> Option<(u64, u64, &Hash)> {
+        self.next.and(self.pivot.as_ref())
+            .map(|(_, topoheight, height, hash)| (*topoheight, *height, hash))
+    }
+
+    // Called once the chain info is received from a peer with the pivot to sync on
+    // Without progress the pivot is pinned, otherwise it must be the pinned one
+    // Returns the step (and its page) to continue from
+    pub fn resume(&mut self, our_topoheight: u64, pivot_topoheight: u64, pivot_height: u64, pivot_hash: &Hash) -> Result<(StepKind, Option<u64>), P2pError> {
+        match (&self.pivot, self.next) {
+            (Some((our, topoheight, height, hash)), Some(next)) => {
+                if *our != our_topoheight || *topoheight != pivot_topoheight || *height != pivot_height || hash != pivot_hash {
+                    return Err(P2pError::BootstrapPivotMismatch(*topoheight, hash.clone()))
+                }
+
+                Ok(next)
+            },
+            _ => {
+                self.pivot = Some((our_topoheight, pivot_topoheight, pivot_height, pivot_hash.clone()));
+                self.report = SyncReport::new();
+                Ok((StepKind::Assets, None))
+            }
+        }
+    }
+
+    // Save the next step to request once the current one is fully processed
+    pub fn save(&mut self, kind: StepKind, page: Option<u64>) {
+        self.next = Some((kind, page));
+    }
+
+    pub fn get_report_mut(&mut self) -> &mut SyncReport {
+        &mut self.report
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum StepRequest<'a> {
     // Request chain info (top topoheight, top height, top hash)
     ChainInfo(IndexSet<BlockId>),
@@ -134,12 +837,14 @@ pub enum StepRequest<'a> {
     Assets(u64, u64, Option<u64>),
     // Min topoheight, Max topoheight, Asset, pagination
     Keys(u64, u64, Option<u64>),
-    // Max topoheight, Asset, Accounts
-    Balances(u64, Cow<'a, Hash>, Cow<'a, IndexSet<PublicKey>>),
+    // Max topoheight, Asset, Accounts, pagination
+    Balances(u64, Cow<'a, Hash>, Cow<'a, IndexSet<PublicKey>>, Option<u64>),
     // Max topoheight, Accounts
     Nonces(u64, Cow<'a, IndexSet<PublicKey>>),
     // Request blocks metadata starting topoheight
-    BlocksMetadata(u64)
+    BlocksMetadata(u64),
+    // Request the block hash at a stable topoheight
+    BlockHashAt(u64)
 }
 
 impl<'a> StepRequest<'a> {
@@ -148,26 +853,37 @@ impl<'a> StepRequest<'a> {
             Self::ChainInfo(_) => StepKind::ChainInfo,
             Self::Assets(_, _, _) => StepKind::Assets,
             Self::Keys(_, _, _) => StepKind::Keys,
-            Self::Balances(_, _, _) => StepKind::Balances,
+            Self::Balances(_, _, _, _) => StepKind::Balances,
             Self::Nonces(_, _) => StepKind::Nonces,
-            Self::BlocksMetadata(_) => StepKind::BlocksMetadata
+            Self::BlocksMetadata(_) => StepKind::BlocksMetadata,
+            Self::BlockHashAt(_) => StepKind::BlockHashAt
         }
     }
 
     pub fn get_requested_topoheight(&self) -> Option<u64> {
         Some(*match self {
-            Self::ChainInfo(_) => return None,
+            // Out of range topoheight is answered with no hash instead of an error
+            Self::ChainInfo(_) | Self::BlockHashAt(_) => return None,
             Self::Assets(_, topo, _) => topo,
             Self::Keys(_, topo, _) => topo,
-            Self::Balances(topo, _, _) => topo,
+            Self::Balances(topo, _, _, _) => topo,
             Self::Nonces(topo, _) => topo,
             Self::BlocksMetadata(topo) => topo
         })
     }
+
+    // Requested page for the paginated steps
+    pub fn get_page(&self) -> Option<u64> {
+        match self {
+            Self::Assets(_, _, page) | Self::Keys(_, _, page) | Self::Balances(_, _, _, page) => *page,
+            _ => None
+        }
+    }
 }
 
-impl Serializer for StepRequest<'_> {
-    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+impl StepRequest<'_> {
+    // Read a step request, the Balances page is only present if it was negotiated with the peer
+    pub fn read_with(reader: &mut Reader, balances_pages: bool) -> Result<Self, ReaderError> {
         Ok(match reader.read_u8()? {
             0 => {
                 let len = reader.read_u8()?;
@@ -195,8 +911,8 @@ impl Serializer for StepRequest<'_> {
 
                 let page = Option::read(reader)?;
                 if let Some(page_number) = &page {
-                    if *page_number == 0 {
-                        debug!("Invalid page number (0) in Step Request");
+                    if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                        debug!("Invalid page number ({}) in Step Request", page_number);
                         return Err(ReaderError::InvalidValue)
                     }
                 }
@@ -212,8 +928,8 @@ impl Serializer for StepRequest<'_> {
 
                 let page = Option::read(reader)?;
                 if let Some(page_number) = &page {
-                    if *page_number == 0 {
-                        debug!("Invalid page number (0) in Step Request");
+                    if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                        debug!("Invalid page number ({}) in Step Request", page_number);
                         return Err(ReaderError::InvalidValue)
                     }
                 }
@@ -222,17 +938,35 @@ impl Serializer for StepRequest<'_> {
             3 => {
                 let topoheight = reader.read_u64()?;
                 let hash = Cow::<'_, Hash>::read(reader)?;
+                // Accounts set is bounded to MAX_ACCOUNTS_PER_REQUEST by its reader
                 let keys = Cow::<'_, IndexSet<PublicKey>>::read(reader)?;
-                Self::Balances(topoheight, hash, keys)
+
+                // Older peers request the balances of the whole accounts set at once
+                let page = if balances_pages {
+                    Option::read(reader)?
+                } else {
+                    None
+                };
+                if let Some(page_number) = &page {
+                    if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                        debug!("Invalid page number ({}) in Step Request", page_number);
+                        return Err(ReaderError::InvalidValue)
+                    }
+                }
+                Self::Balances(topoheight, hash, keys, page)
             },
             4 => {
                 let topoheight = reader.read_u64()?;
+                // Accounts set is bounded to MAX_ACCOUNTS_PER_REQUEST by its reader
                 let keys = Cow::<'_, IndexSet<PublicKey>>::read(reader)?;
                 Self::Nonces(topoheight, keys)
             },
             5 => {
                 Self::BlocksMetadata(reader.read_u64()?)
             },
+            6 => {
+                Self::BlockHashAt(reader.read_u64()?)
+            },
             id => {
                 debug!("Received invalid value for StepResponse: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -240,7 +974,8 @@ impl Serializer for StepRequest<'_> {
         })
     }
 
-    fn write(&self, writer: &mut Writer) {
+    // Write a step request, the Balances page is only written if it was negotiated with the peer
+    pub fn write_with(&self, writer: &mut Writer, balances_pages: bool) {
         match self {
             Self::ChainInfo(blocks) => {
                 writer.write_u8(0);
@@ -261,11 +996,14 @@ impl Serializer for StepRequest<'_> {
                 writer.write_u64(max);
                 page.write(writer);
             },
-            Self::Balances(topoheight, asset, accounts) => {
+            Self::Balances(topoheight, asset, accounts, page) => {
                 writer.write_u8(3);
                 writer.write_u64(topoheight);
                 writer.write_hash(asset);
                 accounts.write(writer);
+                if balances_pages {
+                    page.write(writer);
+                }
             },
             Self::Nonces(topoheight, nonces) => {
                 writer.write_u8(4);
@@ -276,38 +1014,120 @@ impl Serializer for StepRequest<'_> {
                 writer.write_u8(5);
                 writer.write_u64(topoheight);
             },
+            Self::BlockHashAt(topoheight) => {
+                writer.write_u8(6);
+                writer.write_u64(topoheight);
+            },
         };
     }
 
+}
+
+impl Serializer for StepRequest<'_> {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Self::read_with(reader, true)
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        self.write_with(writer, true);
+    }
+
     fn size(&self) -> usize {
         let size = match self {
             Self::ChainInfo(blocks) => 1 + blocks.size(),
             Self::Assets(min, max, page) => min.size() + max.size() + page.size(),
             Self::Keys(min, max, page) => min.size() + max.size() + page.size(),
-            Self::Balances(topoheight, asset, accounts) => topoheight.size() + asset.size() + accounts.size(),
+            Self::Balances(topoheight, asset, accounts, page) => topoheight.size() + asset.size() + accounts.size() + page.size(),
             Self::Nonces(topoheight, nonces) => topoheight.size() + nonces.size(),
-            Self::BlocksMetadata(topoheight) => topoheight.size()
+            Self::BlocksMetadata(topoheight) => topoheight.size(),
+            Self::BlockHashAt(topoheight) => topoheight.size()
         };
         // 1 for the id
         size + 1
     }
 }
 
+// Balance of a requested account for the asset of a Balances step
+// Tags 0 and 1 are the ones of the previous Option encoding, so a None sent by an older peer is read as Missing
+#[derive(Debug, Clone)]
+pub enum BalanceEntry {
+    // Account is registered but has no balance for this asset
+    Missing,
+    // Account is not registered at the requested topoheight
+    Unregistered,
+    // balance, output balance, balance type
+    Present(CiphertextCache, Option<CiphertextCache>, BalanceType)
+}
+
+impl BalanceEntry {
+    // Unregistered account for a peer
+    // Older peers only know the Option encoding and would reject the tag, they receive Missing
+    pub fn unregistered(balances_pages: bool) -> Self {
+        if balances_pages {
+            Self::Unregistered
+        } else {
+            Self::Missing
+        }
+    }
+}
+
+impl Serializer for BalanceEntry {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(match reader.read_u8()? {
+            0 => Self::Missing,
+            1 => Self::Present(CiphertextCache::read(reader)?, Option::read(reader)?, BalanceType::read(reader)?),
+            2 => Self::Unregistered,
+            id => {
+                debug!("Received invalid value for BalanceEntry: {}", id);
+                return Err(ReaderError::InvalidValue)
+            }
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::Missing => writer.write_u8(0),
+            Self::Present(balance, output_balance, balance_type) => {
+                writer.write_u8(1);
+                balance.write(writer);
+                output_balance.write(writer);
+                balance_type.write(writer);
+            },
+            Self::Unregistered => writer.write_u8(2)
+        }
+    }
+
+    fn size(&self) -> usize {
+        1 + match self {
+            Self::Present(balance, output_balance, balance_type) => balance.size() + output_balance.size() + balance_type.size(),
+            _ => 0
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum StepResponse {
     // common point, topoheight of stable hash, stable height, stable hash
     ChainInfo(Option<CommonPoint>, u64, u64, Hash),
     // Set of assets, pagination
+    // An empty set is always the last page: it must not have a next page
     Assets(IndexSet<AssetWithData>, Option<u64>),
     // Set of keys, pagination
+    // Same pagination rules as the assets
     Keys(IndexSet<PublicKey>, Option<u64>),
-    // Balances requested (optional because not all accounts may have balances for requested asset)
-    // (CiphertextCache, Option<CiphertextCache>) (balance, output balance)
-    Balances(Vec<Option<(CiphertextCache, Option<CiphertextCache>, BalanceType)>>),
+    // Balance entry for each requested account of the page, pagination
+    // An account may be unregistered or have no balance for the requested asset
+    Balances(Vec<BalanceEntry>, Option<u64>),
     // Nonces for requested accounts
     Nonces(Vec<u64>),
     // top blocks metadata
+    // A duplicated block hash is rejected, it could hide a real block
     BlocksMetadata(IndexSet<BlockMetadata>),
+    // Block hash at the requested topoheight, None if not in the stable range
+    BlockHashAt(Option<Hash>),
+    // Reply to a ChainInfo step when no common point was found
+    // Only sent to peers supporting it, ChainInfo with no common point is sent otherwise
+    NoCommonPoint(ChainDiagnostic)
 }
 
 impl StepResponse {
@@ -316,15 +1136,97 @@ impl StepResponse {
             Self::ChainInfo(_, _, _, _) => StepKind::ChainInfo,
             Self::Assets(_, _) => StepKind::Assets,
             Self::Keys(_, _) => StepKind::Keys,
-            Self::Balances(_) => StepKind::Balances,
+            Self::Balances(_, _) => StepKind::Balances,
             Self::Nonces(_) => StepKind::Nonces,
-            Self::BlocksMetadata(_) => StepKind::BlocksMetadata
+            Self::BlocksMetadata(_) => StepKind::BlocksMetadata,
+            Self::BlockHashAt(_) => StepKind::BlockHashAt,
+            Self::NoCommonPoint(_) => StepKind::ChainInfo
+        }
+    }
+
+    // Read a keys response without buffering the whole set
+    // The callback is called for each key as soon as it's read, keys out of the canonical order are rejected
+    // Returns the next page
+    pub fn read_keys_streaming<F: FnMut(PublicKey)>(reader: &mut Reader, mut on_key: F) -> Result<Option<u64>, ReaderError> {
+        let id = reader.read_u8()?;
+        if id != 2 {
+            debug!("Expected a keys StepResponse but got {}", id);
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let count = reader.read_u16()? as usize;
+        if count > MAX_ITEMS_PER_PAGE {
+            debug!("Invalid keys length ({}) in Step Response", count);
+            return Err(ReaderError::InvalidSize)
+        }
+
+        let mut previous: Option<PublicKey> = None;
+        for _ in 0..count {
+            let key = PublicKey::read(reader)?;
+            if !is_after_key(previous.as_ref(), &key) {
+                debug!("Keys are not in canonical order in Step Response");
+                return Err(ReaderError::InvalidValue)
+            }
+            previous = Some(key.clone());
+            on_key(key);
+        }
+
+        let page = Option::read(reader)?;
+        if let Some(page_number) = &page {
+            if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                debug!("Invalid page number ({}) in Step Response", page_number);
+                return Err(ReaderError::InvalidValue)
+            }
+        }
+
+        if count == 0 && page.is_some() {
+            debug!("Empty keys page with a next page in Step Response");
+            return Err(ReaderError::InvalidValue)
         }
+
+        Ok(page)
+    }
+
+    // Blocks metadata ordered by descending cumulative difficulty
+    // Blocks with the same cumulative difficulty are ordered by ascending hash
+    // Returns None if this isn't a blocks metadata response
+    pub fn blocks_metadata_sorted(&self) -> Option<Vec<&BlockMetadata>> {
+        match self {
+            Self::BlocksMetadata(blocks) => {
+                let mut sorted: Vec<&BlockMetadata> = blocks.iter().collect();
+                sorted.sort_by(|a, b| b.cumulative_difficulty.cmp(&a.cumulative_difficulty).then_with(|| a.hash.cmp(&b.hash)));
+                Some(sorted)
+            },
+            _ => None
+        }
+    }
+
+    // Cheap emission sanity check of a blocks metadata response
+    // Blocks are sent from the stable block to the lowest one, and the supply of a block includes its reward
+    // So the rewards of all the blocks above the lowest one must sum up to the supply delta between both ends
+    // Returns None if this isn't a blocks metadata response or if it's empty
+    pub fn verify_supply_emission(&self) -> Option<bool> {
+        let Self::BlocksMetadata(blocks) = self else {
+            return None
+        };
+
+        let (highest, lowest) = (blocks.first()?, blocks.last()?);
+        let Some(delta) = supply_delta(lowest, highest) else {
+            debug!("Supply of block {} is lower than the supply of block {}", highest.hash, lowest.hash);
+            return Some(false)
+        };
+
+        let rewards = blocks.iter()
+            .take(blocks.len() - 1)
+            .try_fold(0u64, |total, block| total.checked_add(block.reward));
+
+        Some(rewards == Some(delta))
     }
 }
 
-impl Serializer for StepResponse {
-    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+impl StepResponse {
+    // Read a step response, the Balances next page is only present if it was negotiated with the peer
+    pub fn read_with(reader: &mut Reader, balances_pages: bool) -> Result<Self, ReaderError> {
         Ok(match reader.read_u8()? {
             0 => {
                 let common_point = Option::read(reader)?;
@@ -338,32 +1240,99 @@ impl Serializer for StepResponse {
                 let assets = IndexSet::<AssetWithData>::read(reader)?;
                 let page = Option::read(reader)?;
                 if let Some(page_number) = &page {
-                    if *page_number == 0 {
-                        debug!("Invalid page number (0) in Step Response");
+                    if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                        debug!("Invalid page number ({}) in Step Response", page_number);
                         return Err(ReaderError::InvalidValue)
                     }
                 }
+                if assets.is_empty() && page.is_some() {
+                    debug!("Empty assets page with a next page in Step Response");
+                    return Err(ReaderError::InvalidValue)
+                }
                 Self::Assets(assets, page)
             },
             2 => {
                 let keys = IndexSet::<PublicKey>::read(reader)?;
+                let mut previous = None;
+                for key in keys.iter() {
+                    if !is_after_key(previous, key) {
+                        debug!("Keys are not in canonical order in Step Response");
+                        return Err(ReaderError::InvalidValue)
+                    }
+                    previous = Some(key);
+                }
+
                 let page = Option::read(reader)?;
                 if let Some(page_number) = &page {
-                    if *page_number == 0 {
-                        debug!("Invalid page number (0) in Step Response");
+                    if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                        debug!("Invalid page number ({}) in Step Response", page_number);
                         return Err(ReaderError::InvalidValue)
                     }
                 }
+                if keys.is_empty() && page.is_some() {
+                    debug!("Empty keys page with a next page in Step Response");
+                    return Err(ReaderError::InvalidValue)
+                }
                 Self::Keys(keys, page)
             },
             3 => {
-                Self::Balances(Vec::read(reader)?)
+                // Don't trust the declared length before allocating anything
+                // Balances are answered by pages, or for the whole accounts set by older peers
+                let len = reader.read_u16()? as usize;
+                if len > balances_page_size(balances_pages) {
+                    debug!("Invalid balances length ({}) in Step Response", len);
+                    return Err(ReaderError::InvalidValue)
+                }
+
+                // Each entry is at least one byte (entry tag)
+                if len > reader.size() {
+                    debug!("Balances length ({}) is above remaining bytes ({}) in Step Response", len, reader.size());
+                    return Err(ReaderError::InvalidValue)
+                }
+
+                let mut balances = Vec::with_capacity(len);
+                for _ in 0..len {
+                    balances.push(BalanceEntry::read(reader)?);
+                }
+
+                // Older peers answer the whole accounts set at once
+                let page = if balances_pages {
+                    Option::read(reader)?
+                } else {
+                    None
+                };
+                if let Some(page_number) = &page {
+                    if *page_number == 0 || *page_number >= MAX_STEP_PAGES {
+                        debug!("Invalid page number ({}) in Step Response", page_number);
+                        return Err(ReaderError::InvalidValue)
+                    }
+                }
+                Self::Balances(balances, page)
             },
             4 => {
                 Self::Nonces(Vec::<u64>::read(reader)?)
             },
             5 => {
-                Self::BlocksMetadata(IndexSet::read(reader)?)
+                let len = reader.read_u16()? as usize;
+                if len > MAX_ITEMS_PER_PAGE {
+                    debug!("Invalid blocks metadata length ({}) in Step Response", len);
+                    return Err(ReaderError::InvalidSize)
+                }
+
+                let mut blocks = IndexSet::with_capacity(len);
+                for _ in 0..len {
+                    if !blocks.insert(BlockMetadata::read(reader)?) {
+                        debug!("Duplicated block metadata in Step Response");
+                        return Err(ReaderError::InvalidValue)
+                    }
+                }
+                Self::BlocksMetadata(blocks)
+            },
+            6 => {
+                Self::BlockHashAt(Option::read(reader)?)
+            },
+            7 => {
+                Self::NoCommonPoint(ChainDiagnostic::read(reader)?)
             },
             id => {
                 debug!("Received invalid value for StepResponse: {}", id);
@@ -372,7 +1341,8 @@ impl Serializer for StepResponse {
         })
     }
 
-    fn write(&self, writer: &mut Writer) {
+    // Write a step response, the Balances next page is only written if it was negotiated with the peer
+    pub fn write_with(&self, writer: &mut Writer, balances_pages: bool) {
         match self {
             Self::ChainInfo(common_point, topoheight, stable_height, hash) => {
                 writer.write_u8(0);
@@ -391,9 +1361,12 @@ impl Serializer for StepResponse {
                 keys.write(writer);
                 page.write(writer);
             },
-            Self::Balances(balances) => {
+            Self::Balances(balances, page) => {
                 writer.write_u8(3);
                 balances.write(writer);
+                if balances_pages {
+                    page.write(writer);
+                }
             },
             Self::Nonces(nonces) => {
                 writer.write_u8(4);
@@ -402,10 +1375,29 @@ impl Serializer for StepResponse {
             Self::BlocksMetadata(blocks) => {
                 writer.write_u8(5);
                 blocks.write(writer);
+            },
+            Self::BlockHashAt(hash) => {
+                writer.write_u8(6);
+                hash.write(writer);
+            },
+            Self::NoCommonPoint(diagnostic) => {
+                writer.write_u8(7);
+                diagnostic.write(writer);
             }
         };
     }
 
+}
+
+impl Serializer for StepResponse {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Self::read_with(reader, true)
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        self.write_with(writer, true);
+    }
+
     fn size(&self) -> usize {
         let size = match self {
             Self::ChainInfo(common_point, topoheight, stable_height, hash) => {
@@ -417,14 +1409,20 @@ impl Serializer for StepResponse {
             Self::Keys(keys, page) => {
                 keys.size() + page.size()
             },
-            Self::Balances(balances) => {
-                balances.size()
+            Self::Balances(balances, page) => {
+                balances.size() + page.size()
             },
             Self::Nonces(nonces) => {
                 nonces.size()
             },
             Self::BlocksMetadata(blocks) => {
                 blocks.size()
+            },
+            Self::BlockHashAt(hash) => {
+                hash.size()
+            },
+            Self::NoCommonPoint(diagnostic) => {
+                diagnostic.size()
             }
         };
         // 1 for the id
@@ -432,72 +1430,1203 @@ impl Serializer for StepResponse {
     }
 }
 
+// Checkpoint saved by a light client from a ChainInfo response
+// It can be trusted later instead of verifying the chain again from genesis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LightCheckpoint {
+    // topoheight of the stable hash
+    pub topoheight: u64,
+    pub stable_height: u64,
+    pub hash: Hash,
+    // Cumulative difficulty of the stable block
+    pub cumulative_difficulty: CumulativeDifficulty,
+    // When the checkpoint was created
+    pub timestamp: TimestampSeconds
+}
+
+impl LightCheckpoint {
+    // Create a checkpoint from a ChainInfo response
+    // Cumulative difficulty of the stable block must be retrieved from its metadata
+    pub fn from_chain_info(response: &StepResponse, cumulative_difficulty: CumulativeDifficulty, timestamp: TimestampSeconds) -> Option<Self> {
+        match response {
+            StepResponse::ChainInfo(_, topoheight, stable_height, hash) => Some(Self {
+                topoheight: *topoheight,
+                stable_height: *stable_height,
+                hash: hash.clone(),
+                cumulative_difficulty,
+                timestamp
+            }),
+            _ => None
+        }
+    }
+
+    // Verify that a later ChainInfo extends this checkpoint
+    // The common point must be at or above the checkpoint (with the same hash at its topoheight)
+    // and the cumulative difficulty must have increased
+    pub fn is_extended_by(&self, response: &StepResponse, cumulative_difficulty: &CumulativeDifficulty) -> bool {
+        let StepResponse::ChainInfo(common_point, topoheight, stable_height, _) = response else {
+            return false
+        };
+
+        let Some(common_point) = common_point else {
+            debug!("No common point with checkpoint at topoheight {}", self.topoheight);
+            return false
+        };
+
+        if common_point.get_topoheight() < self.topoheight {
+            debug!("Common point {} is below checkpoint topoheight {}", common_point.get_topoheight(), self.topoheight);
+            return false
+        }
+
+        if common_point.get_topoheight() == self.topoheight && *common_point.get_hash() != self.hash {
+            debug!("Common point {} doesn't match checkpoint {}", common_point.get_hash(), self.hash);
+            return false
+        }
+
+        *topoheight >= self.topoheight
+            && *stable_height >= self.stable_height
+            && *cumulative_difficulty > self.cumulative_difficulty
+    }
+}
+
+impl Serializer for LightCheckpoint {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let topoheight = reader.read_u64()?;
+        let stable_height = reader.read_u64()?;
+        let hash = reader.read_hash()?;
+        let cumulative_difficulty = CumulativeDifficulty::read(reader)?;
+        let timestamp = reader.read_u64()?;
+
+        Ok(Self {
+            topoheight,
+            stable_height,
+            hash,
+            cumulative_difficulty,
+            timestamp
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u64(&self.topoheight);
+        writer.write_u64(&self.stable_height);
+        writer.write_hash(&self.hash);
+        self.cumulative_difficulty.write(writer);
+        writer.write_u64(&self.timestamp);
+    }
+
+    fn size(&self) -> usize {
+        self.topoheight.size()
+        + self.stable_height.size()
+        + self.hash.size()
+        + self.cumulative_difficulty.size()
+        + self.timestamp.size()
+    }
+}
+
+// Envelope of a bootstrap chain request as sent on the wire
+// Like the responses, the step is only decoded once we know the features negotiated with the peer
 #[derive(Debug)]
-pub struct BootstrapChainRequest<'a> {
-    step: StepRequest<'a>
+pub struct BootstrapChainRequest {
+    data: Vec<u8>
 }
 
-impl<'a> BootstrapChainRequest<'a> {
-    pub fn new(step: StepRequest<'a>) -> Self {
+impl BootstrapChainRequest {
+    pub fn new(step: &StepRequest, balances_pages: bool) -> Self {
+        let mut writer = Writer::new();
+        step.write_with(&mut writer, balances_pages);
         Self {
-            step
+            data: writer.bytes()
         }
     }
 
-    pub fn kind(&self) -> StepKind {
-        self.step.kind()
-    }
+    // Decode the requested step
+    pub fn decode(&self, balances_pages: bool) -> Result<StepRequest<'static>, ReaderError> {
+        let mut reader = Reader::new(&self.data);
+        let step = StepRequest::read_with(&mut reader, balances_pages)?;
+        if reader.size() > 0 {
+            debug!("Bootstrap chain request ({:?}) has {} unexpected bytes", step.kind(), reader.size());
+            return Err(ReaderError::InvalidSize)
+        }
 
-    pub fn step(self) -> StepRequest<'a> {
-        self.step
+        Ok(step)
     }
 }
 
-impl Serializer for BootstrapChainRequest<'_> {
+impl Serializer for BootstrapChainRequest {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
-        Ok(Self::new(StepRequest::read(reader)?))
+        // request takes the rest of the packet
+        let data = reader.read_bytes(reader.size())?;
+        Ok(Self {
+            data
+        })
     }
 
     fn write(&self, writer: &mut Writer) {
-        self.step.write(writer);
+        writer.write_bytes(&self.data);
     }
 
     fn size(&self) -> usize {
-        self.step.size()
+        self.data.len()
     }
 }
 
+// Envelope of a bootstrap chain response as sent on the wire
+// The checksum is negotiated with the peer, so the step is only decoded
+// once we know if the peer must send it
 #[derive(Debug)]
 pub struct BootstrapChainResponse {
-    response: StepResponse
+    data: Vec<u8>
 }
 
 impl BootstrapChainResponse {
-    pub fn new(response: StepResponse) -> Self {
+    pub fn new(response: &StepResponse, balances_pages: bool) -> Self {
+        let mut writer = Writer::new();
+        response.write_with(&mut writer, balances_pages);
         Self {
-            response
+            data: writer.bytes()
         }
     }
 
-    pub fn kind(&self) -> StepKind {
-        self.response.kind()
+    // Response followed by its checksum, only for peers supporting it
+    pub fn with_checksum(response: &StepResponse, balances_pages: bool) -> Self {
+        let mut data = Self::new(response, balances_pages).data;
+        let checksum = hash(&data);
+        data.extend_from_slice(checksum.as_bytes());
+        Self {
+            data
+        }
     }
 
-    pub fn response(self) -> StepResponse {
-        self.response
+    // Decode the response of the requested step, the checksum is required if it was negotiated and rejected otherwise
+    // The budget of the step is verified on the received length before decoding anything
+    pub fn decode(&self, kind: StepKind, checksum: bool, balances_pages: bool) -> Result<StepResponse, P2pError> {
+        verify_step_response_size(kind, self.data.len(), balances_pages)?;
+
+        let mut reader = Reader::new(&self.data);
+        let response = StepResponse::read_with(&mut reader, balances_pages)?;
+        if checksum {
+            let step_size = reader.total_read();
+            let expected = reader.read_hash()?;
+            if hash(&self.data[..step_size]) != expected {
+                debug!("Invalid checksum for bootstrap chain response ({:?})", response.kind());
+                return Err(ReaderError::ChecksumMismatch.into())
+            }
+        }
+
+        if reader.size() > 0 {
+            debug!("Bootstrap chain response ({:?}) has {} unexpected bytes", response.kind(), reader.size());
+            return Err(ReaderError::InvalidSize.into())
+        }
+
+        Ok(response)
     }
 }
 
 impl Serializer for BootstrapChainResponse {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
-        Ok(Self::new(StepResponse::read(reader)?))
+        // response takes the rest of the packet
+        let data = reader.read_bytes(reader.size())?;
+        Ok(Self {
+            data
+        })
     }
 
     fn write(&self, writer: &mut Writer) {
-        self.response.write(writer);
+        writer.write_bytes(&self.data);
     }
 
     fn size(&self) -> usize {
-        self.response.size()
+        self.data.len()
+    }
+}
+
+// Envelope of a bootstrap chain response compressed using deflate
+#[derive(Debug)]
+pub struct CompressedBootstrapChainResponse {
+    data: Vec<u8>
+}
+
+impl CompressedBootstrapChainResponse {
+    pub fn compress(response: &BootstrapChainResponse) -> IOResult<Self> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&response.to_bytes())?;
+        Ok(Self {
+            data: encoder.finish()?
+        })
+    }
+
+    // Decompress the response of the requested step
+    // Decompression is aborted as soon as the budget of the step is exceeded
+    // so a small payload can't be inflated above it
+    pub fn decompress(&self, kind: StepKind, balances_pages: bool) -> Result<BootstrapChainResponse, P2pError> {
+        let max = kind.max_response_size(balances_pages);
+        let mut bytes = Vec::new();
+        DeflateDecoder::new(self.data.as_slice())
+            .take(max as u64 + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| {
+                debug!("Error while decompressing bootstrap chain response: {}", e);
+                ReaderError::InvalidValue
+            })?;
+
+        verify_step_response_size(kind, bytes.len(), balances_pages)?;
+
+        Ok(BootstrapChainResponse {
+            data: bytes
+        })
+    }
+}
+
+impl Serializer for CompressedBootstrapChainResponse {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        // compressed data takes the rest of the packet
+        let data = reader.read_bytes(reader.size())?;
+        Ok(Self {
+            data
+        })
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bytes(&self.data);
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{asset::AssetData, crypto::KeyPair};
+    use super::*;
+
+    #[test]
+    fn test_common_point_cache_shortcut() {
+        let mut cache = CommonPointCache::new(NonZeroUsize::new(2).unwrap());
+        let top_hash = Hash::new([1u8; 32]);
+        let point = CommonPoint::new(top_hash.clone(), 100);
+        assert!(cache.get_shortcut(1, &top_hash).is_none());
+
+        cache.insert(1, point.clone());
+        let shortcut = cache.get_shortcut(1, &top_hash).unwrap();
+        assert_eq!(shortcut, point);
+
+        // Only the cached point is sent
+        let StepRequest::ChainInfo(blocks) = shortcut_chain_info_request(&shortcut) else {
+            panic!("expected a chain info request");
+        };
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].get_hash(), &top_hash);
+        assert_eq!(blocks[0].get_topoheight(), 100);
+
+        // Peer agreed on it
+        let response = StepResponse::ChainInfo(Some(point.clone()), 200, 150, Hash::zero());
+        assert!(is_shortcut_accepted(&shortcut, &response));
+        // Peer rejected it, the full list must be sent
+        let response = StepResponse::ChainInfo(None, 200, 150, Hash::zero());
+        assert!(!is_shortcut_accepted(&shortcut, &response));
+
+        // Our chain moved, the cached point is dropped
+        assert!(cache.get_shortcut(1, &Hash::new([2u8; 32])).is_none());
+        assert!(cache.get_shortcut(1, &top_hash).is_none());
+        assert!(cache.remove(1).is_none());
+    }
+
+    #[test]
+    fn test_balances_oversized_length() {
+        // Declared length above the maximum balances per page
+        for len in [u16::MAX, MAX_BALANCES_PER_PAGE as u16 + 1] {
+            let mut writer = Writer::new();
+            writer.write_u8(3);
+            writer.write_u16(len);
+            assert!(matches!(StepResponse::from_bytes(&writer.bytes()), Err(ReaderError::InvalidValue)));
+        }
+
+        // Declared length above the remaining bytes
+        let mut writer = Writer::new();
+        writer.write_u8(3);
+        writer.write_u16(MAX_BALANCES_PER_PAGE as u16);
+        writer.write_bool(false);
+        assert!(matches!(StepResponse::from_bytes(&writer.bytes()), Err(ReaderError::InvalidValue)));
+
+        // Valid empty entries
+        let mut writer = Writer::new();
+        writer.write_u8(3);
+        writer.write_u16(2);
+        writer.write_bool(false);
+        writer.write_bool(false);
+        // no next page
+        writer.write_bool(false);
+        let response = StepResponse::from_bytes(&writer.bytes()).unwrap();
+        assert!(matches!(response, StepResponse::Balances(balances, None) if balances.len() == 2));
+    }
+
+    #[test]
+    fn test_balances_legacy_length() {
+        // Older peers answer the whole accounts set at once
+        let response = StepResponse::Balances(vec![BalanceEntry::Missing; MAX_BALANCES_PER_PAGE + 44], None);
+        let legacy = BootstrapChainResponse::with_checksum(&response, false);
+        let decoded = legacy.decode(StepKind::Balances, true, false).unwrap();
+        assert!(matches!(decoded, StepResponse::Balances(balances, None) if balances.len() == MAX_BALANCES_PER_PAGE + 44));
+
+        // Same length is above a page for peers supporting the pages
+        let paged = BootstrapChainResponse::with_checksum(&response, true);
+        assert!(paged.decode(StepKind::Balances, true, true).is_err());
+
+        // Legacy answers are still bounded by the accounts per request
+        let response = StepResponse::Balances(vec![BalanceEntry::Missing; MAX_ACCOUNTS_PER_REQUEST + 1], None);
+        let legacy = BootstrapChainResponse::new(&response, false);
+        assert!(legacy.decode(StepKind::Balances, false, false).is_err());
+
+        assert!(StepKind::Balances.max_response_size(true) < StepKind::Balances.max_response_size(false));
+        assert!(StepKind::Balances.max_response_size(false) <= MAX_STEP_RESPONSE_BYTES);
+    }
+
+    #[test]
+    fn test_pages_ceiling() {
+        let stable_topoheight = 10;
+        let ceiling = max_pages(StepKind::Keys, stable_topoheight);
+        assert!(ceiling > 1);
+
+        // Mock peer announcing a next page forever
+        let mock_peer = |page: u64| StepResponse::Keys(IndexSet::from([KeyPair::new().get_public_key().compress()]), Some(page + 1));
+        let mut page = 0;
+        let err = loop {
+            let StepResponse::Keys(_, next_page) = mock_peer(page) else {
+                unreachable!()
+            };
+            if let Err(e) = verify_next_page(StepKind::Keys, next_page, stable_topoheight) {
+                break e
+            }
+            page = next_page.unwrap();
+        };
+        assert!(matches!(err, P2pError::TooManyPages { kind: StepKind::Keys }));
+        assert_eq!(page + 1, ceiling);
+
+        // Last page doesn't announce a next one
+        assert!(verify_next_page(StepKind::Assets, None, 0).is_ok());
+        // Balances of a full keys page are split in several pages
+        let balances_ceiling = max_pages(StepKind::Balances, stable_topoheight);
+        assert_eq!(balances_ceiling, (MAX_ACCOUNTS_PER_REQUEST / MAX_BALANCES_PER_PAGE) as u64);
+        assert!(verify_next_page(StepKind::Balances, Some(balances_ceiling - 1), stable_topoheight).is_ok());
+        assert!(verify_next_page(StepKind::Balances, Some(balances_ceiling), stable_topoheight).is_err());
+
+        // Huge stable topoheight announced by a peer is clamped to the protocol ceiling
+        assert_eq!(max_pages(StepKind::Assets, u64::MAX), MAX_STEP_PAGES);
+        assert_eq!(max_pages(StepKind::Keys, u64::MAX), MAX_STEP_PAGES);
+        assert!(verify_next_page(StepKind::Keys, Some(MAX_STEP_PAGES - 1), u64::MAX).is_ok());
+        assert!(matches!(verify_next_page(StepKind::Keys, Some(MAX_STEP_PAGES), u64::MAX), Err(P2pError::TooManyPages { kind: StepKind::Keys })));
+
+        // Pages above the ceiling are rejected when reading requests and responses
+        let request = StepRequest::Keys(0, u64::MAX, Some(MAX_STEP_PAGES));
+        assert!(matches!(StepRequest::from_bytes(&request.to_bytes()), Err(ReaderError::InvalidValue)));
+        let response = StepResponse::Keys(IndexSet::from([KeyPair::new().get_public_key().compress()]), Some(MAX_STEP_PAGES));
+        assert!(matches!(StepResponse::from_bytes(&response.to_bytes()), Err(ReaderError::InvalidValue)));
+        let response = StepResponse::Keys(IndexSet::from([KeyPair::new().get_public_key().compress()]), Some(MAX_STEP_PAGES - 1));
+        assert!(StepResponse::from_bytes(&response.to_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_step_error_context() {
+        let asset = Hash::zero();
+        let keys = IndexSet::new();
+        let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), Some(2));
+        let error = step_error(7, &request, ReaderError::InvalidSize);
+        let P2pError::BootstrapStep { peer, kind, page, source } = &error else {
+            panic!("expected a bootstrap step error, got {}", error)
+        };
+        assert_eq!(*peer, 7);
+        assert_eq!(*kind, StepKind::Balances);
+        assert_eq!(*page, Some(2));
+        assert!(matches!(**source, P2pError::ReaderError(ReaderError::InvalidSize)));
+
+        let message = error.to_string();
+        assert!(message.contains("Balances") && message.contains("Some(2)") && message.contains("peer 7"));
+
+        // Context of the first step is kept
+        let error = step_error(8, &StepRequest::BlocksMetadata(10), error);
+        assert!(matches!(error, P2pError::BootstrapStep { peer: 7, kind: StepKind::Balances, .. }));
+
+        assert_eq!(StepRequest::BlocksMetadata(10).get_page(), None);
+    }
+
+    #[test]
+    fn test_balance_entries() {
+        let keypair = KeyPair::new();
+        let ciphertext = CiphertextCache::Compressed(keypair.get_public_key().encrypt(10u64).compress());
+        let entries = [
+            BalanceEntry::Missing,
+            BalanceEntry::Unregistered,
+            BalanceEntry::Present(ciphertext.clone(), None, BalanceType::Input),
+            BalanceEntry::Present(ciphertext.clone(), Some(ciphertext.clone()), BalanceType::Both)
+        ];
+
+        for entry in entries {
+            let bytes = entry.to_bytes();
+            assert_eq!(bytes.len(), entry.size());
+            let decoded = BalanceEntry::from_bytes(&bytes).unwrap();
+            assert_eq!(std::mem::discriminant(&decoded), std::mem::discriminant(&entry));
+            assert_eq!(decoded.to_bytes(), bytes);
+        }
+
+        // Entries sent as an Option by older peers
+        let legacy: Option<(CiphertextCache, Option<CiphertextCache>, BalanceType)> = None;
+        assert!(matches!(BalanceEntry::from_bytes(&legacy.to_bytes()), Ok(BalanceEntry::Missing)));
+        let legacy = Some((ciphertext, None, BalanceType::Output));
+        assert!(matches!(BalanceEntry::from_bytes(&legacy.to_bytes()), Ok(BalanceEntry::Present(_, None, BalanceType::Output))));
+
+        // Unregistered is only sent to peers negotiating it, older peers can read what they receive
+        let entry = BalanceEntry::unregistered(false);
+        assert!(matches!(Option::<(CiphertextCache, Option<CiphertextCache>, BalanceType)>::from_bytes(&entry.to_bytes()), Ok(None)));
+        assert!(matches!(BalanceEntry::unregistered(true), BalanceEntry::Unregistered));
+
+        assert!(matches!(BalanceEntry::from_bytes(&[3]), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_blocks_metadata_lenient() {
+        let blocks: Vec<BlockMetadata> = (0..3u8).map(|i| BlockMetadata {
+            hash: Hash::new([i; 32]),
+            supply: i as u64,
+            reward: i as u64,
+            difficulty: Difficulty::from(i as u64 + 1),
+            cumulative_difficulty: CumulativeDifficulty::from(i as u64 + 1),
+            p: VarUint::from(i as u64)
+        }).collect();
+
+        let mut writer = Writer::new();
+        writer.write_u16(blocks.len() as u16);
+        blocks[0].write(&mut writer);
+        // Corrupt the difficulty of the second block with an invalid size but keep its framing
+        writer.write_hash(&blocks[1].hash);
+        writer.write_u64(&blocks[1].supply);
+        writer.write_u64(&blocks[1].reward);
+        writer.write_u8(33);
+        writer.write_bytes(&[0xFF; 33]);
+        blocks[1].cumulative_difficulty.write(&mut writer);
+        blocks[1].p.write(&mut writer);
+        blocks[2].write(&mut writer);
+        let bytes = writer.bytes();
+
+        // Strict mode reject everything
+        assert!(IndexSet::<BlockMetadata>::from_bytes(&bytes).is_err());
+
+        let (valid, skipped) = read_blocks_metadata_lenient(&mut Reader::new(&bytes)).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(valid.len(), 2);
+        assert_eq!(valid[0].hash, blocks[0].hash);
+        assert_eq!(valid[1].hash, blocks[2].hash);
+        assert_eq!(valid[1].difficulty, blocks[2].difficulty);
+
+        // Truncated data can't be recovered
+        assert!(read_blocks_metadata_lenient(&mut Reader::new(&bytes[..bytes.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn test_block_hash_at() {
+        let request = StepRequest::BlockHashAt(42);
+        let decoded = StepRequest::from_bytes(&request.to_bytes()).unwrap();
+        assert!(matches!(decoded, StepRequest::BlockHashAt(42)));
+        assert_eq!(decoded.kind(), StepKind::BlockHashAt);
+        // Range is checked by the responder to answer None
+        assert!(decoded.get_requested_topoheight().is_none());
+
+        let hash = Hash::new([1u8; 32]);
+        let response = StepResponse::BlockHashAt(Some(hash.clone()));
+        let decoded = StepResponse::from_bytes(&response.to_bytes()).unwrap();
+        assert!(matches!(decoded, StepResponse::BlockHashAt(Some(h)) if h == hash));
+
+        let decoded = StepResponse::from_bytes(&StepResponse::BlockHashAt(None).to_bytes()).unwrap();
+        assert!(matches!(decoded, StepResponse::BlockHashAt(None)));
+    }
+
+    #[test]
+    fn test_block_hash_at_range() {
+        assert!(is_block_hash_at_available(100, None, 100));
+        assert!(is_block_hash_at_available(0, None, 100));
+        assert!(is_block_hash_at_available(51, Some(50), 100));
+        // Beyond the stable topoheight
+        assert!(!is_block_hash_at_available(101, None, 100));
+        // Pruned
+        assert!(!is_block_hash_at_available(50, Some(50), 100));
+    }
+
+    #[test]
+    fn test_balances_pages() {
+        // 600 accounts are answered in three pages
+        let (range, next_page) = balances_page(600, None, MAX_BALANCES_PER_PAGE).unwrap();
+        assert_eq!(range, 0..MAX_BALANCES_PER_PAGE);
+        assert_eq!(next_page, Some(1));
+
+        let (range, next_page) = balances_page(600, next_page, MAX_BALANCES_PER_PAGE).unwrap();
+        assert_eq!(range, MAX_BALANCES_PER_PAGE..MAX_BALANCES_PER_PAGE * 2);
+        assert_eq!(next_page, Some(2));
+
+        let (range, next_page) = balances_page(600, next_page, MAX_BALANCES_PER_PAGE).unwrap();
+        assert_eq!(range, MAX_BALANCES_PER_PAGE * 2..600);
+        assert_eq!(next_page, None);
+
+        // Out of the accounts set
+        assert!(balances_page(600, Some(3), MAX_BALANCES_PER_PAGE).is_none());
+
+        // Exactly one full page
+        let (range, next_page) = balances_page(MAX_BALANCES_PER_PAGE, None, MAX_BALANCES_PER_PAGE).unwrap();
+        assert_eq!(range, 0..MAX_BALANCES_PER_PAGE);
+        assert_eq!(next_page, None);
+
+        // Empty set
+        let (range, next_page) = balances_page(0, None, MAX_BALANCES_PER_PAGE).unwrap();
+        assert!(range.is_empty());
+        assert_eq!(next_page, None);
+
+        assert_eq!(balances_pages(600, MAX_BALANCES_PER_PAGE), 3);
+        assert_eq!(balances_pages(MAX_BALANCES_PER_PAGE, MAX_BALANCES_PER_PAGE), 1);
+        assert_eq!(balances_pages(MAX_BALANCES_PER_PAGE + 1, MAX_BALANCES_PER_PAGE), 2);
+        assert_eq!(balances_pages(MAX_ACCOUNTS_PER_REQUEST, MAX_BALANCES_PER_PAGE), 4);
+        assert_eq!(balances_pages(0, MAX_BALANCES_PER_PAGE), 1);
+
+        // Older peers answer the whole accounts set at once
+        let page_size = balances_page_size(false);
+        let (range, next_page) = balances_page(MAX_ACCOUNTS_PER_REQUEST, None, page_size).unwrap();
+        assert_eq!(range, 0..MAX_ACCOUNTS_PER_REQUEST);
+        assert_eq!(next_page, None);
+        assert_eq!(balances_pages(MAX_ACCOUNTS_PER_REQUEST, page_size), 1);
+    }
+
+    #[test]
+    fn test_balances_pages_negotiated() {
+        let asset = Hash::zero();
+        let keys: IndexSet<PublicKey> = (0..3).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        let step = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), None);
+
+        // Page is only sent once negotiated
+        let paged = BootstrapChainRequest::new(&step, true);
+        let legacy = BootstrapChainRequest::new(&step, false);
+        assert_eq!(paged.size(), legacy.size() + 1);
+        assert!(matches!(paged.decode(true), Ok(StepRequest::Balances(10, _, k, None)) if k.len() == 3));
+        assert!(matches!(legacy.decode(false), Ok(StepRequest::Balances(10, _, k, None)) if k.len() == 3));
+        // Each side must agree on the encoding
+        assert!(paged.decode(false).is_err());
+        assert!(legacy.decode(true).is_err());
+
+        // Same for the next page of the response
+        let response = StepResponse::Balances(vec![BalanceEntry::Missing; 3], None);
+        let paged = BootstrapChainResponse::with_checksum(&response, true);
+        let legacy = BootstrapChainResponse::with_checksum(&response, false);
+        assert_eq!(paged.size(), legacy.size() + 1);
+        assert!(matches!(paged.decode(StepKind::Balances, true, true), Ok(StepResponse::Balances(b, None)) if b.len() == 3));
+        assert!(matches!(legacy.decode(StepKind::Balances, true, false), Ok(StepResponse::Balances(b, None)) if b.len() == 3));
+        assert!(legacy.decode(StepKind::Balances, true, true).is_err());
+    }
+
+    #[test]
+    fn test_metadata_only_steps() {
+        let mut steps = vec![StepKind::ChainInfo];
+        while let Some(next) = steps.last().and_then(StepKind::next_metadata_only) {
+            steps.push(next);
+        }
+        assert_eq!(steps, vec![StepKind::ChainInfo, StepKind::BlocksMetadata]);
+
+        // Account steps are never part of it
+        for kind in [StepKind::Assets, StepKind::Keys, StepKind::Balances, StepKind::Nonces, StepKind::BlocksMetadata] {
+            assert!(kind.next_metadata_only().is_none());
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_cursor_resume() {
+        let stable_hash = Hash::new([1u8; 32]);
+        let mut cursor = BootstrapCursor::new();
+
+        // Nothing pinned until a step is processed, any pivot is accepted
+        assert!(cursor.get_pinned_pivot().is_none());
+        assert_eq!(cursor.resume(10, 99, 89, &Hash::new([2u8; 32])).unwrap(), (StepKind::Assets, None));
+
+        // First peer starts from the beginning
+        assert_eq!(cursor.resume(10, 100, 90, &stable_hash).unwrap(), (StepKind::Assets, None));
+        cursor.save(StepKind::Assets, Some(1));
+        cursor.save(StepKind::Keys, None);
+        cursor.save(StepKind::Keys, Some(3));
+        assert_eq!(cursor.get_pinned_pivot(), Some((100, 90, &stable_hash)));
+        // First peer fails here, second one continues from the saved page
+        assert_eq!(cursor.resume(10, 100, 90, &stable_hash).unwrap(), (StepKind::Keys, Some(3)));
+        cursor.save(StepKind::BlocksMetadata, None);
+        assert_eq!(cursor.resume(10, 100, 90, &stable_hash).unwrap(), (StepKind::BlocksMetadata, None));
+
+        // Another stable block is rejected, the progress is kept
+        assert!(matches!(cursor.resume(10, 101, 91, &Hash::new([2u8; 32])), Err(P2pError::BootstrapPivotMismatch(100, _))));
+        // Same stable block but our chain changed
+        assert!(matches!(cursor.resume(9, 100, 90, &stable_hash), Err(P2pError::BootstrapPivotMismatch(100, _))));
+        assert_eq!(cursor.resume(10, 100, 90, &stable_hash).unwrap(), (StepKind::BlocksMetadata, None));
+    }
+
+    #[test]
+    fn test_light_checkpoint() {
+        let hash = Hash::new([1u8; 32]);
+        let info = StepResponse::ChainInfo(None, 100, 90, hash.clone());
+        let checkpoint = LightCheckpoint::from_chain_info(&info, CumulativeDifficulty::from(1000u64), 1_700_000_000).unwrap();
+        assert_eq!(LightCheckpoint::from_bytes(&checkpoint.to_bytes()).unwrap(), checkpoint);
+        assert!(LightCheckpoint::from_chain_info(&StepResponse::Nonces(Vec::new()), CumulativeDifficulty::from(1u64), 0).is_none());
+
+        // Later chain info with the checkpoint as common point
+        let later = StepResponse::ChainInfo(Some(CommonPoint::new(hash.clone(), 100)), 150, 140, Hash::new([2u8; 32]));
+        assert!(checkpoint.is_extended_by(&later, &CumulativeDifficulty::from(2000u64)));
+        // Cumulative difficulty didn't increase
+        assert!(!checkpoint.is_extended_by(&later, &CumulativeDifficulty::from(1000u64)));
+
+        // Another block at the checkpoint topoheight
+        let fork = StepResponse::ChainInfo(Some(CommonPoint::new(Hash::new([3u8; 32]), 100)), 150, 140, Hash::new([2u8; 32]));
+        assert!(!checkpoint.is_extended_by(&fork, &CumulativeDifficulty::from(2000u64)));
+
+        // Common point below the checkpoint
+        let below = StepResponse::ChainInfo(Some(CommonPoint::new(Hash::new([4u8; 32]), 50)), 150, 140, Hash::new([2u8; 32]));
+        assert!(!checkpoint.is_extended_by(&below, &CumulativeDifficulty::from(2000u64)));
+
+        // No common point
+        let unknown = StepResponse::ChainInfo(None, 150, 140, Hash::new([2u8; 32]));
+        assert!(!checkpoint.is_extended_by(&unknown, &CumulativeDifficulty::from(2000u64)));
+    }
+
+    #[test]
+    fn test_compressed_response() {
+        let response = BootstrapChainResponse::new(&StepResponse::Nonces(vec![7; 512]), true);
+        let plain_size = response.size();
+
+        // Both peers support compression
+        let compressed = CompressedBootstrapChainResponse::compress(&response).unwrap();
+        assert!(compressed.size() < plain_size);
+        let decoded = CompressedBootstrapChainResponse::from_bytes(&compressed.to_bytes()).unwrap().decompress(StepKind::Nonces, true).unwrap();
+        assert!(matches!(decoded.decode(StepKind::Nonces, false, true), Ok(StepResponse::Nonces(nonces)) if nonces == vec![7; 512]));
+
+        // Invalid compressed data
+        let invalid = CompressedBootstrapChainResponse { data: vec![0xFF; 16] };
+        assert!(invalid.decompress(StepKind::Nonces, true).is_err());
+    }
+
+    #[test]
+    fn test_step_response_budget() {
+        // Biggest valid responses fit in their budget
+        let keys = (0..MAX_ITEMS_PER_PAGE).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        let response = BootstrapChainResponse::with_checksum(&StepResponse::Keys(keys, Some(u64::MAX)), true);
+        assert!(verify_step_response_size(StepKind::Keys, response.size(), true).is_ok());
+        let response = BootstrapChainResponse::with_checksum(&StepResponse::Nonces(vec![u64::MAX; MAX_ACCOUNTS_PER_REQUEST]), true);
+        assert!(verify_step_response_size(StepKind::Nonces, response.size(), true).is_ok());
+
+        // Budgets depend on the step
+        assert!(StepKind::ChainInfo.max_response_size(true) < StepKind::Nonces.max_response_size(true));
+        assert!(StepKind::Nonces.max_response_size(true) < StepKind::Balances.max_response_size(true));
+        assert!(StepKind::all().iter().all(|kind| kind.max_response_size(true) <= MAX_STEP_RESPONSE_BYTES));
+
+        // A nonces response is way above the chain info budget
+        assert!(matches!(verify_step_response_size(StepKind::ChainInfo, response.size(), true), Err(P2pError::StepResponseTooBig(StepKind::ChainInfo, _))));
+        assert!(matches!(response.decode(StepKind::ChainInfo, true, true), Err(P2pError::StepResponseTooBig(StepKind::ChainInfo, _))));
+
+        // Frame above the budget is rejected from its length, even if it isn't a valid step
+        let oversized = BootstrapChainResponse { data: vec![0xFF; StepKind::Nonces.max_response_size(true) + 1] };
+        assert!(matches!(oversized.decode(StepKind::Nonces, true, true), Err(P2pError::StepResponseTooBig(StepKind::Nonces, _))));
+
+        // Highly compressible payload inflating above the budget
+        // Decompression stops at the budget instead of inflating the whole payload
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&vec![0u8; MAX_STEP_RESPONSE_BYTES * 4]).unwrap();
+        let bomb = CompressedBootstrapChainResponse { data: encoder.finish().unwrap() };
+        assert!(bomb.size() < StepKind::ChainInfo.max_response_size(true) * 100);
+        assert!(matches!(bomb.decompress(StepKind::ChainInfo, true), Err(P2pError::StepResponseTooBig(StepKind::ChainInfo, _))));
+        assert!(matches!(bomb.decompress(StepKind::Balances, true), Err(P2pError::StepResponseTooBig(StepKind::Balances, _))));
+    }
+
+    #[test]
+    fn test_sync_report() {
+        // Full sync, keys are paginated
+        let mut report = SyncReport::new();
+        report.record(StepKind::ChainInfo, 1);
+        report.record(StepKind::Assets, 2);
+        for _ in 0..2 {
+            report.record(StepKind::Keys, 10);
+            report.record(StepKind::Balances, 5);
+            report.record(StepKind::Nonces, 10);
+        }
+        report.record(StepKind::BlocksMetadata, 65);
+        assert!(report.finish());
+        assert!(report.get_missing_steps().is_empty());
+        assert_eq!(report.steps[2], (StepKind::Keys, 20));
+
+        // Sync stopped before the balances
+        let mut report = SyncReport::new();
+        report.record(StepKind::ChainInfo, 1);
+        report.record(StepKind::Assets, 2);
+        report.record(StepKind::Keys, 10);
+        assert!(!report.finish());
+        assert!(!report.completed);
+        assert_eq!(report.get_missing_steps(), vec![StepKind::Balances, StepKind::Nonces, StepKind::BlocksMetadata]);
+
+        // Steps out of order
+        let mut report = SyncReport::new();
+        for kind in [StepKind::ChainInfo, StepKind::Keys, StepKind::Assets, StepKind::Balances, StepKind::Nonces, StepKind::BlocksMetadata] {
+            report.record(kind, 1);
+        }
+        assert!(!report.finish());
+        assert!(report.get_missing_steps().is_empty());
+    }
+
+    #[test]
+    fn test_step_response_roundtrip() {
+
+        let keypair = KeyPair::new();
+        let ciphertext = CiphertextCache::Compressed(keypair.get_public_key().encrypt(10u64).compress());
+        let metadata = BlockMetadata {
+            hash: Hash::new([2u8; 32]),
+            supply: 100,
+            reward: 10,
+            difficulty: Difficulty::from(5u64),
+            cumulative_difficulty: CumulativeDifficulty::from(50u64),
+            p: VarUint::from(1u64)
+        };
+
+        let responses = [
+            StepResponse::ChainInfo(Some(CommonPoint::new(Hash::new([1u8; 32]), 5)), 10, 8, Hash::new([3u8; 32])),
+            StepResponse::ChainInfo(None, 0, 0, Hash::zero()),
+            StepResponse::Assets(IndexSet::from([AssetWithData::new(Hash::new([4u8; 32]), AssetData::new(1, 8))]), Some(1)),
+            StepResponse::Keys(IndexSet::from([keypair.get_public_key().compress()]), None),
+            StepResponse::Balances(vec![BalanceEntry::Present(ciphertext.clone(), Some(ciphertext), BalanceType::Both), BalanceEntry::Missing], Some(2)),
+            StepResponse::Nonces(vec![0, 1, u64::MAX]),
+            StepResponse::BlocksMetadata(IndexSet::from([metadata])),
+            StepResponse::BlockHashAt(Some(Hash::new([5u8; 32])))
+        ];
+
+        // Tags must follow the declaration order
+        let tags = [0, 0, 1, 2, 3, 4, 5, 6];
+        for (response, tag) in responses.iter().zip(tags) {
+            let bytes = response.to_bytes();
+            assert_eq!(bytes[0], tag);
+            assert_eq!(bytes.len(), response.size());
+
+            let decoded = StepResponse::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.kind(), response.kind());
+            assert_eq!(decoded.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_response_checksum() {
+        let response = BootstrapChainResponse::with_checksum(&StepResponse::Nonces(vec![1, 2, 3]), true);
+        let mut bytes = response.to_bytes();
+        assert_eq!(bytes.len(), response.size());
+
+        let decoded = BootstrapChainResponse::from_bytes(&bytes).unwrap();
+        assert!(matches!(decoded.decode(StepKind::Nonces, true, true), Ok(StepResponse::Nonces(nonces)) if nonces == vec![1, 2, 3]));
+        // Checksum not negotiated, it's unexpected data
+        assert!(matches!(decoded.decode(StepKind::Nonces, false, true), Err(P2pError::ReaderError(ReaderError::InvalidSize))));
+
+        // Flip a byte of the last nonce, step is still valid but the checksum doesn't match
+        let index = response.size() - HASH_SIZE - 1;
+        bytes[index] ^= 0xFF;
+        assert!(matches!(BootstrapChainResponse::from_bytes(&bytes).unwrap().decode(StepKind::Nonces, true, true), Err(P2pError::ReaderError(ReaderError::ChecksumMismatch))));
+
+        // Older peers don't send any checksum, it's required once negotiated
+        let decoded = BootstrapChainResponse::new(&StepResponse::Nonces(vec![1]), true);
+        assert!(matches!(decoded.decode(StepKind::Nonces, false, true), Ok(StepResponse::Nonces(nonces)) if nonces == vec![1]));
+        assert!(decoded.decode(StepKind::Nonces, true, true).is_err());
+    }
+
+    #[test]
+    fn test_blocks_metadata_sorted() {
+        let metadata = |i: u8, cumulative_difficulty: u64| BlockMetadata {
+            hash: Hash::new([i; 32]),
+            supply: 0,
+            reward: 0,
+            difficulty: Difficulty::from(1u64),
+            cumulative_difficulty: CumulativeDifficulty::from(cumulative_difficulty),
+            p: VarUint::from(0u64)
+        };
+
+        // Same cumulative difficulty for the three first blocks
+        let response = StepResponse::BlocksMetadata(IndexSet::from([
+            metadata(3, 10),
+            metadata(1, 10),
+            metadata(4, 5),
+            metadata(2, 10),
+            metadata(5, 20)
+        ]));
+        let hashes: Vec<Hash> = response.blocks_metadata_sorted().unwrap().into_iter().map(|m| m.hash.clone()).collect();
+        assert_eq!(hashes, [5, 1, 2, 3, 4].map(|i| Hash::new([i; 32])).to_vec());
+
+        assert!(StepResponse::Nonces(Vec::new()).blocks_metadata_sorted().is_none());
+    }
+
+    #[test]
+    fn test_supply_emission() {
+        let metadata = |i: u8, supply: u64, reward: u64| BlockMetadata {
+            hash: Hash::new([i; 32]),
+            supply,
+            reward,
+            difficulty: Difficulty::from(1u64),
+            cumulative_difficulty: CumulativeDifficulty::from(i as u64),
+            p: VarUint::from(0u64)
+        };
+
+        // Synthetic chain, each block emits its reward
+        assert_eq!(supply_delta(&metadata(1, 100, 10), &metadata(3, 125, 15)), Some(25));
+        assert_eq!(supply_delta(&metadata(3, 125, 15), &metadata(1, 100, 10)), None);
+
+        // Sent from the stable block to the lowest one
+        let response = StepResponse::BlocksMetadata(IndexSet::from([
+            metadata(3, 125, 15),
+            metadata(2, 110, 10),
+            metadata(1, 100, 10)
+        ]));
+        assert_eq!(response.verify_supply_emission(), Some(true));
+
+        // Reward not matching the emission
+        let response = StepResponse::BlocksMetadata(IndexSet::from([
+            metadata(3, 130, 15),
+            metadata(2, 110, 10),
+            metadata(1, 100, 10)
+        ]));
+        assert_eq!(response.verify_supply_emission(), Some(false));
+
+        // Supply going backward
+        let response = StepResponse::BlocksMetadata(IndexSet::from([
+            metadata(2, 90, 0),
+            metadata(1, 100, 10)
+        ]));
+        assert_eq!(response.verify_supply_emission(), Some(false));
+
+        // A single block has nothing to verify
+        assert_eq!(StepResponse::BlocksMetadata(IndexSet::from([metadata(1, 100, 10)])).verify_supply_emission(), Some(true));
+        assert!(StepResponse::BlocksMetadata(IndexSet::new()).verify_supply_emission().is_none());
+        assert!(StepResponse::Nonces(Vec::new()).verify_supply_emission().is_none());
+    }
+
+    #[test]
+    fn test_validate_balances_response() {
+        let asset = Hash::zero();
+        let keys: IndexSet<PublicKey> = (0..3).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), None);
+
+        // Exact length
+        assert!(validate_balances_response(&request, &StepResponse::Balances(vec![BalanceEntry::Missing; 3], None), MAX_BALANCES_PER_PAGE).is_ok());
+
+        // Under length
+        assert!(matches!(validate_balances_response(&request, &StepResponse::Balances(vec![BalanceEntry::Missing; 2], None), MAX_BALANCES_PER_PAGE), Err(ReaderError::InvalidSize)));
+
+        // Over length
+        assert!(matches!(validate_balances_response(&request, &StepResponse::Balances(vec![BalanceEntry::Missing; 4], None), MAX_BALANCES_PER_PAGE), Err(ReaderError::InvalidSize)));
+
+        // Unexpected next page
+        assert!(matches!(validate_balances_response(&request, &StepResponse::Balances(vec![BalanceEntry::Missing; 3], Some(1)), MAX_BALANCES_PER_PAGE), Err(ReaderError::InvalidValue)));
+
+        // Not a balances response
+        assert!(matches!(validate_balances_response(&request, &StepResponse::Nonces(vec![0; 3]), MAX_BALANCES_PER_PAGE), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_balances_request_page() {
+        let asset = Hash::zero();
+        let keys: IndexSet<PublicKey> = (0..3).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        for page in [None, Some(1), Some(u64::MAX)] {
+            let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), page);
+            let decoded = StepRequest::from_bytes(&request.to_bytes()).unwrap();
+            assert!(matches!(decoded, StepRequest::Balances(10, _, k, p) if k.len() == 3 && p == page));
+        }
+
+        // Page 0 is rejected like for the keys step
+        let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), Some(0));
+        assert!(matches!(StepRequest::from_bytes(&request.to_bytes()), Err(ReaderError::InvalidValue)));
+        let response = StepResponse::Balances(vec![BalanceEntry::Missing; 3], Some(0));
+        assert!(matches!(StepResponse::from_bytes(&response.to_bytes()), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_balances_pagination_loop() {
+        // Requested accounts are paged until the next page is None
+        let asset = Hash::zero();
+        let keys: IndexSet<PublicKey> = (0..MAX_ACCOUNTS_PER_REQUEST).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        let mut page = None;
+        let mut requests = 0;
+        let mut received = 0;
+        loop {
+            let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), page);
+            // Answer it like the daemon does
+            let (range, next_page) = balances_page(keys.len(), page, MAX_BALANCES_PER_PAGE).unwrap();
+            let response = StepResponse::Balances(vec![BalanceEntry::Missing; range.len()], next_page);
+            let response = StepResponse::from_bytes(&response.to_bytes()).unwrap();
+            validate_balances_response(&request, &response, MAX_BALANCES_PER_PAGE).unwrap();
+
+            requests += 1;
+            received += range.len();
+            page = next_page;
+            if page.is_none() {
+                break
+            }
+        }
+
+        // A full keys page needs several balances pages
+        assert_eq!(requests, 4);
+        assert_eq!(received, keys.len());
+    }
+
+    #[test]
+    fn test_bootstrap_progress() {
+        let pivot_hash = Hash::new([1u8; 32]);
+        let mut cursor = BootstrapCursor::new();
+        assert!(cursor.get_progress().is_none());
+
+        cursor.resume(10, 100, 90, &pivot_hash).unwrap();
+        // Page 2 of the keys is processed, page 3 is next
+        cursor.save(StepKind::Keys, Some(3));
+        let progress = cursor.get_progress().unwrap();
+        assert_eq!(progress, BootstrapProgress { step: StepKind::Keys, last_page: Some(2), pivot_topoheight: 100, pivot_height: 90, pivot_hash: pivot_hash.clone() });
+
+        // Reload it like after a restart
+        let progress = BootstrapProgress::from_bytes(&progress.to_bytes()).unwrap();
+        assert!(matches!(progress.next_step_request(10), StepRequest::Keys(10, 100, Some(3))));
+
+        // Resumed only on the same pivot, which stays pinned
+        let mut cursor = BootstrapCursor::from_progress(progress.clone(), 10);
+        assert_eq!(cursor.get_pinned_pivot(), Some((100, 90, &pivot_hash)));
+        assert_eq!(cursor.resume(10, 100, 90, &pivot_hash).unwrap(), (StepKind::Keys, Some(3)));
+        let mut cursor = BootstrapCursor::from_progress(progress, 10);
+        assert!(matches!(
+            cursor.resume(10, 150, 140, &Hash::new([2u8; 32])),
+            Err(P2pError::BootstrapPivotMismatch(100, hash)) if hash == pivot_hash
+        ));
+        assert_eq!(cursor.get_progress().unwrap().pivot_topoheight, 100);
+
+        // No page processed yet for the step
+        let progress = BootstrapProgress::new(StepKind::Assets, None, 100, 90, pivot_hash);
+        assert!(progress.last_page.is_none());
+        assert!(matches!(progress.next_step_request(10), StepRequest::Assets(10, 100, None)));
+    }
+
+    #[test]
+    fn test_max_accounts_per_request() {
+        let asset = Hash::zero();
+        let mut keys: IndexSet<PublicKey> = (0..MAX_ACCOUNTS_PER_REQUEST).map(|_| KeyPair::new().get_public_key().compress()).collect();
+
+        // Exactly at the limit
+        let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), None);
+        assert!(matches!(StepRequest::from_bytes(&request.to_bytes()), Ok(StepRequest::Balances(_, _, k, _)) if k.len() == MAX_ACCOUNTS_PER_REQUEST));
+        let request = StepRequest::Nonces(10, Cow::Borrowed(&keys));
+        assert!(matches!(StepRequest::from_bytes(&request.to_bytes()), Ok(StepRequest::Nonces(_, k)) if k.len() == MAX_ACCOUNTS_PER_REQUEST));
+
+        // One over the limit is rejected by the accounts set reader
+        keys.insert(KeyPair::new().get_public_key().compress());
+        let request = StepRequest::Balances(10, Cow::Borrowed(&asset), Cow::Borrowed(&keys), None);
+        assert!(matches!(StepRequest::from_bytes(&request.to_bytes()), Err(ReaderError::InvalidSize)));
+        let request = StepRequest::Nonces(10, Cow::Borrowed(&keys));
+        assert!(matches!(StepRequest::from_bytes(&request.to_bytes()), Err(ReaderError::InvalidSize)));
+    }
+
+    #[test]
+    fn test_verify_step_kind() {
+        let response = StepResponse::BlockHashAt(None);
+        assert!(verify_step_kind(StepKind::BlockHashAt, &response).is_ok());
+        assert!(matches!(
+            verify_step_kind(StepKind::ChainInfo, &response),
+            Err(P2pError::InvalidBootstrapStep(StepKind::ChainInfo, StepKind::BlockHashAt))
+        ));
+
+        let response = StepResponse::ChainInfo(None, 100, 90, Hash::new([1u8; 32]));
+        assert!(verify_step_kind(StepKind::ChainInfo, &response).is_ok());
+        assert!(matches!(
+            verify_step_kind(StepKind::Nonces, &response),
+            Err(P2pError::InvalidBootstrapStep(StepKind::Nonces, StepKind::ChainInfo))
+        ));
+    }
+
+    #[test]
+    fn test_verify_top_block() {
+        let blocks: IndexSet<BlockMetadata> = (1..4u8).map(|i| BlockMetadata {
+            hash: Hash::new([i; 32]),
+            supply: 0,
+            reward: 0,
+            difficulty: Difficulty::from(1u64),
+            cumulative_difficulty: CumulativeDifficulty::from(1u64),
+            p: VarUint::from(0u64)
+        }).collect();
+        let blocks = StepResponse::BlocksMetadata(blocks);
+
+        // Stable block matches
+        let chain_info = StepResponse::ChainInfo(None, 100, 90, Hash::new([1u8; 32]));
+        assert!(verify_top_block(&chain_info, &blocks).is_ok());
+
+        // Mismatch
+        let chain_info = StepResponse::ChainInfo(None, 100, 90, Hash::new([2u8; 32]));
+        assert!(matches!(
+            verify_top_block(&chain_info, &blocks),
+            Err(P2pError::BootstrapStableBlockMismatch(expected, got)) if expected == Hash::new([2u8; 32]) && got == Hash::new([1u8; 32])
+        ));
+
+        // No blocks
+        assert!(matches!(verify_top_block(&chain_info, &StepResponse::BlocksMetadata(IndexSet::new())), Err(P2pError::InvalidPacket)));
+        // Wrong responses
+        assert!(matches!(verify_top_block(&blocks, &chain_info), Err(P2pError::InvalidPacket)));
+    }
+
+    #[test]
+    fn test_read_keys_streaming() {
+        let mut keys: IndexSet<PublicKey> = (0..100).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        sort_keys(&mut keys);
+        let bytes = StepResponse::Keys(keys.clone(), Some(2)).to_bytes();
+
+        let mut count = 0;
+        let page = StepResponse::read_keys_streaming(&mut Reader::new(&bytes), |key| {
+            assert_eq!(keys.get_index(count), Some(&key));
+            count += 1;
+        }).unwrap();
+        assert_eq!(count, keys.len());
+        assert_eq!(page, Some(2));
+
+        // Not a keys response
+        let bytes = StepResponse::Nonces(vec![1]).to_bytes();
+        assert!(StepResponse::read_keys_streaming(&mut Reader::new(&bytes), |_| {}).is_err());
+    }
+
+    #[test]
+    fn test_no_common_point_serialization() {
+        let response = StepResponse::NoCommonPoint(ChainDiagnostic::new(Hash::new([1u8; 32]), 100));
+        assert_eq!(response.kind(), StepKind::ChainInfo);
+        assert_eq!(response.size(), response.to_bytes().len());
+        assert!(response.size() <= StepKind::ChainInfo.max_response_size(true));
+
+        let decoded = StepResponse::from_bytes(&response.to_bytes()).unwrap();
+        assert!(matches!(decoded, StepResponse::NoCommonPoint(diagnostic) if diagnostic == ChainDiagnostic::new(Hash::new([1u8; 32]), 100)));
+    }
+
+    #[test]
+    fn test_no_common_point_other_network() {
+        let our_genesis = Hash::new([1u8; 32]);
+        let diagnostic = ChainDiagnostic::new(Hash::new([2u8; 32]), 0);
+        assert!(diagnostic.is_other_network(&our_genesis));
+        assert!(!diagnostic.is_pruned_past(50));
+
+        let err = diagnostic.into_error();
+        assert!(matches!(err, P2pError::NoCommonPoint(genesis, 0) if genesis == Hash::new([2u8; 32])));
+    }
+
+    #[test]
+    fn test_no_common_point_pruned() {
+        let our_genesis = Hash::new([1u8; 32]);
+        let diagnostic = ChainDiagnostic::new(our_genesis.clone(), 100);
+        assert!(!diagnostic.is_other_network(&our_genesis));
+        // Peer deleted all the blocks up to our top block
+        assert!(diagnostic.is_pruned_past(50));
+        // Our top block is still available on the peer
+        assert!(!diagnostic.is_pruned_past(100));
+
+        let err = diagnostic.into_error();
+        assert!(matches!(err, P2pError::NoCommonPoint(genesis, 100) if genesis == our_genesis));
+    }
+
+    #[test]
+    fn test_keys_canonical_order() {
+        let mut keys: IndexSet<PublicKey> = (0..20).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        sort_keys(&mut keys);
+        let bytes = StepResponse::Keys(keys.clone(), None).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Ok(StepResponse::Keys(decoded, None)) if decoded == keys));
+
+        // Unsorted page is rejected
+        keys.swap_indices(0, 1);
+        let bytes = StepResponse::Keys(keys, None).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Err(ReaderError::InvalidValue)));
+        assert!(matches!(StepResponse::read_keys_streaming(&mut Reader::new(&bytes), |_| {}), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_empty_pages() {
+        // Empty set is the last page
+        let bytes = StepResponse::Assets(IndexSet::new(), None).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Ok(StepResponse::Assets(assets, None)) if assets.is_empty()));
+        let bytes = StepResponse::Keys(IndexSet::new(), None).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Ok(StepResponse::Keys(keys, None)) if keys.is_empty()));
+        assert!(matches!(StepResponse::read_keys_streaming(&mut Reader::new(&bytes), |_| {}), Ok(None)));
+
+        // Empty set with a next page would loop forever
+        let bytes = StepResponse::Assets(IndexSet::new(), Some(2)).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Err(ReaderError::InvalidValue)));
+        let bytes = StepResponse::Keys(IndexSet::new(), Some(2)).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Err(ReaderError::InvalidValue)));
+        assert!(matches!(StepResponse::read_keys_streaming(&mut Reader::new(&bytes), |_| {}), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_blocks_metadata_duplicated() {
+        let block = |i: u8| BlockMetadata {
+            hash: Hash::new([i; 32]),
+            supply: i as u64,
+            reward: i as u64,
+            difficulty: Difficulty::from(i as u64 + 1),
+            cumulative_difficulty: CumulativeDifficulty::from(i as u64 + 1),
+            p: VarUint::from(i as u64)
+        };
+
+        let bytes = StepResponse::BlocksMetadata((0..3u8).map(block).collect()).to_bytes();
+        assert!(matches!(StepResponse::from_bytes(&bytes), Ok(StepResponse::BlocksMetadata(decoded)) if decoded.len() == 3));
+
+        // Second block replaced by the first one
+        let mut writer = Writer::new();
+        writer.write_u8(5);
+        writer.write_u16(3);
+        for i in [0, 0, 2] {
+            block(i).write(&mut writer);
+        }
+        assert!(matches!(StepResponse::from_bytes(&writer.bytes()), Err(ReaderError::InvalidValue)));
+    }
+
+    #[test]
+    fn test_trusted_checkpoint() {
+        let hash = Hash::new([1u8; 32]);
+        let checkpoint = TrustedCheckpoint::new(100, hash.clone());
+        assert!(checkpoint.verify_block_hash(Some(&hash)).is_ok());
+
+        // Common point above the checkpoint in our chain, the peer agrees with it
+        assert!(!checkpoint.needs_block_hash(true, &CommonPoint::new(Hash::zero(), 150)));
+        assert!(!checkpoint.needs_block_hash(true, &CommonPoint::new(hash.clone(), 100)));
+        // Otherwise its block hash at the checkpoint must be verified
+        assert!(checkpoint.needs_block_hash(true, &CommonPoint::new(Hash::zero(), 99)));
+        assert!(checkpoint.needs_block_hash(false, &CommonPoint::new(Hash::zero(), 150)));
+
+        // Disagreeing peers
+        let fork = Hash::new([2u8; 32]);
+        assert!(matches!(checkpoint.verify_block_hash(Some(&fork)), Err(P2pError::CheckpointMismatch(100, _))));
+        // Checkpoint isn't in the stable range of the peer
+        assert!(matches!(checkpoint.verify_block_hash(None), Err(P2pError::CheckpointMismatch(100, _))));
+    }
+
+    #[test]
+    fn test_rewind_target() {
+        // Node already past the checkpoint at topoheight 100 keeps the blocks shared with the peer
+        let our_topoheight = 150;
+        assert_eq!(rewind_target(150, Some(100), 0, 0), our_topoheight);
+        assert_eq!(rewind_target(120, Some(100), 0, 0), 120);
+
+        // Peer only agreed on the checkpoint itself
+        assert_eq!(rewind_target(50, Some(100), 0, 0), 100);
+        // Without an agreed checkpoint, the common point is used
+        assert_eq!(rewind_target(50, None, 0, 0), 50);
+
+        // Never below our stable or pruned topoheight
+        assert_eq!(rewind_target(120, Some(100), 0, 130), 130);
+        assert_eq!(rewind_target(50, None, 80, 60), 80);
+    }
+
+    #[test]
+    fn test_zip_nonces() {
+        let keys: IndexSet<PublicKey> = (0..3).map(|_| KeyPair::new().get_public_key().compress()).collect();
+        let request = StepRequest::Nonces(10, Cow::Borrowed(&keys));
+
+        // One nonce per account, in the requested order
+        let nonces = zip_nonces(&request, StepResponse::Nonces(vec![1, 2, 3])).unwrap();
+        assert_eq!(nonces.len(), 3);
+        for ((key, nonce), (expected_key, expected_nonce)) in nonces.iter().zip(keys.iter().zip([1u64, 2, 3])) {
+            assert_eq!(key, expected_key);
+            assert_eq!(*nonce, expected_nonce);
+        }
+
+        // Short and long responses would shift the nonces
+        assert!(matches!(zip_nonces(&request, StepResponse::Nonces(vec![1, 2])), Err(ReaderError::InvalidSize)));
+        assert!(matches!(zip_nonces(&request, StepResponse::Nonces(vec![1, 2, 3, 4])), Err(ReaderError::InvalidSize)));
+        assert!(matches!(validate_nonces_response(&request, &StepResponse::BlockHashAt(None)), Err(ReaderError::InvalidValue)));
     }
 }
@@ -0,0 +1,156 @@
+// Canonical test vectors for the bootstrap chain protocol
+// Each StepRequest / StepResponse variant is built with fixed inputs
+// and its serialized form is compared against the reference vectors stored in vectors/bootstrap_chain.txt
+// Any change in the wire format will make these tests fail and requires an intentional protocol version bump
+
+use std::{borrow::Cow, collections::HashMap};
+use indexmap::IndexSet;
+use xelis_common::{
+    account::{BalanceType, CiphertextCache},
+    asset::{AssetData, AssetWithData},
+    crypto::{elgamal::CompressedCiphertext, Hash, PublicKey},
+    serializer::Serializer,
+    varuint::VarUint
+};
+use super::{
+    bootstrap_chain::{BalanceEntry, BlockMetadata, ChainDiagnostic, StepRequest, StepResponse},
+    chain::{BlockId, CommonPoint}
+};
+
+const REFERENCE_VECTORS: &str = include_str!("vectors/bootstrap_chain.txt");
+
+fn hash(byte: u8) -> Hash {
+    Hash::new([byte; 32])
+}
+
+fn key(byte: u8) -> PublicKey {
+    PublicKey::from_bytes(&[byte; 32]).expect("valid public key bytes")
+}
+
+fn ciphertext(byte: u8) -> CiphertextCache {
+    CiphertextCache::Compressed(CompressedCiphertext::from_bytes(&[byte; 64]).expect("valid ciphertext bytes"))
+}
+
+// One instance of each StepRequest variant with fixed inputs
+pub fn step_requests() -> Vec<(&'static str, StepRequest<'static>)> {
+    vec![
+        ("request_chain_info", StepRequest::ChainInfo(IndexSet::from([BlockId::new(hash(1), 10), BlockId::new(hash(0), 0)]))),
+        ("request_assets", StepRequest::Assets(5, 10, None)),
+        ("request_assets_paginated", StepRequest::Assets(5, 10, Some(2))),
+        ("request_keys", StepRequest::Keys(5, 10, None)),
+        ("request_keys_paginated", StepRequest::Keys(5, 10, Some(3))),
+        ("request_balances", StepRequest::Balances(10, Cow::Owned(hash(2)), Cow::Owned(IndexSet::from([key(3), key(4)])), None)),
+        ("request_balances_paginated", StepRequest::Balances(10, Cow::Owned(hash(2)), Cow::Owned(IndexSet::from([key(3), key(4)])), Some(2))),
+        ("request_balances_empty", StepRequest::Balances(10, Cow::Owned(hash(2)), Cow::Owned(IndexSet::new()), None)),
+        ("request_nonces", StepRequest::Nonces(10, Cow::Owned(IndexSet::from([key(3)])))),
+        ("request_nonces_empty", StepRequest::Nonces(10, Cow::Owned(IndexSet::new()))),
+        ("request_blocks_metadata", StepRequest::BlocksMetadata(10)),
+        ("request_block_hash_at", StepRequest::BlockHashAt(10)),
+    ]
+}
+
+// One instance of each StepResponse variant with fixed inputs
+pub fn step_responses() -> Vec<(&'static str, StepResponse)> {
+    let asset = AssetWithData::new(hash(0), AssetData::new(0, 8));
+    let block = BlockMetadata {
+        hash: hash(8),
+        supply: 1000,
+        reward: 50,
+        difficulty: VarUint::from_u64(100),
+        cumulative_difficulty: VarUint::from_u64(5000),
+        p: VarUint::from_u64(1)
+    };
+
+    vec![
+        ("response_chain_info", StepResponse::ChainInfo(Some(CommonPoint::new(hash(1), 8)), 10, 9, hash(5))),
+        ("response_chain_info_no_common_point", StepResponse::ChainInfo(None, 10, 9, hash(5))),
+        ("response_assets", StepResponse::Assets(IndexSet::from([asset.clone()]), None)),
+        ("response_assets_paginated", StepResponse::Assets(IndexSet::from([asset]), Some(2))),
+        ("response_assets_empty", StepResponse::Assets(IndexSet::new(), None)),
+        ("response_keys", StepResponse::Keys(IndexSet::from([key(3), key(4)]), None)),
+        ("response_keys_paginated", StepResponse::Keys(IndexSet::from([key(3), key(4)]), Some(2))),
+        ("response_keys_empty", StepResponse::Keys(IndexSet::new(), None)),
+        ("response_balances", StepResponse::Balances(vec![
+            BalanceEntry::Present(ciphertext(6), Some(ciphertext(7)), BalanceType::Both),
+            BalanceEntry::Present(ciphertext(6), None, BalanceType::Input),
+            BalanceEntry::Missing
+        ], None)),
+        ("response_balances_paginated", StepResponse::Balances(vec![BalanceEntry::Missing], Some(2))),
+        ("response_balances_unregistered", StepResponse::Balances(vec![BalanceEntry::Unregistered, BalanceEntry::Missing], None)),
+        ("response_balances_empty", StepResponse::Balances(Vec::new(), None)),
+        ("response_nonces", StepResponse::Nonces(vec![1, 42])),
+        ("response_nonces_empty", StepResponse::Nonces(Vec::new())),
+        ("response_blocks_metadata", StepResponse::BlocksMetadata(IndexSet::from([block]))),
+        ("response_blocks_metadata_empty", StepResponse::BlocksMetadata(IndexSet::new())),
+        ("response_block_hash_at", StepResponse::BlockHashAt(Some(hash(9)))),
+        ("response_block_hash_at_none", StepResponse::BlockHashAt(None)),
+        ("response_no_common_point", StepResponse::NoCommonPoint(ChainDiagnostic::new(hash(1), 8))),
+    ]
+}
+
+// Serialized hex of each vector
+pub fn generate_vectors() -> Vec<(&'static str, String)> {
+    let mut vectors: Vec<(&'static str, String)> = step_requests().into_iter()
+        .map(|(name, request)| (name, request.to_hex()))
+        .collect();
+    vectors.extend(step_responses().into_iter().map(|(name, response)| (name, response.to_hex())));
+    vectors
+}
+
+fn reference_vectors() -> HashMap<&'static str, &'static str> {
+    REFERENCE_VECTORS.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split_once(' ').expect("invalid reference vector line"))
+        .collect()
+}
+
+#[test]
+fn test_step_requests_vectors() {
+    let references = reference_vectors();
+    for (name, request) in step_requests() {
+        let expected = references.get(name).expect("missing reference vector");
+        assert_eq!(request.to_hex(), *expected, "wire format changed for {}", name);
+
+        // It must be read back to the exact same bytes
+        let decoded = StepRequest::from_hex(expected.to_string()).unwrap();
+        assert_eq!(decoded.to_hex(), *expected, "invalid read for {}", name);
+    }
+}
+
+#[test]
+fn test_step_responses_vectors() {
+    let references = reference_vectors();
+    for (name, response) in step_responses() {
+        let expected = references.get(name).expect("missing reference vector");
+        assert_eq!(response.to_hex(), *expected, "wire format changed for {}", name);
+
+        // It must be read back to the exact same bytes
+        let decoded = StepResponse::from_hex(expected.to_string()).unwrap();
+        assert_eq!(decoded.to_hex(), *expected, "invalid read for {}", name);
+    }
+}
+
+#[test]
+fn test_all_vectors_referenced() {
+    let references = reference_vectors();
+    let generated = generate_vectors();
+    assert_eq!(references.len(), generated.len());
+    for (name, _) in generated {
+        assert!(references.contains_key(name), "missing reference vector {}", name);
+    }
+}
+
+#[test]
+fn test_vectors_deterministic() {
+    assert_eq!(generate_vectors(), generate_vectors());
+}
+
+// Print the vectors in the reference file format
+// Run with `cargo test print_vectors -- --ignored --nocapture` after an intentional wire format change
+#[test]
+#[ignore]
+fn print_vectors() {
+    for (name, hex) in generate_vectors() {
+        println!("{} {}", name, hex);
+    }
+}
@@ -0,0 +1,122 @@
+use std::ops::BitOr;
+use xelis_common::serializer::{Reader, ReaderError, Serializer, Writer};
+use crate::config::P2P_CAPABILITIES_MIN_VERSION;
+
+// Optional features of the P2P protocol announced by a peer
+// A feature is only used if both peers support it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeerCapabilities(u8);
+
+impl PeerCapabilities {
+    // Peer is able to receive compressed bootstrap chain responses
+    pub const COMPRESSION: Self = Self(1 << 0);
+    // Peer is able to verify checksummed bootstrap chain responses
+    pub const CHECKSUM: Self = Self(1 << 1);
+    // Peer can explain why no common point was found in a ChainInfo step
+    pub const CHAIN_DIAGNOSTIC: Self = Self(1 << 2);
+    // Peer can request and answer the Balances step by pages
+    pub const BALANCES_PAGES: Self = Self(1 << 3);
+
+    // All the capabilities known by this version
+    const KNOWN: Self = Self::COMPRESSION
+        .union(Self::CHECKSUM)
+        .union(Self::CHAIN_DIAGNOSTIC)
+        .union(Self::BALANCES_PAGES);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    // Capabilities usable with a peer: supported by both sides
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    // Check if the flag is part of the capabilities
+    pub const fn supports(&self, flag: Self) -> bool {
+        self.0 & flag.0 == flag.0
+    }
+}
+
+// Parse the "major.minor.patch" part of a daemon version such as "1.10.0-abcdef0"
+fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let release = version.split('-').next()?;
+    let mut parts = release.split('.').map(|part| part.parse::<u64>().ok());
+    let version = [parts.next()??, parts.next()??, parts.next()??];
+    if parts.next().is_some() {
+        return None
+    }
+
+    Some(version)
+}
+
+// Check if the version announced in the handshake of a peer can read the capabilities packet
+// An unknown version format is considered as an older node
+pub fn is_capabilities_supported(version: &str) -> bool {
+    matches!(parse_version(version), Some(version) if version >= P2P_CAPABILITIES_MIN_VERSION)
+}
+
+impl BitOr for PeerCapabilities {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        self.union(other)
+    }
+}
+
+impl Serializer for PeerCapabilities {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.0);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        // Flags from newer versions are unknown to us and ignored
+        let flags = reader.read_u8()?;
+        Ok(Self(flags).intersection(Self::KNOWN))
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let ours = PeerCapabilities::COMPRESSION | PeerCapabilities::CHECKSUM | PeerCapabilities::BALANCES_PAGES;
+        assert!(ours.supports(PeerCapabilities::CHECKSUM));
+        assert!(!ours.supports(PeerCapabilities::CHAIN_DIAGNOSTIC));
+        assert!(!PeerCapabilities::empty().supports(PeerCapabilities::COMPRESSION));
+
+        // Only the features supported by both peers are used
+        let negotiated = ours.intersection(PeerCapabilities::CHECKSUM | PeerCapabilities::CHAIN_DIAGNOSTIC);
+        assert_eq!(negotiated, PeerCapabilities::CHECKSUM);
+
+        assert_eq!(PeerCapabilities::from_bytes(&ours.to_bytes()).unwrap(), ours);
+        // Unknown flags are dropped
+        assert_eq!(PeerCapabilities::from_bytes(&[0xF0 | 0x01]).unwrap(), PeerCapabilities::COMPRESSION);
+    }
+
+    #[test]
+    fn test_capabilities_version() {
+        // Released before the capabilities packet
+        assert!(!is_capabilities_supported("1.9.0-58e425a"));
+        assert!(!is_capabilities_supported("1.8.4"));
+
+        assert!(is_capabilities_supported("1.10.0-abcdef0"));
+        assert!(is_capabilities_supported("1.10.1"));
+        assert!(is_capabilities_supported("2.0.0-abcdef0"));
+
+        // Unknown formats are treated as older nodes
+        assert!(!is_capabilities_supported("dev"));
+        assert!(!is_capabilities_supported("1.10"));
+        assert!(!is_capabilities_supported("1.10.0.1"));
+    }
+}
@@ -143,7 +143,7 @@ impl Serializer for ChainRequest {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CommonPoint {
     hash: Hash,
     topoheight: u64
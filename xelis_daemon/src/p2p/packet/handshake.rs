@@ -246,4 +246,52 @@ impl Display for Handshake<'_> {
         };
         write!(f, "Handshake[version: {}, node tag: {}, network_id: {}, peer_id: {}, utc_time: {}, block_height: {}, block_top_hash: {}]", self.get_version(), node_tag, hex::encode(self.get_network_id()), self.get_peer_id(), self.get_utc_time(), self.get_block_height(), self.get_block_top_hash())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::packet::{capabilities::PeerCapabilities, Packet};
+    use super::*;
+
+    // Reader of the nodes released before the capabilities packet
+    // It must consume the whole handshake packet, any byte left is rejected by the connection
+    fn read_baseline_handshake(bytes: &[u8]) -> Result<(), ReaderError> {
+        let mut reader = Reader::new(bytes);
+        assert_eq!(reader.read_u8()?, 1);
+        reader.read_string()?;
+        Network::read(&mut reader)?;
+        reader.read_optional_string()?;
+        let _: [u8; 16] = reader.read_bytes(16)?;
+        reader.read_u64()?;
+        reader.read_u16()?;
+        reader.read_u64()?;
+        reader.read_u64()?;
+        reader.read_u64()?;
+        Option::<u64>::read(&mut reader)?;
+        reader.read_hash()?;
+        reader.read_hash()?;
+        CumulativeDifficulty::read(&mut reader)?;
+        reader.read_bool()?;
+
+        if reader.total_read() != bytes.len() {
+            return Err(ReaderError::InvalidSize)
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_baseline_reader() {
+        let handshake = Handshake::new(Cow::Owned("1.10.0-abcdef0".to_owned()), Network::Mainnet, Cow::Owned(Some("node".to_owned())), Cow::Owned([7u8; 16]), 42, 2125, 1000, 100, 90, Some(10), Cow::Owned(Hash::new([1u8; 32])), Cow::Owned(Hash::new([2u8; 32])), Cow::Owned(CumulativeDifficulty::from(5000u64)), true);
+        let bytes = Packet::Handshake(Cow::Borrowed(&handshake)).to_bytes();
+        assert!(read_baseline_handshake(&bytes).is_ok());
+
+        let decoded = Handshake::from_bytes(&bytes[1..]).unwrap();
+        assert_eq!(decoded.get_peer_id(), 42);
+        assert_eq!(decoded.get_version(), "1.10.0-abcdef0");
+
+        // Capabilities appended to the handshake would be rejected by older nodes
+        let capabilities = Packet::Capabilities(PeerCapabilities::COMPRESSION).to_bytes();
+        assert!(read_baseline_handshake(&[bytes, capabilities[1..].to_vec()].concat()).is_err());
+    }
+}
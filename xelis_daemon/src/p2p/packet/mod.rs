@@ -1,4 +1,5 @@
 pub mod handshake;
+pub mod capabilities;
 pub mod chain;
 pub mod ping;
 pub mod object;
@@ -6,7 +7,11 @@ pub mod inventory;
 pub mod bootstrap_chain;
 pub mod peer_disconnected;
 
-use self::bootstrap_chain::{BootstrapChainRequest, BootstrapChainResponse};
+#[cfg(test)]
+mod bootstrap_chain_vectors;
+
+use self::bootstrap_chain::{BootstrapChainRequest, BootstrapChainResponse, CompressedBootstrapChainResponse};
+use self::capabilities::PeerCapabilities;
 use self::inventory::{NotifyInventoryResponse, NotifyInventoryRequest};
 use self::object::{ObjectRequest, ObjectResponse};
 use self::chain::{ChainRequest, ChainResponse};
@@ -38,6 +43,8 @@ const NOTIFY_INV_RESPONSE_ID: u8 = 10;
 const BOOTSTRAP_CHAIN_REQUEST_ID: u8 = 11;
 const BOOTSTRAP_CHAIN_RESPONSE_ID: u8 = 12;
 const PEER_DISCONNECTED_ID: u8 = 13;
+const COMPRESSED_BOOTSTRAP_CHAIN_RESPONSE_ID: u8 = 14;
+const CAPABILITIES_ID: u8 = 15;
 
 // PacketWrapper allows us to link any Packet to a Ping
 #[derive(Debug)]
@@ -94,9 +101,13 @@ pub enum Packet<'a> {
     ObjectResponse(ObjectResponse<'a>),
     NotifyInventoryRequest(PacketWrapper<'a, NotifyInventoryRequest>),
     NotifyInventoryResponse(NotifyInventoryResponse<'a>),
-    BootstrapChainRequest(BootstrapChainRequest<'a>),
+    BootstrapChainRequest(BootstrapChainRequest),
     BootstrapChainResponse(BootstrapChainResponse),
+    // Only sent if the peer supports compression
+    CompressedBootstrapChainResponse(CompressedBootstrapChainResponse),
     PeerDisconnected(PacketPeerDisconnected),
+    // Sent right after the handshake, only to peers able to read it
+    Capabilities(PeerCapabilities),
     // Encryption
     KeyExchange(Cow<'a, EncryptionKey>),
 }
@@ -116,7 +127,9 @@ impl Packet<'_> {
             Packet::NotifyInventoryResponse(_) => NOTIFY_INV_RESPONSE_ID,
             Packet::BootstrapChainRequest(_) => BOOTSTRAP_CHAIN_REQUEST_ID,
             Packet::BootstrapChainResponse(_) => BOOTSTRAP_CHAIN_RESPONSE_ID,
+            Packet::CompressedBootstrapChainResponse(_) => COMPRESSED_BOOTSTRAP_CHAIN_RESPONSE_ID,
             Packet::PeerDisconnected(_) => PEER_DISCONNECTED_ID,
+            Packet::Capabilities(_) => CAPABILITIES_ID,
             Packet::KeyExchange(_) => KEY_EXCHANGE_ID,
         }
     }
@@ -140,7 +153,9 @@ impl<'a> Serializer for Packet<'a> {
             NOTIFY_INV_RESPONSE_ID => Packet::NotifyInventoryResponse(NotifyInventoryResponse::read(reader)?),
             BOOTSTRAP_CHAIN_REQUEST_ID => Packet::BootstrapChainRequest(BootstrapChainRequest::read(reader)?),
             BOOTSTRAP_CHAIN_RESPONSE_ID => Packet::BootstrapChainResponse(BootstrapChainResponse::read(reader)?),
+            COMPRESSED_BOOTSTRAP_CHAIN_RESPONSE_ID => Packet::CompressedBootstrapChainResponse(CompressedBootstrapChainResponse::read(reader)?),
             PEER_DISCONNECTED_ID => Packet::PeerDisconnected(PacketPeerDisconnected::read(reader)?),
+            CAPABILITIES_ID => Packet::Capabilities(PeerCapabilities::read(reader)?),
             id => {
                 debug!("invalid packet id received: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -169,7 +184,9 @@ impl<'a> Serializer for Packet<'a> {
             Packet::NotifyInventoryResponse(inventory) => (NOTIFY_INV_RESPONSE_ID, inventory),
             Packet::BootstrapChainRequest(request) => (BOOTSTRAP_CHAIN_REQUEST_ID, request),
             Packet::BootstrapChainResponse(response) => (BOOTSTRAP_CHAIN_RESPONSE_ID, response),
+            Packet::CompressedBootstrapChainResponse(response) => (COMPRESSED_BOOTSTRAP_CHAIN_RESPONSE_ID, response),
             Packet::PeerDisconnected(disconnected) => (PEER_DISCONNECTED_ID, disconnected),
+            Packet::Capabilities(capabilities) => (CAPABILITIES_ID, capabilities),
         };
 
         let packet = serializer.to_bytes();
@@ -0,0 +1,239 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Display,
+    future::Future,
+    sync::Mutex
+};
+use futures_util::future::join_all;
+use log::{debug, warn};
+use tokio::sync::Notify;
+use super::error::P2pError;
+
+// Pages left to fetch
+struct PagesState {
+    // pages not assigned to a peer
+    pending: VecDeque<u64>,
+    // pages currently requested to a peer
+    in_flight: usize
+}
+
+// Wait for the next page to fetch
+// Returns None once every page has been fetched
+async fn next_page(state: &Mutex<PagesState>, notify: &Notify) -> Option<u64> {
+    loop {
+        // Created before checking the state to not miss a page given back meanwhile
+        let notified = notify.notified();
+        {
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(page) = state.pending.pop_front() {
+                state.in_flight += 1;
+                return Some(page)
+            }
+
+            if state.in_flight == 0 {
+                return None
+            }
+        }
+
+        // A page in flight may be given back by a failing peer
+        notified.await;
+    }
+}
+
+// Fetch the pages 0..pages of a fast sync step from several peers in parallel
+// Each peer requests one page at a time and takes the next pending one once done,
+// so a page is never requested to two peers at the same time
+// The fetch function must validate the page, any error drops the peer and its page is given to the others
+// Dropped peers are removed from the list, results are returned in the pages order
+pub async fn fetch_pages<P, T, F, Fut>(peers: &mut Vec<P>, pages: u64, fetch: F) -> Result<Vec<T>, P2pError>
+where
+    P: Clone + Display,
+    F: Fn(P, u64) -> Fut,
+    Fut: Future<Output = Result<T, P2pError>>
+{
+    debug!("Fetching {} pages from {} peers", pages, peers.len());
+    let state = Mutex::new(PagesState {
+        pending: (0..pages).collect(),
+        in_flight: 0
+    });
+    let notify = Notify::new();
+    let results = Mutex::new(BTreeMap::new());
+
+    let healthy = {
+        let (state, notify, results, fetch) = (&state, &notify, &results, &fetch);
+        let workers = peers.iter().map(|peer| async move {
+            while let Some(page) = next_page(state, notify).await {
+                let error = match fetch(peer.clone(), page).await {
+                    Ok(value) => {
+                        results.lock().unwrap_or_else(|e| e.into_inner()).insert(page, value);
+                        None
+                    },
+                    Err(e) => Some(e)
+                };
+
+                {
+                    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                    state.in_flight -= 1;
+                    if error.is_some() {
+                        state.pending.push_front(page);
+                    }
+                }
+                notify.notify_waiters();
+
+                if let Some(e) = error {
+                    warn!("Dropping {} from fast sync after failing page {}: {}", peer, page, e);
+                    return false
+                }
+            }
+
+            true
+        });
+        join_all(workers).await
+    };
+
+    let mut healthy = healthy.into_iter();
+    peers.retain(|_| healthy.next().unwrap_or(false));
+
+    let results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+    let missing = pages - results.len() as u64;
+    if missing > 0 {
+        return Err(P2pError::NoPeerForPages(missing))
+    }
+
+    Ok(results.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc
+        },
+        time::Duration
+    };
+    use tokio::time::sleep;
+    use super::*;
+
+    // Mock peer serving each page after a delay
+    struct MockPeer {
+        name: &'static str,
+        // page for which inconsistent data is returned
+        invalid_page: Option<u64>,
+        delay: Duration,
+        served: Mutex<Vec<u64>>
+    }
+
+    impl MockPeer {
+        fn new(name: &'static str, invalid_page: Option<u64>) -> Self {
+            Self {
+                name,
+                invalid_page,
+                delay: Duration::from_millis(5),
+                served: Mutex::new(Vec::new())
+            }
+        }
+
+        fn with_delay(name: &'static str, delay: u64) -> Self {
+            Self {
+                delay: Duration::from_millis(delay),
+                ..Self::new(name, None)
+            }
+        }
+
+        async fn fetch(&self, page: u64) -> Result<u64, P2pError> {
+            sleep(self.delay).await;
+            if self.invalid_page == Some(page) {
+                return Err(P2pError::InvalidPacket)
+            }
+
+            self.served.lock().unwrap().push(page);
+            Ok(page * 10)
+        }
+
+        fn served(&self) -> Vec<u64> {
+            self.served.lock().unwrap().clone()
+        }
+    }
+
+    impl fmt::Display for MockPeer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.name)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pages_split_across_peers() {
+        let mut peers = vec![Arc::new(MockPeer::new("a", None)), Arc::new(MockPeer::new("b", None))];
+        let pages = fetch_pages(&mut peers, 10, |peer: Arc<MockPeer>, page| async move { peer.fetch(page).await }).await.unwrap();
+        assert_eq!(pages, (0..10).map(|page| page * 10).collect::<Vec<_>>());
+        assert_eq!(peers.len(), 2);
+
+        // Each peer served a part of the pages, without overlap
+        let (a, b) = (peers[0].served(), peers[1].served());
+        assert!(!a.is_empty() && !b.is_empty());
+        assert_eq!(a.len() + b.len(), 10);
+        assert!(a.iter().all(|page| !b.contains(page)));
+    }
+
+    #[tokio::test]
+    async fn test_pages_fetched_concurrently_in_order() {
+        // Peers answering at different speeds complete their pages out of order
+        let mut peers = vec![
+            Arc::new(MockPeer::with_delay("a", 1)),
+            Arc::new(MockPeer::with_delay("b", 10)),
+            Arc::new(MockPeer::with_delay("c", 40))
+        ];
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let completed = Mutex::new(Vec::new());
+
+        let pages = fetch_pages(&mut peers, 24, |peer: Arc<MockPeer>, page| {
+            let (in_flight, max_in_flight, completed) = (&in_flight, &max_in_flight, &completed);
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                let res = peer.fetch(page).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                completed.lock().unwrap().push(page);
+                res
+            }
+        }).await.unwrap();
+
+        // Every peer had a request in flight at the same time, never more than one each
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+
+        // The slowest peer completed its first page after pages requested later
+        let completed = completed.into_inner().unwrap();
+        assert_ne!(completed, (0..24).collect::<Vec<_>>());
+
+        // Results are still returned in the pages order
+        assert_eq!(pages, (0..24).map(|page| page * 10).collect::<Vec<_>>());
+
+        // Each page was served once, the fastest peer served the most
+        let mut served: Vec<u64> = peers.iter().flat_map(|peer| peer.served()).collect();
+        served.sort();
+        assert_eq!(served, (0..24).collect::<Vec<_>>());
+        assert!(peers[0].served().len() > peers[2].served().len());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_peer_dropped() {
+        // Peers take the first pages in order, b fails its first one
+        let mut peers = vec![Arc::new(MockPeer::new("a", None)), Arc::new(MockPeer::new("b", Some(1)))];
+        let pages = fetch_pages(&mut peers, 10, |peer: Arc<MockPeer>, page| async move { peer.fetch(page).await }).await.unwrap();
+        assert_eq!(pages, (0..10).map(|page| page * 10).collect::<Vec<_>>());
+
+        // Invalid peer is dropped and its page fetched from the other one
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].name, "a");
+        assert!(peers[0].served().contains(&1));
+
+        // No peer left for the remaining pages
+        let mut peers = vec![Arc::new(MockPeer::new("a", Some(0)))];
+        let res = fetch_pages(&mut peers, 4, |peer: Arc<MockPeer>, page| async move { peer.fetch(page).await }).await;
+        assert!(matches!(res, Err(P2pError::NoPeerForPages(4))));
+        assert!(peers.is_empty());
+    }
+}
@@ -1,8 +1,9 @@
 use crate::{
     config::{
         PEER_FAIL_TIME_RESET, PEER_BLOCK_CACHE_SIZE, PEER_TX_CACHE_SIZE,
-        PEER_TEMP_BAN_TIME, PEER_TIMEOUT_BOOTSTRAP_STEP,
-        PEER_TIMEOUT_REQUEST_OBJECT, CHAIN_SYNC_TIMEOUT_SECS
+        PEER_TEMP_BAN_TIME,
+        PEER_TIMEOUT_REQUEST_OBJECT, CHAIN_SYNC_TIMEOUT_SECS,
+        P2P_CAPABILITIES
     },
     p2p::packet::PacketWrapper
 };
@@ -21,8 +22,11 @@ use super::{
         bootstrap_chain::{
             StepRequest,
             BootstrapChainRequest,
+            verify_step_kind,
+            StepKind,
             StepResponse
         },
+        capabilities::PeerCapabilities,
         chain::{
             ChainRequest,
             ChainResponse
@@ -122,12 +126,16 @@ pub struct Peer {
     is_pruned: AtomicBool,
     // used for await on bootstrap chain packets
     bootstrap_chain: Mutex<Option<Sender<StepResponse>>>,
+    // step of the last bootstrap chain request, used to limit the response size
+    bootstrap_chain_step: Mutex<Option<StepKind>>,
     // used to wait on chain response when syncing chain
     sync_chain: Mutex<Option<Sender<ChainResponse>>>,
     // IP address with local port
     outgoing_address: SocketAddr,
     // Determine if this peer allows to be shared to others and/or through API
-    sharable: bool
+    sharable: bool,
+    // Optional features usable with this peer, supported by both sides
+    capabilities: PeerCapabilities
 }
 
 impl Peer {
@@ -167,9 +175,11 @@ impl Peer {
             pruned_topoheight: AtomicU64::new(pruned_topoheight.unwrap_or(0)),
             is_pruned: AtomicBool::new(pruned_topoheight.is_some()),
             bootstrap_chain: Mutex::new(None),
+            bootstrap_chain_step: Mutex::new(None),
             sync_chain: Mutex::new(None),
             outgoing_address,
-            sharable
+            sharable,
+            capabilities: PeerCapabilities::empty()
         }
     }
 
@@ -294,6 +304,18 @@ impl Peer {
         self.sharable
     }
 
+    // Set the capabilities announced by the peer
+    // Only the ones we also support are kept
+    pub fn set_capabilities(&mut self, capabilities: PeerCapabilities) {
+        self.capabilities = P2P_CAPABILITIES.intersection(capabilities);
+    }
+
+    // Check if this feature can be used with the peer
+    // It must be supported by both of us
+    pub fn supports(&self, flag: PeerCapabilities) -> bool {
+        self.capabilities.supports(flag)
+    }
+
     // Get the last time we got a fail from the peer
     pub fn get_last_fail_count(&self) -> u64 {
         self.last_fail_count.load(Ordering::Acquire)
@@ -406,7 +428,7 @@ impl Peer {
     }
 
     // Request a bootstrap chain from this peer and wait on it until we receive it or until timeout
-    pub async fn request_boostrap_chain(&self, step: StepRequest<'_>) -> Result<StepResponse, P2pError> {
+    pub async fn request_boostrap_chain(&self, step: StepRequest<'_>, step_timeout: Duration) -> Result<StepResponse, P2pError> {
         debug!("Requesting bootstrap chain step: {:?}", step.kind());
         let step_kind = step.kind();
         let (sender, receiver) = tokio::sync::oneshot::channel();
@@ -414,12 +436,14 @@ impl Peer {
             let mut sender_lock = self.bootstrap_chain.lock().await;
             *sender_lock = Some(sender);
         }
+        *self.bootstrap_chain_step.lock().await = Some(step_kind);
 
         // send the packet
-        self.send_packet(Packet::BootstrapChainRequest(BootstrapChainRequest::new(step))).await?;
+        let balances_pages = self.supports(PeerCapabilities::BALANCES_PAGES);
+        self.send_packet(Packet::BootstrapChainRequest(BootstrapChainRequest::new(&step, balances_pages))).await?;
 
         // wait on the response
-        let response: StepResponse = match timeout(Duration::from_millis(PEER_TIMEOUT_BOOTSTRAP_STEP), receiver).await {
+        let response: StepResponse = match timeout(step_timeout, receiver).await {
             Ok(res) => res?,
             Err(e) => {
                 debug!("Requested bootstrap chain step {:?} has timed out", step_kind);
@@ -428,10 +452,7 @@ impl Peer {
         };
 
         // check that the response is what we asked for
-        let response_kind = response.kind();
-        if response_kind != step_kind {
-            return Err(P2pError::InvalidBootstrapStep(step_kind, response_kind))
-        }
+        verify_step_kind(step_kind, &response)?;
 
         Ok(response)
     }
@@ -466,6 +487,11 @@ impl Peer {
         &self.bootstrap_chain
     }
 
+    // Step of the last bootstrap chain request sent to this peer
+    pub async fn get_requested_bootstrap_step(&self) -> Option<StepKind> {
+        *self.bootstrap_chain_step.lock().await
+    }
+
     // Get the sync chain channel
     // This is used for chain sync requests to be fully awaited
     pub fn get_sync_chain_channel(&self) -> &Mutex<Option<Sender<ChainResponse>>> {
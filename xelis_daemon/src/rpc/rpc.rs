@@ -12,12 +12,14 @@ use crate::{
         },
         error::BlockchainError,
         mempool::Mempool,
+        merkle::{build_balances_merkle_hash, get_balances_merkle_leaves, BalanceLeaf},
         storage::Storage
     },
     p2p::peer::Peer
 };
 use super::{InternalRpcError, ApiError};
 use xelis_common::{
+    account::build_merkle_path,
     api::{
         daemon::{
             AccountHistoryEntry,
@@ -30,7 +32,10 @@ use xelis_common::{
             GetAssetParams,
             GetAssetsParams,
             GetBalanceAtTopoHeightParams,
+            GetBalanceMerkleProofParams,
+            GetBalanceMerkleProofResult,
             GetBalanceParams,
+            GetBalancesMerkleHashParams,
             GetBalanceResult,
             GetBlockAtTopoHeightParams,
             GetBlockByHashParams,
@@ -298,6 +303,8 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("get_balance", async_handler!(get_balance::<S>));
     handler.register_method("has_balance", async_handler!(has_balance::<S>));
     handler.register_method("get_balance_at_topoheight", async_handler!(get_balance_at_topoheight::<S>));
+    handler.register_method("get_balances_merkle_hash", async_handler!(get_balances_merkle_hash::<S>));
+    handler.register_method("get_balance_merkle_proof", async_handler!(get_balance_merkle_proof::<S>));
     handler.register_method("get_info", async_handler!(get_info::<S>));
     handler.register_method("get_nonce", async_handler!(get_nonce::<S>));
     handler.register_method("has_nonce", async_handler!(has_nonce::<S>));
@@ -501,6 +508,77 @@ async fn get_balance_at_topoheight<S: Storage>(context: Context, body: Value) ->
     Ok(json!(balance))
 }
 
+// Compute the leaves and the root of the balances merkle tree at the topoheight
+// Root at a stable topoheight is stored, blocks up to it can't be reordered anymore
+async fn compute_balances_merkle_tree<S: Storage>(blockchain: &Blockchain<S>, topoheight: u64) -> Result<(Vec<BalanceLeaf>, Hash), InternalRpcError> {
+    if topoheight > blockchain.get_topo_height() {
+        return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be greater than current chain topoheight")?
+    }
+
+    let (leaves, merkle_hash, stored) = {
+        let storage = blockchain.get_storage().read().await;
+        if storage.get_pruned_topoheight().await.context("Error while retrieving pruned topoheight")?.is_some_and(|pruned| topoheight < pruned) {
+            return Err(InternalRpcError::UnexpectedParams).context("Topoheight cannot be lower than pruned topoheight")?
+        }
+
+        let leaves = get_balances_merkle_leaves(&*storage, topoheight).await.context("Error while retrieving balances merkle leaves")?;
+        let merkle_hash = build_balances_merkle_hash(&leaves);
+        let stored = storage.has_balances_merkle_hash_at_topoheight(topoheight).await.context("Error while checking balances merkle hash")?;
+        (leaves, merkle_hash, stored)
+    };
+
+    if !stored && topoheight <= blockchain.get_stable_topoheight() {
+        let mut storage = blockchain.get_storage().write().await;
+        storage.set_balances_merkle_hash_at_topoheight(topoheight, &merkle_hash).await.context("Error while storing balances merkle hash")?;
+    }
+
+    Ok((leaves, merkle_hash))
+}
+
+async fn get_balances_merkle_hash<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetBalancesMerkleHashParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    {
+        let storage = blockchain.get_storage().read().await;
+        if storage.has_balances_merkle_hash_at_topoheight(params.topoheight).await.context("Error while checking balances merkle hash")? {
+            let merkle_hash = storage.get_balances_merkle_hash_at_topoheight(params.topoheight).await.context("Error while retrieving balances merkle hash")?;
+            return Ok(json!(merkle_hash))
+        }
+    }
+
+    let (_, merkle_hash) = compute_balances_merkle_tree(&blockchain, params.topoheight).await?;
+    Ok(json!(merkle_hash))
+}
+
+// Path of the account balance in the balances merkle tree at the topoheight
+// null is returned if the account has no balance for this asset at the topoheight
+async fn get_balance_merkle_proof<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetBalanceMerkleProofParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let key = params.address.get_public_key();
+    let (leaves, merkle_hash) = compute_balances_merkle_tree(&blockchain, params.topoheight).await?;
+    let Some(index) = leaves.iter().position(|(k, asset, _)| k == key && *asset == *params.asset) else {
+        return Ok(Value::Null)
+    };
+
+    let (_, path) = build_merkle_path(leaves.into_iter().map(|(_, _, leaf)| leaf).collect(), index)
+        .ok_or(InternalRpcError::InvalidRequest)?;
+    let storage = blockchain.get_storage().read().await;
+    let (_, version) = storage.get_balance_at_maximum_topoheight(key, &params.asset, params.topoheight).await
+        .context("Error while retrieving balance at maximum topoheight")?
+        .ok_or(InternalRpcError::InvalidRequest)?;
+
+    Ok(json!(GetBalanceMerkleProofResult {
+        version,
+        path,
+        merkle_hash
+    }))
+}
+
 async fn has_nonce<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: HasNonceParams = parse_params(body)?;
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
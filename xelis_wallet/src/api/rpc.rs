@@ -153,10 +153,11 @@ async fn split_address(_: Context, body: Value) -> Result<Value, InternalRpcErro
 }
 
 // Rescan the wallet from the provided topoheight (or from the beginning if not provided)
+// Only the balance of the asset is deleted if one is provided
 async fn rescan(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: RescanParams = parse_params(body)?;
     let wallet: &Arc<Wallet> = context.get()?;
-    wallet.rescan(params.until_topoheight.unwrap_or(0)).await.context("Error while rescanning wallet")?;
+    wallet.rescan(params.until_topoheight.unwrap_or(0), params.asset).await.context("Error while rescanning wallet")?;
     Ok(json!(true))
 }
 
@@ -166,11 +167,10 @@ async fn get_balance(context: Context, body: Value) -> Result<Value, InternalRpc
     let params: GetBalanceParams = parse_params(body)?;
     let asset = params.asset.unwrap_or(XELIS_ASSET);
     let wallet: &Arc<Wallet> = context.get()?;
-    let storage = wallet.get_storage().read().await;
 
     // If the asset is not found, it will returns 0
     // Use has_balance below to check if the wallet has a balance for a specific asset
-    let balance = storage.get_plaintext_balance_for(&asset).await.unwrap_or(0);
+    let balance = wallet.get_balance(&asset).await.unwrap_or(0);
     Ok(json!(balance))
 }
 
@@ -322,7 +322,7 @@ async fn set_offline_mode(context: Context, body: Value) -> Result<Value, Intern
         return Err(InternalRpcError::CustomStr("Wallet is already in offline mode"))
     }
 
-    wallet.set_offline_mode().await.context("Error while setting offline mode")?;
+    wallet.set_offline_mode(false).await.context("Error while setting offline mode")?;
 
     Ok(json!(true))
 }
@@ -332,7 +332,7 @@ async fn sign_data(context: Context, body: Value) -> Result<Value, InternalRpcEr
     let params: DataElement = parse_params(body)?;
 
     let wallet: &Arc<Wallet> = context.get()?;
-    let signature = wallet.sign_data(&params.to_bytes());
+    let signature = wallet.sign_data(&params.to_bytes()).context("Error while signing data")?;
     Ok(json!(signature))
 }
 
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use chacha20poly1305::aead::OsRng;
+use log::trace;
+use rand::RngCore;
+use xelis_common::{
+    crypto::{Hash, PrivateKey},
+    network::Network,
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+use zeroize::Zeroizing;
+use crate::{
+    cipher::Cipher,
+    config::{SALT_SIZE, WALLET_BACKUP_VERSION},
+    contact::Contact,
+    entry::TransactionEntry,
+    storage::{Balance, EncryptedStorage},
+    wallet::{hash_password, KdfParams, WalletError}
+};
+
+// Everything needed to rebuild a wallet on another machine
+// It is exported as a single versioned blob encrypted with a password-derived key
+pub struct WalletBackup {
+    // Network the wallet was created for
+    pub network: Network,
+    pub private_key: PrivateKey,
+    pub nonce: Option<u64>,
+    pub synced_topoheight: Option<u64>,
+    // Sync markers, so the restored wallet resumes from the same point without a full rescan
+    pub top_block_hash: Option<Hash>,
+    pub changes_topoheight: Vec<(u64, Hash)>,
+    // Registered assets with their decimals
+    pub assets: Vec<(Hash, u8)>,
+    pub balances: Vec<(Hash, Balance)>,
+    // Transaction history
+    pub transactions: Vec<TransactionEntry>,
+    // Address book
+    pub contacts: Vec<(String, Contact)>
+}
+
+impl WalletBackup {
+    // Read the backup content from the storage
+    pub async fn from_storage(storage: &EncryptedStorage, network: Network) -> Result<Self> {
+        trace!("create wallet backup");
+        if storage.is_watch_only()? {
+            return Err(WalletError::WatchOnly.into())
+        }
+
+        let assets = storage.get_assets_with_decimals().await?;
+        let mut balances = Vec::new();
+        for (asset, _) in assets.iter() {
+            if storage.has_balance_for(asset).await? {
+                balances.push((asset.clone(), storage.get_balance_for(asset).await?));
+            }
+        }
+
+        let top_block_hash = if storage.has_top_block_hash()? {
+            Some(storage.get_top_block_hash()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            network,
+            private_key: storage.get_private_key()?,
+            nonce: storage.get_nonce().ok(),
+            synced_topoheight: storage.get_synced_topoheight().ok(),
+            top_block_hash,
+            changes_topoheight: storage.get_topoheight_changes().collect::<Result<_>>()?,
+            assets,
+            balances,
+            transactions: storage.get_transactions()?,
+            contacts: storage.get_contacts()?
+        })
+    }
+
+    // Write the backup content in a new storage
+    pub async fn restore_into(self, storage: &mut EncryptedStorage) -> Result<()> {
+        trace!("restore wallet backup");
+        storage.set_private_key(&self.private_key)?;
+        if let Some(nonce) = self.nonce {
+            storage.set_nonce(nonce)?;
+        }
+
+        if let Some(topoheight) = self.synced_topoheight {
+            storage.set_synced_topoheight(topoheight)?;
+        }
+
+        if let Some(hash) = &self.top_block_hash {
+            storage.set_top_block_hash(hash)?;
+        }
+
+        for (topoheight, hash) in self.changes_topoheight {
+            storage.add_topoheight_to_changes(topoheight, &hash)?;
+        }
+
+        for (asset, decimals) in self.assets {
+            storage.add_asset(&asset, decimals).await?;
+        }
+
+        for (asset, balance) in self.balances {
+            storage.set_balance_for(&asset, balance).await?;
+        }
+
+        for transaction in self.transactions {
+            storage.save_transaction(transaction.get_hash(), &transaction)?;
+            storage.save_subaddress_transaction(&transaction)?;
+        }
+
+        for (label, contact) in self.contacts {
+            storage.set_contact(&label, &contact)?;
+        }
+
+        storage.flush()
+    }
+
+    // Encrypt the backup using a key derived from the password
+    // Format is: version, password salt, KDF params, then the encrypted content
+    pub fn encrypt(&self, password: String) -> Result<Vec<u8>> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+
+        let kdf_params = KdfParams::default();
+        let hashed_password = hash_password(password, &salt, &kdf_params)?;
+        let cipher = Cipher::new(hashed_password.as_slice(), None)?;
+        let plaintext = Zeroizing::new(self.to_bytes());
+        let encrypted = cipher.encrypt_value(&plaintext)?;
+
+        let mut writer = Writer::new();
+        writer.write_u8(WALLET_BACKUP_VERSION);
+        writer.write_bytes(&salt);
+        kdf_params.write(&mut writer);
+        writer.write_bytes(&encrypted);
+
+        Ok(writer.bytes())
+    }
+
+    // Decrypt a backup, the password must be the one used to export it
+    pub fn decrypt(bytes: &[u8], password: String) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let version = reader.read_u8()?;
+        if version != WALLET_BACKUP_VERSION {
+            return Err(WalletError::UnsupportedBackupVersion(version).into())
+        }
+
+        let salt: [u8; SALT_SIZE] = reader.read_bytes(SALT_SIZE)?;
+        let kdf_params = KdfParams::read(&mut reader)?;
+        let encrypted = reader.read_bytes_ref(reader.size())?;
+
+        let hashed_password = hash_password(password, &salt, &kdf_params)?;
+        let cipher = Cipher::new(hashed_password.as_slice(), None)?;
+        let decrypted = cipher.decrypt_value(encrypted).context("Invalid password provided for this backup")?;
+
+        Ok(Self::from_bytes(&decrypted)?)
+    }
+}
+
+// Balances and entries have optional trailing fields, so they are prefixed by their size
+fn write_sized<T: Serializer>(writer: &mut Writer, value: &T) {
+    let bytes = value.to_bytes();
+    writer.write_u32(&(bytes.len() as u32));
+    writer.write_bytes(&bytes);
+}
+
+fn read_sized<T: Serializer>(reader: &mut Reader) -> Result<T, ReaderError> {
+    let size = reader.read_u32()? as usize;
+    T::from_bytes(reader.read_bytes_ref(size)?)
+}
+
+// Lists are prefixed by a u64 count, history can be above the default Vec limit
+fn write_list<T>(writer: &mut Writer, values: &[T], write: impl Fn(&T, &mut Writer)) {
+    writer.write_u64(&(values.len() as u64));
+    for value in values {
+        write(value, writer);
+    }
+}
+
+fn read_list<T>(reader: &mut Reader, read: impl Fn(&mut Reader) -> Result<T, ReaderError>) -> Result<Vec<T>, ReaderError> {
+    let count = reader.read_u64()?;
+    // Each element is at least one byte
+    if count > reader.size() as u64 {
+        return Err(ReaderError::InvalidSize)
+    }
+
+    let mut values = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        values.push(read(reader)?);
+    }
+
+    Ok(values)
+}
+
+impl Serializer for WalletBackup {
+    fn write(&self, writer: &mut Writer) {
+        self.network.write(writer);
+        self.private_key.write(writer);
+        self.nonce.write(writer);
+        self.synced_topoheight.write(writer);
+        self.top_block_hash.write(writer);
+        write_list(writer, &self.changes_topoheight, |(topoheight, hash), writer| {
+            topoheight.write(writer);
+            hash.write(writer);
+        });
+        write_list(writer, &self.assets, |(asset, decimals), writer| {
+            asset.write(writer);
+            decimals.write(writer);
+        });
+        write_list(writer, &self.balances, |(asset, balance), writer| {
+            asset.write(writer);
+            write_sized(writer, balance);
+        });
+        write_list(writer, &self.transactions, |transaction, writer| write_sized(writer, transaction));
+        write_list(writer, &self.contacts, |(label, contact), writer| {
+            label.write(writer);
+            contact.write(writer);
+        });
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(Self {
+            network: Network::read(reader)?,
+            private_key: PrivateKey::read(reader)?,
+            nonce: Option::read(reader)?,
+            synced_topoheight: Option::read(reader)?,
+            top_block_hash: Option::read(reader)?,
+            changes_topoheight: read_list(reader, |reader| Ok((u64::read(reader)?, Hash::read(reader)?)))?,
+            assets: read_list(reader, |reader| Ok((Hash::read(reader)?, u8::read(reader)?)))?,
+            balances: read_list(reader, |reader| Ok((Hash::read(reader)?, read_sized(reader)?)))?,
+            transactions: read_list(reader, read_sized)?,
+            contacts: read_list(reader, |reader| Ok((String::read(reader)?, Contact::read(reader)?)))?
+        })
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant}
+};
+use xelis_common::crypto::Hash;
+
+// Plaintext balances recently read from the storage
+// An entry is served until its TTL expires or until it is invalidated by a balance change
+pub struct BalanceCache {
+    // Duration for which a cached balance is valid
+    ttl: Duration,
+    // Plaintext balance per asset with the instant it was cached
+    balances: HashMap<Hash, (u64, Instant)>,
+    // Incremented on each invalidation
+    // A balance read from the storage before an invalidation may be outdated and isn't cached
+    generation: u64
+}
+
+impl BalanceCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            balances: HashMap::new(),
+            generation: 0
+        }
+    }
+
+    // Configure the TTL, already cached balances are checked against the new one
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    pub fn get_ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    // Get the cached balance of an asset if it's not expired
+    pub fn get(&self, asset: &Hash) -> Option<u64> {
+        self.get_at(asset, Instant::now())
+    }
+
+    fn get_at(&self, asset: &Hash, now: Instant) -> Option<u64> {
+        self.balances.get(asset)
+            .filter(|(_, cached_at)| now.saturating_duration_since(*cached_at) < self.ttl)
+            .map(|(amount, _)| *amount)
+    }
+
+    // Generation to fetch before reading a balance from the storage
+    pub fn get_generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Cache the balance read from the storage at this generation
+    // It is ignored if the cache was invalidated since, returns true if it was cached
+    pub fn put(&mut self, asset: Hash, amount: u64, generation: u64) -> bool {
+        if generation != self.generation {
+            return false
+        }
+
+        self.balances.insert(asset, (amount, Instant::now()));
+        true
+    }
+
+    // Invalidate the cached balance of an asset, returns true if it was cached
+    pub fn invalidate(&mut self, asset: &Hash) -> bool {
+        self.generation += 1;
+        self.balances.remove(asset).is_some()
+    }
+
+    // Invalidate all the cached balances
+    pub fn clear(&mut self) {
+        self.generation += 1;
+        self.balances.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_generation() {
+        let mut cache = BalanceCache::new(Duration::from_secs(60));
+        let asset = Hash::new([1u8; 32]);
+
+        // Balance was read before an invalidation, it may be outdated
+        let generation = cache.get_generation();
+        assert!(!cache.invalidate(&asset));
+        assert!(!cache.put(asset.clone(), 100, generation));
+        assert_eq!(cache.get(&asset), None);
+
+        assert!(cache.put(asset.clone(), 50, cache.get_generation()));
+        assert_eq!(cache.get(&asset), Some(50));
+        assert!(cache.invalidate(&asset));
+        assert_eq!(cache.get(&asset), None);
+    }
+
+    #[test]
+    fn test_cache_ttl() {
+        let mut cache = BalanceCache::new(Duration::from_secs(60));
+        let asset = Hash::new([1u8; 32]);
+        cache.put(asset.clone(), 100, cache.get_generation());
+
+        let now = Instant::now();
+        assert_eq!(cache.get_at(&asset, now), Some(100));
+        assert_eq!(cache.get_at(&asset, now + Duration::from_secs(61)), None);
+
+        // A TTL of zero disables the cache
+        cache.set_ttl(Duration::ZERO);
+        assert_eq!(cache.get(&asset), None);
+
+        cache.set_ttl(Duration::from_secs(60));
+        cache.clear();
+        assert_eq!(cache.get(&asset), None);
+    }
+}
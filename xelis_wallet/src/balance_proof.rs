@@ -0,0 +1,211 @@
+use xelis_common::{
+    account::{balance_leaf_hash, compute_merkle_root, MerklePath},
+    crypto::{
+        elgamal::CompressedCiphertext,
+        hash,
+        proofs::AmountProof,
+        Hash,
+        KeyPair,
+        PublicKey
+    },
+    serializer::{Reader, ReaderError, Serializer, Writer}
+};
+
+// Proof that an account held an amount of an asset at a topoheight
+// Every balance of the chain at this topoheight is a leaf of the balances merkle tree computed by the daemons,
+// the proof contains the path from our leaf up to its root
+// The amount is bound to our encrypted balance, so an auditor only needs the root
+// announced by any node at this topoheight to verify it
+#[derive(Debug, Clone)]
+pub struct BalanceProof {
+    pub asset: Hash,
+    pub topoheight: u64,
+    // Account holding the balance
+    pub public_key: PublicKey,
+    // Plaintext balance
+    pub amount: u64,
+    // Encrypted balance on-chain the amount was decoded from
+    pub ciphertext: CompressedCiphertext,
+    // Proof that the ciphertext encrypts the amount to the account
+    pub amount_proof: AmountProof,
+    // Sibling hashes from the leaf to the root
+    // flag is set when the sibling is on the left side
+    pub path: MerklePath,
+    // Root of the balances merkle tree at the topoheight
+    pub merkle_hash: Hash
+}
+
+impl BalanceProof {
+    // Build the proof of our balance from its path in the balances merkle tree
+    // Returns None if the ciphertext is invalid
+    pub fn new(keypair: &KeyPair, asset: Hash, topoheight: u64, amount: u64, ciphertext: CompressedCiphertext, path: MerklePath, merkle_hash: Hash) -> Option<Self> {
+        let public_key = keypair.get_public_key().compress();
+        let context = amount_context(&balance_leaf_hash(&public_key, &asset, &ciphertext), topoheight);
+        let amount_proof = AmountProof::new(keypair, &ciphertext.decompress().ok()?, amount, &context);
+
+        Some(Self {
+            asset,
+            topoheight,
+            public_key,
+            amount,
+            ciphertext,
+            amount_proof,
+            path,
+            merkle_hash
+        })
+    }
+
+    // Compute the merkle root from the leaf and its path
+    pub fn compute_root(&self) -> Hash {
+        compute_merkle_root(self.leaf_hash(), &self.path)
+    }
+
+    fn leaf_hash(&self) -> Hash {
+        balance_leaf_hash(&self.public_key, &self.asset, &self.ciphertext)
+    }
+}
+
+// Validate the proof offline against the root the auditor expects
+pub fn verify_balance_proof(proof: &BalanceProof, expected_root: &Hash) -> bool {
+    if proof.merkle_hash != *expected_root || proof.compute_root() != *expected_root {
+        return false
+    }
+
+    let (Ok(public_key), Ok(ciphertext)) = (proof.public_key.decompress(), proof.ciphertext.decompress()) else {
+        return false
+    };
+    let context = amount_context(&proof.leaf_hash(), proof.topoheight);
+    proof.amount_proof.verify(&public_key, &ciphertext, proof.amount, &context).is_ok()
+}
+
+// Context of the amount proof, so it can't be reused for another balance or topoheight
+fn amount_context(leaf: &Hash, topoheight: u64) -> Hash {
+    let mut writer = Writer::new();
+    leaf.write(&mut writer);
+    topoheight.write(&mut writer);
+    hash(writer.as_bytes())
+}
+
+impl Serializer for BalanceProof {
+    fn write(&self, writer: &mut Writer) {
+        self.asset.write(writer);
+        self.topoheight.write(writer);
+        self.public_key.write(writer);
+        self.amount.write(writer);
+        self.ciphertext.write(writer);
+        self.amount_proof.write(writer);
+        self.path.write(writer);
+        self.merkle_hash.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(Self {
+            asset: Hash::read(reader)?,
+            topoheight: u64::read(reader)?,
+            public_key: PublicKey::read(reader)?,
+            amount: u64::read(reader)?,
+            ciphertext: CompressedCiphertext::read(reader)?,
+            amount_proof: AmountProof::read(reader)?,
+            path: Vec::read(reader)?,
+            merkle_hash: Hash::read(reader)?
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.asset.size()
+            + self.topoheight.size()
+            + self.public_key.size()
+            + self.amount.size()
+            + self.ciphertext.size()
+            + self.amount_proof.size()
+            + self.path.size()
+            + self.merkle_hash.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{account::build_merkle_path, config::XELIS_ASSET};
+    use super::*;
+
+    // Proofs of all the balances of a tree, the balances of the other accounts are random leaves
+    fn proofs(keypair: &KeyPair, count: u8) -> Vec<BalanceProof> {
+        let balances: Vec<(Hash, u64, CompressedCiphertext)> = (0..count).map(|i| {
+            let amount = 100 * (i as u64 + 1);
+            (Hash::new([i; 32]), amount, keypair.get_public_key().encrypt(amount).compress())
+        }).collect();
+        let public_key = keypair.get_public_key().compress();
+        let mut leaves: Vec<Hash> = balances.iter()
+            .map(|(asset, _, ciphertext)| balance_leaf_hash(&public_key, asset, ciphertext))
+            .collect();
+        leaves.push(Hash::new([42u8; 32]));
+
+        balances.into_iter().enumerate().map(|(index, (asset, amount, ciphertext))| {
+            let (root, path) = build_merkle_path(leaves.clone(), index).unwrap();
+            BalanceProof::new(keypair, asset, 10, amount, ciphertext, path, root).unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn test_balance_proof_round_trip() {
+        let keypair = KeyPair::new();
+        let proofs = proofs(&keypair, 5);
+        let root = proofs[0].merkle_hash.clone();
+        for proof in proofs.iter() {
+            assert_eq!(proof.merkle_hash, root);
+            assert!(verify_balance_proof(proof, &root));
+
+            let decoded = BalanceProof::from_bytes(&proof.to_bytes()).unwrap();
+            assert_eq!(decoded.to_bytes().len(), proof.size());
+            assert!(verify_balance_proof(&decoded, &root));
+        }
+
+        // Single balance, the leaf is the root
+        let public_key = keypair.get_public_key().compress();
+        let ciphertext = keypair.get_public_key().encrypt(100u64).compress();
+        let root = balance_leaf_hash(&public_key, &XELIS_ASSET, &ciphertext);
+        let single = BalanceProof::new(&keypair, XELIS_ASSET, 10, 100, ciphertext, Vec::new(), root.clone()).unwrap();
+        assert!(verify_balance_proof(&single, &root));
+    }
+
+    #[test]
+    fn test_balance_proof_tampered() {
+        let keypair = KeyPair::new();
+        let proof = proofs(&keypair, 4).remove(1);
+        let root = proof.merkle_hash.clone();
+
+        // Amount doesn't match the ciphertext anymore
+        let mut tampered = proof.clone();
+        tampered.amount += 1;
+        assert!(!verify_balance_proof(&tampered, &root));
+
+        // Ciphertext of the claimed amount isn't in the tree
+        let mut tampered = proof.clone();
+        tampered.amount += 1;
+        tampered.ciphertext = keypair.get_public_key().encrypt(tampered.amount).compress();
+        tampered.amount_proof = BalanceProof::new(&keypair, tampered.asset.clone(), 10, tampered.amount, tampered.ciphertext.clone(), Vec::new(), Hash::zero()).unwrap().amount_proof;
+        assert!(!verify_balance_proof(&tampered, &root));
+
+        let mut tampered = proof.clone();
+        tampered.topoheight = 11;
+        assert!(!verify_balance_proof(&tampered, &root));
+
+        let mut tampered = proof.clone();
+        tampered.public_key = KeyPair::new().get_public_key().compress();
+        assert!(!verify_balance_proof(&tampered, &root));
+
+        let mut tampered = proof.clone();
+        tampered.asset = Hash::new([9u8; 32]);
+        assert!(!verify_balance_proof(&tampered, &root));
+
+        let mut tampered = proof.clone();
+        tampered.path[0].0 = !tampered.path[0].0;
+        assert!(!verify_balance_proof(&tampered, &root));
+
+        // Proof claiming another root
+        let mut tampered = proof.clone();
+        tampered.merkle_hash = Hash::zero();
+        assert!(!verify_balance_proof(&tampered, &root));
+        assert!(!verify_balance_proof(&proof, &Hash::zero()));
+    }
+}
@@ -11,6 +11,7 @@ use xelis_common::crypto::{
     HASH_SIZE,
     hash
 };
+use zeroize::Zeroizing;
 use crate::{wallet::WalletError, config::SALT_SIZE};
 
 
@@ -30,6 +31,11 @@ impl Cipher {
         })
     }
 
+    // Same salt but another key, used to rotate the master key
+    pub fn with_key(&self, key: &[u8]) -> Result<Self> {
+        Self::new(key, self.salt)
+    }
+
     // encrypt value passed in param and add plaintext nonce before encrypted value
     // a Nonce is generated randomly at each call
     pub fn encrypt_value(&self, value: &[u8]) -> Result<Vec<u8>, WalletError> {
@@ -40,7 +46,7 @@ impl Cipher {
 
     // encrypt value passed in param and add plaintext nonce before encrypted value
     pub fn encrypt_value_with_nonce(&self, value: &[u8], nonce: &[u8; Self::NONCE_SIZE]) -> Result<Vec<u8>, WalletError> {
-        let mut plaintext: Vec<u8> = Vec::with_capacity(SALT_SIZE + value.len());
+        let mut plaintext = Zeroizing::new(Vec::with_capacity(SALT_SIZE + value.len()));
         // add salt to the plaintext value
         if let Some(salt) = &self.salt {
             plaintext.extend_from_slice(salt);
@@ -59,7 +65,8 @@ impl Cipher {
     }
 
     // decrypt any value loaded from disk, with the format of above function
-    pub fn decrypt_value(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+    // decrypted value is wiped from memory once dropped
+    pub fn decrypt_value(&self, encrypted: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
         // nonce is 24 bytes and is mandatory in encrypted slice
         if encrypted.len() < 25 {
             return Err(WalletError::InvalidEncryptedValue.into())
@@ -68,7 +75,7 @@ impl Cipher {
         // read the nonce for this data 
         let nonce = XNonce::from_slice(&encrypted[0..24]);
         // decrypt the value using the nonce previously decoded
-        let mut decrypted = self.cipher.decrypt(nonce, &encrypted[nonce.len()..]).map_err(|e| WalletError::CryptoError(e))?;
+        let mut decrypted = Zeroizing::new(self.cipher.decrypt(nonce, &encrypted[nonce.len()..]).map_err(|e| WalletError::CryptoError(e))?);
         // delete the salt from the decrypted slice
         if let Some(salt) = &self.salt {
             decrypted.drain(0..salt.len());
@@ -86,4 +93,23 @@ impl Cipher {
         data.extend_from_slice(key.as_ref());
         hash(&data).to_bytes()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrypt_value_zeroizing() {
+        let cipher = Cipher::new(&[1u8; 32], Some([2u8; SALT_SIZE])).unwrap();
+        let encrypted = cipher.encrypt_value(b"master key").unwrap();
+
+        // Decrypted values are wiped on drop
+        let decrypted: Zeroizing<Vec<u8>> = cipher.decrypt_value(&encrypted).unwrap();
+        assert_eq!(decrypted.as_slice(), b"master key");
+
+        // Wrong key
+        let other = Cipher::new(&[3u8; 32], Some([2u8; SALT_SIZE])).unwrap();
+        assert!(other.decrypt_value(&encrypted).is_err());
+    }
+}
@@ -1,6 +1,3 @@
-use argon2::{Params, Argon2, Algorithm, Version};
-use lazy_static::lazy_static;
-
 pub const DIR_PATH: &str = "wallets/";
 pub const XSWD_BIND_ADDRESS: &str = "0.0.0.0:44325";
 pub const PASSWORD_HASH_SIZE: usize = 32;
@@ -10,10 +7,37 @@ pub const KEY_SIZE: usize = 32;
 // daemon address by default when no specified
 pub const DEFAULT_DAEMON_ADDRESS: &str = "http://127.0.0.1:8080";
 
-lazy_static! {
-    pub static ref PASSWORD_ALGORITHM: Argon2<'static> = {
-        // 15 MB, 16 iterations
-        let params = Params::new(15 * 1000, 16, 1,  Some(PASSWORD_HASH_SIZE)).unwrap();
-        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
-    };
-}
\ No newline at end of file
+// Maximum supply increase per block (in basis points) before alerting for a tracked asset
+// 10 = 0.1% of the previous supply
+pub const DEFAULT_SUPPLY_ALERT_RATE: u64 = 10;
+
+// Human readable prefixes of encoded payment requests
+pub const PAYMENT_REQUEST_PREFIX: &str = "xelpay";
+pub const TESTNET_PAYMENT_REQUEST_PREFIX: &str = "xetpay";
+
+// Topoheights kept below the synced topoheight when pruning the wallet history
+// Blocks deeper than this margin are considered stable and can't be reorganized
+pub const PRUNE_SAFETY_MARGIN: u64 = 64;
+
+// Argon2id parameters used by default to derive the password-based key
+// 15 MB, 16 iterations
+pub const DEFAULT_KDF_MEMORY: u32 = 15 * 1000;
+pub const DEFAULT_KDF_ITERATIONS: u32 = 16;
+pub const DEFAULT_KDF_PARALLELISM: u32 = 1;
+
+// Seconds during which a plaintext balance read by the wallet is served from memory
+pub const DEFAULT_BALANCE_CACHE_TTL: u64 = 5;
+
+// Maximum rounds to estimate the fee of a sweep transaction
+// The fee is computed again with the amount left after paying it until it doesn't change
+pub const MAX_SWEEP_FEE_ROUNDS: usize = 8;
+
+// Prefix of the messages signed to prove the ownership of an address
+// A signed message can't be used as a transaction signature
+pub const SIGNED_MESSAGE_DOMAIN: &[u8] = b"XELIS Signed Message:\n";
+
+// Version of the encrypted wallet backups format
+pub const WALLET_BACKUP_VERSION: u8 = 1;
+
+// Weight of the newest sample in the sync rate moving average
+pub const SYNC_RATE_EWMA_ALPHA: f64 = 0.1;
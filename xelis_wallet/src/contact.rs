@@ -0,0 +1,118 @@
+use std::collections::BTreeSet;
+use xelis_common::{
+    crypto::{
+        Address,
+        AddressType,
+        PublicKey
+    },
+    serializer::{
+        Reader,
+        ReaderError,
+        Serializer,
+        Writer
+    }
+};
+
+// Contact saved in the wallet address book
+// Tags are used to categorize contacts ("exchange", "friend"...)
+// They are case-insensitive and stored in lowercase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Contact {
+    address: Address,
+    tags: BTreeSet<String>
+}
+
+// Tags are compared without case and surrounding whitespaces
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+impl Contact {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            tags: BTreeSet::new()
+        }
+    }
+
+    pub fn get_address(&self) -> &Address {
+        &self.address
+    }
+
+    pub fn get_tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    // Add a tag to the contact, returns false if it was already set or is empty
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() {
+            return false
+        }
+
+        self.tags.insert(tag)
+    }
+
+    // Remove a tag from the contact, returns false if it wasn't set
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        self.tags.remove(&normalize_tag(tag))
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(&normalize_tag(tag))
+    }
+}
+
+impl Serializer for Contact {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bool(self.address.is_mainnet());
+        self.address.get_type().write(writer);
+        self.address.get_public_key().write(writer);
+        self.tags.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let mainnet = reader.read_bool()?;
+        let addr_type = AddressType::read(reader)?;
+        let key = PublicKey::read(reader)?;
+        let tags = BTreeSet::read(reader)?;
+
+        Ok(Self {
+            address: Address::new(mainnet, addr_type, key),
+            tags
+        })
+    }
+
+    fn size(&self) -> usize {
+        1 + self.address.get_type().size() + self.address.get_public_key().size() + self.tags.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    #[test]
+    fn test_contact_tags() {
+        let address = KeyPair::new().get_public_key().to_address(true);
+        let mut contact = Contact::new(address);
+
+        assert!(contact.add_tag("Exchange"));
+        // same tag with a different case
+        assert!(!contact.add_tag(" exchange "));
+        assert!(contact.add_tag("friend"));
+        assert!(!contact.add_tag("  "));
+
+        assert!(contact.has_tag("EXCHANGE"));
+        assert_eq!(contact.get_tags().len(), 2);
+
+        // tags are kept through serialization
+        let decoded = Contact::from_bytes(&contact.to_bytes()).unwrap();
+        assert_eq!(decoded, contact);
+        assert_eq!(contact.size(), contact.to_bytes().len());
+
+        assert!(contact.remove_tag("Friend"));
+        assert!(!contact.has_tag("friend"));
+    }
+}
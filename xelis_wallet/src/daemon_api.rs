@@ -14,6 +14,8 @@ use xelis_common::{
     api::daemon::{
         GetBalanceResult,
         GetBalanceAtTopoHeightParams,
+        GetBalanceMerkleProofParams,
+        GetBalanceMerkleProofResult,
         GetBalanceParams,
         GetInfoResult,
         SubmitTransactionParams,
@@ -165,6 +167,16 @@ impl DaemonAPI {
         Ok(balance)
     }
 
+    // None if the account has no balance for this asset at the topoheight
+    pub async fn get_balance_merkle_proof(&self, address: &Address, asset: &Hash, topoheight: u64) -> Result<Option<GetBalanceMerkleProofResult>> {
+        let proof = self.client.call_with("get_balance_merkle_proof", &GetBalanceMerkleProofParams {
+            address: Cow::Borrowed(address),
+            asset: Cow::Borrowed(asset),
+            topoheight
+        }).await.context("Error while retrieving balance merkle proof")?;
+        Ok(proof)
+    }
+
     pub async fn get_block_at_topoheight(&self, topoheight: u64) -> Result<BlockResponse> {
         let block = self.client.call_with("get_block_at_topoheight", &GetBlockAtTopoHeightParams {
             topoheight,
@@ -2,6 +2,7 @@ use xelis_common::{
     api::{
         DataElement,
         wallet::{
+            MergedTransactionEntry,
             TransactionEntry as RPCTransactionEntry,
             EntryType as RPCEntryType,
             TransferIn as RPCTransferIn,
@@ -19,12 +20,10 @@ use xelis_common::{
         Serializer,
         Writer
     },
-    utils::{
-        format_coin,
-        format_xelis
-    }
+    utils::format_xelis
 };
 use anyhow::Result;
+use indexmap::IndexMap;
 use crate::storage::EncryptedStorage;
 
 #[derive(Debug, Clone)]
@@ -177,6 +176,19 @@ pub enum EntryData {
     }
 }
 
+impl EntryData {
+    // Returns true if this entry moved funds of this asset
+    pub fn has_asset(&self, asset: &Hash) -> bool {
+        match self {
+            Self::Coinbase { .. } => *asset == XELIS_ASSET,
+            Self::Burn { asset: burned, .. } => *burned == *asset,
+            Self::Incoming { transfers, .. } => transfers.iter().any(|t| *t.get_asset() == *asset),
+            // fees are always paid in XELIS
+            Self::Outgoing { transfers, .. } => *asset == XELIS_ASSET || transfers.iter().any(|t| *t.get_asset() == *asset)
+        }
+    }
+}
+
 impl Serializer for EntryData {
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let id = reader.read_u8()?;
@@ -323,8 +335,7 @@ impl TransactionEntry {
         let entry_str = match self.get_entry() {
             EntryData::Coinbase { reward } => format!("Coinbase {} XELIS", format_xelis(*reward)),
             EntryData::Burn { asset, amount } => {
-                let decimals = storage.get_asset_decimals(asset)?;
-                format!("Burn {} of {}", format_coin(*amount, decimals), asset)
+                format!("Burn {}", storage.format_amount(asset, *amount)?)
             },
             EntryData::Incoming { from, transfers } => {
                 let mut str = String::new();
@@ -332,8 +343,7 @@ impl TransactionEntry {
                     if *transfer.get_asset() == XELIS_ASSET {
                         str.push_str(&format!("Received {} XELIS from {}", format_xelis(transfer.get_amount()), from.as_address(mainnet)));
                     } else {
-                        let decimals = storage.get_asset_decimals(transfer.get_asset())?;
-                        str.push_str(&format!("Received {} {} from {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), from.as_address(mainnet)));
+                        str.push_str(&format!("Received {} from {}", storage.format_amount(transfer.get_asset(), transfer.get_amount())?, from.as_address(mainnet)));
                     }
                 }
                 str
@@ -344,8 +354,7 @@ impl TransactionEntry {
                     if *transfer.get_asset() == XELIS_ASSET {
                         str.push_str(&format!("Sent {} XELIS to {}", format_xelis(transfer.get_amount()), transfer.get_destination().as_address(mainnet)));
                     } else {
-                        let decimals = storage.get_asset_decimals(transfer.get_asset())?;
-                        str.push_str(&format!("Sent {} {} to {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), transfer.get_destination().as_address(mainnet)));
+                        str.push_str(&format!("Sent {} to {}", storage.format_amount(transfer.get_asset(), transfer.get_amount())?, transfer.get_destination().as_address(mainnet)));
                     }
                 }
                 str
@@ -408,3 +417,165 @@ impl<'a> Transfer<'a> {
         }
     }
 }
+
+// Criteria used to select entries from the transactions history
+// Topoheight bounds are both included
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    pub asset: Option<Hash>,
+    pub min_topoheight: Option<u64>,
+    pub max_topoheight: Option<u64>
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &TransactionEntry) -> bool {
+        let topoheight = entry.get_topoheight();
+        if self.min_topoheight.is_some_and(|min| topoheight < min) || self.max_topoheight.is_some_and(|max| topoheight > max) {
+            return false
+        }
+
+        match &self.asset {
+            Some(asset) => entry.get_entry().has_asset(asset),
+            None => true
+        }
+    }
+}
+
+// Merge two exported histories (for example before and after a key migration) into one
+// Transactions are deduplicated by hash and sorted by topoheight
+// When a transaction is present in both, the entry from the first history is kept and flagged
+pub fn merge_histories(a: &[RPCTransactionEntry], b: &[RPCTransactionEntry]) -> Vec<MergedTransactionEntry> {
+    let mut merged: IndexMap<&Hash, MergedTransactionEntry> = IndexMap::with_capacity(a.len() + b.len());
+    for entry in a.iter().chain(b) {
+        if let Some(merged_entry) = merged.get_mut(&entry.hash) {
+            merged_entry.in_both = true;
+        } else {
+            merged.insert(&entry.hash, MergedTransactionEntry {
+                entry: entry.clone(),
+                in_both: false
+            });
+        }
+    }
+
+    let mut entries: Vec<MergedTransactionEntry> = merged.into_values().collect();
+    // stable sort, same topoheight entries keep their insertion order
+    entries.sort_by_key(|merged_entry| merged_entry.entry.topoheight);
+    entries
+}
+
+// Estimate the yield of an asset over the last window of topoheights
+// The balance at the start of the window is rebuilt from the current balance and the transactions history
+// Transfers, burns and fees are netted out, so only rewards are counted as growth
+// Returns None if the history can't cover the window or if the balance was empty at its start
+pub fn estimate_yield(entries: &[TransactionEntry], asset: &Hash, balance: u64, topoheight: u64, window_topoheights: u64) -> Option<f64> {
+    if entries.is_empty() || window_topoheights == 0 {
+        return None
+    }
+
+    let start = topoheight.checked_sub(window_topoheights)?;
+    // History must start before the window, otherwise older changes are unknown
+    let oldest = entries.iter().map(TransactionEntry::get_topoheight).min()?;
+    if oldest > start {
+        return None
+    }
+
+    let mut start_balance = balance as i128;
+    let mut rewards: i128 = 0;
+    for entry in entries.iter().filter(|e| e.get_topoheight() > start && e.get_topoheight() <= topoheight) {
+        match entry.get_entry() {
+            EntryData::Coinbase { reward } => if *asset == XELIS_ASSET {
+                start_balance -= *reward as i128;
+                rewards += *reward as i128;
+            },
+            EntryData::Burn { asset: burned, amount } => if burned == asset {
+                start_balance += *amount as i128;
+            },
+            EntryData::Incoming { transfers, .. } => {
+                for transfer in transfers.iter().filter(|t| t.get_asset() == asset) {
+                    start_balance -= transfer.get_amount() as i128;
+                }
+            },
+            EntryData::Outgoing { transfers, fee, .. } => {
+                if *asset == XELIS_ASSET {
+                    start_balance += *fee as i128;
+                }
+                for transfer in transfers.iter().filter(|t| t.get_asset() == asset) {
+                    start_balance += transfer.get_amount() as i128;
+                }
+            }
+        }
+    }
+
+    if start_balance <= 0 {
+        return None
+    }
+
+    Some(rewards as f64 / start_balance as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    fn coinbase(byte: u8, topoheight: u64) -> RPCTransactionEntry {
+        RPCTransactionEntry {
+            hash: Hash::new([byte; 32]),
+            topoheight,
+            entry: RPCEntryType::Coinbase { reward: byte as u64 }
+        }
+    }
+
+    #[test]
+    fn test_merge_histories() {
+        let a = vec![coinbase(1, 10), coinbase(2, 5), coinbase(3, 20)];
+        // tx 2 was also seen by the second wallet
+        let b = vec![coinbase(4, 15), coinbase(2, 5), coinbase(5, 1)];
+
+        let merged = merge_histories(&a, &b);
+        assert_eq!(merged.len(), 5);
+
+        let topoheights: Vec<u64> = merged.iter().map(|e| e.entry.topoheight).collect();
+        assert_eq!(topoheights, vec![1, 5, 10, 15, 20]);
+
+        for e in merged {
+            assert_eq!(e.in_both, e.entry.hash == Hash::new([2; 32]));
+        }
+    }
+
+    #[test]
+    fn test_estimate_yield() {
+        let from = KeyPair::new().get_public_key().compress();
+        let entries = vec![
+            // before the window
+            TransactionEntry::new(Hash::new([1; 32]), 5, EntryData::Coinbase { reward: 500 }),
+            TransactionEntry::new(Hash::new([2; 32]), 20, EntryData::Coinbase { reward: 50 }),
+            TransactionEntry::new(Hash::new([3; 32]), 30, EntryData::Incoming {
+                from,
+                transfers: vec![TransferIn::new(XELIS_ASSET, 300, None)]
+            }),
+            TransactionEntry::new(Hash::new([4; 32]), 40, EntryData::Outgoing {
+                transfers: vec![TransferOut::new(from, XELIS_ASSET, 100, None)],
+                fee: 10,
+                nonce: 0
+            }),
+            TransactionEntry::new(Hash::new([5; 32]), 50, EntryData::Coinbase { reward: 50 }),
+        ];
+
+        // 1000 at topoheight 10, +100 rewards, +300 received, -110 sent
+        let balance = 1290;
+        let estimated = estimate_yield(&entries, &XELIS_ASSET, balance, 60, 50).unwrap();
+        assert!((estimated - 0.1).abs() < f64::EPSILON);
+
+        // No rewards for another asset
+        let asset = Hash::new([9; 32]);
+        assert!(estimate_yield(&entries, &asset, 0, 60, 50).is_none());
+
+        // Window bigger than the chain
+        assert!(estimate_yield(&entries, &XELIS_ASSET, balance, 60, 100).is_none());
+        assert!(estimate_yield(&[], &XELIS_ASSET, balance, 60, 50).is_none());
+
+        // History starting inside the window
+        assert!(estimate_yield(&entries[1..], &XELIS_ASSET, balance, 60, 50).is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, RwLock}
+};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use sled::{transaction::TransactionResult, Transactional};
+
+// Key/value entry returned by a tree iteration
+pub type KvEntry = (Vec<u8>, Vec<u8>);
+
+// Iterator over all entries of a tree, sorted by key
+pub type KvIter<'a> = Box<dyn DoubleEndedIterator<Item = Result<KvEntry>> + Send + 'a>;
+
+// Write operation of a batch
+pub enum KvOp {
+    Insert(Vec<u8>, Vec<u8>),
+    Remove(Vec<u8>)
+}
+
+// Write operations on several trees that must be applied all at once
+// Trees are identified by the name used to open them
+#[derive(Default)]
+pub struct KvBatch {
+    ops: Vec<(Vec<u8>, KvOp)>
+}
+
+impl KvBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, tree: &[u8], key: Vec<u8>, value: Vec<u8>) {
+        self.ops.push((tree.to_vec(), KvOp::Insert(key, value)));
+    }
+
+    pub fn remove(&mut self, tree: &[u8], key: Vec<u8>) {
+        self.ops.push((tree.to_vec(), KvOp::Remove(key)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+// A named key/value tree in which the wallet stores its (encrypted) data
+// Entries must be iterated in keys order
+pub trait KvTree: Send + Sync {
+    // Name of the tree, used for debugging purpose
+    fn name(&self) -> String;
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    fn remove(&self, key: &[u8]) -> Result<()>;
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool>;
+
+    // Delete all entries of this tree
+    fn clear(&self) -> Result<()>;
+
+    // Number of entries in this tree
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> KvIter<'_>;
+}
+
+// Storage backend used by the wallet
+// Sled is used by default, but any platform storage can be used by implementing it
+#[async_trait]
+pub trait KvStore: Send + Sync {
+    // Open the tree with this name, creating it if it doesn't exist
+    fn open_tree(&self, name: &[u8]) -> Result<Arc<dyn KvTree>>;
+
+    // Get a value from the default tree
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    // Insert a value in the default tree
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    // Remove a value from the default tree
+    fn remove(&self, key: &[u8]) -> Result<()>;
+
+    // Names of all the trees, the default one excluded
+    fn tree_names(&self) -> Result<Vec<Vec<u8>>>;
+
+    // Apply all the operations of the batch atomically
+    // Either all of them are persisted or none of them
+    fn apply_batch(&self, batch: KvBatch) -> Result<()>;
+
+    // Make sure that everything is persisted
+    fn flush(&self) -> Result<()>;
+
+    async fn flush_async(&self) -> Result<()> {
+        self.flush()
+    }
+}
+
+impl KvTree for sled::Tree {
+    fn name(&self) -> String {
+        String::from_utf8_lossy(&sled::Tree::name(self)).into_owned()
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(sled::Tree::get(self, key)?.map(|v| v.to_vec()))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        sled::Tree::insert(self, key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        sled::Tree::remove(self, key)?;
+        Ok(())
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool> {
+        Ok(sled::Tree::contains_key(self, key)?)
+    }
+
+    fn clear(&self) -> Result<()> {
+        sled::Tree::clear(self)?;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        sled::Tree::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        sled::Tree::is_empty(self)
+    }
+
+    fn iter(&self) -> KvIter<'_> {
+        Box::new(sled::Tree::iter(self).map(|res| {
+            let (key, value) = res?;
+            Ok((key.to_vec(), value.to_vec()))
+        }))
+    }
+}
+
+#[async_trait]
+impl KvStore for sled::Db {
+    fn open_tree(&self, name: &[u8]) -> Result<Arc<dyn KvTree>> {
+        Ok(Arc::new(sled::Db::open_tree(self, name)?))
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(sled::Tree::get(self, key)?.map(|v| v.to_vec()))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        sled::Tree::insert(self, key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        sled::Tree::remove(self, key)?;
+        Ok(())
+    }
+
+    fn tree_names(&self) -> Result<Vec<Vec<u8>>> {
+        let default_name = sled::Tree::name(self);
+        let names = sled::Db::tree_names(self).into_iter()
+            .filter(|name| *name != default_name)
+            .map(|name| name.to_vec())
+            .collect();
+        Ok(names)
+    }
+
+    // Operations are grouped by tree and applied in a single transaction over all of them
+    fn apply_batch(&self, batch: KvBatch) -> Result<()> {
+        let mut names: Vec<Vec<u8>> = Vec::new();
+        let mut batches: Vec<sled::Batch> = Vec::new();
+        for (name, op) in batch.ops {
+            let index = match names.iter().position(|n| *n == name) {
+                Some(index) => index,
+                None => {
+                    names.push(name);
+                    batches.push(sled::Batch::default());
+                    batches.len() - 1
+                }
+            };
+
+            match op {
+                KvOp::Insert(key, value) => batches[index].insert(key, value),
+                KvOp::Remove(key) => batches[index].remove(key)
+            }
+        }
+
+        let trees = names.iter()
+            .map(|name| sled::Db::open_tree(self, name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let result: TransactionResult<()> = trees.as_slice().transaction(|trees| {
+            for (tree, batch) in trees.iter().zip(batches.iter()) {
+                tree.apply_batch(batch)?;
+            }
+            Ok(())
+        });
+
+        result.map_err(|e| anyhow!("Error while applying batch: {:?}", e))
+    }
+
+    fn flush(&self) -> Result<()> {
+        sled::Tree::flush(self)?;
+        Ok(())
+    }
+
+    async fn flush_async(&self) -> Result<()> {
+        sled::Tree::flush_async(self).await?;
+        Ok(())
+    }
+}
+
+// In memory tree, nothing is persisted
+#[derive(Default)]
+pub struct MemoryTree {
+    name: String,
+    entries: RwLock<BTreeMap<Vec<u8>, Vec<u8>>>
+}
+
+impl MemoryTree {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            entries: RwLock::new(BTreeMap::new())
+        }
+    }
+}
+
+impl KvTree for MemoryTree {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let entries = self.entries.read().map_err(|_| anyhow!("Memory tree lock is poisoned"))?;
+        Ok(entries.get(key).cloned())
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let mut entries = self.entries.write().map_err(|_| anyhow!("Memory tree lock is poisoned"))?;
+        entries.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        let mut entries = self.entries.write().map_err(|_| anyhow!("Memory tree lock is poisoned"))?;
+        entries.remove(key);
+        Ok(())
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool> {
+        let entries = self.entries.read().map_err(|_| anyhow!("Memory tree lock is poisoned"))?;
+        Ok(entries.contains_key(key))
+    }
+
+    fn clear(&self) -> Result<()> {
+        let mut entries = self.entries.write().map_err(|_| anyhow!("Memory tree lock is poisoned"))?;
+        entries.clear();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.entries.read().map(|entries| entries.len()).unwrap_or(0)
+    }
+
+    // Iterate over a snapshot of the entries, like sled does
+    fn iter(&self) -> KvIter<'_> {
+        match self.entries.read() {
+            Ok(entries) => {
+                let snapshot: Vec<KvEntry> = entries.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                Box::new(snapshot.into_iter().map(Ok))
+            },
+            Err(_) => Box::new(std::iter::once(Err(anyhow!("Memory tree lock is poisoned"))))
+        }
+    }
+}
+
+// In memory storage backend
+// Useful for tests or for platforms without a filesystem
+// Clones are sharing the same data, so a storage can be reopened from it
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    default: Arc<MemoryTree>,
+    trees: Arc<Mutex<HashMap<Vec<u8>, Arc<MemoryTree>>>>
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl KvStore for MemoryStore {
+    fn open_tree(&self, name: &[u8]) -> Result<Arc<dyn KvTree>> {
+        let mut trees = self.trees.lock().map_err(|_| anyhow!("Memory store lock is poisoned"))?;
+        let tree = trees.entry(name.to_vec())
+            .or_insert_with(|| Arc::new(MemoryTree::new(hex::encode(name))))
+            .clone();
+        Ok(tree)
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.default.get(key)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.default.insert(key, value)
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<()> {
+        self.default.remove(key)
+    }
+
+    fn tree_names(&self) -> Result<Vec<Vec<u8>>> {
+        let trees = self.trees.lock().map_err(|_| anyhow!("Memory store lock is poisoned"))?;
+        Ok(trees.keys().cloned().collect())
+    }
+
+    // Nothing is persisted, so a crash can't leave the batch half applied
+    fn apply_batch(&self, batch: KvBatch) -> Result<()> {
+        for (name, op) in batch.ops {
+            let tree = self.open_tree(&name)?;
+            match op {
+                KvOp::Insert(key, value) => tree.insert(&key, &value)?,
+                KvOp::Remove(key) => tree.remove(&key)?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
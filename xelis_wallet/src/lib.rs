@@ -1,12 +1,27 @@
 pub mod storage;
+pub mod kv_store;
 pub mod wallet;
 pub mod config;
 pub mod cipher;
 pub mod daemon_api;
 pub mod network_handler;
 pub mod entry;
+pub mod contact;
+pub mod payment_request;
 pub mod mnemonics;
 pub mod transaction_builder;
+pub mod supply_tracker;
+pub mod rescan_guard;
+pub mod balance_cache;
+pub mod balance_proof;
+pub mod backup;
+pub mod sync_status;
+
+#[cfg(test)]
+mod mock_daemon;
+
+#[cfg(test)]
+mod test_utils;
 
 #[cfg(feature = "api_server")]
 pub mod api;
\ No newline at end of file
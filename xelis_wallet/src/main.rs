@@ -366,7 +366,7 @@ async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &Com
     command_manager.add_command(Command::with_optional_arguments("history", "Show all your transactions", vec![Arg::new("page", ArgType::Number)], CommandHandler::Async(async_handler!(history))))?;
     command_manager.add_command(Command::with_optional_arguments("online_mode", "Set your wallet in online mode", vec![Arg::new("daemon_address", ArgType::String)], CommandHandler::Async(async_handler!(online_mode))))?;
     command_manager.add_command(Command::new("offline_mode", "Set your wallet in offline mode", CommandHandler::Async(async_handler!(offline_mode))))?;
-    command_manager.add_command(Command::with_optional_arguments("rescan", "Rescan balance and transactions", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(rescan))))?;
+    command_manager.add_command(Command::with_optional_arguments("rescan", "Rescan balance and transactions", vec![Arg::new("topoheight", ArgType::Number), Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(rescan))))?;
     command_manager.add_command(Command::with_optional_arguments("seed", "Show seed of selected language", vec![Arg::new("language", ArgType::Number)], CommandHandler::Async(async_handler!(seed))))?;
     command_manager.add_command(Command::new("nonce", "Show current nonce", CommandHandler::Async(async_handler!(nonce))))?;
     command_manager.add_command(Command::new("set_nonce", "Set new nonce", CommandHandler::Async(async_handler!(set_nonce))))?;
@@ -773,13 +773,15 @@ async fn balance(manager: &CommandManager, mut arguments: ArgumentManager) -> Re
     if arguments.has_argument("asset") {
         let asset = arguments.get_value("asset")?.to_hash()?;
         let balance = storage.get_plaintext_balance_for(&asset).await.unwrap_or(0);
-        let decimals = storage.get_asset_decimals(&asset).unwrap_or(0);
-        manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)));
+        let amount = storage.format_amount(&asset, balance)
+            .unwrap_or_else(|_| format_coin(balance, 0));
+        manager.message(format!("Balance for asset {}: {}", asset, amount));
     } else {
         for (asset, decimals) in storage.get_assets_with_decimals().await? {
             let balance = storage.get_plaintext_balance_for(&asset).await.unwrap_or(0);
             if balance > 0 {
-                manager.message(format!("Balance for asset {}: {}", asset, format_coin(balance, decimals)));
+                let name = storage.get_asset_display_name(&asset)?;
+                manager.message(format!("Balance for asset {}: {} {}", asset, format_coin(balance, decimals), name));
             }
         }
     }
@@ -856,7 +858,7 @@ async fn offline_mode(manager: &CommandManager, _: ArgumentManager) -> Result<()
     if !wallet.is_online().await {
         manager.error("Wallet is already offline");
     } else {
-        wallet.set_offline_mode().await.context("Error on offline mode")?;
+        wallet.set_offline_mode(false).await.context("Error on offline mode")?;
         manager.message("Wallet is now offline");
     }
     Ok(())
@@ -871,8 +873,13 @@ async fn rescan(manager: &CommandManager, mut arguments: ArgumentManager) -> Res
     } else {
         0
     };
+    let asset = if arguments.has_argument("asset") {
+        Some(arguments.get_value("asset")?.to_hash()?)
+    } else {
+        None
+    };
 
-    wallet.rescan(topoheight).await.context("error while restarting network handler")?;
+    wallet.rescan(topoheight, asset).await.context("error while restarting network handler")?;
     manager.message("Network handler has been restarted!");
     Ok(())
 }
@@ -9,6 +9,7 @@ use xelis_common::{
     serializer::Serializer
 };
 use languages::*;
+use crate::wallet::WalletError;
 
 const KEY_SIZE: usize = 32;
 const SEED_LENGTH: usize = 24;
@@ -37,6 +38,31 @@ pub struct Language<'a> {
     words: [&'a str; WORDS_LIST]
 }
 
+impl<'a> Language<'a> {
+    pub fn get_name(&self) -> &'a str {
+        self.name
+    }
+}
+
+// List all supported languages with their index, to be displayed to the user
+pub fn available_languages() -> Vec<(usize, &'static str)> {
+    LANGUAGES.iter().enumerate().map(|(i, language)| (i, language.name)).collect()
+}
+
+// Find a language using its name (case-insensitive)
+pub fn find_language(name: &str) -> Option<&'static Language<'static>> {
+    LANGUAGES.iter().find(|language| language.name.eq_ignore_ascii_case(name.trim()))
+}
+
+// Detect the language of a seed, all its words must be in the same words list
+pub fn detect_language(words: &[String]) -> Option<&'static Language<'static>> {
+    if words.is_empty() {
+        return None
+    }
+
+    LANGUAGES.iter().find(|language| words.iter().all(|word| language.words.contains(&word.as_str())))
+}
+
 fn calculate_checksum_index(words: &[String], prefix_len: usize) -> Result<u32> {
     if words.len() != SEED_LENGTH {
         return Err(anyhow!("Invalid number of words"));
@@ -93,12 +119,46 @@ fn find_indices(words: &Vec<String>) -> Result<Option<(Vec<usize>, usize)>> {
     Ok(None)
 }
 
-// convert a words list to a Private Key (32 bytes)
-pub fn words_to_key(words: &Vec<String>) -> Result<PrivateKey> {
+// verify that the words list has the expected number of words (seed + checksum word)
+pub fn validate_word_count(words: &[String]) -> Result<(), WalletError> {
     if words.len() != SEED_LENGTH + 1 {
-        return Err(anyhow!("Invalid number of words"));
+        return Err(WalletError::InvalidSeedLength(SEED_LENGTH + 1, words.len()));
+    }
+
+    Ok(())
+}
+
+// verify that the words list is a valid seed: word count, words from the same language and checksum
+// returns the language of the seed
+pub fn validate_seed(words: &[String]) -> Result<&'static Language<'static>, WalletError> {
+    validate_word_count(words)?;
+
+    // the language is the one with the most known words, so a misspelled word can be reported
+    let (language, known) = LANGUAGES.iter()
+        .map(|language| (language, words.iter().filter(|word| language.words.contains(&word.as_str())).count()))
+        .max_by_key(|(_, known)| *known)
+        .ok_or(WalletError::UnknownSeedLanguage)?;
+
+    if known == 0 {
+        return Err(WalletError::UnknownSeedLanguage)
     }
 
+    if let Some(position) = words.iter().position(|word| !language.words.contains(&word.as_str())) {
+        return Err(WalletError::InvalidSeedWord(position))
+    }
+
+    let words = words.to_vec();
+    if !verify_checksum(&words, language.prefix_length)? {
+        return Err(WalletError::InvalidSeedChecksum)
+    }
+
+    Ok(language)
+}
+
+// convert a words list to a Private Key (32 bytes)
+pub fn words_to_key(words: &Vec<String>) -> Result<PrivateKey> {
+    validate_word_count(words)?;
+
     let (indices, language_index) = find_indices(words)?.context("No indices found")?;
     debug!("Language found: {}", LANGUAGES[language_index].name);
 
@@ -156,6 +216,7 @@ pub fn key_to_words_with_language(key: &PrivateKey, language: &Language) -> Resu
 #[cfg(test)]
 mod tests {
     use xelis_common::crypto::KeyPair;
+    use crate::wallet::WalletError;
 
     #[test]
     fn test_languages() {
@@ -169,4 +230,65 @@ mod tests {
             assert_eq!(words, words2);
         }
     }
+
+    #[test]
+    fn test_available_languages() {
+        let (_, key) = KeyPair::new().split();
+        let languages = super::available_languages();
+        assert_eq!(languages.len(), super::LANGUAGES.len());
+
+        for (index, name) in languages {
+            let language = super::find_language(&name.to_uppercase()).unwrap();
+            assert_eq!(language.get_name(), name);
+
+            let words = super::key_to_words_with_language(&key, language).unwrap();
+            assert_eq!(words, super::key_to_words(&key, index).unwrap());
+            assert_eq!(super::detect_language(&words).unwrap().get_name(), name);
+            assert_eq!(super::words_to_key(&words).unwrap().as_scalar(), key.as_scalar());
+        }
+
+        assert!(super::find_language("klingon").is_none());
+        assert!(super::detect_language(&["notaword".to_owned()]).is_none());
+        assert!(super::detect_language(&[]).is_none());
+    }
+
+    #[test]
+    fn test_validate_word_count() {
+        let (_, key) = KeyPair::new().split();
+        let mut words = super::key_to_words(&key, 0).unwrap();
+        assert!(super::validate_word_count(&words).is_ok());
+
+        // too few words
+        let missing = words.pop().unwrap();
+        assert!(matches!(super::validate_word_count(&words), Err(WalletError::InvalidSeedLength(25, 24))));
+        assert!(super::words_to_key(&words).is_err());
+
+        // too many words
+        words.push(missing.clone());
+        words.push(missing);
+        assert!(matches!(super::validate_word_count(&words), Err(WalletError::InvalidSeedLength(25, 26))));
+        assert!(super::words_to_key(&words).is_err());
+    }
+
+    #[test]
+    fn test_validate_seed() {
+        let (_, key) = KeyPair::new().split();
+        let words = super::key_to_words(&key, 0).unwrap();
+        assert_eq!(super::validate_seed(&words).unwrap().get_name(), "English");
+
+        // misspelled word
+        let mut misspelled = words.clone();
+        misspelled[7].push_str("xyz");
+        assert!(matches!(super::validate_seed(&misspelled), Err(WalletError::InvalidSeedWord(7))));
+
+        // checksum word swapped with another word of the list
+        let mut swapped = words.clone();
+        let language = super::find_language("english").unwrap();
+        swapped[super::SEED_LENGTH] = language.words.iter().find(|word| **word != words[super::SEED_LENGTH]).unwrap().to_string();
+        assert!(matches!(super::validate_seed(&swapped), Err(WalletError::InvalidSeedChecksum)));
+
+        // no known word
+        let unknown = vec!["notaword".to_owned(); super::SEED_LENGTH + 1];
+        assert!(matches!(super::validate_seed(&unknown), Err(WalletError::UnknownSeedLanguage)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,182 @@
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex}
+};
+use futures_util::{SinkExt, StreamExt};
+use indexmap::IndexSet;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+use xelis_common::{
+    api::daemon::{BlockResponse, BlockType, GetInfoResult, NotifyEvent},
+    block::EXTRA_NONCE_SIZE,
+    crypto::{Address, Hash},
+    network::Network
+};
+use crate::daemon_api::DaemonAPI;
+
+// Answer of the mock daemon to a JSON-RPC method and its params
+// An error is sent back as a JSON-RPC error with this message
+pub type MockHandler = dyn Fn(&str, &Value) -> Result<Value, String> + Send + Sync;
+
+// Daemon listening on a local port to answer the JSON-RPC requests of a wallet in tests
+// Every request received is recorded to be inspected
+pub struct MockDaemon {
+    address: String,
+    requests: Arc<Mutex<Vec<(String, Value)>>>,
+    // Events pushed to the connections subscribed to them
+    events: broadcast::Sender<(Value, Value)>
+}
+
+impl MockDaemon {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&str, &Value) -> Result<Value, String> + Send + Sync + 'static
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("ws://{}/json_rpc", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<MockHandler> = Arc::new(handler);
+        let (events, _) = broadcast::channel(16);
+
+        {
+            let requests = Arc::clone(&requests);
+            let events = events.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let Ok(ws) = accept_async(stream).await else {
+                        continue;
+                    };
+
+                    let requests = Arc::clone(&requests);
+                    let handler = Arc::clone(&handler);
+                    let mut events = events.subscribe();
+                    tokio::spawn(async move {
+                        let (mut write, mut read) = ws.split();
+                        // Event subscribed with the id of the subscribe request, used to send it
+                        let mut subscriptions: Vec<(Value, Value)> = Vec::new();
+                        loop {
+                            let responses = tokio::select! {
+                                msg = read.next() => {
+                                    let Some(Ok(Message::Text(text))) = msg else {
+                                        break;
+                                    };
+                                    let Ok(request) = serde_json::from_str::<Value>(&text) else {
+                                        break;
+                                    };
+
+                                    let method = request["method"].as_str().unwrap_or_default().to_owned();
+                                    let params = request["params"].clone();
+                                    requests.lock().unwrap().push((method.clone(), params.clone()));
+
+                                    let result = match method.as_str() {
+                                        "subscribe" => {
+                                            subscriptions.push((params["notify"].clone(), request["id"].clone()));
+                                            Ok(Value::Bool(true))
+                                        },
+                                        "unsubscribe" => {
+                                            subscriptions.retain(|(event, _)| *event != params["notify"]);
+                                            Ok(Value::Bool(true))
+                                        },
+                                        _ => handler(&method, &params)
+                                    };
+                                    vec![match result {
+                                        Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+                                        Err(message) => json!({ "jsonrpc": "2.0", "id": request["id"], "error": { "code": -32603, "message": message } })
+                                    }]
+                                },
+                                Ok((event, result)) = events.recv() => {
+                                    subscriptions.iter()
+                                        .filter(|(subscribed, _)| *subscribed == event)
+                                        .map(|(_, id)| json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+                                        .collect()
+                                }
+                            };
+
+                            for response in responses {
+                                if write.send(Message::Text(response.to_string())).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    });
+                }
+            });
+        }
+
+        Self {
+            address,
+            requests,
+            events
+        }
+    }
+
+    // Send the event to every connection subscribed to it
+    pub fn notify<T: Serialize>(&self, event: NotifyEvent, value: &T) {
+        let event = serde_json::to_value(event).unwrap();
+        let _ = self.events.send((event, serde_json::to_value(value).unwrap()));
+    }
+
+    pub fn get_address(&self) -> &String {
+        &self.address
+    }
+
+    // Open a new connection to the mock daemon
+    pub async fn connect(&self) -> Arc<DaemonAPI> {
+        Arc::new(DaemonAPI::new(self.address.clone()).await.unwrap())
+    }
+
+    // Params of every request received for this method, in order
+    pub fn requests(&self, method: &str) -> Vec<Value> {
+        self.requests.lock().unwrap()
+            .iter()
+            .filter(|(m, _)| m == method)
+            .map(|(_, params)| params.clone())
+            .collect()
+    }
+}
+
+// Block without transactions ordered at this topoheight, its hash is filled with the topoheight
+pub fn block_response(topoheight: u64, miner: &Address, reward: Option<u64>) -> BlockResponse {
+    BlockResponse {
+        hash: Cow::Owned(Hash::new([topoheight as u8; 32])),
+        topoheight: Some(topoheight),
+        block_type: BlockType::Normal,
+        difficulty: Cow::Owned(1u64.into()),
+        supply: Some(0),
+        reward,
+        cumulative_difficulty: Cow::Owned(1u64.into()),
+        total_fees: Some(0),
+        total_size_in_bytes: 0,
+        version: 0,
+        tips: Cow::Owned(IndexSet::new()),
+        timestamp: 0,
+        height: topoheight,
+        nonce: 0,
+        extra_nonce: Cow::Owned([0u8; EXTRA_NONCE_SIZE]),
+        miner: Cow::Owned(miner.clone()),
+        txs_hashes: Cow::Owned(IndexSet::new()),
+        transactions: Vec::new()
+    }
+}
+
+// Info of a mainnet daemon whose top block is the block_response at this topoheight
+pub fn info_response(topoheight: u64) -> GetInfoResult {
+    GetInfoResult {
+        height: topoheight,
+        topoheight,
+        stableheight: topoheight.saturating_sub(1),
+        pruned_topoheight: None,
+        top_block_hash: Hash::new([topoheight as u8; 32]),
+        circulating_supply: 0,
+        maximum_supply: 0,
+        difficulty: 1u64.into(),
+        block_time_target: 0,
+        average_block_time: 0,
+        block_reward: 0,
+        mempool_size: 0,
+        version: "1.9.0".to_owned(),
+        network: Network::Mainnet
+    }
+}
@@ -1,5 +1,8 @@
 use std::{
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc
+    },
     collections::{
         HashMap,
         HashSet
@@ -8,9 +11,9 @@ use std::{
 use thiserror::Error;
 use anyhow::Error;
 use log::{debug, error, trace, warn};
-use tokio::{task::JoinHandle, sync::Mutex};
+use tokio::{task::JoinHandle, sync::{Mutex, Notify}};
 use xelis_common::{
-    account::CiphertextCache,
+    account::{BalanceType, CiphertextCache},
     api::{
         daemon::{
             BlockResponse,
@@ -39,7 +42,9 @@ use crate::{
     },
     storage::Balance,
     wallet::{
-        Event, Wallet
+        verify_balance_ciphertexts,
+        Event,
+        Wallet
     }
 };
 
@@ -52,6 +57,10 @@ pub enum NetworkError {
     AlreadyRunning,
     #[error("network handler is not running")]
     NotRunning,
+    #[error("network handler is already paused")]
+    AlreadyPaused,
+    #[error("network handler is not paused")]
+    NotPaused,
     #[error(transparent)]
     TaskError(#[from] tokio::task::JoinError),
     #[error(transparent)]
@@ -68,7 +77,11 @@ pub struct NetworkHandler {
     // api to communicate with daemon
     // It is behind a Arc to be shared across several wallets
     // in case someone make a custom service and don't want to create a new connection
-    api: Arc<DaemonAPI>
+    api: Arc<DaemonAPI>,
+    // Syncing is suspended but the connection is kept alive
+    paused: AtomicBool,
+    // Wake up the syncing loop when it get resumed
+    resume_notifier: Notify
 }
 
 impl NetworkHandler {
@@ -89,7 +102,9 @@ impl NetworkHandler {
         Ok(Arc::new(Self {
             task: Mutex::new(None),
             wallet,
-            api
+            api,
+            paused: AtomicBool::new(false),
+            resume_notifier: Notify::new()
         }))
     }
 
@@ -109,6 +124,9 @@ impl NetworkHandler {
             }
         }
 
+        // A fresh start is never paused
+        self.paused.store(false, Ordering::SeqCst);
+
         let zelf = Arc::clone(&self);
         *self.task.lock().await = Some(tokio::spawn(async move {
             let res =  zelf.start_syncing().await;
@@ -159,6 +177,41 @@ impl NetworkHandler {
         }
     }
 
+    // Suspend the syncing loop without closing the connection to the daemon
+    // New blocks are ignored until resumed
+    pub async fn pause(&self) -> Result<(), NetworkError> {
+        trace!("Pausing network handler");
+        if !self.is_running().await {
+            return Err(NetworkError::NotRunning)
+        }
+
+        if self.paused.swap(true, Ordering::SeqCst) {
+            return Err(NetworkError::AlreadyPaused)
+        }
+
+        Ok(())
+    }
+
+    // Resume the syncing loop, it will sync all blocks missed while paused
+    pub async fn resume(&self) -> Result<(), NetworkError> {
+        trace!("Resuming network handler");
+        if !self.is_running().await {
+            return Err(NetworkError::NotRunning)
+        }
+
+        if !self.paused.swap(false, Ordering::SeqCst) {
+            return Err(NetworkError::NotPaused)
+        }
+
+        self.resume_notifier.notify_one();
+        Ok(())
+    }
+
+    // Check if the syncing loop is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
     // Retrieve the daemon API used
     pub fn get_api(&self) -> &DaemonAPI {
         &self.api
@@ -236,6 +289,11 @@ impl NetworkHandler {
                         None
                     }
                 },
+                RPCTransactionType::Transfers(_) if self.wallet.is_watch_only() => {
+                    // Amounts are encrypted to the private key, a watch-only wallet can't read them
+                    debug!("Skipping transfers of TX {} in watch-only mode", tx.hash);
+                    None
+                },
                 RPCTransactionType::Transfers(txs) => {
                     let mut transfers_in: Vec<TransferIn> = Vec::new();
                     let mut transfers_out: Vec<TransferOut> = Vec::new();
@@ -273,13 +331,13 @@ impl NetworkHandler {
                                 None
                             };
 
+                            let asset = transfer.asset.into_owned();
+                            assets_changed.insert(asset.clone());
+
                             debug!("Decrypting amount from TX {}", tx.hash);
                             let ciphertext = Ciphertext::new(commitment, handle);
                             let amount = Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext).await?;
 
-                            let asset = transfer.asset.into_owned();
-                            assets_changed.insert(asset.clone());
-
                             if is_owner {
                                 let transfer = TransferOut::new(destination, asset, amount, extra_data);
                                 transfers_out.push(transfer);
@@ -320,6 +378,7 @@ impl NetworkHandler {
                     // It shouldn't be found because it got deleted from storage
                     if !found {
                         storage.save_transaction(entry.get_hash(), &entry)?;
+                        storage.save_subaddress_transaction(&entry)?;
                         // Store the changes for history
                         if !changes_stored {
                             storage.add_topoheight_to_changes(topoheight, &block_hash)?;
@@ -358,12 +417,16 @@ impl NetworkHandler {
         // This is used to save the latest balance
         let mut highest_version = true;
         loop {
-            let (mut balance, _, _, previous_topoheight) = version.consume();
+            let (mut balance, output_balance, balance_type, previous_topoheight) = version.consume();
             // add this topoheight in cache to not re-process it (blocks are independant of asset to have faster sync)
             // if its not already processed, do it
             if topoheight_processed.insert(topoheight) {
                 let response = self.api.get_block_with_txs_at_topoheight(topoheight).await?;
                 let changes = self.process_block(address, response, topoheight).await?;
+                self.wallet.record_synced_block();
+                // Versions are scanned from the highest one down to the synced topoheight
+                self.wallet.set_sync_remaining_blocks(topoheight.saturating_sub(min_topoheight));
+                self.wallet.propagate_event(Event::SyncProgress { topoheight }).await;
 
                 // Check if a change occured, we are the highest version and update balances is requested
                 if let Some((_, nonce)) = changes.filter(|_| balances && highest_version) {
@@ -383,7 +446,8 @@ impl NetworkHandler {
                     }
 
                     // If we have no balance in storage OR the stored ciphertext isn't the same, we should store it
-                    let store = storage.get_balance_for(asset).await.map(|b| b.ciphertext != balance).unwrap_or(true);
+                    // A watch-only wallet can't decrypt it, so it never stores any balance
+                    let store = !self.wallet.is_watch_only() && storage.get_balance_for(asset).await.map(|b| b.ciphertext != balance).unwrap_or(true);
                     if store {
                         debug!("Storing balance for asset {}", asset);
                         let plaintext_balance = if let Some(plaintext_balance) = storage.get_unconfirmed_balance_decoded_for(&asset, &balance.compressed()).await? {
@@ -394,8 +458,12 @@ impl NetworkHandler {
                             Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext.clone()).await?
                         };
 
+                        let output_amount = self.decrypt_output_balance(balance_type, output_balance.clone()).await?;
+                        verify_balance_ciphertexts(&self.wallet.get_keypair()?, &balance, plaintext_balance, output_balance.as_ref(), output_amount, balance_type)?;
+
                         // Store the new balance
-                        storage.set_balance_for(asset, Balance::new(plaintext_balance, balance)).await?;
+                        storage.set_balance_for(asset, Balance::with_type(plaintext_balance, balance, balance_type, output_amount)).await?;
+                        self.wallet.invalidate_cached_balance(asset).await;
 
                         // Propagate the event
                         self.wallet.propagate_event(Event::BalanceChanged(BalanceChanged {
@@ -550,12 +618,27 @@ impl NetworkHandler {
         Ok((daemon_topoheight, daemon_block_hash, maximum, true))
     }
 
+    // Decrypt the output balance of a version having both inputs and outputs
+    // This is the part of the balance that can be spent by our next transactions
+    async fn decrypt_output_balance(&self, balance_type: BalanceType, output_balance: Option<CiphertextCache>) -> Result<Option<u64>, Error> {
+        match (balance_type, output_balance) {
+            (BalanceType::Both, Some(mut output_balance)) => {
+                trace!("Decrypting output balance");
+                let ciphertext = output_balance.decompressed()?.clone();
+                Ok(Some(Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext).await?))
+            },
+            _ => Ok(None)
+        }
+    }
+
     // Sync the latest version of our balances and nonces and determine if we should parse all blocks
     // If assets are provided, we'll only sync these assets
     // TODO: this may bug with Smart Contract integration as we could receive a new asset and not detect it
     // If nonce is not provided, we will fetch it from the daemon
     async fn sync_head_state(&self, address: &Address, assets: Option<HashSet<Hash>>, nonce: Option<u64>, sync_nonce: bool) -> Result<bool, Error> {
         trace!("syncing head state");
+        // Storage is being rewritten by a rescan, balances are synced once it's done
+        self.wallet.wait_for_rescan().await;
         let new_nonce = if nonce.is_some() {
             nonce
         } else if sync_nonce {
@@ -570,6 +653,7 @@ impl NetworkHandler {
                             warn!("We have balances but we couldn't fetch the nonce, deleting all balances");
                             storage.delete_balances().await?;
                             storage.delete_assets().await?;
+                            self.wallet.invalidate_cached_balances().await;
                         }
                     }
                     // Account is not registered, we can return safely here
@@ -589,7 +673,7 @@ impl NetworkHandler {
 
         trace!("assets: {}", assets.len());
 
-        let mut balances: HashMap<&Hash, CiphertextCache>  = HashMap::new();
+        let mut balances: HashMap<&Hash, (CiphertextCache, Option<CiphertextCache>, BalanceType)>  = HashMap::new();
         // Store newly detected assets
         // Get the final balance of each asset
         for asset in &assets {
@@ -614,7 +698,8 @@ impl NetworkHandler {
             // get the balance for this asset
             let result = self.api.get_balance(&address, &asset).await?;
             trace!("found balance at topoheight: {}", result.topoheight);
-            balances.insert(asset, result.version.take_balance());
+            let (balance, output_balance, balance_type, _) = result.version.consume();
+            balances.insert(asset, (balance, output_balance, balance_type));
         }
 
         let mut should_sync_blocks = false;
@@ -629,7 +714,13 @@ impl NetworkHandler {
                 }
             }
 
-            for (asset, mut ciphertext) in balances {
+            // Balances can't be decrypted without the private key, so they are never stored
+            // Blocks are scanned instead to find the new entries of the history
+            if self.wallet.is_watch_only() {
+                return Ok(should_sync_blocks || !balances.is_empty())
+            }
+
+            for (asset, (mut ciphertext, output_balance, balance_type)) in balances {
                 let (must_update, balance_cache) = {
                     let storage = self.wallet.get_storage().read().await;
                     let must_update = match storage.get_balance_for(&asset).await {
@@ -657,6 +748,9 @@ impl NetworkHandler {
                         Arc::clone(&self.wallet).decrypt_ciphertext(ciphertext.decompressed()?.clone()).await?
                     };
 
+                    let output_amount = self.decrypt_output_balance(balance_type, output_balance.clone()).await?;
+                    verify_balance_ciphertexts(&self.wallet.get_keypair()?, &ciphertext, value, output_balance.as_ref(), output_amount, balance_type)?;
+
                     // Inform the change of the balance
                     self.wallet.propagate_event(Event::BalanceChanged(BalanceChanged {
                         asset: asset.clone(),
@@ -665,7 +759,8 @@ impl NetworkHandler {
 
                     // Update the balance
                     let mut storage = self.wallet.get_storage().write().await;
-                    storage.set_balance_for(asset, Balance::new(value, ciphertext)).await?;
+                    storage.set_balance_for(asset, Balance::with_type(value, ciphertext, balance_type, output_amount)).await?;
+                    self.wallet.invalidate_cached_balance(asset).await;
 
                     // We should sync new blocks to get the TXs
                     should_sync_blocks = true;
@@ -682,6 +777,7 @@ impl NetworkHandler {
         trace!("sync");
         // First, locate the last topoheight valid for syncing
         let (daemon_topoheight, daemon_block_hash, wallet_topoheight, sync_back) = self.locate_sync_topoheight_and_clean().await?;
+        self.wallet.set_sync_topoheights(wallet_topoheight, daemon_topoheight);
 
         // Sync back is requested, sync the head state again
         if sync_back {
@@ -698,7 +794,15 @@ impl NetworkHandler {
             if let Some(block) = event {
                 // We can safely handle it by hand because `locate_sync_topoheight_and_clean` secure us from being on a wrong chain
                 if let Some(topoheight) = block.topoheight {
-                    if let Some((assets, mut nonce)) = self.process_block(address, block, topoheight).await? {
+                    // Supply included in the block is the native asset one
+                    if let Some(supply) = block.supply {
+                        self.wallet.record_asset_supply(&XELIS_ASSET, supply, topoheight).await;
+                    }
+
+                    let changes = self.process_block(address, block, topoheight).await?;
+                    self.wallet.record_synced_block();
+                    self.wallet.set_sync_topoheights(topoheight, daemon_topoheight);
+                    if let Some((assets, mut nonce)) = changes {
                         trace!("We must sync head state");
                         {
                             let storage = self.wallet.get_storage().read().await;
@@ -729,6 +833,7 @@ impl NetworkHandler {
             storage.set_synced_topoheight(daemon_topoheight)?;
             storage.set_top_block_hash(&daemon_block_hash)?;
         }
+        self.wallet.set_sync_topoheights(daemon_topoheight, daemon_topoheight);
 
         // Propagate the event
         self.wallet.propagate_event(Event::NewTopoHeight { topoheight: daemon_topoheight }).await;
@@ -760,14 +865,27 @@ impl NetworkHandler {
                 res = receiver.next() => {
                     trace!("on_new_block_event");
                     let event = res?;
+                    if self.is_paused() {
+                        trace!("syncing is paused, ignoring new block event");
+                        continue;
+                    }
                     self.sync(&address, Some(event)).await?;
                 },
+                // Syncing got resumed, catch up with all blocks we missed
+                _ = self.resume_notifier.notified() => {
+                    trace!("on_resume");
+                    if !self.is_paused() {
+                        self.sync(&address, None).await?;
+                    }
+                },
                 // Detect network events
                 res = on_connection.recv() => {
                     trace!("on_connection");
                     res?;
                     // We are connected again, make sure we are still up-to-date with node 
-                    self.sync(&address, None).await?;
+                    if !self.is_paused() {
+                        self.sync(&address, None).await?;
+                    }
 
                     self.wallet.propagate_event(Event::Online).await;
                 },
@@ -780,6 +898,24 @@ impl NetworkHandler {
         }
     }
 
+    // Process again all the blocks in this topoheight range, both bounds included
+    // Missing transactions are saved, balances and nonce are left untouched
+    pub async fn rescan_range(&self, from_topoheight: u64, to_topoheight: u64) -> Result<(), Error> {
+        trace!("rescan range {} to {}", from_topoheight, to_topoheight);
+        let address = self.wallet.get_address();
+        for topoheight in from_topoheight..=to_topoheight {
+            let block = self.api.get_block_with_txs_at_topoheight(topoheight).await?;
+            // Range is processed in order, so each block supply can be compared to the previous one
+            if let Some(supply) = block.supply {
+                self.wallet.record_asset_supply(&XELIS_ASSET, supply, topoheight).await;
+            }
+            self.process_block(&address, block, topoheight).await?;
+            self.wallet.propagate_event(Event::SyncProgress { topoheight }).await;
+        }
+
+        Ok(())
+    }
+
     // Sync all new blocks until the current topoheight
     async fn sync_new_blocks(&self, address: &Address, current_topoheight: u64, balances: bool) -> Result<(), Error> {
         let assets = {
@@ -801,4 +937,74 @@ impl NetworkHandler {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use xelis_common::{
+        account::{VersionedBalance, VersionedNonce},
+        api::daemon::{GetBalanceResult, GetNonceResult},
+        asset::AssetData,
+        crypto::KeyPair
+    };
+    use crate::{
+        mock_daemon::{block_response, info_response, MockDaemon},
+        test_utils::create_test_watch_only_wallet,
+        wallet::WalletError
+    };
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_only_sync() {
+        let keypair = KeyPair::new();
+        let address = keypair.get_public_key().to_address(true);
+        let ciphertext = keypair.get_public_key().encrypt(100u64);
+
+        let daemon = {
+            let address = address.clone();
+            let balance = CiphertextCache::Decompressed(ciphertext);
+            MockDaemon::start(move |method, params| {
+                let value = match method {
+                    "get_version" => Value::String("1.9.0".to_owned()),
+                    "get_info" => serde_json::to_value(info_response(1)).unwrap(),
+                    "get_block_at_topoheight" => {
+                        let topoheight = params["topoheight"].as_u64().unwrap();
+                        // The block at topoheight 1 is mined by the watched address
+                        serde_json::to_value(block_response(topoheight, &address, Some(50).filter(|_| topoheight == 1))).unwrap()
+                    },
+                    "get_nonce" => serde_json::to_value(GetNonceResult {
+                        topoheight: 1,
+                        version: VersionedNonce::new(0, None)
+                    }).unwrap(),
+                    "get_account_assets" => serde_json::to_value([XELIS_ASSET]).unwrap(),
+                    "get_asset" => serde_json::to_value(AssetData::new(0, 8)).unwrap(),
+                    "get_balance" => serde_json::to_value(GetBalanceResult {
+                        version: VersionedBalance::new(balance.clone(), None),
+                        topoheight: 1
+                    }).unwrap(),
+                    _ => return Err(format!("unexpected method {}", method))
+                };
+                Ok(value)
+            }).await
+        };
+
+        let wallet = create_test_watch_only_wallet(keypair.get_public_key().clone());
+        let handler = NetworkHandler::with_api(Arc::clone(&wallet), daemon.connect().await).await.unwrap();
+        handler.sync(&address, None).await.unwrap();
+
+        // Plaintext data from the daemon is synced
+        {
+            let storage = wallet.get_storage().read().await;
+            assert_eq!(storage.get_synced_topoheight().unwrap(), 1);
+            assert_eq!(storage.get_nonce().unwrap(), 0);
+
+            let entry = storage.get_transaction(&Hash::new([1u8; 32])).unwrap();
+            assert!(matches!(entry.get_entry(), EntryData::Coinbase { reward: 50 }));
+
+            // Encrypted balance can't be decrypted without the private key
+            assert!(!storage.has_any_balance().await.unwrap());
+        }
+        assert!(matches!(wallet.get_balance(&XELIS_ASSET).await, Err(WalletError::WatchOnly)));
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::Error;
+use xelis_common::{
+    api::DataElement,
+    crypto::{
+        bech32::{convert_bits, decode, encode, Bech32Error},
+        Address,
+        AddressType,
+        Hash,
+        PublicKey
+    },
+    serializer::{
+        Reader,
+        ReaderError,
+        Serializer,
+        Writer
+    },
+    transaction::builder::{TransactionTypeBuilder, TransferBuilder}
+};
+use crate::config::{PAYMENT_REQUEST_PREFIX, TESTNET_PAYMENT_REQUEST_PREFIX};
+
+// Shareable payment request created by a merchant
+// It contains everything needed to build the expected transfer
+// and is encoded using bech32 like addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentRequest {
+    // Recipient of the payment
+    pub address: Address,
+    // Asset requested
+    pub asset: Hash,
+    // Amount requested in atomic units
+    pub amount: u64,
+    // Optional memo set as extra data in the transfer
+    pub memo: Option<DataElement>
+}
+
+impl PaymentRequest {
+    pub fn new(address: Address, asset: Hash, amount: u64, memo: Option<DataElement>) -> Self {
+        Self {
+            address,
+            asset,
+            amount,
+            memo
+        }
+    }
+
+    // Build the transaction type paying this request
+    pub fn to_transaction_type(&self) -> TransactionTypeBuilder {
+        TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: self.asset.clone(),
+            amount: self.amount,
+            destination: self.address.clone(),
+            extra_data: self.memo.clone()
+        }])
+    }
+
+    // Returns the payment request as a string (human readable format)
+    pub fn as_string(&self) -> Result<String, Bech32Error> {
+        let bits = convert_bits(&self.to_bytes(), 8, 5, true)?;
+        let hrp = if self.address.is_mainnet() {
+            PAYMENT_REQUEST_PREFIX
+        } else {
+            TESTNET_PAYMENT_REQUEST_PREFIX
+        };
+
+        encode(hrp.to_owned(), &bits)
+    }
+
+    // Parse a payment request from a string (human readable format)
+    pub fn from_string(request: &String) -> Result<Self, Error> {
+        let (hrp, decoded) = decode(request)?;
+        if hrp != PAYMENT_REQUEST_PREFIX && hrp != TESTNET_PAYMENT_REQUEST_PREFIX {
+            return Err(Bech32Error::InvalidPrefix(hrp, format!("{} or {}", PAYMENT_REQUEST_PREFIX, TESTNET_PAYMENT_REQUEST_PREFIX)).into())
+        }
+
+        let bits = convert_bits(&decoded, 5, 8, false)?;
+        let request = Self::from_bytes(&bits)?;
+
+        // the prefix must match the network of the address
+        let expected = if request.address.is_mainnet() {
+            PAYMENT_REQUEST_PREFIX
+        } else {
+            TESTNET_PAYMENT_REQUEST_PREFIX
+        };
+
+        if hrp != expected {
+            return Err(Bech32Error::InvalidPrefix(hrp, expected.to_owned()).into())
+        }
+
+        Ok(request)
+    }
+}
+
+impl Serializer for PaymentRequest {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bool(self.address.is_mainnet());
+        self.address.get_public_key().write(writer);
+        self.address.get_type().write(writer);
+        writer.write_hash(&self.asset);
+        writer.write_u64(&self.amount);
+        self.memo.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let mainnet = reader.read_bool()?;
+        let key = PublicKey::read(reader)?;
+        let addr_type = AddressType::read(reader)?;
+        let asset = reader.read_hash()?;
+        let amount = reader.read_u64()?;
+        let memo = Option::read(reader)?;
+
+        Ok(Self {
+            address: Address::new(mainnet, addr_type, key),
+            asset,
+            amount,
+            memo
+        })
+    }
+
+    fn size(&self) -> usize {
+        1 + self.address.get_public_key().size()
+        + self.address.get_type().size()
+        + self.asset.size()
+        + self.amount.size()
+        + self.memo.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        api::DataValue,
+        config::XELIS_ASSET,
+        crypto::KeyPair
+    };
+    use super::*;
+
+    #[test]
+    fn test_payment_request_encoding() {
+        let address = KeyPair::new().get_public_key().to_address(true);
+        let memo = DataElement::Value(DataValue::String("invoice #42".to_owned()));
+        let request = PaymentRequest::new(address, XELIS_ASSET, 1000, Some(memo));
+
+        let encoded = request.as_string().unwrap();
+        assert!(encoded.starts_with(PAYMENT_REQUEST_PREFIX));
+        let decoded = PaymentRequest::from_string(&encoded).unwrap();
+        assert_eq!(decoded, request);
+
+        // testnet request without memo
+        let address = KeyPair::new().get_public_key().to_address(false);
+        let request = PaymentRequest::new(address, XELIS_ASSET, 1, None);
+        let encoded = request.as_string().unwrap();
+        assert!(encoded.starts_with(TESTNET_PAYMENT_REQUEST_PREFIX));
+        assert_eq!(PaymentRequest::from_string(&encoded).unwrap(), request);
+
+        // an address isn't a payment request
+        assert!(PaymentRequest::from_string(&request.address.as_string().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_payment_request_transfer() {
+        let address = KeyPair::new().get_public_key().to_address(true);
+        let memo = DataElement::Value(DataValue::U64(42));
+        let request = PaymentRequest::new(address.clone(), XELIS_ASSET, 500, Some(memo.clone()));
+
+        let TransactionTypeBuilder::Transfers(transfers) = request.to_transaction_type() else {
+            panic!("expected transfers");
+        };
+        assert_eq!(transfers.len(), 1);
+
+        let transfer = &transfers[0];
+        assert_eq!(transfer.destination, address);
+        assert_eq!(transfer.asset, XELIS_ASSET);
+        assert_eq!(transfer.amount, 500);
+        assert_eq!(transfer.extra_data, Some(memo));
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::debug;
+use tokio::sync::Notify;
+use crate::wallet::WalletError;
+
+// Coordinate a rescan with the operations mutating the wallet storage
+// While a rescan is in progress, transactions can't be created or submitted
+// and balances can't be synced, as the storage is being rewritten
+pub struct RescanState {
+    in_progress: AtomicBool,
+    // Notified when the rescan ends
+    done: Notify
+}
+
+impl RescanState {
+    pub fn new() -> Self {
+        Self {
+            in_progress: AtomicBool::new(false),
+            done: Notify::new()
+        }
+    }
+
+    // Check if a rescan is currently running
+    pub fn is_in_progress(&self) -> bool {
+        self.in_progress.load(Ordering::SeqCst)
+    }
+
+    // Mark the rescan as started
+    // Only one rescan can run at a time
+    // The rescan ends when the returned guard is dropped
+    pub fn start(&self) -> Result<RescanGuard<'_>, WalletError> {
+        if self.in_progress.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+            return Err(WalletError::RescanInProgress)
+        }
+
+        debug!("Rescan started");
+        Ok(RescanGuard { state: self })
+    }
+
+    // Returns an error if a rescan is in progress
+    pub fn ensure_not_in_progress(&self) -> Result<(), WalletError> {
+        if self.is_in_progress() {
+            return Err(WalletError::RescanInProgress)
+        }
+
+        Ok(())
+    }
+
+    // Wait until no rescan is running anymore
+    pub async fn wait(&self) {
+        loop {
+            // Created before checking the state so the end of the rescan can't be missed
+            let done = self.done.notified();
+            if !self.is_in_progress() {
+                break
+            }
+            done.await;
+        }
+    }
+}
+
+impl Default for RescanState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Guard held during a rescan
+pub struct RescanGuard<'a> {
+    state: &'a RescanState
+}
+
+impl Drop for RescanGuard<'_> {
+    fn drop(&mut self) {
+        debug!("Rescan done");
+        self.state.in_progress.store(false, Ordering::SeqCst);
+        self.state.done.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rescan_guard() {
+        let state = RescanState::new();
+        assert!(state.ensure_not_in_progress().is_ok());
+
+        let guard = state.start().unwrap();
+        // Submitting a transaction or starting another rescan is rejected
+        assert!(matches!(state.ensure_not_in_progress(), Err(WalletError::RescanInProgress)));
+        assert!(matches!(state.start(), Err(WalletError::RescanInProgress)));
+
+        drop(guard);
+        assert!(!state.is_in_progress());
+        assert!(state.ensure_not_in_progress().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wait_rescan() {
+        let state = std::sync::Arc::new(RescanState::new());
+        // Nothing to wait for
+        state.wait().await;
+
+        let guard = state.start().unwrap();
+        let waiter = {
+            let state = std::sync::Arc::clone(&state);
+            tokio::spawn(async move { state.wait().await })
+        };
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(guard);
+        tokio::time::timeout(std::time::Duration::from_secs(1), waiter).await.unwrap().unwrap();
+    }
+}
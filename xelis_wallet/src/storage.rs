@@ -1,17 +1,17 @@
 use std::{
-    collections::{HashMap, HashSet, VecDeque},
-    num::NonZeroUsize
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    num::NonZeroUsize,
+    sync::Arc
 };
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 use indexmap::IndexMap;
 use log::trace;
 use lru::LruCache;
-use sled::{
-    Tree,
-    Db
-};
 use tokio::sync::Mutex;
 use xelis_common::{
-    account::CiphertextCache,
+    account::{BalanceType, CiphertextCache},
+    config::XELIS_ASSET,
     api::{
         query::{
             Query,
@@ -22,7 +22,9 @@ use xelis_common::{
     },
     crypto::{
         elgamal::CompressedCiphertext,
+        hash,
         Hash,
+        HASH_SIZE,
         PrivateKey,
         PublicKey
     },
@@ -32,7 +34,8 @@ use xelis_common::{
         ReaderError,
         Serializer,
         Writer
-    }
+    },
+    utils::format_coin
 };
 use anyhow::{
     Context,
@@ -42,12 +45,15 @@ use anyhow::{
 use crate::{
     cipher::Cipher,
     config::SALT_SIZE,
+    contact::Contact,
     entry::{
         EntryData,
+        HistoryFilter,
         TransactionEntry,
         Transfer
     },
-    wallet::WalletError
+    kv_store::{KvBatch, KvStore, KvTree},
+    wallet::{KdfParams, WalletError}
 };
 use log::error;
 
@@ -56,9 +62,15 @@ const NONCE_KEY: &[u8] = b"NONCE";
 const SALT_KEY: &[u8] = b"SALT";
 // Password + salt is necessary to decrypt master key
 const PASSWORD_SALT_KEY: &[u8] = b"PSALT";
+// Parameters used to derive the password-based key
+const KDF_PARAMS_KEY: &[u8] = b"KDF";
 // Master key to encrypt/decrypt while interacting with the storage 
 const MASTER_KEY: &[u8] = b"MKEY";
+// New master key while the storage is being encrypted again with it
+const PENDING_MASTER_KEY: &[u8] = b"NMKEY";
 const PRIVATE_KEY: &[u8] = b"PKEY";
+// Public key of a watch-only wallet, set instead of the private key
+const WATCH_ONLY_KEY: &[u8] = b"WKEY";
 
 // const used for online mode
 // represent the daemon topoheight
@@ -66,21 +78,88 @@ const TOPOHEIGHT_KEY: &[u8] = b"TOPH";
 // represent the daemon top block hash
 const TOP_BLOCK_HASH_KEY: &[u8] = b"TOPBH";
 const NETWORK: &[u8] = b"NET";
+// index of the next receive address to suggest
+const RECEIVE_INDEX_KEY: &[u8] = b"RIDX";
+// Account indexes derived from the wallet key
+const DERIVED_ACCOUNTS_KEY: &[u8] = b"DACC";
+// topoheight below which the history was pruned
+const PRUNED_TOPOHEIGHT_KEY: &[u8] = b"PRUNED";
+// address of the last daemon the wallet was connected to
+const DAEMON_ADDRESS_KEY: &[u8] = b"DAEMON";
 
 // Default cache size
 const DEFAULT_CACHE_SIZE: usize = 100;
 
+// Data integrated in the receive address at this index
+// It is set as extra data by the sender, so we can detect when the address is used
+pub fn receive_address_data(index: u64) -> DataElement {
+    DataElement::Value(DataValue::U64(index))
+}
+
+// Index of the receive address whose data was set as extra data
+pub fn receive_address_index(data: &DataElement) -> Option<u64> {
+    match data {
+        DataElement::Value(DataValue::U64(index)) => Some(*index),
+        _ => None
+    }
+}
+
+// Write the entries of a tree in the state digest, prefixed by their count
+fn write_digest_entries(writer: &mut Writer, entries: BTreeMap<Vec<u8>, Vec<u8>>) {
+    writer.write_u64(&(entries.len() as u64));
+    for (key, value) in entries {
+        writer.write_u64(&(key.len() as u64));
+        writer.write_bytes(&key);
+        writer.write_u64(&(value.len() as u64));
+        writer.write_bytes(&value);
+    }
+}
+
+// In-memory state of the wallet storage
+// Used to simulate a crash and recover scenario in tests
+#[cfg(any(test, feature = "test_utils"))]
+#[derive(Debug, Clone)]
+pub struct WalletStateSnapshot {
+    pub nonce: Option<u64>,
+    pub synced_topoheight: Option<u64>,
+    // Cached balances, from the least to the most recently used
+    pub balances: Vec<(Hash, Balance)>,
+    // Pending balances of the transactions not yet confirmed
+    pub unconfirmed_balances: HashMap<Hash, VecDeque<Balance>>,
+    // Cached assets, from the least to the most recently used
+    pub assets: Vec<(Hash, u8)>
+}
+
 #[derive(Debug, Clone)]
 pub struct Balance {
     pub amount: u64,
-    pub ciphertext: CiphertextCache
+    pub ciphertext: CiphertextCache,
+    // Type of the versioned balance it comes from
+    pub balance_type: BalanceType,
+    // Plaintext output balance, only set when the version has both inputs and outputs
+    // Funds received in the same version as our outgoing transactions aren't spendable yet
+    pub output_amount: Option<u64>
 }
 
 impl Balance {
     pub fn new(amount: u64, ciphertext: CiphertextCache) -> Self {
+        Self::with_type(amount, ciphertext, BalanceType::Input, None)
+    }
+
+    pub fn with_type(amount: u64, ciphertext: CiphertextCache, balance_type: BalanceType, output_amount: Option<u64>) -> Self {
         Self {
             amount,
-            ciphertext
+            ciphertext,
+            balance_type,
+            output_amount
+        }
+    }
+
+    // Amount that can be spent by a new transaction
+    pub fn spendable_amount(&self) -> u64 {
+        match (self.balance_type, self.output_amount) {
+            (BalanceType::Both, Some(output_amount)) => output_amount.min(self.amount),
+            _ => self.amount
         }
     }
 }
@@ -89,21 +168,35 @@ impl Serializer for Balance {
     fn write(&self, writer: &mut Writer) {
         self.amount.write(writer);
         self.ciphertext.write(writer);
+        self.balance_type.write(writer);
+        self.output_amount.write(writer);
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
         let amount = u64::read(reader)?;
         let ciphertext = CiphertextCache::read(reader)?;
+        // Balances stored before the balance type was tracked are inputs only
+        let (balance_type, output_amount) = if reader.size() > 0 {
+            (BalanceType::read(reader)?, Option::read(reader)?)
+        } else {
+            (BalanceType::Input, None)
+        };
+
         Ok(Self {
             amount,
-            ciphertext
+            ciphertext,
+            balance_type,
+            output_amount
         })
     }
 }
 
+// Tree opened from the storage backend
+type Tree = Arc<dyn KvTree>;
+
 // Use this struct to get access to non-encrypted keys (such as salt for KDF and encrypted master key)
 pub struct Storage {
-    db: Db
+    db: Box<dyn KvStore>
 }
 
 // Implement an encrypted storage system 
@@ -120,6 +213,12 @@ pub struct EncryptedStorage {
     assets: Tree,
     // This tree is used to store all topoheight where a change in the wallet occured
     changes_topoheight: Tree,
+    // Address book, contacts are stored by label
+    contacts: Tree,
+    // User defined labels for assets
+    asset_labels: Tree,
+    // Transactions received on each receive address, keyed by the address index and the TX hash
+    subaddress_transactions: Tree,
     // The inner storage
     inner: Storage,
     // Caches
@@ -131,7 +230,10 @@ pub struct EncryptedStorage {
     unconfirmed_balances_cache: Mutex<HashMap<Hash, VecDeque<Balance>>>,
     assets_cache: Mutex<LruCache<Hash, u8>>,
     // Cache for the synced topoheight
-    synced_topoheight: Option<u64>
+    synced_topoheight: Option<u64>,
+    // Count of plaintext balance reads, used by tests to check the wallet balance cache
+    #[cfg(test)]
+    plaintext_balance_reads: AtomicUsize
 }
 
 impl EncryptedStorage {
@@ -143,12 +245,17 @@ impl EncryptedStorage {
             extra: inner.db.open_tree(&cipher.hash_key("extra"))?,
             assets: inner.db.open_tree(&cipher.hash_key("assets"))?,
             changes_topoheight: inner.db.open_tree(&cipher.hash_key("changes_topoheight"))?,
+            contacts: inner.db.open_tree(&cipher.hash_key("contacts"))?,
+            asset_labels: inner.db.open_tree(&cipher.hash_key("asset_labels"))?,
+            subaddress_transactions: inner.db.open_tree(&cipher.hash_key("subaddress_transactions"))?,
             cipher,
             inner,
             balances_cache: Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap())),
             unconfirmed_balances_cache: Mutex::new(HashMap::new()),
             assets_cache: Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap())),
             synced_topoheight: None,
+            #[cfg(test)]
+            plaintext_balance_reads: AtomicUsize::new(0)
         };
 
         if storage.has_network()? {
@@ -216,41 +323,41 @@ impl EncryptedStorage {
     fn save_to_disk_with_encrypted_key(&self, tree: &Tree, key: &[u8], value: &[u8]) -> Result<()> {
         let encrypted_key = self.create_encrypted_key(key)?;
         let encrypted_value = self.cipher.encrypt_value(value)?;
-        tree.insert(encrypted_key, encrypted_value)?;
+        tree.insert(&encrypted_key, &encrypted_value)?;
         Ok(())
     }
 
     // hash key, encrypt data and then save to disk 
     fn save_to_disk(&self, tree: &Tree, key: &[u8], value: &[u8]) -> Result<()> {
         let hashed_key = self.cipher.hash_key(key);
-        tree.insert(hashed_key, self.cipher.encrypt_value(value)?)?;
+        tree.insert(&hashed_key, &self.cipher.encrypt_value(value)?)?;
         Ok(())
     }
 
     // hash key, encrypt data and then save to disk 
     fn delete_from_disk(&self, tree: &Tree, key: &[u8]) -> Result<()> {
         let hashed_key = self.cipher.hash_key(key);
-        tree.remove(hashed_key)?;
+        tree.remove(&hashed_key)?;
         Ok(())
     }
 
     // hash key, encrypt data and then save to disk 
     fn delete_from_disk_with_encrypted_key(&self, tree: &Tree, key: &[u8]) -> Result<()> {
         let encrypted_key = self.create_encrypted_key(key)?;
-        tree.remove(encrypted_key)?;
+        tree.remove(&encrypted_key)?;
         Ok(())
     }
 
     // Search if the data is present in the tree using hashed key
     fn contains_data(&self, tree: &Tree, key: &[u8]) -> Result<bool> {
         let hashed_key = self.cipher.hash_key(key);
-        Ok(tree.contains_key(hashed_key)?)
+        Ok(tree.contains_key(&hashed_key)?)
     }
 
     // Encrypt instead of hash the key to recover it later
     fn contains_encrypted_data(&self, tree: &Tree, key: &[u8]) -> Result<bool> {
         let encrypted_key = self.create_encrypted_key(key)?;
-        Ok(tree.contains_key(encrypted_key)?)
+        Ok(tree.contains_key(&encrypted_key)?)
     }
 
     // Open the named tree
@@ -446,8 +553,53 @@ impl EncryptedStorage {
         self.load_from_disk_with_encrypted_key(&self.assets, asset.as_bytes())
     }
 
+    // Set a private label for this asset, it is only known by the wallet
+    pub fn set_asset_label(&mut self, asset: &Hash, label: &String) -> Result<()> {
+        trace!("set asset label for {}", asset);
+        self.save_to_disk(&self.asset_labels, asset.as_bytes(), &label.to_bytes())
+    }
+
+    // Retrieve the label set by the user for this asset
+    pub fn get_asset_label(&self, asset: &Hash) -> Result<Option<String>> {
+        trace!("get asset label for {}", asset);
+        if !self.contains_data(&self.asset_labels, asset.as_bytes())? {
+            return Ok(None)
+        }
+
+        self.load_from_disk(&self.asset_labels, asset.as_bytes()).map(Some)
+    }
+
+    // Delete the label set by the user for this asset
+    pub fn delete_asset_label(&mut self, asset: &Hash) -> Result<()> {
+        trace!("delete asset label for {}", asset);
+        self.delete_from_disk(&self.asset_labels, asset.as_bytes())
+    }
+
+    // Name used to display this asset
+    // The user label is preferred over the asset hash
+    pub fn get_asset_display_name(&self, asset: &Hash) -> Result<String> {
+        if let Some(label) = self.get_asset_label(asset)? {
+            return Ok(label)
+        }
+
+        if *asset == XELIS_ASSET {
+            return Ok("XELIS".to_owned())
+        }
+
+        Ok(asset.to_string())
+    }
+
+    // Format an amount of this asset with its decimals and display name
+    pub fn format_amount(&self, asset: &Hash, amount: u64) -> Result<String> {
+        let decimals = self.get_asset_decimals(asset)?;
+        Ok(format!("{} {}", format_coin(amount, decimals), self.get_asset_display_name(asset)?))
+    }
+
     // Retrieve the plaintext balance for this asset
     pub async fn get_plaintext_balance_for(&self, asset: &Hash) -> Result<u64> {
+        #[cfg(test)]
+        self.plaintext_balance_reads.fetch_add(1, Ordering::SeqCst);
+
         let mut cache = self.balances_cache.lock().await;
         if let Some(balance) = cache.get(asset) {
             return Ok(balance.amount);
@@ -460,6 +612,11 @@ impl EncryptedStorage {
         Ok(plaintext_balance)
     }
 
+    #[cfg(test)]
+    pub(crate) fn get_plaintext_balance_reads(&self) -> usize {
+        self.plaintext_balance_reads.load(Ordering::SeqCst)
+    }
+
     // Retrieve the balance for this asset
     pub async fn get_balance_for(&self, asset: &Hash) -> Result<Balance> {
         let mut cache = self.balances_cache.lock().await;
@@ -473,6 +630,25 @@ impl EncryptedStorage {
         Ok(balance)
     }
 
+    // Retrieve the plaintext balance of every registered asset, sorted by asset
+    // Balance keys are hashed on disk, so the assets tree is used to know which balances exist
+    // A registered asset without balance is returned with 0
+    pub async fn get_all_balances(&self) -> Result<Vec<(Hash, u64)>> {
+        trace!("get all balances");
+        let mut balances = Vec::new();
+        for asset in self.get_assets().await? {
+            let balance = if self.has_balance_for(&asset).await? {
+                self.get_plaintext_balance_for(&asset).await?
+            } else {
+                0
+            };
+            balances.push((asset, balance));
+        }
+        balances.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(balances)
+    }
+
     // Retrieve the unconfirmed balance for this asset if present
     // otherwise, fall back on the confirmed balance
     pub async fn get_unconfirmed_balance_for(&self, asset: &Hash) -> Result<Balance> {
@@ -481,10 +657,7 @@ impl EncryptedStorage {
         if let Some(balances) = cache.get(asset) {
             // get the latest unconfirmed balance
             if let Some(balance) = balances.back() {
-                return Ok(Balance {
-                    amount: balance.amount,
-                    ciphertext: balance.ciphertext.clone()
-                });
+                return Ok(balance.clone());
             }
         }
 
@@ -569,11 +742,42 @@ impl EncryptedStorage {
         self.get_filtered_transactions(None, None, None, true, true, true, true, None)
     }
 
+    // Get all transactions involving this asset
+    pub fn get_transactions_for_asset(&self, asset: &Hash) -> Result<Vec<TransactionEntry>> {
+        self.get_history(&HistoryFilter {
+            asset: Some(asset.clone()),
+            ..Default::default()
+        })
+    }
+
+    // Get all transactions in the topoheight range, both bounds included
+    pub fn get_transactions_in_range(&self, from_topoheight: u64, to_topoheight: u64) -> Result<Vec<TransactionEntry>> {
+        self.get_history(&HistoryFilter {
+            min_topoheight: Some(from_topoheight),
+            max_topoheight: Some(to_topoheight),
+            ..Default::default()
+        })
+    }
+
+    // Entries are filtered while iterating, so only the matching ones are kept in memory
+    pub fn get_history(&self, filter: &HistoryFilter) -> Result<Vec<TransactionEntry>> {
+        let mut transactions = Vec::new();
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
+            let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            if filter.matches(&entry) {
+                transactions.push(entry);
+            }
+        }
+
+        Ok(transactions)
+    }
+
     // delete all transactions above the specified topoheight
     // This will go through each transaction, deserialize it, check topoheight, and delete it if required
     pub fn delete_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<()> {
-        for el in self.transactions.iter().values() {
-            let value = el?;
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
             let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
             if entry.get_topoheight() > topoheight {
                 self.delete_transaction(entry.get_hash())?;
@@ -583,11 +787,40 @@ impl EncryptedStorage {
         Ok(())
     }
 
+    // delete all transactions in the topoheight range, both bounds included
+    pub fn delete_transactions_in_range(&mut self, from_topoheight: u64, to_topoheight: u64) -> Result<()> {
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
+            let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            if (from_topoheight..=to_topoheight).contains(&entry.get_topoheight()) {
+                self.delete_transaction(entry.get_hash())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // delete all transactions below the specified topoheight
+    // Returns the number of deleted transactions
+    pub fn delete_transactions_below_topoheight(&mut self, topoheight: u64) -> Result<usize> {
+        let mut deleted = 0;
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
+            let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            if entry.get_topoheight() < topoheight {
+                self.delete_transaction(entry.get_hash())?;
+                deleted += 1;
+            }
+        }
+
+        Ok(deleted)
+    }
+
     // Filter when the data is deserialized to not load all transactions in memory
     pub fn get_filtered_transactions(&self, address: Option<&PublicKey>, min_topoheight: Option<u64>, max_topoheight: Option<u64>, accept_incoming: bool, accept_outgoing: bool, accept_coinbase: bool, accept_burn: bool, query: Option<&Query>) -> Result<Vec<TransactionEntry>> {
         let mut transactions = Vec::new();
-        for el in self.transactions.iter().values() {
-            let value = el?;
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
             let mut entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
             if let Some(topoheight) = min_topoheight {
                 if entry.get_topoheight() < topoheight {
@@ -655,13 +888,14 @@ impl EncryptedStorage {
 
     // Delete a transaction saved in wallet using its hash
     pub fn delete_transaction(&mut self, hash: &Hash) -> Result<()> {
-        self.transactions.remove(self.cipher.hash_key(hash.as_bytes()))?;
+        self.transactions.remove(&self.cipher.hash_key(hash.as_bytes()))?;
         Ok(())
     }
 
     // Delete all transactions from this wallet
     pub fn delete_transactions(&mut self) -> Result<()> {
         self.transactions.clear()?;
+        self.subaddress_transactions.clear()?;
         Ok(())
     }
 
@@ -673,6 +907,15 @@ impl EncryptedStorage {
         Ok(())
     }
 
+    // Delete the balance of a single asset, others are kept
+    pub async fn delete_balance_for(&mut self, asset: &Hash) -> Result<()> {
+        trace!("delete balance for {}", asset);
+        self.delete_from_disk(&self.balances, asset.as_bytes())?;
+        self.unconfirmed_balances_cache.lock().await.remove(asset);
+        self.balances_cache.lock().await.pop(asset);
+        Ok(())
+    }
+
     // Delete all unconfirmed balances from this wallet
     pub async fn delete_unconfirmed_balances(&mut self) -> Result<()> {
         self.unconfirmed_balances_cache.lock().await.clear();
@@ -724,6 +967,48 @@ impl EncryptedStorage {
         self.load_from_disk(&self.extra, PRIVATE_KEY)
     }
 
+    // Store the public key watched by a watch-only wallet
+    pub fn set_watch_only_public_key(&mut self, public_key: &PublicKey) -> Result<()> {
+        trace!("set watch-only public key");
+        self.save_to_disk(&self.extra, WATCH_ONLY_KEY, &public_key.to_bytes())
+    }
+
+    // Retrieve the public key watched by a watch-only wallet
+    pub fn get_watch_only_public_key(&self) -> Result<PublicKey> {
+        trace!("get watch-only public key");
+        self.load_from_disk(&self.extra, WATCH_ONLY_KEY)
+    }
+
+    // Returns true if this wallet has no private key stored
+    pub fn is_watch_only(&self) -> Result<bool> {
+        trace!("is watch-only");
+        self.contains_data(&self.extra, WATCH_ONLY_KEY)
+    }
+
+    // Get all the account indexes derived from the wallet key, sorted
+    // Index 0 is the wallet key itself and is always present
+    pub fn get_derived_accounts(&self) -> Result<Vec<u32>> {
+        trace!("get derived accounts");
+        if !self.contains_data(&self.extra, DERIVED_ACCOUNTS_KEY)? {
+            return Ok(vec![0])
+        }
+        self.load_from_disk(&self.extra, DERIVED_ACCOUNTS_KEY)
+    }
+
+    // Save an account index derived from the wallet key
+    // Returns false if it was already saved
+    pub fn add_derived_account(&mut self, index: u32) -> Result<bool> {
+        trace!("add derived account {}", index);
+        let mut accounts = self.get_derived_accounts()?;
+        let Err(position) = accounts.binary_search(&index) else {
+            return Ok(false)
+        };
+
+        accounts.insert(position, index);
+        self.save_to_disk(&self.extra, DERIVED_ACCOUNTS_KEY, &accounts.to_bytes())?;
+        Ok(true)
+    }
+
     // Set the topoheight until which the wallet is synchronized
     pub fn set_synced_topoheight(&mut self, topoheight: u64) -> Result<()> {
         trace!("set synced topoheight to {}", topoheight);
@@ -768,6 +1053,251 @@ impl EncryptedStorage {
         self.load_from_disk(&self.extra, TOP_BLOCK_HASH_KEY)
     }
 
+    // Check if we received funds with this extra data
+    // If no extra data is provided, any incoming transaction is accepted
+    pub fn has_incoming_with_extra_data(&self, extra_data: Option<&DataElement>) -> Result<bool> {
+        trace!("has incoming with extra data");
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
+            let entry = TransactionEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            if let EntryData::Incoming { transfers, .. } = entry.get_entry() {
+                let found = match extra_data {
+                    Some(data) => transfers.iter().any(|t| t.get_extra_data().as_ref() == Some(data)),
+                    None => true
+                };
+
+                if found {
+                    return Ok(true)
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Attribute an incoming transaction to the receive addresses it was sent to
+    // Funds sent to a receive address are recognized by the extra data integrated in it
+    pub fn save_subaddress_transaction(&mut self, entry: &TransactionEntry) -> Result<()> {
+        let EntryData::Incoming { transfers, .. } = entry.get_entry() else {
+            return Ok(())
+        };
+
+        let indexes: HashSet<u64> = transfers.iter()
+            .filter_map(|t| t.get_extra_data().as_ref().and_then(receive_address_index))
+            .collect();
+        for index in indexes {
+            trace!("save transaction {} for receive address {}", entry.get_hash(), index);
+            let mut key = index.to_be_bytes().to_vec();
+            key.extend_from_slice(entry.get_hash().as_bytes());
+            self.save_to_disk_with_encrypted_key(&self.subaddress_transactions, &key, &[])?;
+        }
+
+        Ok(())
+    }
+
+    // Get all transactions received on the receive address at this index
+    // Only the transactions attributed while syncing are returned, deleted ones are skipped
+    pub fn get_transactions_for_subaddress(&self, index: u64) -> Result<Vec<TransactionEntry>> {
+        trace!("get transactions for subaddress {}", index);
+        let mut entries = Vec::new();
+        for el in self.subaddress_transactions.iter() {
+            let (key, _) = el?;
+            let key = self.cipher.decrypt_value(&key)?;
+            if key.len() != 8 + HASH_SIZE || key[..8] != index.to_be_bytes() {
+                continue;
+            }
+
+            let hash = Hash::from_bytes(&key[8..])?;
+            if self.has_transaction(&hash)? {
+                entries.push(self.get_transaction(&hash)?);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    // Get the index of the last suggested receive address
+    pub fn get_receive_address_index(&self) -> Result<u64> {
+        trace!("get receive address index");
+        if !self.contains_data(&self.extra, RECEIVE_INDEX_KEY)? {
+            return Ok(0)
+        }
+        self.load_from_disk(&self.extra, RECEIVE_INDEX_KEY)
+    }
+
+    // Search the first receive address index without any incoming funds, starting from the last suggested one
+    // The found index is saved so already used addresses are never suggested again
+    pub fn next_unused_receive_address_index(&mut self) -> Result<u64> {
+        trace!("next unused receive address index");
+        let mut index = self.get_receive_address_index()?;
+        while self.has_incoming_with_extra_data(Some(&receive_address_data(index)))? {
+            index += 1;
+        }
+
+        self.save_to_disk(&self.extra, RECEIVE_INDEX_KEY, &index.to_be_bytes())?;
+        Ok(index)
+    }
+
+    // Decrypt both keys and values of a tree using encrypted keys, sorted by the decrypted key
+    fn get_decrypted_entries(&self, tree: &Tree) -> Result<BTreeMap<Vec<u8>, Vec<u8>>> {
+        let mut entries = BTreeMap::new();
+        for el in tree.iter() {
+            let (key, value) = el?;
+            entries.insert(self.cipher.decrypt_value(&key)?, self.cipher.decrypt_value(&value)?);
+        }
+
+        Ok(entries)
+    }
+
+    // Compute a digest of all the data stored in the wallet of this public key
+    // Keys on disk are hashed or encrypted with the master key, so the logical keys are used instead
+    // and each tree is written sorted by them: the same wallet state always produce the same digest,
+    // even once restored under another master key. Values are decrypted as their encryption nonce is random
+    pub fn get_state_digest(&self, public_key: &PublicKey) -> Result<Hash> {
+        trace!("get state digest");
+        let mut writer = Writer::new();
+        public_key.write(&mut writer);
+
+        // Trees with encrypted keys
+        let assets = self.get_decrypted_entries(&self.assets)?;
+        let asset_keys: Vec<Vec<u8>> = assets.keys().cloned().collect();
+        write_digest_entries(&mut writer, assets);
+        for tree in [&self.contacts, &self.changes_topoheight] {
+            write_digest_entries(&mut writer, self.get_decrypted_entries(tree)?);
+        }
+
+        // Transactions are keyed by their hash, also stored in the entry
+        let mut entries = BTreeMap::new();
+        for el in self.transactions.iter() {
+            let (_, value) = el?;
+            let value = self.cipher.decrypt_value(&value)?;
+            let entry = TransactionEntry::from_bytes(&value)?;
+            entries.insert(entry.get_hash().to_bytes(), value);
+        }
+        write_digest_entries(&mut writer, entries);
+
+        // Hashed keys can't be reversed, so these trees are read from the keys we know
+        // Their size is written too, so an entry stored under an unknown key still changes the digest
+        let asset_keys: Vec<&[u8]> = asset_keys.iter().map(Vec::as_slice).collect();
+        let extra_keys = [NONCE_KEY, PRIVATE_KEY, WATCH_ONLY_KEY, TOPOHEIGHT_KEY, TOP_BLOCK_HASH_KEY, NETWORK, RECEIVE_INDEX_KEY, DERIVED_ACCOUNTS_KEY, PRUNED_TOPOHEIGHT_KEY, DAEMON_ADDRESS_KEY];
+        for (tree, keys) in [(&self.balances, asset_keys.as_slice()), (&self.asset_labels, asset_keys.as_slice()), (&self.extra, &extra_keys[..])] {
+            writer.write_u64(&(tree.len() as u64));
+            let mut entries = BTreeMap::new();
+            for key in keys {
+                if let Some(value) = tree.get(&self.cipher.hash_key(key))? {
+                    entries.insert(key.to_vec(), self.cipher.decrypt_value(&value)?);
+                }
+            }
+            write_digest_entries(&mut writer, entries);
+        }
+
+        Ok(hash(writer.as_bytes()))
+    }
+
+    // Save a contact under its label
+    pub fn set_contact(&mut self, label: &String, contact: &Contact) -> Result<()> {
+        trace!("set contact {}", label);
+        self.save_to_disk_with_encrypted_key(&self.contacts, &label.to_bytes(), &contact.to_bytes())
+    }
+
+    // Check if a contact exists with this label
+    pub fn has_contact(&self, label: &String) -> Result<bool> {
+        trace!("has contact {}", label);
+        self.contains_encrypted_data(&self.contacts, &label.to_bytes())
+    }
+
+    // Retrieve the contact saved under this label
+    pub fn get_contact(&self, label: &String) -> Result<Contact> {
+        trace!("get contact {}", label);
+        self.load_from_disk_with_encrypted_key(&self.contacts, &label.to_bytes())
+    }
+
+    // Delete the contact saved under this label
+    pub fn delete_contact(&mut self, label: &String) -> Result<()> {
+        trace!("delete contact {}", label);
+        self.delete_from_disk_with_encrypted_key(&self.contacts, &label.to_bytes())
+    }
+
+    // Retrieve all contacts with their labels
+    pub fn get_contacts(&self) -> Result<Vec<(String, Contact)>> {
+        trace!("get contacts");
+        let mut contacts = Vec::new();
+        for res in self.contacts.iter() {
+            let (key, value) = res?;
+            let label = String::from_bytes(&self.cipher.decrypt_value(&key)?)?;
+            let contact = Contact::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            contacts.push((label, contact));
+        }
+
+        Ok(contacts)
+    }
+
+    // Retrieve all contacts having this tag (case-insensitive)
+    pub fn get_contacts_by_tag(&self, tag: &str) -> Result<Vec<(String, Contact)>> {
+        trace!("get contacts by tag {}", tag);
+        let contacts = self.get_contacts()?
+            .into_iter()
+            .filter(|(_, contact)| contact.has_tag(tag))
+            .collect();
+
+        Ok(contacts)
+    }
+
+    // Capture the in-memory state of the storage
+    #[cfg(any(test, feature = "test_utils"))]
+    pub async fn capture_state(&self) -> Result<WalletStateSnapshot> {
+        trace!("capture state");
+        let nonce = if self.contains_data(&self.extra, NONCE_KEY)? {
+            Some(self.get_nonce()?)
+        } else {
+            None
+        };
+
+        let balances = self.balances_cache.lock().await.iter().rev().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let unconfirmed_balances = self.unconfirmed_balances_cache.lock().await.clone();
+        let assets = self.assets_cache.lock().await.iter().rev().map(|(k, v)| (k.clone(), *v)).collect();
+
+        Ok(WalletStateSnapshot {
+            nonce,
+            synced_topoheight: self.synced_topoheight,
+            balances,
+            unconfirmed_balances,
+            assets
+        })
+    }
+
+    // Restore a previously captured in-memory state
+    #[cfg(any(test, feature = "test_utils"))]
+    pub async fn restore_state(&mut self, snapshot: WalletStateSnapshot) -> Result<()> {
+        trace!("restore state");
+        if let Some(nonce) = snapshot.nonce {
+            self.set_nonce(nonce)?;
+        }
+
+        match snapshot.synced_topoheight {
+            Some(topoheight) => self.set_synced_topoheight(topoheight)?,
+            None => self.synced_topoheight = None
+        };
+
+        {
+            let mut cache = self.balances_cache.lock().await;
+            cache.clear();
+            for (asset, balance) in snapshot.balances {
+                cache.put(asset, balance);
+            }
+        }
+
+        *self.unconfirmed_balances_cache.lock().await = snapshot.unconfirmed_balances;
+
+        let mut cache = self.assets_cache.lock().await;
+        cache.clear();
+        for (asset, decimals) in snapshot.assets {
+            cache.put(asset, decimals);
+        }
+
+        Ok(())
+    }
+
     pub fn get_public_storage(&self) -> &Storage {
         trace!("get public storage");
         &self.inner
@@ -778,6 +1308,16 @@ impl EncryptedStorage {
         &mut self.inner
     }
 
+    // Encrypt again all the trees using a new master key
+    // The new key must be saved as pending before, so the rotation can be resumed if interrupted
+    pub fn rotate_master_key(&mut self, master_key: &[u8]) -> Result<()> {
+        trace!("rotate master key");
+        let cipher = self.cipher.with_key(master_key)?;
+        self.inner.encrypt_trees_again(&self.cipher, &cipher)?;
+        self.cipher = cipher;
+        Ok(())
+    }
+
     // Get the network on which this wallet is
     fn get_network(&self) -> Result<Network> {
         trace!("get network");
@@ -819,13 +1359,13 @@ impl EncryptedStorage {
     pub fn delete_changes_above_topoheight(&mut self, topoheight: u64) -> Result<bool> {
         trace!("delete changes above topoheight {}", topoheight);
         let mut deleted = false;
-        for res in self.changes_topoheight.iter().keys() {
-            let key = res?;
+        for res in self.changes_topoheight.iter() {
+            let (key, _) = res?;
             let raw = self.cipher.decrypt_value(&key).context("Error while decrypting key from disk")?;
             let topo = u64::from_bytes(&raw)?;
             if topo > topoheight {
                 trace!("deleting topoheight changes at {}", topo);
-                self.changes_topoheight.remove(key)?;
+                self.changes_topoheight.remove(&key)?;
                 deleted = true;
             }
         }
@@ -833,6 +1373,116 @@ impl EncryptedStorage {
         Ok(deleted)
     }
 
+    // Delete all changes below topoheight
+    pub fn delete_changes_below_topoheight(&mut self, topoheight: u64) -> Result<()> {
+        trace!("delete changes below topoheight {}", topoheight);
+        for res in self.changes_topoheight.iter() {
+            let (key, _) = res?;
+            let raw = self.cipher.decrypt_value(&key).context("Error while decrypting key from disk")?;
+            let topo = u64::from_bytes(&raw)?;
+            if topo < topoheight {
+                trace!("deleting topoheight changes at {}", topo);
+                self.changes_topoheight.remove(&key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Find the lowest topoheight still needed to reconcile the wallet with the chain
+    // This is the highest change at or below the stable topoheight:
+    // a reorg can't go deeper, so it's the last point the wallet may have to rewind to
+    // Balances and nonce are always kept in their latest version, history below it isn't needed to spend
+    pub fn get_prunable_topoheight(&self, stable_topoheight: u64) -> Result<u64> {
+        trace!("get prunable topoheight below {}", stable_topoheight);
+        if self.has_topoheight_in_changes(stable_topoheight)? {
+            return Ok(stable_topoheight)
+        }
+
+        self.get_highest_topoheight_in_changes_below(stable_topoheight)
+    }
+
+    // Delete all transactions and changes below the topoheight
+    // Balances, nonce and assets are kept
+    // Returns the number of deleted transactions
+    pub fn prune_below_topoheight(&mut self, topoheight: u64) -> Result<usize> {
+        trace!("prune below topoheight {}", topoheight);
+        let deleted = self.delete_transactions_below_topoheight(topoheight)?;
+        self.delete_changes_below_topoheight(topoheight)?;
+
+        if self.get_pruned_topoheight()?.map_or(true, |pruned| pruned < topoheight) {
+            self.save_to_disk(&self.extra, PRUNED_TOPOHEIGHT_KEY, &topoheight.to_be_bytes())?;
+        }
+
+        Ok(deleted)
+    }
+
+    // Delete everything learned from the chain: balances, assets, history, nonce and sync progress
+    // Keys, contacts and custom data are kept so the wallet can sync again from the start
+    pub async fn reset_sync_state(&mut self) -> Result<()> {
+        trace!("reset sync state");
+        // Everything is deleted in one batch, so a crash can't leave a half reset wallet
+        let mut batch = KvBatch::new();
+        let trees = [
+            ("balances", &self.balances),
+            ("assets", &self.assets),
+            ("transactions", &self.transactions),
+            ("subaddress_transactions", &self.subaddress_transactions),
+            ("changes_topoheight", &self.changes_topoheight)
+        ];
+        for (name, tree) in trees {
+            let tree_name = self.cipher.hash_key(name);
+            for el in tree.iter() {
+                let (key, _) = el?;
+                batch.remove(&tree_name, key);
+            }
+        }
+
+        let extra = self.cipher.hash_key("extra");
+        for key in [NONCE_KEY, TOP_BLOCK_HASH_KEY, TOPOHEIGHT_KEY, PRUNED_TOPOHEIGHT_KEY] {
+            batch.remove(&extra, self.cipher.hash_key(key).to_vec());
+        }
+        self.inner.db.apply_batch(batch)?;
+
+        self.delete_unconfirmed_balances().await?;
+        self.balances_cache.lock().await.clear();
+        self.assets_cache.lock().await.clear();
+        self.synced_topoheight = None;
+        self.flush()
+    }
+
+    // Topoheight below which the history was pruned
+    pub fn get_pruned_topoheight(&self) -> Result<Option<u64>> {
+        trace!("get pruned topoheight");
+        if !self.contains_data(&self.extra, PRUNED_TOPOHEIGHT_KEY)? {
+            return Ok(None)
+        }
+
+        self.load_from_disk(&self.extra, PRUNED_TOPOHEIGHT_KEY).map(Some)
+    }
+
+    // Save the address of the daemon used in online mode
+    pub fn set_daemon_address(&mut self, daemon_address: &String) -> Result<()> {
+        trace!("set daemon address");
+        self.save_to_disk(&self.extra, DAEMON_ADDRESS_KEY, &daemon_address.to_bytes())
+    }
+
+    // Address of the last daemon used in online mode, if any
+    pub fn get_daemon_address(&self) -> Result<Option<String>> {
+        trace!("get daemon address");
+        if !self.contains_data(&self.extra, DAEMON_ADDRESS_KEY)? {
+            return Ok(None)
+        }
+
+        self.load_from_disk(&self.extra, DAEMON_ADDRESS_KEY).map(Some)
+    }
+
+    // Forget the daemon used in online mode
+    pub fn delete_daemon_address(&mut self) -> Result<()> {
+        trace!("delete daemon address");
+        self.delete_from_disk(&self.extra, DAEMON_ADDRESS_KEY)
+    }
+
     // Retrieve topoheight changes 
     pub fn get_topoheight_changes<'a>(&'a self) -> impl Iterator<Item = Result<(u64, Hash)>> + 'a {
         trace!("get topoheight changes");
@@ -848,8 +1498,8 @@ impl EncryptedStorage {
     pub fn get_highest_topoheight_in_changes_below(&self, max: u64) -> Result<u64> {
         trace!("get highest topoheight in changes below {}", max);
         let mut highest = 0;
-        for res in self.changes_topoheight.iter().keys() {
-            let key = res?;
+        for res in self.changes_topoheight.iter() {
+            let (key, _) = res?;
             let raw = self.cipher.decrypt_value(&key).context("Error while decrypting key from disk")?;
             let topo = u64::from_bytes(&raw)?;
             if topo > highest && topo < max {
@@ -865,9 +1515,14 @@ impl Storage {
     pub fn new(name: String) -> Result<Self> {
         let db = sled::open(name)?;
 
-        Ok(Self {
-            db
-        })
+        Ok(Self::with_store(db))
+    }
+
+    // Use a custom storage backend instead of sled
+    pub fn with_store<S: KvStore + 'static>(store: S) -> Self {
+        Self {
+            db: Box::new(store)
+        }
     }
 
     // save the encrypted form of the master key
@@ -891,6 +1546,65 @@ impl Storage {
         }
     }
 
+    // save the encrypted form of the new master key before encrypting the storage again with it
+    pub fn set_pending_master_key(&mut self, encrypted_key: &[u8]) -> Result<()> {
+        trace!("set pending master key");
+        self.db.insert(PENDING_MASTER_KEY, encrypted_key)?;
+        Ok(())
+    }
+
+    // retrieve the encrypted form of the new master key if a rotation was interrupted
+    pub fn get_pending_master_key(&self) -> Result<Option<Vec<u8>>> {
+        trace!("get pending master key");
+        self.db.get(PENDING_MASTER_KEY)
+    }
+
+    // Replace the master key by the pending one once the storage is fully encrypted with it
+    pub fn commit_pending_master_key(&mut self) -> Result<()> {
+        trace!("commit pending master key");
+        let encrypted_key = self.get_pending_master_key()?.context("No pending master key found")?;
+        self.set_encrypted_master_key(&encrypted_key)?;
+        self.db.flush()?;
+        self.db.remove(PENDING_MASTER_KEY)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    // Encrypt again the keys and values of all the trees using the new cipher
+    // Entries already encrypted with the new cipher are skipped,
+    // so calling it again after an interruption completes the rotation
+    pub fn encrypt_trees_again(&self, old_cipher: &Cipher, new_cipher: &Cipher) -> Result<()> {
+        trace!("encrypt trees again");
+        for name in self.db.tree_names()? {
+            let tree = self.db.open_tree(&name)?;
+            for el in tree.iter() {
+                let (key, value) = el?;
+                if new_cipher.decrypt_value(&value).is_ok() {
+                    continue;
+                }
+
+                let plaintext = old_cipher.decrypt_value(&value).context(format!("Error while decrypting value from tree {:?} to rotate the master key", tree.name()))?;
+                let encrypted_value = new_cipher.encrypt_value(&plaintext)?;
+
+                match old_cipher.decrypt_value(&key) {
+                    // Encrypted keys keep their deterministic nonce
+                    Ok(plaintext_key) => {
+                        let mut nonce = [0u8; Cipher::NONCE_SIZE];
+                        nonce.copy_from_slice(&key[0..Cipher::NONCE_SIZE]);
+                        let encrypted_key = new_cipher.encrypt_value_with_nonce(&plaintext_key, &nonce)?;
+                        tree.insert(&encrypted_key, &encrypted_value)?;
+                        tree.remove(&key)?;
+                    },
+                    // Hashed keys don't depend on the master key
+                    Err(_) => tree.insert(&key, &encrypted_value)?
+                }
+            }
+        }
+
+        self.db.flush()?;
+        Ok(())
+    }
+
     // set password salt used to derive the password-based key
     pub fn set_password_salt(&mut self, salt: &[u8]) -> Result<()> {
         trace!("set password salt");
@@ -918,6 +1632,23 @@ impl Storage {
         Ok(salt)
     }
 
+    // set the parameters used to derive the password-based key
+    pub fn set_kdf_params(&mut self, params: &KdfParams) -> Result<()> {
+        trace!("set kdf params");
+        self.db.insert(KDF_PARAMS_KEY, &params.to_bytes())?;
+        Ok(())
+    }
+
+    // retrieve the parameters used to derive the password-based key
+    // wallets created before they were stored are using the default ones
+    pub fn get_kdf_params(&self) -> Result<KdfParams> {
+        trace!("get kdf params");
+        match self.db.get(KDF_PARAMS_KEY)? {
+            Some(value) => Ok(KdfParams::from_bytes(&value)?),
+            None => Ok(KdfParams::default())
+        }
+    }
+
     // get the salt used for encrypted storage
     pub fn get_encrypted_storage_salt(&self) -> Result<Vec<u8>> {
         trace!("get encrypted storage salt");
@@ -934,4 +1665,428 @@ impl Storage {
         self.db.insert(SALT_KEY, salt)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use crate::{backup::WalletBackup, entry::TransferIn, kv_store::MemoryStore, test_utils::create_test_storage};
+    use super::*;
+
+    #[test]
+    fn test_contacts_by_tag() {
+        let mut storage = create_test_storage();
+
+        let mut exchange = Contact::new(KeyPair::new().get_public_key().to_address(true));
+        exchange.add_tag("Exchange");
+        exchange.add_tag("business");
+        storage.set_contact(&"exchange".to_string(), &exchange).unwrap();
+
+        let mut friend = Contact::new(KeyPair::new().get_public_key().to_address(true));
+        friend.add_tag("friend");
+        storage.set_contact(&"friend".to_string(), &friend).unwrap();
+
+        let mut partner = Contact::new(KeyPair::new().get_public_key().to_address(true));
+        partner.add_tag("BUSINESS");
+        storage.set_contact(&"partner".to_string(), &partner).unwrap();
+
+        assert_eq!(storage.get_contacts().unwrap().len(), 3);
+
+        let mut business: Vec<String> = storage.get_contacts_by_tag("Business").unwrap()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        business.sort();
+        assert_eq!(business, vec!["exchange".to_string(), "partner".to_string()]);
+
+        let friends = storage.get_contacts_by_tag("friend").unwrap();
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].1, friend);
+
+        assert!(storage.get_contacts_by_tag("unknown").unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_state_digest() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().compress();
+        let asset = Hash::new([5u8; 32]);
+        let tx = TransactionEntry::new(Hash::new([6u8; 32]), 10, EntryData::Incoming {
+            from: KeyPair::new().get_public_key().compress(),
+            transfers: vec![TransferIn::new(asset.clone(), 100, None)]
+        });
+
+        let mut storage = create_test_storage();
+        storage.set_private_key(keypair.get_private_key()).unwrap();
+        storage.set_nonce(5).unwrap();
+        storage.set_synced_topoheight(10).unwrap();
+        storage.add_asset(&asset, 8).await.unwrap();
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+        storage.set_balance_for(&asset, Balance::new(100, ciphertext)).await.unwrap();
+        storage.save_transaction(tx.get_hash(), &tx).unwrap();
+        storage.set_contact(&"alice".to_owned(), &Contact::new(KeyPair::new().get_public_key().to_address(true))).unwrap();
+        let digest = storage.get_state_digest(&public_key).unwrap();
+
+        // Exported then imported under another master key, so every key is stored differently on disk
+        let backup = WalletBackup::from_storage(&storage, Network::Mainnet).await.unwrap().encrypt("password".to_owned()).unwrap();
+        let mut restored = EncryptedStorage::new(Storage::with_store(MemoryStore::new()), &[3u8; 32], [4u8; SALT_SIZE], Network::Mainnet).unwrap();
+        WalletBackup::decrypt(&backup, "password".to_owned()).unwrap().restore_into(&mut restored).await.unwrap();
+        assert_eq!(restored.get_state_digest(&public_key).unwrap(), digest);
+
+        // Same data for another wallet
+        assert_ne!(restored.get_state_digest(&KeyPair::new().get_public_key().compress()).unwrap(), digest);
+
+        // Modified state
+        restored.set_nonce(6).unwrap();
+        assert_ne!(restored.get_state_digest(&public_key).unwrap(), digest);
+    }
+
+    #[test]
+    fn test_transactions_for_subaddress() {
+        let mut storage = create_test_storage();
+        let from = KeyPair::new().get_public_key().compress();
+        for (i, index) in [0u64, 1, 0, 1, 1].into_iter().enumerate() {
+            let entry = TransactionEntry::new(Hash::new([i as u8; 32]), i as u64, EntryData::Incoming {
+                from: from.clone(),
+                transfers: vec![TransferIn::new(Hash::zero(), 100, Some(receive_address_data(index)))]
+            });
+            storage.save_transaction(entry.get_hash(), &entry).unwrap();
+            storage.save_subaddress_transaction(&entry).unwrap();
+        }
+
+        // Not sent to any receive address
+        let entry = TransactionEntry::new(Hash::new([9u8; 32]), 9, EntryData::Incoming {
+            from,
+            transfers: vec![TransferIn::new(Hash::zero(), 100, None)]
+        });
+        storage.save_transaction(entry.get_hash(), &entry).unwrap();
+        storage.save_subaddress_transaction(&entry).unwrap();
+
+        let mut first: Vec<u64> = storage.get_transactions_for_subaddress(0).unwrap().iter().map(|e| e.get_topoheight()).collect();
+        first.sort();
+        assert_eq!(first, vec![0, 2]);
+
+        let mut second: Vec<u64> = storage.get_transactions_for_subaddress(1).unwrap().iter().map(|e| e.get_topoheight()).collect();
+        second.sort();
+        assert_eq!(second, vec![1, 3, 4]);
+
+        assert!(storage.get_transactions_for_subaddress(2).unwrap().is_empty());
+
+        // Deleted transactions are not returned anymore
+        storage.delete_transaction(&Hash::new([0u8; 32])).unwrap();
+        assert_eq!(storage.get_transactions_for_subaddress(0).unwrap().iter().map(|e| e.get_topoheight()).collect::<Vec<_>>(), vec![2]);
+        storage.delete_transactions().unwrap();
+        assert!(storage.get_transactions_for_subaddress(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_receive_address_reuse() {
+        let mut storage = create_test_storage();
+        assert_eq!(storage.next_unused_receive_address_index().unwrap(), 0);
+        // Still unused
+        assert_eq!(storage.next_unused_receive_address_index().unwrap(), 0);
+        assert!(!storage.has_incoming_with_extra_data(None).unwrap());
+
+        // Funds arrive at the suggested address
+        let entry = TransactionEntry::new(Hash::new([1u8; 32]), 10, EntryData::Incoming {
+            from: KeyPair::new().get_public_key().compress(),
+            transfers: vec![TransferIn::new(Hash::zero(), 100, Some(receive_address_data(0)))]
+        });
+        storage.save_transaction(entry.get_hash(), &entry).unwrap();
+
+        assert!(storage.has_incoming_with_extra_data(None).unwrap());
+        assert!(storage.has_incoming_with_extra_data(Some(&receive_address_data(0))).unwrap());
+        assert!(!storage.has_incoming_with_extra_data(Some(&receive_address_data(1))).unwrap());
+        assert_eq!(storage.next_unused_receive_address_index().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new();
+        let mut storage = Storage::with_store(store.clone());
+        storage.set_password_salt(&[3u8; SALT_SIZE]).unwrap();
+        storage.set_encrypted_master_key(&[4u8; 64]).unwrap();
+
+        let keypair = KeyPair::new();
+        let asset = Hash::new([5u8; 32]);
+        let tx = TransactionEntry::new(Hash::new([6u8; 32]), 10, EntryData::Incoming {
+            from: KeyPair::new().get_public_key().compress(),
+            transfers: vec![TransferIn::new(asset.clone(), 100, None)]
+        });
+        let contact = Contact::new(keypair.get_public_key().to_address(true));
+
+        let digest = {
+            let mut storage = EncryptedStorage::new(storage, &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+            storage.set_nonce(7).unwrap();
+            storage.set_synced_topoheight(10).unwrap();
+            storage.add_asset(&asset, 8).await.unwrap();
+            let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+            storage.set_balance_for(&asset, Balance::new(100, ciphertext)).await.unwrap();
+            storage.save_transaction(tx.get_hash(), &tx).unwrap();
+            storage.add_topoheight_to_changes(10, &Hash::new([7u8; 32])).unwrap();
+            storage.set_contact(&"alice".to_owned(), &contact).unwrap();
+            storage.flush().unwrap();
+            storage.get_state_digest(&keypair.get_public_key().compress()).unwrap()
+        };
+
+        // Reopen the wallet from the same backend
+        let storage = Storage::with_store(store);
+        assert_eq!(storage.get_password_salt().unwrap(), [3u8; SALT_SIZE]);
+        assert_eq!(storage.get_encrypted_master_key().unwrap(), vec![4u8; 64]);
+
+        let storage = EncryptedStorage::new(storage, &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+        assert_eq!(storage.get_nonce().unwrap(), 7);
+        assert_eq!(storage.get_synced_topoheight().unwrap(), 10);
+        assert_eq!(storage.get_assets().await.unwrap(), HashSet::from([asset.clone()]));
+        assert_eq!(storage.get_balance_for(&asset).await.unwrap().amount, 100);
+        assert_eq!(storage.get_transactions().unwrap().len(), 1);
+        assert_eq!(storage.get_topoheight_changes().collect::<Result<Vec<_>>>().unwrap(), vec![(10, Hash::new([7u8; 32]))]);
+        assert_eq!(storage.get_contact(&"alice".to_owned()).unwrap(), contact);
+        assert_eq!(storage.get_state_digest(&keypair.get_public_key().compress()).unwrap(), digest);
+    }
+
+    #[test]
+    fn test_derived_accounts() {
+        let store = MemoryStore::new();
+        let mut storage = EncryptedStorage::new(Storage::with_store(store.clone()), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+        assert_eq!(storage.get_derived_accounts().unwrap(), vec![0]);
+
+        assert!(storage.add_derived_account(5).unwrap());
+        assert!(storage.add_derived_account(2).unwrap());
+        assert!(!storage.add_derived_account(5).unwrap());
+        assert!(!storage.add_derived_account(0).unwrap());
+        storage.flush().unwrap();
+
+        // Still present after reopening the wallet
+        let storage = EncryptedStorage::new(Storage::with_store(store), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+        assert_eq!(storage.get_derived_accounts().unwrap(), vec![0, 2, 5]);
+    }
+
+    #[test]
+    fn test_daemon_address() {
+        let store = MemoryStore::new();
+        let mut storage = EncryptedStorage::new(Storage::with_store(store.clone()), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+        assert_eq!(storage.get_daemon_address().unwrap(), None);
+
+        storage.set_daemon_address(&"127.0.0.1:8080".to_owned()).unwrap();
+        storage.flush().unwrap();
+
+        // Still present after reopening the wallet
+        let mut storage = EncryptedStorage::new(Storage::with_store(store), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+        assert_eq!(storage.get_daemon_address().unwrap(), Some("127.0.0.1:8080".to_owned()));
+
+        storage.delete_daemon_address().unwrap();
+        assert_eq!(storage.get_daemon_address().unwrap(), None);
+    }
+
+    #[test]
+    fn test_prune_below_topoheight() {
+        let mut storage = create_test_storage();
+        let from = KeyPair::new().get_public_key().compress();
+        for topoheight in [10u64, 20, 30, 40] {
+            let entry = TransactionEntry::new(Hash::new([topoheight as u8; 32]), topoheight, EntryData::Incoming {
+                from: from.clone(),
+                transfers: vec![TransferIn::new(Hash::zero(), 100, None)]
+            });
+            storage.save_transaction(entry.get_hash(), &entry).unwrap();
+            storage.add_topoheight_to_changes(topoheight, &Hash::new([topoheight as u8; 32])).unwrap();
+        }
+        storage.set_nonce(4).unwrap();
+
+        // 30 is the last change below the stable topoheight 35
+        assert_eq!(storage.get_prunable_topoheight(35).unwrap(), 30);
+        assert_eq!(storage.get_prunable_topoheight(30).unwrap(), 30);
+        assert_eq!(storage.get_prunable_topoheight(5).unwrap(), 0);
+        assert_eq!(storage.get_pruned_topoheight().unwrap(), None);
+
+        assert_eq!(storage.prune_below_topoheight(30).unwrap(), 2);
+        let mut topoheights: Vec<u64> = storage.get_transactions().unwrap().iter().map(|e| e.get_topoheight()).collect();
+        topoheights.sort();
+        assert_eq!(topoheights, vec![30, 40]);
+
+        // The reconciliation anchor is still available
+        let mut changes: Vec<u64> = storage.get_topoheight_changes().map(|res| res.unwrap().0).collect();
+        changes.sort();
+        assert_eq!(changes, vec![30, 40]);
+        assert_eq!(storage.get_prunable_topoheight(35).unwrap(), 30);
+
+        assert_eq!(storage.get_nonce().unwrap(), 4);
+        assert_eq!(storage.get_pruned_topoheight().unwrap(), Some(30));
+    }
+
+    #[test]
+    fn test_delete_transactions_in_range() {
+        let mut storage = create_test_storage();
+        let from = KeyPair::new().get_public_key().compress();
+        for topoheight in [10u64, 20, 30, 40] {
+            let entry = TransactionEntry::new(Hash::new([topoheight as u8; 32]), topoheight, EntryData::Incoming {
+                from: from.clone(),
+                transfers: vec![TransferIn::new(Hash::zero(), 100, None)]
+            });
+            storage.save_transaction(entry.get_hash(), &entry).unwrap();
+        }
+
+        storage.delete_transactions_in_range(20, 30).unwrap();
+        let mut topoheights: Vec<u64> = storage.get_transactions().unwrap().iter().map(|e| e.get_topoheight()).collect();
+        topoheights.sort();
+        assert_eq!(topoheights, vec![10, 40]);
+
+        // Single topoheight range
+        storage.delete_transactions_in_range(40, 40).unwrap();
+        assert_eq!(storage.get_transactions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_transactions_history_filter() {
+        let mut storage = create_test_storage();
+        let asset = Hash::new([1u8; 32]);
+        let from = KeyPair::new().get_public_key().compress();
+        let incoming = TransactionEntry::new(Hash::new([1u8; 32]), 10, EntryData::Incoming {
+            from,
+            transfers: vec![TransferIn::new(asset.clone(), 100, None)]
+        });
+        let burn = TransactionEntry::new(Hash::new([2u8; 32]), 20, EntryData::Burn { asset: asset.clone(), amount: 5 });
+        let coinbase = TransactionEntry::new(Hash::new([3u8; 32]), 30, EntryData::Coinbase { reward: 50 });
+        for entry in [&incoming, &burn, &coinbase] {
+            storage.save_transaction(entry.get_hash(), entry).unwrap();
+        }
+
+        let mut hashes: Vec<Hash> = storage.get_transactions_for_asset(&asset).unwrap().iter().map(|e| e.get_hash().clone()).collect();
+        hashes.sort();
+        assert_eq!(hashes, vec![incoming.get_hash().clone(), burn.get_hash().clone()]);
+        assert_eq!(storage.get_transactions_for_asset(&XELIS_ASSET).unwrap().len(), 1);
+        assert!(storage.get_transactions_for_asset(&Hash::new([9u8; 32])).unwrap().is_empty());
+
+        assert_eq!(storage.get_transactions_in_range(15, 30).unwrap().len(), 2);
+        // Empty ranges
+        assert!(storage.get_transactions_in_range(11, 19).unwrap().is_empty());
+        assert!(storage.get_transactions_in_range(30, 10).unwrap().is_empty());
+
+        // Both criteria at once
+        let filter = HistoryFilter {
+            asset: Some(asset),
+            min_topoheight: Some(15),
+            max_topoheight: None
+        };
+        let entries = storage.get_history(&filter).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].get_hash(), burn.get_hash());
+    }
+
+    #[tokio::test]
+    async fn test_capture_and_restore_state() {
+        let mut storage = create_test_storage();
+        let keypair = KeyPair::new();
+        let asset = Hash::new([1u8; 32]);
+        storage.set_nonce(3).unwrap();
+        storage.set_synced_topoheight(10).unwrap();
+        storage.add_asset(&asset, 8).await.unwrap();
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+        storage.set_balance_for(&asset, Balance::new(100, ciphertext)).await.unwrap();
+
+        let snapshot = storage.capture_state().await.unwrap();
+
+        // A transaction is built and the wallet synced further
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(40u64));
+        storage.set_unconfirmed_balance_for(asset.clone(), Balance::new(40, ciphertext)).await.unwrap();
+        storage.set_nonce(4).unwrap();
+        storage.set_synced_topoheight(20).unwrap();
+        assert_eq!(storage.get_unconfirmed_balance_for(&asset).await.unwrap().amount, 40);
+
+        // Crash and recover
+        storage.restore_state(snapshot).await.unwrap();
+        assert_eq!(storage.get_nonce().unwrap(), 3);
+        assert_eq!(storage.get_synced_topoheight().unwrap(), 10);
+        assert_eq!(storage.get_unconfirmed_balance_for(&asset).await.unwrap().amount, 100);
+        assert_eq!(storage.get_balance_for(&asset).await.unwrap().amount, 100);
+    }
+
+    #[tokio::test]
+    async fn test_asset_label() {
+        let mut storage = create_test_storage();
+        let asset = Hash::new([1u8; 32]);
+        storage.add_asset(&asset, 2).await.unwrap();
+
+        assert_eq!(storage.get_asset_label(&asset).unwrap(), None);
+        assert_eq!(storage.format_amount(&asset, 150).unwrap(), format!("1.50 {}", asset));
+
+        storage.set_asset_label(&asset, &"my staking token".to_owned()).unwrap();
+        assert_eq!(storage.get_asset_label(&asset).unwrap(), Some("my staking token".to_owned()));
+        assert_eq!(storage.format_amount(&asset, 150).unwrap(), "1.50 my staking token");
+        // Asset is still available by its hash with its decimals
+        assert!(storage.get_assets().await.unwrap().contains(&asset));
+        assert_eq!(storage.get_asset_decimals(&asset).unwrap(), 2);
+
+        storage.delete_asset_label(&asset).unwrap();
+        assert_eq!(storage.get_asset_display_name(&asset).unwrap(), asset.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_delete_balance_for() {
+        let mut storage = create_test_storage();
+        let keypair = KeyPair::new();
+        let first = Hash::new([1u8; 32]);
+        let second = Hash::new([2u8; 32]);
+        for asset in [&first, &second] {
+            let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+            storage.set_balance_for(asset, Balance::new(100, ciphertext)).await.unwrap();
+        }
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(40u64));
+        storage.set_unconfirmed_balance_for(first.clone(), Balance::new(40, ciphertext)).await.unwrap();
+
+        storage.delete_balance_for(&first).await.unwrap();
+        assert!(!storage.has_balance_for(&first).await.unwrap());
+        assert!(storage.get_balance_for(&first).await.is_err());
+        assert!(storage.get_unconfirmed_balance_for(&first).await.is_err());
+
+        // Other assets keep their cached balance
+        assert!(storage.has_balance_for(&second).await.unwrap());
+        assert_eq!(storage.get_balance_for(&second).await.unwrap().amount, 100);
+        assert!(storage.has_any_balance().await.unwrap());
+    }
+
+    #[test]
+    fn test_balance_type() {
+        let keypair = KeyPair::new();
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+
+        let balance = Balance::with_type(100, ciphertext.clone(), BalanceType::Both, Some(40));
+        assert_eq!(balance.spendable_amount(), 40);
+        let decoded = Balance::from_bytes(&balance.to_bytes()).unwrap();
+        assert_eq!(decoded.balance_type, BalanceType::Both);
+        assert_eq!(decoded.output_amount, Some(40));
+        assert_eq!(decoded.spendable_amount(), 40);
+
+        // Only a version with both inputs and outputs has unspendable funds
+        assert_eq!(Balance::with_type(100, ciphertext.clone(), BalanceType::Output, Some(40)).spendable_amount(), 100);
+        assert_eq!(Balance::with_type(100, ciphertext.clone(), BalanceType::Both, None).spendable_amount(), 100);
+
+        // Balances stored without their type are inputs
+        let mut writer = Writer::new();
+        100u64.write(&mut writer);
+        ciphertext.write(&mut writer);
+        let decoded = Balance::from_bytes(&writer.bytes()).unwrap();
+        assert_eq!(decoded.balance_type, BalanceType::Input);
+        assert_eq!(decoded.spendable_amount(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_get_all_balances() {
+        let mut storage = create_test_storage();
+        let keypair = KeyPair::new();
+        let assets: Vec<Hash> = (1..4u8).map(|i| Hash::new([i; 32])).collect();
+        assert!(storage.get_all_balances().await.unwrap().is_empty());
+
+        for (i, asset) in assets.iter().enumerate() {
+            storage.add_asset(asset, 8).await.unwrap();
+            // Last asset was seen but never received any funds
+            if i < 2 {
+                let amount = (i as u64 + 1) * 100;
+                let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(amount));
+                storage.set_balance_for(asset, Balance::new(amount, ciphertext)).await.unwrap();
+            }
+        }
+
+        let balances = storage.get_all_balances().await.unwrap();
+        assert_eq!(balances, vec![(assets[0].clone(), 100), (assets[1].clone(), 200), (assets[2].clone(), 0)]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use log::{debug, warn};
+use xelis_common::{
+    api::wallet::SupplyEvent,
+    crypto::Hash
+};
+
+// Rates are expressed in basis points (1/10000)
+const BASIS_POINTS: u128 = 10_000;
+
+// Keep track of the circulating supply of the assets requested by the user
+// An alert is generated when the supply grows faster than the configured rate per block
+pub struct SupplyTracker {
+    // Maximum supply increase allowed per block, in basis points of the previous supply
+    max_rate_per_block: u64,
+    // Last observation for each tracked asset (topoheight, supply)
+    supplies: HashMap<Hash, Option<(u64, u64)>>
+}
+
+impl SupplyTracker {
+    pub fn new(max_rate_per_block: u64) -> Self {
+        Self {
+            max_rate_per_block,
+            supplies: HashMap::new()
+        }
+    }
+
+    // Start tracking an asset, returns false if it was already tracked
+    pub fn track(&mut self, asset: Hash) -> bool {
+        if self.supplies.contains_key(&asset) {
+            return false
+        }
+
+        self.supplies.insert(asset, None);
+        true
+    }
+
+    // Stop tracking an asset, returns false if it wasn't tracked
+    pub fn untrack(&mut self, asset: &Hash) -> bool {
+        self.supplies.remove(asset).is_some()
+    }
+
+    // Check if the asset supply is tracked
+    pub fn is_tracked(&self, asset: &Hash) -> bool {
+        self.supplies.contains_key(asset)
+    }
+
+    // Configure the maximum rate per block before an alert is generated
+    pub fn set_max_rate_per_block(&mut self, max_rate_per_block: u64) {
+        self.max_rate_per_block = max_rate_per_block;
+    }
+
+    pub fn get_max_rate_per_block(&self) -> u64 {
+        self.max_rate_per_block
+    }
+
+    // Record the supply of an asset at a topoheight
+    // Returns an event if the increase since the last observation is above the allowed rate
+    pub fn record(&mut self, asset: &Hash, supply: u64, topoheight: u64) -> Option<SupplyEvent> {
+        let max_rate_per_block = self.max_rate_per_block;
+        let entry = self.supplies.get_mut(asset)?;
+
+        let event = match entry.as_ref() {
+            // Only compare against a lower topoheight, otherwise a DAG reorg happened
+            // and the previous observation can't be trusted anymore
+            Some((previous_topoheight, previous_supply)) if *previous_topoheight < topoheight => {
+                let previous_supply = *previous_supply;
+                if supply > previous_supply {
+                    let blocks = (topoheight - previous_topoheight) as u128;
+                    let increase = (supply - previous_supply) as u128;
+                    let allowed = previous_supply as u128 * max_rate_per_block as u128 * blocks;
+                    if increase * BASIS_POINTS > allowed {
+                        warn!("Supply of asset {} increased from {} to {} at topoheight {}", asset, previous_supply, supply, topoheight);
+                        Some(SupplyEvent {
+                            asset: asset.clone(),
+                            old_supply: previous_supply,
+                            new_supply: supply,
+                            topoheight
+                        })
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                }
+            },
+            Some((previous_topoheight, _)) => {
+                debug!("Supply observation for {} at topoheight {} is not above {}, resetting it", asset, topoheight, previous_topoheight);
+                None
+            },
+            None => None
+        };
+
+        *entry = Some((topoheight, supply));
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::config::XELIS_ASSET;
+    use super::*;
+
+    #[test]
+    fn test_supply_jump() {
+        // 0.1% per block
+        let mut tracker = SupplyTracker::new(10);
+        assert!(tracker.track(XELIS_ASSET));
+        assert!(!tracker.track(XELIS_ASSET));
+
+        // First observation is only recorded
+        assert!(tracker.record(&XELIS_ASSET, 1_000_000, 10).is_none());
+        // Regular emission
+        assert!(tracker.record(&XELIS_ASSET, 1_000_500, 11).is_none());
+        // Allowed over several blocks
+        assert!(tracker.record(&XELIS_ASSET, 1_003_000, 14).is_none());
+
+        // Unexpected jump
+        let event = tracker.record(&XELIS_ASSET, 2_000_000, 15).expect("supply alert");
+        assert_eq!(event.old_supply, 1_003_000);
+        assert_eq!(event.new_supply, 2_000_000);
+        assert_eq!(event.topoheight, 15);
+
+        // Untracked assets are ignored
+        let asset = Hash::new([1u8; 32]);
+        assert!(tracker.record(&asset, 1, 1).is_none());
+        assert!(tracker.record(&asset, 1_000, 2).is_none());
+    }
+
+    #[test]
+    fn test_supply_gap() {
+        // 0.1% per block
+        let mut tracker = SupplyTracker::new(10);
+        tracker.track(XELIS_ASSET);
+        assert!(tracker.record(&XELIS_ASSET, 1_000_000, 10).is_none());
+
+        // Blocks 11 to 19 weren't observed, up to 1% is allowed over the gap
+        let event = tracker.record(&XELIS_ASSET, 1_020_000, 20).expect("supply alert");
+        assert_eq!(event.old_supply, 1_000_000);
+        assert!(tracker.record(&XELIS_ASSET, 1_030_000, 30).is_none());
+
+        // Lower topoheight resets the observation without alerting
+        assert!(tracker.record(&XELIS_ASSET, 2_000_000, 25).is_none());
+        assert!(tracker.record(&XELIS_ASSET, 2_000_100, 26).is_none());
+    }
+}
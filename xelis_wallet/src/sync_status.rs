@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Progress of the wallet sync against the daemon
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SyncStatus {
+    // Topoheight the wallet is synced to
+    pub current_topoheight: u64,
+    // Topoheight of the daemon
+    pub target_topoheight: u64,
+    // Exponentially-weighted moving average of the blocks applied per second
+    pub blocks_per_second_ewma: f64
+}
+
+impl SyncStatus {
+    pub fn remaining_blocks(&self) -> u64 {
+        self.target_topoheight.saturating_sub(self.current_topoheight)
+    }
+
+    // Estimated time to reach the target topoheight, None if no rate is known yet
+    pub fn eta(&self) -> Option<Duration> {
+        if self.blocks_per_second_ewma <= 0.0 {
+            return None
+        }
+
+        Some(Duration::from_secs_f64(self.remaining_blocks() as f64 / self.blocks_per_second_ewma))
+    }
+}
+
+// Track the sync rate as blocks are applied
+pub struct SyncRateTracker {
+    // Weight of the newest sample in the moving average, between 0 and 1
+    alpha: f64,
+    current_topoheight: u64,
+    target_topoheight: u64,
+    blocks_per_second: f64,
+    // Blocks applied since the last sample
+    pending_blocks: u64,
+    // Instant of the last sample
+    last_sample: Option<Instant>
+}
+
+impl SyncRateTracker {
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha,
+            current_topoheight: 0,
+            target_topoheight: 0,
+            blocks_per_second: 0.0,
+            pending_blocks: 0,
+            last_sample: None
+        }
+    }
+
+    // Update the synced topoheight and the daemon one
+    pub fn set_topoheights(&mut self, current_topoheight: u64, target_topoheight: u64) {
+        self.current_topoheight = current_topoheight;
+        self.target_topoheight = target_topoheight;
+    }
+
+    // Blocks left to sync below the daemon topoheight
+    // The synced topoheight only moves forward, so scanning the versions of another asset doesn't move it back
+    pub fn set_remaining_blocks(&mut self, remaining: u64) {
+        let current_topoheight = self.target_topoheight.saturating_sub(remaining);
+        self.current_topoheight = self.current_topoheight.max(current_topoheight);
+    }
+
+    // A block has been applied by the wallet
+    pub fn record_block(&mut self) {
+        self.record_block_at(Instant::now());
+    }
+
+    fn record_block_at(&mut self, now: Instant) {
+        let Some(last_sample) = self.last_sample else {
+            // First block only starts the clock
+            self.last_sample = Some(now);
+            return
+        };
+
+        self.pending_blocks += 1;
+        let elapsed = now.saturating_duration_since(last_sample).as_secs_f64();
+        // Blocks applied at the same instant are part of the next sample
+        if elapsed <= 0.0 {
+            return
+        }
+
+        let rate = self.pending_blocks as f64 / elapsed;
+        self.blocks_per_second = if self.blocks_per_second == 0.0 {
+            rate
+        } else {
+            self.alpha * rate + (1.0 - self.alpha) * self.blocks_per_second
+        };
+        self.pending_blocks = 0;
+        self.last_sample = Some(now);
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        SyncStatus {
+            current_topoheight: self.current_topoheight,
+            target_topoheight: self.target_topoheight,
+            blocks_per_second_ewma: self.blocks_per_second
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_rate() {
+        let mut tracker = SyncRateTracker::new(0.2);
+        tracker.set_topoheights(0, 1000);
+        assert_eq!(tracker.status().blocks_per_second_ewma, 0.0);
+        assert!(tracker.status().eta().is_none());
+
+        // 10 blocks per second
+        let start = Instant::now();
+        for i in 0..50 {
+            tracker.record_block_at(start + Duration::from_millis(100 * i));
+        }
+
+        let rate = tracker.status().blocks_per_second_ewma;
+        assert!((rate - 10.0).abs() < 0.01);
+
+        // Slowing down to 5 blocks per second moves the average toward it
+        for i in 0..10 {
+            tracker.record_block_at(start + Duration::from_millis(4900 + 200 * (i + 1)));
+        }
+        let rate = tracker.status().blocks_per_second_ewma;
+        assert!(rate > 5.0 && rate < 10.0);
+
+        tracker.set_topoheights(500, 1000);
+        let status = tracker.status();
+        assert_eq!(status.remaining_blocks(), 500);
+        let eta = status.eta().unwrap();
+        assert!(eta > Duration::from_secs(50) && eta < Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_sync_rate_same_instant() {
+        let mut tracker = SyncRateTracker::new(0.2);
+        let start = Instant::now();
+        tracker.record_block_at(start);
+        // Both blocks are counted in the next sample
+        tracker.record_block_at(start);
+        tracker.record_block_at(start + Duration::from_secs(1));
+        assert_eq!(tracker.status().blocks_per_second_ewma, 2.0);
+    }
+
+    #[test]
+    fn test_sync_remaining_blocks() {
+        let mut tracker = SyncRateTracker::new(0.2);
+        tracker.set_topoheights(100, 1000);
+
+        tracker.set_remaining_blocks(600);
+        assert_eq!(tracker.status().current_topoheight, 400);
+        assert_eq!(tracker.status().remaining_blocks(), 600);
+
+        // Another asset is scanned from a higher version
+        tracker.set_remaining_blocks(800);
+        assert_eq!(tracker.status().current_topoheight, 400);
+
+        tracker.set_remaining_blocks(0);
+        assert_eq!(tracker.status().remaining_blocks(), 0);
+
+        // Never above the daemon topoheight
+        tracker.set_remaining_blocks(u64::MAX);
+        assert_eq!(tracker.status().current_topoheight, 1000);
+    }
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+use xelis_common::{
+    crypto::{elgamal::PublicKey, KeyPair},
+    network::Network
+};
+use crate::{
+    config::SALT_SIZE,
+    kv_store::MemoryStore,
+    storage::{EncryptedStorage, Storage},
+    wallet::{PrecomputedTables, Wallet}
+};
+
+// Encrypted storage kept in memory, using a fixed master key and salt
+pub(crate) fn create_test_storage() -> EncryptedStorage {
+    EncryptedStorage::new(Storage::with_store(MemoryStore::new()), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap()
+}
+
+// Mainnet wallet on top of the storage, small precomputed tables are enough for tests
+pub(crate) fn create_test_wallet_with_storage(storage: EncryptedStorage, keypair: KeyPair) -> Arc<Wallet> {
+    Wallet::new(storage, keypair, Network::Mainnet, Arc::new(PrecomputedTables::new(8)))
+}
+
+// Mainnet wallet using an in-memory storage
+pub(crate) fn create_test_wallet(keypair: KeyPair) -> Arc<Wallet> {
+    create_test_wallet_with_storage(create_test_storage(), keypair)
+}
+
+// Watch-only mainnet wallet of this public key using an in-memory storage
+pub(crate) fn create_test_watch_only_wallet(public_key: PublicKey) -> Arc<Wallet> {
+    Wallet::new_watch_only(create_test_storage(), public_key, Network::Mainnet, Arc::new(PrecomputedTables::new(8)))
+}
@@ -1,6 +1,6 @@
 use std::collections::{HashMap, HashSet};
 use xelis_common::{
-    account::CiphertextCache,
+    account::{BalanceType, CiphertextCache},
     crypto::{elgamal::Ciphertext, Hash, PublicKey},
     transaction::{builder::{AccountState, FeeHelper}, Reference}
 };
@@ -69,6 +69,11 @@ impl TransactionBuilderState {
         self.balances.insert(asset, balance);
     }
 
+    // Get the amount of the balance that can be spent by the transaction
+    pub fn get_spendable_balance(&self, asset: &Hash) -> Result<u64, WalletError> {
+        self.balances.get(asset).map(|b| b.spendable_amount()).ok_or_else(|| WalletError::BalanceNotFound(asset.clone()))
+    }
+
     pub fn set_registered_keys(&mut self, registered_keys: HashSet<PublicKey>) {
         self.inner.registered_keys = registered_keys;
     }
@@ -113,10 +118,16 @@ impl AccountState for TransactionBuilderState {
     }
 
     fn update_account_balance(&mut self, asset: &Hash, new_balance: u64, ciphertext: Ciphertext) -> Result<(), Self::Error> {
-        self.balances.insert(asset.clone(), Balance {
-            amount: new_balance,
-            ciphertext: CiphertextCache::Decompressed(ciphertext)
-        });
+        // The spent amount is also removed from the spendable part of the balance
+        let (balance_type, output_amount) = match self.balances.get(asset) {
+            Some(previous) => {
+                let spent = previous.amount.saturating_sub(new_balance);
+                (previous.balance_type, previous.output_amount.map(|amount| amount.saturating_sub(spent)))
+            },
+            None => (BalanceType::Input, None)
+        };
+
+        self.balances.insert(asset.clone(), Balance::with_type(new_balance, CiphertextCache::Decompressed(ciphertext), balance_type, output_amount));
         Ok(())
     }
 
@@ -1,10 +1,12 @@
 use std::{
-    fs::{create_dir_all, File},
+    collections::{HashMap, HashSet},
+    fs::{create_dir_all, remove_dir_all, rename, File},
     io::{Read, Write},
     path::Path,
-    sync::Arc
+    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock},
+    time::Duration
 };
-use anyhow::{Error, Context};
+use anyhow::{anyhow, Error, Context};
 use serde::Serialize;
 use tokio::sync::{
     broadcast::{
@@ -15,10 +17,12 @@ use tokio::sync::{
     RwLock
 };
 use xelis_common::{
+    account::{BalanceType, CiphertextCache},
     api::{
         wallet::{
             BalanceChanged,
             NotifyEvent,
+            SupplyEvent,
             TransactionEntry
         },
         DataElement
@@ -28,22 +32,29 @@ use xelis_common::{
         ecdlp::{self, ECDLPTablesFileView},
         elgamal::{Ciphertext, DecryptHandle, PublicKey as DecompressedPublicKey},
         Address,
+        AddressType,
         Hash,
+        Hashable,
         KeyPair,
+        PrivateKey,
         PublicKey,
         Signature
     },
+    config::{MAX_TRANSACTION_SIZE, XELIS_ASSET},
     network::Network,
-    serializer::Serializer,
+    serializer::{Reader, ReaderError, Serializer, Writer},
     transaction::{
         aead::{self, AEADCipher},
         builder::{
             FeeBuilder,
+            GenerationError,
             TransactionBuilder,
-            TransactionTypeBuilder
+            TransactionTypeBuilder,
+            TransferBuilder
         },
         Reference,
-        Transaction
+        Transaction,
+        TransactionType
     },
     utils::{
         format_coin,
@@ -53,34 +64,58 @@ use xelis_common::{
 use crate::{
     cipher::Cipher,
     config::{
-        PASSWORD_ALGORITHM,
+        DEFAULT_BALANCE_CACHE_TTL,
+        DEFAULT_KDF_ITERATIONS,
+        DEFAULT_KDF_MEMORY,
+        DEFAULT_KDF_PARALLELISM,
+        DEFAULT_SUPPLY_ALERT_RATE,
+        MAX_SWEEP_FEE_ROUNDS,
+        PRUNE_SAFETY_MARGIN,
         PASSWORD_HASH_SIZE,
-        SALT_SIZE
+        SALT_SIZE,
+        SIGNED_MESSAGE_DOMAIN,
+        SYNC_RATE_EWMA_ALPHA
     },
+    contact::Contact,
     daemon_api::DaemonAPI,
-    mnemonics,
+    entry,
+    mnemonics::{self, Language},
     network_handler::{
         NetworkError,
         NetworkHandler,
         SharedNetworkHandler
     },
+    payment_request::PaymentRequest,
+    rescan_guard::RescanState,
     storage::{
+        receive_address_data,
+        Balance,
         EncryptedStorage,
         Storage
     },
+    backup::WalletBackup,
+    balance_cache::BalanceCache,
+    balance_proof::{verify_balance_proof, BalanceProof},
+    supply_tracker::SupplyTracker,
+    sync_status::{SyncRateTracker, SyncStatus},
     transaction_builder::{EstimateFeesState, TransactionBuilderState}
 };
+#[cfg(any(test, feature = "test_utils"))]
+use crate::storage::WalletStateSnapshot;
 use chacha20poly1305::{
     aead::OsRng,
     Error as CryptoError
 };
 use rand::RngCore;
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
+use argon2::{Algorithm, Argon2, Params, Version};
 use log::{
     trace,
     debug,
     error,
-    info
+    info,
+    warn
 };
 
 #[cfg(feature = "api_server")]
@@ -126,6 +161,8 @@ pub enum WalletError {
     TooManyTx,
     #[error("Transaction owner is the receiver")]
     TxOwnerIsReceiver,
+    #[error("Address network mismatch, expected mainnet: {}, got mainnet: {}", _0, _1)]
+    NetworkMismatch(bool, bool),
     #[error("Error from crypto: {}", _0)]
     CryptoError(CryptoError),
     #[error("Unexpected error on database: {}", _0)]
@@ -148,6 +185,8 @@ pub enum WalletError {
     NotEnoughFunds(u64, u64, u8, Hash),
     #[error("Your wallet don't have enough funds to pay fees: expected {} but have only {}", format_xelis(*_0), format_xelis(*_1))]
     NotEnoughFundsForFee(u64, u64),
+    #[error("Your wallet don't have enough funds for this transaction: {}", format_shortfalls(_0))]
+    InsufficientFundsMulti(Vec<(Hash, u64, u64)>),
     #[error("Invalid address params")]
     InvalidAddressParams,
     #[error("Invalid extra data in this transaction, expected maximum {} bytes but got {} bytes", _0, _1)]
@@ -156,10 +195,26 @@ pub enum WalletError {
     NotOnlineMode,
     #[error("Wallet is already in online mode")]
     AlreadyOnlineMode,
+    #[error("No daemon address was saved, online mode can't be resumed")]
+    NoSavedDaemon,
     #[error("Asset is already present on disk")]
     AssetAlreadyRegistered,
     #[error("Topoheight is too high to rescan")]
     RescanTopoheightTooHigh,
+    #[error("A rescan is in progress")]
+    RescanInProgress,
+    #[error("Transaction is too large: {} bytes, maximum is {} bytes", _0, _1)]
+    TransactionTooLarge(usize, usize),
+    #[error("Invalid seed length, expected {} words but got {}", _0, _1)]
+    InvalidSeedLength(usize, usize),
+    #[error("Seed words are not part of any supported language")]
+    UnknownSeedLanguage,
+    #[error("Invalid seed word at position {}", _0)]
+    InvalidSeedWord(usize),
+    #[error("Invalid seed checksum")]
+    InvalidSeedChecksum,
+    #[error("Topoheight {} is too high to prune, maximum is {}", _0, _1)]
+    PruneTopoheightTooHigh(u64, u64),
     #[error(transparent)]
     Any(#[from] Error),
     #[error("No API Server is running")]
@@ -178,10 +233,44 @@ pub enum WalletError {
     NetworkError(#[from] NetworkError),
     #[error("Balance for asset {} was not found", _0)]
     BalanceNotFound(Hash),
+    #[error("Fees can't be paid in asset {}", _0)]
+    FeeAssetUnsupported(Hash),
+    #[error("Contact {} was not found", _0)]
+    ContactNotFound(String),
+    #[error("A contact already exists with label {}", _0)]
+    ContactAlreadyExists(String),
+    #[error("Nonce overflow, no more transactions can be created")]
+    NonceOverflow,
+    #[error("Inconsistent balance ciphertexts")]
+    InconsistentBalanceCiphertexts,
+    #[error("Balance ciphertext doesn't decrypt to the stored amount")]
+    BalanceCiphertextMismatch,
+    #[error("Balance proof doesn't lead to the balances merkle hash")]
+    InvalidBalanceProof,
     #[error("No result found for ciphertext")]
     CiphertextDecode,
     #[error(transparent)]
     AEADCipherFormatError(#[from] aead::CipherFormatError),
+    #[error("No valid key can be derived for account index {}", _0)]
+    InvalidAccountIndex(u32),
+    #[error("Wallet is in watch-only mode, private key is not available")]
+    WatchOnly,
+    #[error("Fee multiplier {} is too low, minimum is 1", _0)]
+    FeeTooLow(f64),
+    #[error("Invalid rescan range, topoheight {} is above {}", _0, _1)]
+    InvalidRescanRange(u64, u64),
+    #[error("Nonce mismatch, expected at least {} but transaction has {}", _0, _1)]
+    NonceMismatch(u64, u64),
+    #[error("Invalid signed transaction bytes: {}", _0)]
+    InvalidRawTransaction(ReaderError),
+    #[error("New KDF parameters can't be weaker than the current ones")]
+    WeakerKdfParams,
+    #[error("Unsupported wallet backup version {}", _0)]
+    UnsupportedBackupVersion(u8),
+    #[error("Backup was made for network {} but {} was requested", _0, _1)]
+    BackupNetworkMismatch(Network, Network),
+    #[error("Wallet is locked, unlock it with its password first")]
+    Locked,
 }
 
 #[derive(Serialize, Clone)]
@@ -210,7 +299,14 @@ pub enum Event {
     // Wallet is now in online mode
     Online,
     // Wallet is now in offline mode
-    Offline
+    Offline,
+    // A tracked asset supply increased above the configured rate
+    SupplyAlert(SupplyEvent),
+    // A block was processed while syncing the history
+    // Blocks are processed from the highest topoheight to the lowest
+    SyncProgress {
+        topoheight: u64
+    }
 }
 
 impl Event {
@@ -222,7 +318,9 @@ impl Event {
             Event::NewAsset(_) => NotifyEvent::NewAsset,
             Event::Rescan { .. } => NotifyEvent::Rescan,
             Event::Online => NotifyEvent::Online,
-            Event::Offline => NotifyEvent::Offline
+            Event::Offline => NotifyEvent::Offline,
+            Event::SupplyAlert(_) => NotifyEvent::SupplyAlert,
+            Event::SyncProgress { .. } => NotifyEvent::SyncProgress
         }
     }
 
@@ -284,7 +382,13 @@ pub struct Wallet {
     // Encrypted Wallet Storage
     storage: RwLock<EncryptedStorage>,
     // Private & Public key linked for this wallet
-    keypair: KeyPair,
+    // None while the wallet is locked, and always in watch-only mode
+    // It's read from sync functions, so it's behind a std lock that is never held across an await
+    keypair: StdRwLock<Option<Arc<KeyPair>>>,
+    // Only the public key of the watched address is known: nothing can be signed nor decrypted
+    watch_only: bool,
+    // Decompressed public key
+    decompressed_public_key: DecompressedPublicKey,
     // Compressed public key
     public_key: PublicKey,
     // network handler for online mode to keep wallet synced
@@ -300,15 +404,342 @@ pub struct Wallet {
     // Event broadcaster
     event_broadcaster: Mutex<Option<BroadcastSender<Event>>>,
     // Precomputed tables byte array
-    precomputed_tables: PrecomputedTablesShared
+    precomputed_tables: PrecomputedTablesShared,
+    // Assets supply tracked to detect unexpected inflation
+    supply_tracker: Mutex<SupplyTracker>,
+    // Prevent storage mutations during a rescan
+    rescan_state: RescanState,
+    // Plaintext balances recently read from the storage
+    balance_cache: RwLock<BalanceCache>,
+    // Sync progress and rate of the network handler
+    sync_tracker: StdMutex<SyncRateTracker>
+}
+
+// Argon2id parameters used to derive the password-based key
+// They are stored with the password salt so the cost can be increased later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    // Memory cost in KiB
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::new(DEFAULT_KDF_MEMORY, DEFAULT_KDF_ITERATIONS, DEFAULT_KDF_PARALLELISM)
+    }
+}
+
+impl KdfParams {
+    pub fn new(memory: u32, iterations: u32, parallelism: u32) -> Self {
+        Self {
+            memory,
+            iterations,
+            parallelism
+        }
+    }
+
+    // Returns true if none of the costs is lower than the other parameters
+    pub fn is_at_least(&self, other: &KdfParams) -> bool {
+        self.memory >= other.memory && self.iterations >= other.iterations && self.parallelism >= other.parallelism
+    }
+
+    fn algorithm(&self) -> Result<Argon2<'static>, WalletError> {
+        let params = Params::new(self.memory, self.iterations, self.parallelism, Some(PASSWORD_HASH_SIZE))
+            .map_err(|e| WalletError::AlgorithmHashingError(e.to_string()))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
 }
 
-pub fn hash_password(password: String, salt: &[u8]) -> Result<[u8; PASSWORD_HASH_SIZE], WalletError> {
-    let mut output = [0; PASSWORD_HASH_SIZE];
-    PASSWORD_ALGORITHM.hash_password_into(password.as_bytes(), salt, &mut output).map_err(|e| WalletError::AlgorithmHashingError(e.to_string()))?;
+impl Serializer for KdfParams {
+    fn write(&self, writer: &mut Writer) {
+        self.memory.write(writer);
+        self.iterations.write(writer);
+        self.parallelism.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(Self::new(u32::read(reader)?, u32::read(reader)?, u32::read(reader)?))
+    }
+
+    fn size(&self) -> usize {
+        self.memory.size() + self.iterations.size() + self.parallelism.size()
+    }
+}
+
+// Both the password and its hash are wiped from memory once dropped
+pub fn hash_password(password: String, salt: &[u8], params: &KdfParams) -> Result<Zeroizing<[u8; PASSWORD_HASH_SIZE]>, WalletError> {
+    let password = Zeroizing::new(password);
+    let mut output = Zeroizing::new([0; PASSWORD_HASH_SIZE]);
+    params.algorithm()?.hash_password_into(password.as_bytes(), salt, output.as_mut_slice()).map_err(|e| WalletError::AlgorithmHashingError(e.to_string()))?;
     Ok(output)
 }
 
+// Message prefixed by its domain before being signed
+fn signed_message_bytes(message: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(SIGNED_MESSAGE_DOMAIN.len() + message.len());
+    bytes.extend_from_slice(SIGNED_MESSAGE_DOMAIN);
+    bytes.extend_from_slice(message);
+    bytes
+}
+
+// Verify that a balance received from a peer is consistent with its type
+// An output balance is only set when the version has both incoming and outgoing funds,
+// and every ciphertext must decrypt with our key to the plaintext we are about to store
+// This protects against a peer sending mismatched or garbage ciphertexts
+pub fn verify_balance_ciphertexts(keypair: &KeyPair, balance: &CiphertextCache, amount: u64, output_balance: Option<&CiphertextCache>, output_amount: Option<u64>, balance_type: BalanceType) -> Result<(), WalletError> {
+    match (balance_type, output_balance.is_some()) {
+        (BalanceType::Both, true) | (BalanceType::Input, false) | (BalanceType::Output, false) => {},
+        (balance_type, has_output) => {
+            debug!("Balance type {:?} is inconsistent with output balance presence ({})", balance_type, has_output);
+            return Err(WalletError::InconsistentBalanceCiphertexts)
+        }
+    }
+
+    let output = match (output_balance, output_amount) {
+        (Some(ciphertext), Some(amount)) => Some((ciphertext, amount)),
+        (None, None) => None,
+        _ => return Err(WalletError::InconsistentBalanceCiphertexts)
+    };
+
+    for (ciphertext, amount) in std::iter::once((balance, amount)).chain(output) {
+        let ciphertext = match ciphertext {
+            CiphertextCache::Compressed(compressed) => compressed.decompress().map_err(|_| WalletError::InconsistentBalanceCiphertexts)?,
+            CiphertextCache::Decompressed(ciphertext) | CiphertextCache::Both(_, ciphertext, _) => ciphertext.clone()
+        };
+
+        // Both decrypt to amount * G if the ciphertext really holds this amount, whatever the opening used
+        let expected = keypair.get_public_key().encrypt(amount);
+        if keypair.decrypt_to_point(&ciphertext) != keypair.decrypt_to_point(&expected) {
+            return Err(WalletError::BalanceCiphertextMismatch)
+        }
+    }
+
+    Ok(())
+}
+
+// List the assets that can't be moved alone because their balance can't cover a transfer fee
+// For XELIS, it's a non empty balance below the minimum fee
+// Other assets need XELIS to pay the fees, so they are dust as long as XELIS balance is below the minimum fee
+pub fn dust_assets(balances: &[(Hash, u64)], minimum_fee: u64) -> Vec<(Hash, u64)> {
+    let native_balance = balances.iter()
+        .find(|(asset, _)| *asset == XELIS_ASSET)
+        .map_or(0, |(_, balance)| *balance);
+
+    balances.iter()
+        .filter(|(_, balance)| *balance > 0 && native_balance < minimum_fee)
+        .cloned()
+        .collect()
+}
+
+// Display each asset shortfall as "asset: expected X but have only Y" in atomic units
+fn format_shortfalls(shortfalls: &[(Hash, u64, u64)]) -> String {
+    shortfalls.iter()
+        .map(|(asset, expected, balance)| format!("{}: expected {} but have only {}", asset, expected, balance))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Verify that every asset used by the transaction, fees included, is covered by the balances in the state
+// All the shortfalls are reported at once so the transaction is either fully valid or rejected before being built
+// Returns the estimated fee
+pub fn verify_transaction_funds(builder: &TransactionBuilder, used_assets: &HashSet<Hash>, state: &mut TransactionBuilderState) -> Result<u64, WalletError> {
+    let fee = builder.estimate_fees(state).map_err(|e| WalletError::Any(e.into()))?;
+
+    let mut assets: Vec<&Hash> = used_assets.iter().collect();
+    assets.sort();
+
+    let mut shortfalls = Vec::new();
+    for asset in assets {
+        // Only the spendable part of the balance can be used
+        let balance = state.get_spendable_balance(asset)?;
+        let cost = builder.get_transaction_cost(fee, asset);
+        if cost > balance {
+            shortfalls.push((asset.clone(), cost, balance));
+        }
+    }
+
+    if !shortfalls.is_empty() {
+        return Err(WalletError::InsufficientFundsMulti(shortfalls))
+    }
+
+    Ok(fee)
+}
+
+// Merge the data integrated in an address with the extra data provided by the sender
+// Fields are merged and the address ones take precedence, as they are set by the receiver
+// Otherwise both are kept in an array, the address data first
+pub fn merge_extra_data(address_data: Option<DataElement>, extra_data: Option<DataElement>) -> Option<DataElement> {
+    match (address_data, extra_data) {
+        (Some(DataElement::Fields(address_fields)), Some(DataElement::Fields(mut fields))) => {
+            fields.extend(address_fields);
+            Some(DataElement::Fields(fields))
+        },
+        (Some(address_data), Some(extra_data)) => Some(DataElement::Array(vec![address_data, extra_data])),
+        (address_data, extra_data) => address_data.or(extra_data)
+    }
+}
+
+// Build the transfers paying all these outputs (asset, destination, extra data, amount) in a single transaction
+// Each destination must be on the same network as the wallet and can't be the source itself
+// Amounts are summed per asset and checked against the balances before building any transfer
+// Data integrated in a destination address is attached to its transfer as extra data
+// Fees are verified later, when the transaction is built
+pub fn build_transfers(outputs: Vec<(Hash, Address, Option<DataElement>, u64)>, source: &PublicKey, mainnet: bool, balances: &HashMap<Hash, u64>) -> Result<Vec<TransferBuilder>, WalletError> {
+    let mut totals: HashMap<&Hash, u64> = HashMap::new();
+    for (asset, destination, _, amount) in outputs.iter() {
+        if destination.is_mainnet() != mainnet {
+            return Err(WalletError::NetworkMismatch(mainnet, destination.is_mainnet()))
+        }
+
+        if destination.get_public_key() == source {
+            return Err(WalletError::TxOwnerIsReceiver)
+        }
+
+        let total = totals.entry(asset).or_insert(0);
+        *total = total.saturating_add(*amount);
+    }
+
+    let mut shortfalls: Vec<(Hash, u64, u64)> = totals.into_iter()
+        .filter_map(|(asset, total)| {
+            let balance = balances.get(asset).copied().unwrap_or(0);
+            (total > balance).then(|| (asset.clone(), total, balance))
+        })
+        .collect();
+
+    if !shortfalls.is_empty() {
+        shortfalls.sort_by(|a, b| a.0.cmp(&b.0));
+        return Err(WalletError::InsufficientFundsMulti(shortfalls))
+    }
+
+    let transfers = outputs.into_iter()
+        .map(|(asset, destination, extra_data, amount)| {
+            let (address_data, destination) = destination.extract_data();
+            TransferBuilder {
+                asset,
+                amount,
+                destination,
+                extra_data: merge_extra_data(address_data, extra_data)
+            }
+        })
+        .collect();
+
+    Ok(transfers)
+}
+
+// Verify that the fee multiplier doesn't go below the minimum fees accepted by the network
+pub fn verify_fee_multiplier(fee: &FeeBuilder) -> Result<(), WalletError> {
+    if let FeeBuilder::Multiplier(multiplier) = fee {
+        if multiplier.is_nan() || *multiplier < 1f64 {
+            return Err(WalletError::FeeTooLow(*multiplier))
+        }
+    }
+
+    Ok(())
+}
+
+// Verify that the transaction nonce wasn't already used on chain
+// A higher nonce is valid when the previous transactions are still in mempool
+pub fn verify_transaction_nonce(transaction: &Transaction, chain_nonce: u64) -> Result<(), WalletError> {
+    if transaction.get_nonce() < chain_nonce {
+        return Err(WalletError::NonceMismatch(chain_nonce, transaction.get_nonce()))
+    }
+
+    Ok(())
+}
+
+// Verify that the serialized transaction doesn't exceed the maximum size
+// Returns the size of the transaction
+pub fn verify_transaction_size(transaction: &Transaction, max_size: usize) -> Result<usize, WalletError> {
+    let size = transaction.serialized_size();
+    if size > max_size {
+        return Err(WalletError::TransactionTooLarge(size, max_size))
+    }
+
+    Ok(size)
+}
+
+// Assets spent by a transaction, XELIS is always included for the fees
+pub fn transaction_assets(transaction: &Transaction) -> HashSet<Hash> {
+    let mut assets = HashSet::new();
+    assets.insert(XELIS_ASSET);
+    match transaction.get_data() {
+        TransactionType::Transfers(transfers) => {
+            for transfer in transfers {
+                assets.insert(transfer.get_asset().clone());
+            }
+        },
+        TransactionType::Burn(payload) => {
+            assets.insert(payload.asset.clone());
+        }
+    }
+
+    assets
+}
+
+// Derive the keypair of the account at this index from the wallet keypair
+// Index 0 is the wallet keypair itself
+pub fn derive_account_keypair(keypair: &KeyPair, account_index: u32) -> Result<KeyPair, WalletError> {
+    let private_key = keypair.get_private_key()
+        .derive_child(account_index)
+        .ok_or(WalletError::InvalidAccountIndex(account_index))?;
+
+    Ok(KeyPair::from_private_key(private_key))
+}
+
+// Build a transaction sending the full balance of the asset from the source keypair to the destination
+// The state must contains the source balances, including XELIS to pay the fees
+// When sweeping XELIS, fees are deducted from the amount sent
+pub fn build_sweep_transaction(state: &mut TransactionBuilderState, source: &KeyPair, destination: Address, asset: Hash) -> Result<Transaction, WalletError> {
+    let balance = state.get_spendable_balance(&asset)?;
+    let transfers = |amount| TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+        asset: asset.clone(),
+        amount,
+        destination: destination.clone(),
+        extra_data: None
+    }]);
+
+    let source_key = source.get_public_key().compress();
+    let mut estimate_fee = |amount| TransactionBuilder::new(0, source_key.clone(), transfers(amount), FeeBuilder::default())
+        .estimate_fees(state)
+        .map_err(|e| WalletError::Any(e.into()));
+
+    // Only XELIS pays for its own fee, so its amount depends on the fee
+    // Estimate again with the amount left until the fee converges
+    let mut fee = estimate_fee(balance)?;
+    if asset == XELIS_ASSET {
+        for _ in 0..MAX_SWEEP_FEE_ROUNDS {
+            if balance <= fee {
+                return Err(WalletError::NotEnoughFundsForFee(fee, balance))
+            }
+
+            // Never pay less than a previous estimation
+            let next_fee = estimate_fee(balance - fee)?;
+            if next_fee <= fee {
+                break
+            }
+            fee = next_fee;
+        }
+    }
+
+    let amount = if asset == XELIS_ASSET {
+        if balance <= fee {
+            return Err(WalletError::NotEnoughFundsForFee(fee, balance))
+        }
+        balance - fee
+    } else {
+        balance
+    };
+
+    let builder = TransactionBuilder::new(0, source_key, transfers(amount), FeeBuilder::Value(fee));
+    builder.build(state, source)
+        .map_err(|e| match e {
+            GenerationError::NonceOverflow => WalletError::NonceOverflow,
+            e => WalletError::Any(e.into())
+        })
+}
+
 impl Wallet {
     // This will read from file if exists, or generate and store it in file
     // This must be call only one time, and can be cloned to be shared through differents wallets
@@ -340,10 +771,23 @@ impl Wallet {
 
     // Create a new wallet with the specificed storage, keypair and its network
     fn new(storage: EncryptedStorage, keypair: KeyPair, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
+        let public_key = keypair.get_public_key().clone();
+        Self::new_with_keys(storage, public_key, Some(keypair), network, precomputed_tables)
+    }
+
+    // Create a new wallet knowing only the public key of the watched address
+    pub(crate) fn new_watch_only(storage: EncryptedStorage, public_key: DecompressedPublicKey, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
+        Self::new_with_keys(storage, public_key, None, network, precomputed_tables)
+    }
+
+    // Wallet is watch-only if no keypair is provided
+    fn new_with_keys(storage: EncryptedStorage, public_key: DecompressedPublicKey, keypair: Option<KeyPair>, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
         let zelf = Self {
             storage: RwLock::new(storage),
-            public_key: keypair.get_public_key().compress(),
-            keypair,
+            public_key: public_key.compress(),
+            decompressed_public_key: public_key,
+            watch_only: keypair.is_none(),
+            keypair: StdRwLock::new(keypair.map(Arc::new)),
             network_handler: Mutex::new(None),
             network,
             #[cfg(feature = "api_server")]
@@ -351,7 +795,11 @@ impl Wallet {
             #[cfg(feature = "api_server")]
             xswd_channel: RwLock::new(None),
             event_broadcaster: Mutex::new(None),
-            precomputed_tables
+            precomputed_tables,
+            supply_tracker: Mutex::new(SupplyTracker::new(DEFAULT_SUPPLY_ALERT_RATE)),
+            rescan_state: RescanState::new(),
+            balance_cache: RwLock::new(BalanceCache::new(Duration::from_secs(DEFAULT_BALANCE_CACHE_TTL))),
+            sync_tracker: StdMutex::new(SyncRateTracker::new(SYNC_RATE_EWMA_ALPHA))
         };
 
         Arc::new(zelf)
@@ -365,79 +813,158 @@ impl Wallet {
 
         // generate random keypair or recover it from seed
         let keypair = if let Some(seed) = seed {
-        debug!("Retrieving keypair from seed...");
-        let words: Vec<String> = seed.split_whitespace().map(str::to_string).collect();
-        let key = mnemonics::words_to_key(&words)?;
-            KeyPair::from_private_key(key)
+            debug!("Retrieving keypair from seed...");
+            Self::keypair_from_seed(&seed)?
         } else {
             debug!("Generating a new keypair...");
             KeyPair::new()
         };
 
+        let mut storage = Self::create_storage(name, password, network)?;
+
+        // Store the private key
+        storage.set_private_key(&keypair.get_private_key())?;
+
+        // Flush the storage to be sure its written on disk
+        storage.flush()?;
+
+        Ok(Self::new(storage, keypair, network, precomputed_tables))
+    }
+
+    // Derive the keypair from a seed
+    fn keypair_from_seed(seed: &str) -> Result<KeyPair, Error> {
+        let words: Vec<String> = seed.split_whitespace().map(str::to_string).collect();
+        // verify the seed before deriving the key to report which word is wrong
+        let language = mnemonics::validate_seed(&words)?;
+        debug!("Seed language detected: {}", language.get_name());
+        let key = mnemonics::words_to_key(&words)?;
+        Ok(KeyPair::from_private_key(key))
+    }
+
+    // Returns the address of the wallet the seed would restore
+    // Nothing is written, so a typo in the seed can be spotted before creating the wallet
+    pub fn preview_address_from_seed(seed: &str, network: Network) -> Result<Address, Error> {
+        let keypair = Self::keypair_from_seed(seed)?;
+        Ok(keypair.get_public_key().to_address(network.is_mainnet()))
+    }
+
+    // Create the encrypted storage of a new wallet on disk
+    fn create_storage(name: String, password: String, network: Network) -> Result<EncryptedStorage, Error> {
+        debug!("Creating storage for {}", name);
+        let inner = Storage::new(name)?;
+        Self::init_storage(inner, password, network)
+    }
+
+    // Initialize the keys of a new storage, encrypted using the password
+    fn init_storage(mut inner: Storage, password: String, network: Network) -> Result<EncryptedStorage, Error> {
         // generate random salt for hashed password
         let mut salt: [u8; SALT_SIZE] = [0; SALT_SIZE];
         OsRng.fill_bytes(&mut salt);
 
         // generate hashed password which will be used as key to encrypt master_key
         debug!("hashing provided password");
-        let hashed_password = hash_password(password, &salt)?;
-
-        debug!("Creating storage for {}", name);
-        let mut inner = Storage::new(name)?;
+        let kdf_params = KdfParams::default();
+        let hashed_password = hash_password(password, &salt, &kdf_params)?;
 
         // generate the Cipher
-        let cipher = Cipher::new(&hashed_password, None)?;
+        let cipher = Cipher::new(hashed_password.as_slice(), None)?;
 
         // save the salt used for password
         debug!("Save password salt in public storage");
         inner.set_password_salt(&salt)?;
+        inner.set_kdf_params(&kdf_params)?;
 
         // generate the master key which is used for storage and then save it in encrypted form
-        let mut master_key: [u8; 32] = [0; 32];
-        OsRng.fill_bytes(&mut master_key);
-        let encrypted_master_key = cipher.encrypt_value(&master_key)?;
+        let mut master_key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(master_key.as_mut_slice());
+        let encrypted_master_key = cipher.encrypt_value(master_key.as_slice())?;
         debug!("Save encrypted master key in public storage");
         inner.set_encrypted_master_key(&encrypted_master_key)?;
         
         // generate the storage salt and save it in encrypted form
-        let mut storage_salt = [0; SALT_SIZE];
-        OsRng.fill_bytes(&mut storage_salt);
-        let encrypted_storage_salt = cipher.encrypt_value(&storage_salt)?;
+        let mut storage_salt = Zeroizing::new([0; SALT_SIZE]);
+        OsRng.fill_bytes(storage_salt.as_mut_slice());
+        let encrypted_storage_salt = cipher.encrypt_value(storage_salt.as_slice())?;
         inner.set_encrypted_storage_salt(&encrypted_storage_salt)?;
 
         debug!("Creating encrypted storage");
-        let mut storage = EncryptedStorage::new(inner, &master_key, storage_salt, network)?;
+        let storage = EncryptedStorage::new(inner, master_key.as_slice(), *storage_salt, network)?;
 
-        // Store the private key
-        storage.set_private_key(&keypair.get_private_key())?;
+        Ok(storage)
+    }
 
-        // Flush the storage to be sure its written on disk
-        storage.flush()?;
+    // Open an existing wallet on disk
+    pub fn open(name: String, password: String, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        if name.is_empty() {
+            return Err(WalletError::EmptyName.into())
+        }
+
+        let storage = Self::open_storage(name, password, network)?;
+        if storage.is_watch_only()? {
+            return Err(WalletError::WatchOnly.into())
+        }
+
+        debug!("Retrieving private key from encrypted storage");
+        let private_key =  storage.get_private_key()?;
+        let keypair = KeyPair::from_private_key(private_key);
 
         Ok(Self::new(storage, keypair, network, precomputed_tables))
     }
 
-    // Open an existing wallet on disk
-    pub fn open(name: String, password: String, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+    // Open a watch-only wallet for this address, creating it on disk if it doesn't exist
+    // Only its public key is known: every signing path is refused,
+    // and what is encrypted to the account (balances, transfer amounts) can't be read
+    pub fn open_watch_only(name: String, password: String, address: Address, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
         if name.is_empty() {
             return Err(WalletError::EmptyName.into())
         }
 
+        if address.is_mainnet() != network.is_mainnet() {
+            return Err(WalletError::InvalidAddressParams.into())
+        }
+
+        let public_key = address.to_public_key();
+        let decompressed_public_key = public_key.decompress()
+            .map_err(|_| WalletError::InvalidAddressParams)?;
+
+        let storage = if Path::new(&name).exists() {
+            let storage = Self::open_storage(name, password, network)?;
+            if !storage.is_watch_only()? || storage.get_watch_only_public_key()? != public_key {
+                return Err(anyhow!("This wallet is not watching the address provided"))
+            }
+            storage
+        } else {
+            let mut storage = Self::create_storage(name, password, network)?;
+            storage.set_watch_only_public_key(&public_key)?;
+            storage.flush()?;
+            storage
+        };
+
+        Ok(Self::new_watch_only(storage, decompressed_public_key, network, precomputed_tables))
+    }
+
+    // Open the encrypted storage of an existing wallet on disk
+    fn open_storage(name: String, password: String, network: Network) -> Result<EncryptedStorage, Error> {
         debug!("Creating storage for {}", name);
         let storage = Storage::new(name)?;
-        
+        Self::unlock_storage(storage, password, network)
+    }
+
+    // Decrypt the keys of an existing storage using the password
+    fn unlock_storage(mut storage: Storage, password: String, network: Network) -> Result<EncryptedStorage, Error> {
         // get password salt for KDF
         debug!("Retrieving password salt from public storage");
         let salt = storage.get_password_salt()?;
+        let kdf_params = storage.get_kdf_params()?;
 
         // retrieve encrypted master key from storage
         debug!("Retrieving encrypted master key from public storage");
         let encrypted_master_key = storage.get_encrypted_master_key()?;
 
-        let hashed_password = hash_password(password, &salt)?;
+        let hashed_password = hash_password(password, &salt, &kdf_params)?;
 
         // decrypt the encrypted master key using the hashed password (used as key)
-        let cipher = Cipher::new(&hashed_password, None)?;
+        let cipher = Cipher::new(hashed_password.as_slice(), None)?;
         let master_key = cipher.decrypt_value(&encrypted_master_key).context("Invalid password provided for this wallet")?;
 
         // Retrieve the encrypted storage salt
@@ -448,18 +975,154 @@ impl Wallet {
             return Err(WalletError::InvalidSaltSize.into());
         }
 
-        let mut salt: [u8; SALT_SIZE] = [0; SALT_SIZE];
+        let mut salt = Zeroizing::new([0; SALT_SIZE]);
         salt.copy_from_slice(&storage_salt);
 
+        // Finish an interrupted rotation of the master key
+        let master_key = match storage.get_pending_master_key()? {
+            Some(encrypted_pending_key) => {
+                info!("Resuming the interrupted rotation of the master key");
+                let pending_key = cipher.decrypt_value(&encrypted_pending_key).context("Invalid pending master key for this wallet")?;
+                let old_cipher = Cipher::new(&master_key, Some(*salt))?;
+                storage.encrypt_trees_again(&old_cipher, &old_cipher.with_key(&pending_key)?)?;
+                storage.commit_pending_master_key()?;
+                pending_key
+            },
+            None => master_key
+        };
+
         debug!("Creating encrypted storage");
-        let storage = EncryptedStorage::new(storage, &master_key, salt, network)?;
-        debug!("Retrieving private key from encrypted storage");
-        let private_key =  storage.get_private_key()?;
-        let keypair = KeyPair::from_private_key(private_key);
+        let storage = EncryptedStorage::new(storage, &master_key, *salt, network)?;
+
+        Ok(storage)
+    }
+
+    // Export the keys, balances, history, nonce and contacts into a single blob encrypted with the password
+    // It can be imported on another machine using import_encrypted_backup
+    pub async fn export_encrypted_backup(&self, password: String) -> Result<Vec<u8>, Error> {
+        trace!("export encrypted backup");
+        let backup = {
+            let storage = self.storage.read().await;
+            WalletBackup::from_storage(&storage, self.network).await?
+        };
+
+        backup.encrypt(password)
+    }
+
+    // Create a new wallet on disk from an encrypted backup
+    // Its storage is encrypted using the same password as the backup
+    pub async fn import_encrypted_backup(name: String, bytes: &[u8], password: String, network: Network, precomputed_tables: PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        if name.is_empty() {
+            return Err(WalletError::EmptyName.into())
+        }
+
+        if Path::new(&name).exists() {
+            return Err(anyhow!("A wallet already exists at {}", name))
+        }
+
+        // Verify the backup before creating anything on disk
+        let backup = Self::decrypt_backup(bytes, password.clone(), network)?;
+
+        // Restore in a temporary directory that is only moved in place once complete,
+        // so a failed import doesn't leave a partial wallet behind
+        let tmp_name = format!("{}.import", name);
+        if Path::new(&tmp_name).exists() {
+            remove_dir_all(&tmp_name)?;
+        }
+
+        let restored = async {
+            let mut storage = Self::create_storage(tmp_name.clone(), password.clone(), network)?;
+            backup.restore_into(&mut storage).await
+        }.await;
+
+        if let Err(e) = restored {
+            if let Err(e) = remove_dir_all(&tmp_name) {
+                warn!("Error while removing the partial import {}: {}", tmp_name, e);
+            }
+            return Err(e)
+        }
+
+        // The temporary storage is closed at this point
+        rename(&tmp_name, &name)?;
+
+        let storage = Self::open_storage(name, password, network)?;
+        debug!("Retrieving private key from restored storage");
+        let keypair = KeyPair::from_private_key(storage.get_private_key()?);
 
         Ok(Self::new(storage, keypair, network, precomputed_tables))
     }
 
+    // Decrypt a backup, rejecting it if it was made for another network
+    fn decrypt_backup(bytes: &[u8], password: String, network: Network) -> Result<WalletBackup, Error> {
+        let backup = WalletBackup::decrypt(bytes, password)?;
+        if backup.network != network {
+            return Err(WalletError::BackupNetworkMismatch(backup.network, network).into())
+        }
+
+        Ok(backup)
+    }
+
+    // Returns true if the wallet can't sign
+    pub fn is_watch_only(&self) -> bool {
+        self.watch_only
+    }
+
+    // Get the keypair of the wallet to sign or decrypt, unavailable in watch-only mode and while locked
+    pub(crate) fn get_keypair(&self) -> Result<Arc<KeyPair>, WalletError> {
+        if self.watch_only {
+            return Err(WalletError::WatchOnly)
+        }
+
+        let keypair = self.keypair.read().unwrap_or_else(|e| e.into_inner());
+        keypair.clone().ok_or(WalletError::Locked)
+    }
+
+    // Returns true if the private key is not in memory
+    pub fn is_locked(&self) -> bool {
+        !self.watch_only && self.keypair.read().unwrap_or_else(|e| e.into_inner()).is_none()
+    }
+
+    // Drop the private key from memory
+    // Signing and decrypting are unavailable until the wallet is unlocked,
+    // while balances, history and addresses can still be read from the storage
+    pub fn lock(&self) -> Result<(), WalletError> {
+        trace!("lock wallet");
+        if self.watch_only {
+            return Err(WalletError::WatchOnly)
+        }
+
+        let keypair = self.keypair.write().unwrap_or_else(|e| e.into_inner()).take();
+        // Wipe it if no operation is still using it, otherwise it's dropped once they are done
+        if let Some(Ok(keypair)) = keypair.map(Arc::try_unwrap) {
+            let (_, mut private_key) = keypair.split();
+            private_key.zeroize();
+        }
+
+        Ok(())
+    }
+
+    // Read the private key again from the encrypted storage once the password is verified
+    pub async fn unlock(&self, password: String) -> Result<(), Error> {
+        trace!("unlock wallet");
+        if self.watch_only {
+            return Err(WalletError::WatchOnly.into())
+        }
+
+        self.is_valid_password(password).await?;
+        let private_key = {
+            let storage = self.storage.read().await;
+            storage.get_private_key()?
+        };
+
+        let keypair = KeyPair::from_private_key(private_key);
+        if keypair.get_public_key().compress() != self.public_key {
+            return Err(anyhow!("Private key stored doesn't match the wallet public key"))
+        }
+
+        *self.keypair.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(keypair));
+        Ok(())
+    }
+
     // Close the wallet
     // this will stop the network handler and the API Server if it's running
     // Because wallet is behind Arc, we need to close differents modules that has a copy of it
@@ -605,8 +1268,8 @@ impl Wallet {
         let mut encrypted_storage = self.storage.write().await;
         let storage = encrypted_storage.get_mutable_public_storage();
         let salt = storage.get_password_salt()?;
-        let hashed_password = hash_password(password, &salt)?;
-        let cipher = Cipher::new(&hashed_password, None)?;
+        let hashed_password = hash_password(password, &salt, &storage.get_kdf_params()?)?;
+        let cipher = Cipher::new(hashed_password.as_slice(), None)?;
         let encrypted_master_key = storage.get_encrypted_master_key()?;
         let _ = cipher.decrypt_value(&encrypted_master_key).context("Invalid password provided")?;
         Ok(())
@@ -616,16 +1279,59 @@ impl Wallet {
     pub async fn set_password(&self, old_password: String, password: String) -> Result<(), Error> {
         let mut encrypted_storage = self.storage.write().await;
         let storage = encrypted_storage.get_mutable_public_storage();
+        let kdf_params = storage.get_kdf_params()?;
+        Self::encrypt_master_key(storage, old_password, password, kdf_params)
+    }
+
+    // Derive again the password-based key using stronger KDF parameters
+    // The master key is encrypted again with it, the password is unchanged
+    pub async fn upgrade_kdf(&self, password: String, new_params: KdfParams) -> Result<(), Error> {
+        let mut encrypted_storage = self.storage.write().await;
+        let storage = encrypted_storage.get_mutable_public_storage();
+        if !new_params.is_at_least(&storage.get_kdf_params()?) {
+            return Err(WalletError::WeakerKdfParams.into())
+        }
+
+        Self::encrypt_master_key(storage, password.clone(), password, new_params)
+    }
+
+    // Generate a new master key and encrypt again all the storage with it, the password is unchanged
+    // The new key is saved as pending first: if the rotation is interrupted,
+    // it is resumed the next time the wallet is opened
+    pub async fn rotate_master_key(&self, password: String) -> Result<(), Error> {
+        let mut encrypted_storage = self.storage.write().await;
+        let master_key = {
+            let storage = encrypted_storage.get_mutable_public_storage();
+            let salt = storage.get_password_salt()?;
+            let hashed_password = hash_password(password, &salt, &storage.get_kdf_params()?)?;
+            let cipher = Cipher::new(hashed_password.as_slice(), None)?;
+            let _ = cipher.decrypt_value(&storage.get_encrypted_master_key()?).context("Invalid password provided")?;
+
+            let mut master_key = Zeroizing::new([0u8; 32]);
+            OsRng.fill_bytes(master_key.as_mut_slice());
+            storage.set_pending_master_key(&cipher.encrypt_value(master_key.as_slice())?)?;
+            master_key
+        };
+        encrypted_storage.flush()?;
+
+        encrypted_storage.rotate_master_key(master_key.as_slice())?;
+        encrypted_storage.get_mutable_public_storage().commit_pending_master_key()?;
+        Ok(())
+    }
+
+    // Encrypt the master key and the storage salt with a new password-based key
+    // The old password is required to decrypt them first
+    fn encrypt_master_key(storage: &mut Storage, old_password: String, password: String, kdf_params: KdfParams) -> Result<(), Error> {
         let (master_key, storage_salt) = {
             // retrieve old salt to build key from current password
             let salt = storage.get_password_salt()?;
-            let hashed_password = hash_password(old_password, &salt)?;
+            let hashed_password = hash_password(old_password, &salt, &storage.get_kdf_params()?)?;
 
             let encrypted_master_key = storage.get_encrypted_master_key()?;
             let encrypted_storage_salt = storage.get_encrypted_storage_salt()?;
 
             // decrypt the encrypted master key using the provided password
-            let cipher = Cipher::new(&hashed_password, None)?;
+            let cipher = Cipher::new(hashed_password.as_slice(), None)?;
             let master_key = cipher.decrypt_value(&encrypted_master_key).context("Invalid password provided")?;
             let storage_salt = cipher.decrypt_value(&encrypted_storage_salt)?;
             (master_key, storage_salt)
@@ -636,8 +1342,8 @@ impl Wallet {
         OsRng.fill_bytes(&mut salt);
 
         // generate the password-based derivated key to encrypt the master key
-        let hashed_password = hash_password(password, &salt)?;
-        let cipher = Cipher::new(&hashed_password, None)?;
+        let hashed_password = hash_password(password, &salt, &kdf_params)?;
+        let cipher = Cipher::new(hashed_password.as_slice(), None)?;
 
         // encrypt the master key using the new password
         let encrypted_key = cipher.encrypt_value(&master_key)?;
@@ -647,6 +1353,7 @@ impl Wallet {
 
         // save on disk
         storage.set_password_salt(&salt)?;
+        storage.set_kdf_params(&kdf_params)?;
         storage.set_encrypted_master_key(&encrypted_key)?;
         storage.set_encrypted_storage_salt(&encrypted_storage_salt)?;
 
@@ -658,7 +1365,8 @@ impl Wallet {
         trace!("decrypt ciphertext");
         tokio::task::spawn_blocking(move || {
             let view = ECDLPTablesFileView::<PRECOMPUTED_TABLES_L1>::from_bytes(self.precomputed_tables.get());
-            self.keypair.get_private_key()
+            self.get_keypair()?
+                .get_private_key()
                 .decrypt(&view, &ciphertext)
                 .ok_or(WalletError::CiphertextDecode)
         }).await.context("Error while decrypting ciphertext")?
@@ -667,7 +1375,7 @@ impl Wallet {
     // Decrypt the extra data from a transfer
     pub fn decrypt_extra_data(&self, cipher: AEADCipher, handle: &DecryptHandle) -> Result<DataElement, WalletError> {
         trace!("decrypt extra data");
-        let key = aead::derive_aead_key_from_handle(&self.keypair.get_private_key(), handle);
+        let key = aead::derive_aead_key_from_handle(self.get_keypair()?.get_private_key(), handle);
         let plaintext = cipher.decrypt_in_place(&key)?;
         DataElement::from_bytes(&plaintext.0).map_err(|_| WalletError::CiphertextDecode)
     }
@@ -680,6 +1388,7 @@ impl Wallet {
         let (mut state, transaction) = self.create_transaction_with_storage(&mut storage, transaction_type, fee).await?;
 
         state.apply_changes(&mut storage).await?;
+        self.invalidate_transaction_balances(&transaction).await;
 
         Ok(transaction)
     }
@@ -690,6 +1399,9 @@ impl Wallet {
     // You must handle "apply changes" to the storage
     pub async fn create_transaction_with_storage(&self, storage: &mut EncryptedStorage, transaction_type: TransactionTypeBuilder, fee: FeeBuilder) -> Result<(TransactionBuilderState, Transaction), WalletError> {
         trace!("create transaction with storage");
+        let keypair = self.get_keypair()?;
+        verify_fee_multiplier(&fee)?;
+        self.rescan_state.ensure_not_in_progress()?;
         let nonce = storage.get_nonce().unwrap_or(0);
 
         // Build the state for the builder
@@ -706,14 +1418,14 @@ impl Wallet {
         );
 
         // Get all balances used
-        for asset in used_assets {
+        for asset in used_assets.iter() {
             trace!("Checking balance for asset {}", asset);
-            if !storage.has_balance_for(&asset).await? {
-                return Err(WalletError::BalanceNotFound(asset));
+            if !storage.has_balance_for(asset).await? {
+                return Err(WalletError::BalanceNotFound(asset.clone()));
             }
 
-            let balance = storage.get_unconfirmed_balance_for(&asset).await?;
-            state.add_balance(asset, balance);
+            let balance = storage.get_unconfirmed_balance_for(asset).await?;
+            state.add_balance(asset.clone(), balance);
         }
 
         self.add_registered_keys_for_fees_estimation(state.as_mut(), &fee, &transaction_type).await?;
@@ -721,36 +1433,330 @@ impl Wallet {
         // Create the transaction builder
         let builder = TransactionBuilder::new(0, self.public_key.clone(), transaction_type, fee);
 
+        // Check all assets at once before building anything
+        verify_transaction_funds(&builder, &used_assets, &mut state)?;
+
         // Build the final transaction
-        let transaction = builder.build(&mut state, &self.keypair)
-            .map_err(|e| WalletError::Any(e.into()))?;
+        let transaction = builder.build(&mut state, &keypair)
+            .map_err(|e| match e {
+                GenerationError::NonceOverflow => WalletError::NonceOverflow,
+                e => WalletError::Any(e.into())
+            })?;
+
+        verify_transaction_size(&transaction, MAX_TRANSACTION_SIZE)?;
 
         Ok((state, transaction))
     }
 
-    // submit a transaction to the network through the connection to daemon
-    // It will increase the local nonce by 1 if the TX is accepted by the daemon
-    // returns error if the wallet is in offline mode or if the TX is rejected
-    pub async fn submit_transaction(&self, transaction: &Transaction) -> Result<(), WalletError> {
-        trace!("submit transaction");
-        let network_handler = self.network_handler.lock().await;
-        if let Some(network_handler) = network_handler.as_ref() {
-            network_handler.get_api().submit_transaction(transaction).await?;
-            Ok(())
-        } else {
-            Err(WalletError::NotOnlineMode)
+    // create the transaction paying its fees in the requested asset
+    // Fees are always paid in XELIS by the protocol, any other asset is rejected
+    // You must handle "apply changes" to the storage
+    pub async fn create_transaction_with_fee_asset(&self, storage: &mut EncryptedStorage, transaction_type: TransactionTypeBuilder, fee: FeeBuilder, fee_asset: Hash) -> Result<(TransactionBuilderState, Transaction), WalletError> {
+        trace!("create transaction with fee asset {}", fee_asset);
+        if fee_asset != XELIS_ASSET {
+            return Err(WalletError::FeeAssetUnsupported(fee_asset))
         }
+
+        if !storage.has_balance_for(&fee_asset).await? {
+            return Err(WalletError::BalanceNotFound(fee_asset))
+        }
+
+        self.create_transaction_with_storage(storage, transaction_type, fee).await
     }
 
-    // Search if possible all registered keys for the transaction type
-    pub async fn add_registered_keys_for_fees_estimation(&self, state: &mut EstimateFeesState, fee: &FeeBuilder, transaction_type: &TransactionTypeBuilder) -> Result<(), WalletError> {
-        trace!("add registered keys for fees estimation");
-        if let FeeBuilder::Multiplier(_) = fee {
-            // To pay exact fees needed, we must verify that we don't have to pay more than needed
-            let used_keys = transaction_type.used_keys();
-            if !used_keys.is_empty() {
-                trace!("Checking if destination keys are registered");
-                if let Some(network_handler) = self.network_handler.lock().await.as_ref() {
+    // Create the transfers paying all these outputs in a single transaction
+    // The total spent per asset is checked against our balances first
+    pub async fn create_transfers(&self, storage: &EncryptedStorage, outputs: Vec<(Hash, Address, Option<DataElement>, u64)>) -> Result<Vec<TransferBuilder>, WalletError> {
+        trace!("create transfers");
+        let mut balances = HashMap::new();
+        for (asset, _, _, _) in outputs.iter() {
+            if !balances.contains_key(asset) && storage.has_balance_for(asset).await? {
+                let balance = storage.get_unconfirmed_balance_for(asset).await?;
+                balances.insert(asset.clone(), balance.spendable_amount());
+            }
+        }
+
+        build_transfers(outputs, self.get_public_key(), self.network.is_mainnet(), &balances)
+    }
+
+    // Send the full spendable balance of an asset to the destination
+    // When sweeping XELIS, the fee is deducted from the amount sent so no balance is left
+    // The changes are applied to the storage like any created transaction
+    pub async fn create_sweep_transfer(&self, storage: &mut EncryptedStorage, asset: Hash, destination: Address) -> Result<Transaction, WalletError> {
+        trace!("create sweep transfer of {}", asset);
+        let keypair = self.get_keypair()?;
+        self.rescan_state.ensure_not_in_progress()?;
+        if destination.is_mainnet() != self.network.is_mainnet() {
+            return Err(WalletError::NetworkMismatch(self.network.is_mainnet(), destination.is_mainnet()))
+        }
+
+        if *destination.get_public_key() == self.public_key {
+            return Err(WalletError::TxOwnerIsReceiver)
+        }
+
+        let mut state = TransactionBuilderState::new(
+            self.network.is_mainnet(),
+            Reference {
+                topoheight: storage.get_synced_topoheight()?,
+                hash: storage.get_top_block_hash()?
+            },
+            storage.get_nonce().unwrap_or(0)
+        );
+
+        // XELIS balance is always needed to pay the fees
+        let mut assets = vec![asset.clone()];
+        if asset != XELIS_ASSET {
+            assets.push(XELIS_ASSET);
+        }
+
+        for asset in assets {
+            if !storage.has_balance_for(&asset).await? {
+                return Err(WalletError::BalanceNotFound(asset));
+            }
+
+            let balance = storage.get_unconfirmed_balance_for(&asset).await?;
+            state.add_balance(asset, balance);
+        }
+
+        let transaction_type = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: asset.clone(),
+            amount: 0,
+            destination: destination.clone(),
+            extra_data: None
+        }]);
+        self.add_registered_keys_for_fees_estimation(state.as_mut(), &FeeBuilder::default(), &transaction_type).await?;
+
+        let transaction = build_sweep_transaction(&mut state, &keypair, destination, asset)?;
+        verify_transaction_size(&transaction, MAX_TRANSACTION_SIZE)?;
+
+        state.apply_changes(storage).await?;
+        self.invalidate_transaction_balances(&transaction).await;
+
+        Ok(transaction)
+    }
+
+    // Create a transfer to this address, its integrated data is sent as extra data
+    // merged with the extra data provided if both are set
+    pub async fn create_transfer_to_address(&self, storage: &EncryptedStorage, asset: Hash, address: Address, amount: u64, extra_data: Option<DataElement>) -> Result<TransferBuilder, WalletError> {
+        trace!("create transfer to address");
+        let mut transfers = self.create_transfers(storage, vec![(asset, address, extra_data, amount)]).await?;
+        transfers.pop().ok_or(WalletError::Any(anyhow!("No transfer created")))
+    }
+
+    // Create a transaction paying the estimated fees multiplied by this value
+    // A higher multiplier can be used to get included faster, it can't be below 1
+    // You must handle "apply changes" to the storage
+    pub async fn create_transaction_with_fee(&self, storage: &mut EncryptedStorage, transaction_type: TransactionTypeBuilder, fee_multiplier: f64) -> Result<(TransactionBuilderState, Transaction), WalletError> {
+        trace!("create transaction with fee multiplier {}", fee_multiplier);
+        self.create_transaction_with_storage(storage, transaction_type, FeeBuilder::Multiplier(fee_multiplier)).await
+    }
+
+    // Exact size of the transaction once serialized
+    // Fees and extra data limits are depending on it
+    pub fn transaction_size(&self, transaction: &Transaction) -> usize {
+        transaction.serialized_size()
+    }
+
+    // Build the transaction paying a payment request
+    // The request must be for the same network as the wallet
+    // You must handle "apply changes" to the storage
+    pub async fn create_transaction_from_request(&self, storage: &mut EncryptedStorage, request: &PaymentRequest) -> Result<(TransactionBuilderState, Transaction), WalletError> {
+        trace!("create transaction from request");
+        if request.address.is_mainnet() != self.network.is_mainnet() {
+            return Err(WalletError::InvalidAddressParams)
+        }
+
+        self.create_transaction_with_storage(storage, request.to_transaction_type(), FeeBuilder::default()).await
+    }
+
+    // Sweep all the funds of an asset owned by the source private key into this wallet
+    // Balances and nonce of the source are fetched from the daemon
+    // The transaction is signed by the source key and doesn't change our storage
+    pub async fn sweep_private_key(&self, storage: &EncryptedStorage, source_key: PrivateKey, asset: Hash) -> Result<Transaction, WalletError> {
+        trace!("sweep private key");
+        let source = KeyPair::from_private_key(source_key);
+        let address = source.get_public_key().to_address(self.network.is_mainnet());
+
+        let mut state = {
+            let network_handler = self.network_handler.lock().await;
+            let api = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)?.get_api();
+
+            let nonce = api.get_nonce(&address).await?.version.get_nonce();
+            let mut state = TransactionBuilderState::new(
+                self.network.is_mainnet(),
+                Reference {
+                    topoheight: storage.get_synced_topoheight()?,
+                    hash: storage.get_top_block_hash()?
+                },
+                nonce
+            );
+
+            // XELIS balance is always needed to pay the fees
+            let mut assets = vec![asset.clone()];
+            if asset != XELIS_ASSET {
+                assets.push(XELIS_ASSET);
+            }
+
+            for asset in assets {
+                let mut ciphertext = api.get_balance(&address, &asset).await?.version.take_balance();
+                let decompressed = ciphertext.computable().context("Error while decompressing source balance")?.clone();
+
+                let tables = self.precomputed_tables.clone();
+                let keypair = source.clone();
+                let amount = tokio::task::spawn_blocking(move || {
+                    let view = ECDLPTablesFileView::<PRECOMPUTED_TABLES_L1>::from_bytes(tables.get());
+                    keypair.decrypt(&view, &decompressed)
+                }).await.context("Error while decrypting source balance")?
+                    .ok_or(WalletError::CiphertextDecode)?;
+
+                debug!("Source balance for asset {} is {}", asset, amount);
+                state.add_balance(asset, Balance::new(amount, ciphertext));
+            }
+
+            state
+        };
+
+        self.add_registered_keys_for_fees_estimation(state.as_mut(), &FeeBuilder::default(), &TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: asset.clone(),
+            amount: 0,
+            destination: self.get_address(),
+            extra_data: None
+        }])).await?;
+
+        build_sweep_transaction(&mut state, &source, self.get_address(), asset)
+    }
+
+    // Export a signed transaction to be submitted from another machine
+    // This allows to sign transactions on an offline wallet
+    pub fn export_signed_transaction(&self, transaction: &Transaction) -> Vec<u8> {
+        transaction.to_bytes()
+    }
+
+    // Submit a transaction exported by an offline wallet
+    // As it was signed elsewhere, our local nonce is increased once it is accepted
+    pub async fn submit_raw_transaction(&self, bytes: &[u8]) -> Result<Transaction, WalletError> {
+        trace!("submit raw transaction");
+        let transaction = Transaction::from_bytes(bytes).map_err(WalletError::InvalidRawTransaction)?;
+        self.submit_transaction(&transaction).await?;
+
+        if *transaction.get_source() == self.public_key {
+            let mut storage = self.storage.write().await;
+            let nonce = transaction.get_nonce().checked_add(1).ok_or(WalletError::NonceOverflow)?;
+            if storage.get_nonce().map(|n| n < nonce).unwrap_or(true) {
+                debug!("Set nonce to {} after submitting raw transaction", nonce);
+                storage.set_nonce(nonce)?;
+            }
+        }
+
+        Ok(transaction)
+    }
+
+    // submit a transaction to the network through the connection to daemon
+    // It will increase the local nonce by 1 if the TX is accepted by the daemon
+    // returns error if the wallet is in offline mode or if the TX is rejected
+    pub async fn submit_transaction(&self, transaction: &Transaction) -> Result<(), WalletError> {
+        trace!("submit transaction");
+        self.rescan_state.ensure_not_in_progress()?;
+        verify_transaction_size(transaction, MAX_TRANSACTION_SIZE)?;
+
+        let network_handler = self.network_handler.lock().await;
+        if let Some(network_handler) = network_handler.as_ref() {
+            let api = network_handler.get_api();
+            // A previous submission may have been accepted by the daemon even if we didn't get its response
+            // (timeout, connection lost...), so check first if it already knows this exact transaction
+            let hash = transaction.hash();
+            if api.get_transaction(&hash).await.is_ok() {
+                debug!("Transaction {} is already known by the daemon, skipping submission", hash);
+                return Ok(())
+            }
+
+            // The transaction may have been built against a stale nonce (another device spent in between)
+            let source = transaction.get_source().clone().to_address(self.network.is_mainnet());
+            let chain_nonce = api.get_nonce(&source).await
+                // Account is not registered yet
+                .map(|v| v.version.get_nonce()).unwrap_or(0);
+            verify_transaction_nonce(transaction, chain_nonce)?;
+
+            api.submit_transaction(transaction).await?;
+            self.invalidate_transaction_balances(transaction).await;
+            Ok(())
+        } else {
+            Err(WalletError::NotOnlineMode)
+        }
+    }
+
+    // Invalidate the cached balances of the assets spent by a transaction
+    async fn invalidate_transaction_balances(&self, transaction: &Transaction) {
+        let mut cache = self.balance_cache.write().await;
+        for asset in transaction_assets(transaction) {
+            cache.invalidate(&asset);
+        }
+    }
+
+    // Get the plaintext balance of an asset
+    // It is served from memory if it was read from the storage during the last TTL
+    pub async fn get_balance(&self, asset: &Hash) -> Result<u64, WalletError> {
+        trace!("get balance for {}", asset);
+        // Balances are encrypted to the private key, a watch-only wallet never stores them
+        if self.watch_only {
+            return Err(WalletError::WatchOnly)
+        }
+
+        let generation = {
+            let cache = self.balance_cache.read().await;
+            if let Some(amount) = cache.get(asset) {
+                return Ok(amount)
+            }
+            cache.get_generation()
+        };
+
+        let amount = {
+            let storage = self.storage.read().await;
+            storage.get_plaintext_balance_for(asset).await?
+        };
+        // Not cached if the balance was invalidated during the storage read
+        self.balance_cache.write().await.put(asset.clone(), amount, generation);
+
+        Ok(amount)
+    }
+
+    // List all the assets ever seen by the wallet with their current balance
+    pub async fn list_balances(&self) -> Result<Vec<(Hash, u64)>, Error> {
+        trace!("list balances");
+        if self.watch_only {
+            return Err(WalletError::WatchOnly.into())
+        }
+
+        let storage = self.storage.read().await;
+        storage.get_all_balances().await
+    }
+
+    // Invalidate the cached balance of an asset, must be called when its balance changes
+    pub async fn invalidate_cached_balance(&self, asset: &Hash) {
+        trace!("invalidate cached balance for {}", asset);
+        self.balance_cache.write().await.invalidate(asset);
+    }
+
+    // Invalidate all the cached balances
+    pub async fn invalidate_cached_balances(&self) {
+        trace!("invalidate cached balances");
+        self.balance_cache.write().await.clear();
+    }
+
+    // Configure for how long a balance read from the storage is cached
+    pub async fn set_balance_cache_ttl(&self, ttl: Duration) {
+        self.balance_cache.write().await.set_ttl(ttl);
+    }
+
+    // Search if possible all registered keys for the transaction type
+    pub async fn add_registered_keys_for_fees_estimation(&self, state: &mut EstimateFeesState, fee: &FeeBuilder, transaction_type: &TransactionTypeBuilder) -> Result<(), WalletError> {
+        trace!("add registered keys for fees estimation");
+        if let FeeBuilder::Multiplier(_) = fee {
+            // To pay exact fees needed, we must verify that we don't have to pay more than needed
+            let used_keys = transaction_type.used_keys();
+            if !used_keys.is_empty() {
+                trace!("Checking if destination keys are registered");
+                if let Some(network_handler) = self.network_handler.lock().await.as_ref() {
                     if network_handler.is_running().await {
                         trace!("Network handler is running, checking if keys are registered");
                         for key in used_keys {
@@ -768,15 +1774,56 @@ impl Wallet {
         Ok(())
     }
 
+    // Minimum fee to pay for a single transfer to an already registered account
+    pub fn get_minimum_transfer_fee(&self) -> Result<u64, WalletError> {
+        let mut state = EstimateFeesState::new();
+        state.add_registered_key(self.public_key.clone());
+
+        let transfers = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 0,
+            destination: self.get_address(),
+            extra_data: None
+        }]);
+        let builder = TransactionBuilder::new(0, self.public_key.clone(), transfers, FeeBuilder::default());
+        builder.estimate_fees(&mut state).map_err(|e| WalletError::Any(e.into()))
+    }
+
+    // List all assets with a balance too small to be moved alone
+    // This is used to warn the user about unspendable balances
+    pub async fn dust_assets(&self) -> Result<Vec<(Hash, u64)>, WalletError> {
+        trace!("dust assets");
+        let minimum_fee = self.get_minimum_transfer_fee()?;
+        let storage = self.storage.read().await;
+        let mut balances = Vec::new();
+        for asset in storage.get_assets().await? {
+            if storage.has_balance_for(&asset).await? {
+                let balance = storage.get_plaintext_balance_for(&asset).await?;
+                balances.push((asset, balance));
+            }
+        }
+
+        Ok(dust_assets(&balances, minimum_fee))
+    }
+
     // Estimate fees for a given transaction type
     // Estimated fees returned are the minimum required to be valid on chain
     pub async fn estimate_fees(&self, tx_type: TransactionTypeBuilder) -> Result<u64, WalletError> {
         trace!("estimate fees");
-        let mut state = EstimateFeesState::new();
+        self.estimate_transaction_fee(&tx_type, 1f64).await
+    }
 
-        self.add_registered_keys_for_fees_estimation(&mut state, &FeeBuilder::default(), &tx_type).await?;
+    // Estimate the fee paid by a transaction created with this fee multiplier
+    // Balances are not checked, so it can be displayed before having the funds
+    pub async fn estimate_transaction_fee(&self, tx_type: &TransactionTypeBuilder, fee_multiplier: f64) -> Result<u64, WalletError> {
+        trace!("estimate transaction fee with multiplier {}", fee_multiplier);
+        let fee = FeeBuilder::Multiplier(fee_multiplier);
+        verify_fee_multiplier(&fee)?;
+
+        let mut state = EstimateFeesState::new();
+        self.add_registered_keys_for_fees_estimation(&mut state, &fee, tx_type).await?;
 
-        let builder = TransactionBuilder::new(0, self.public_key.clone(), tx_type, FeeBuilder::default());
+        let builder = TransactionBuilder::new(0, self.public_key.clone(), tx_type.clone(), fee);
         let estimated_fees = builder.estimate_fees(&mut state)
             .map_err(|e| WalletError::Any(e.into()))?;
 
@@ -797,6 +1844,67 @@ impl Wallet {
         network_handler.start().await?;
         *self.network_handler.lock().await = Some(network_handler);
 
+        // remember it to reconnect after a restart
+        {
+            let mut storage = self.storage.write().await;
+            storage.set_daemon_address(daemon_address)?;
+        }
+
+        Ok(())
+    }
+
+    // set the wallet in online mode using the last daemon address saved
+    pub async fn resume_online_mode(self: &Arc<Self>) -> Result<(), WalletError> {
+        trace!("Resume online mode");
+        let daemon_address = {
+            let storage = self.storage.read().await;
+            storage.get_daemon_address()?.ok_or(WalletError::NoSavedDaemon)?
+        };
+
+        self.set_online_mode(&daemon_address).await
+    }
+
+    // Switch the wallet to another daemon without going through the offline mode
+    // The new network handler resumes from the synced topoheight and top block hash saved in storage
+    // The current connection is kept if the new daemon can't be reached or isn't on our network
+    pub async fn switch_daemon(self: &Arc<Self>, new_address: &String) -> Result<(), WalletError> {
+        trace!("Switch daemon to {}", new_address);
+        let mut handler = self.network_handler.lock().await;
+        let Some(current) = handler.as_ref() else {
+            return Err(WalletError::NotOnlineMode)
+        };
+
+        let network_handler = NetworkHandler::new(Arc::clone(&self), new_address).await?;
+        let info = network_handler.get_api().get_info().await?;
+        if info.network != self.network {
+            return Err(WalletError::NetworkMismatch(self.network.is_mainnet(), info.network.is_mainnet()))
+        }
+
+        debug!("Stopping network handler of the previous daemon");
+        // it may have already stopped on a connection error, which doesn't prevent the switch
+        let was_running = current.is_running().await;
+        if let Err(e) = current.stop().await {
+            debug!("Error while stopping the previous network handler: {}", e);
+        }
+
+        if let Err(e) = network_handler.start().await {
+            // Both handlers can't sync at the same time, so the previous one is restarted instead
+            if was_running {
+                debug!("Restarting network handler of the previous daemon");
+                if let Err(e) = current.start().await {
+                    warn!("Error while restarting the previous network handler: {}", e);
+                }
+            }
+            return Err(e.into())
+        }
+        *handler = Some(network_handler);
+
+        // remember it to reconnect after a restart
+        {
+            let mut storage = self.storage.write().await;
+            storage.set_daemon_address(new_address)?;
+        }
+
         Ok(())
     }
 
@@ -819,7 +1927,8 @@ impl Wallet {
     }
 
     // set wallet in offline mode: stop communication task if exists
-    pub async fn set_offline_mode(&self) -> Result<(), WalletError> {
+    // if forget is set, the saved daemon address is deleted and can't be resumed
+    pub async fn set_offline_mode(&self, forget: bool) -> Result<(), WalletError> {
         trace!("Set offline mode");
         let mut handler = self.network_handler.lock().await;
         if let Some(network_handler) = handler.take() {
@@ -828,19 +1937,97 @@ impl Wallet {
             return Err(WalletError::NotOnlineMode)
         }
 
+        if forget {
+            let mut storage = self.storage.write().await;
+            storage.delete_daemon_address()?;
+        }
+
+        Ok(())
+    }
+
+    // Suspend the syncing of the wallet while keeping the connection to the daemon
+    // This is lighter than going offline as the network handler isn't torn down
+    pub async fn pause_sync(&self) -> Result<(), WalletError> {
+        trace!("Pause sync");
+        let handler = self.network_handler.lock().await;
+        if let Some(network_handler) = handler.as_ref() {
+            network_handler.pause().await?;
+            Ok(())
+        } else {
+            Err(WalletError::NotOnlineMode)
+        }
+    }
+
+    // Resume the syncing of the wallet from where it was paused
+    pub async fn resume_sync(&self) -> Result<(), WalletError> {
+        trace!("Resume sync");
+        let handler = self.network_handler.lock().await;
+        if let Some(network_handler) = handler.as_ref() {
+            network_handler.resume().await?;
+            Ok(())
+        } else {
+            Err(WalletError::NotOnlineMode)
+        }
+    }
+
+    // Check if our stored top block hash is still the one at our synced topoheight in the daemon chain
+    // Returns true on mismatch, meaning a reorg happened and the wallet should be reconciled
+    // If no top block hash is stored yet, nothing can be stale
+    pub async fn is_top_block_stale(&self) -> Result<bool, WalletError> {
+        trace!("is top block stale");
+        let (topoheight, top_block_hash) = {
+            let storage = self.storage.read().await;
+            if !storage.has_top_block_hash()? {
+                return Ok(false)
+            }
+            (storage.get_synced_topoheight()?, storage.get_top_block_hash()?)
+        };
+
+        let network_handler = self.network_handler.lock().await;
+        if let Some(network_handler) = network_handler.as_ref() {
+            let block = network_handler.get_api().get_block_at_topoheight(topoheight).await?;
+            let stale = *block.hash != top_block_hash;
+            if stale {
+                debug!("Stored top block hash {} at topoheight {} is stale, daemon has {}", top_block_hash, topoheight, block.hash);
+            }
+            Ok(stale)
+        } else {
+            Err(WalletError::NotOnlineMode)
+        }
+    }
+
+    // Delete all the wallet state learned from the chain to sync it again from genesis
+    // Only the keys, password and user data (contacts, labels) are kept
+    // Wallet must be offline so no sync can happen during the reset
+    pub async fn hard_reset(&self) -> Result<(), WalletError> {
+        trace!("hard reset");
+        if self.is_online().await {
+            return Err(WalletError::AlreadyOnlineMode)
+        }
+
+        let _rescan_guard = self.rescan_state.start()?;
+        {
+            let mut storage = self.get_storage().write().await;
+            storage.reset_sync_state().await?;
+        }
+        self.invalidate_cached_balances().await;
+        self.set_sync_topoheights(0, 0);
+
         Ok(())
     }
 
     // rescan the wallet from the given topoheight
     // that will delete all transactions above the given topoheight and all balances
+    // if an asset is provided, only its balance is deleted and the others are kept
     // then it will re-fetch all transactions and balances from daemon
-    pub async fn rescan(&self, topoheight: u64) -> Result<(), WalletError> {
-        trace!("Rescan wallet from topoheight {}", topoheight);
+    pub async fn rescan(&self, topoheight: u64, asset: Option<Hash>) -> Result<(), WalletError> {
+        trace!("Rescan wallet from topoheight {} for asset {:?}", topoheight, asset);
         if !self.is_online().await {
             // user have to set it online
             return Err(WalletError::NotOnlineMode)
         }
 
+        let rescan_guard = self.rescan_state.start()?;
         let mut storage = self.get_storage().write().await;
         if topoheight > storage.get_synced_topoheight()? {
             return Err(WalletError::RescanTopoheightTooHigh)
@@ -855,8 +2042,15 @@ impl Wallet {
                 storage.set_synced_topoheight(topoheight)?;
                 storage.delete_top_block_hash()?;
                 // balances will be re-fetched from daemon
-                storage.delete_balances().await?;
-                storage.delete_assets().await?;
+                if let Some(asset) = &asset {
+                    debug!("Deleting balance of {} only", asset);
+                    storage.delete_balance_for(asset).await?;
+                    self.invalidate_cached_balance(asset).await;
+                } else {
+                    storage.delete_balances().await?;
+                    storage.delete_assets().await?;
+                    self.invalidate_cached_balances().await;
+                }
 
                 debug!("Retrieve current wallet nonce");
                 let nonce_result = network_handler.get_api()
@@ -874,6 +2068,8 @@ impl Wallet {
                     storage.delete_transactions_above_topoheight(topoheight)?;
                 }
             }
+            // Storage is ready, the network handler can sync it again
+            drop(rescan_guard);
             debug!("Starting again network handler");
             network_handler.start().await.context("Error while restarting network handler")?;
         } else {
@@ -883,6 +2079,109 @@ impl Wallet {
         Ok(())
     }
 
+    // Rescan only the blocks in the topoheight range, both bounds included
+    // Transactions in this range are deleted and fetched again, everything else is kept
+    pub async fn rescan_range(&self, from_topoheight: u64, to_topoheight: u64) -> Result<(), WalletError> {
+        trace!("Rescan wallet from topoheight {} to {}", from_topoheight, to_topoheight);
+        if from_topoheight > to_topoheight {
+            return Err(WalletError::InvalidRescanRange(from_topoheight, to_topoheight))
+        }
+
+        let handler = self.network_handler.lock().await;
+        let network_handler = handler.as_ref().ok_or(WalletError::NotOnlineMode)?;
+        if !network_handler.is_running().await {
+            return Err(WalletError::NotOnlineMode)
+        }
+
+        let info = network_handler.get_api().get_info().await?;
+        if to_topoheight > info.topoheight {
+            return Err(WalletError::RescanTopoheightTooHigh)
+        }
+
+        // Don't sync new blocks while the range is processed
+        let paused = network_handler.is_paused();
+        if !paused {
+            network_handler.pause().await?;
+        }
+
+        let res: Result<(), WalletError> = async {
+            // Kept until the range is synced again so nothing reads a half rebuilt range
+            let _rescan_guard = self.rescan_state.start()?;
+            {
+                let mut storage = self.get_storage().write().await;
+                debug!("Deleting transactions from {} to {} for range rescan", from_topoheight, to_topoheight);
+                storage.delete_transactions_in_range(from_topoheight, to_topoheight)?;
+            }
+            network_handler.rescan_range(from_topoheight, to_topoheight).await?;
+            Ok(())
+        }.await;
+
+        // Resume even if the rescan failed
+        if !paused {
+            network_handler.resume().await?;
+        }
+
+        res
+    }
+
+    // Track the circulating supply of an asset during sync
+    // An Event::SupplyAlert is propagated when it increases above the configured rate
+    // NOTE: daemon only exposes the supply of the native asset per block
+    // Supply is recorded from new blocks and rescanned ranges. The balances sync only fetches
+    // the blocks of this wallet, from the highest one, so it's skipped: the next observation
+    // is compared to the last one using the rate over all the blocks in between
+    pub async fn track_asset_supply(&self, asset: Hash) -> bool {
+        trace!("track asset supply {}", asset);
+        let mut tracker = self.supply_tracker.lock().await;
+        tracker.track(asset)
+    }
+
+    // Stop tracking the circulating supply of an asset
+    pub async fn untrack_asset_supply(&self, asset: &Hash) -> bool {
+        trace!("untrack asset supply {}", asset);
+        let mut tracker = self.supply_tracker.lock().await;
+        tracker.untrack(asset)
+    }
+
+    // Set the maximum supply increase per block (in basis points) allowed before alerting
+    pub async fn set_supply_alert_rate(&self, max_rate_per_block: u64) {
+        let mut tracker = self.supply_tracker.lock().await;
+        tracker.set_max_rate_per_block(max_rate_per_block);
+    }
+
+    // Current sync progress with the daemon
+    // A UI can show an ETA from the remaining topoheights and the sync rate
+    pub fn sync_status(&self) -> SyncStatus {
+        self.sync_tracker.lock().unwrap_or_else(|e| e.into_inner()).status()
+    }
+
+    // Update the synced topoheight and the daemon one
+    pub fn set_sync_topoheights(&self, current_topoheight: u64, target_topoheight: u64) {
+        self.sync_tracker.lock().unwrap_or_else(|e| e.into_inner()).set_topoheights(current_topoheight, target_topoheight);
+    }
+
+    // Update the blocks left to sync while the sync is running
+    pub fn set_sync_remaining_blocks(&self, remaining: u64) {
+        self.sync_tracker.lock().unwrap_or_else(|e| e.into_inner()).set_remaining_blocks(remaining);
+    }
+
+    // A synced block has been applied, used to compute the sync rate
+    pub fn record_synced_block(&self) {
+        self.sync_tracker.lock().unwrap_or_else(|e| e.into_inner()).record_block();
+    }
+
+    // Record the supply of an asset observed in a synced block
+    pub async fn record_asset_supply(&self, asset: &Hash, supply: u64, topoheight: u64) {
+        let event = {
+            let mut tracker = self.supply_tracker.lock().await;
+            tracker.record(asset, supply, topoheight)
+        };
+
+        if let Some(event) = event {
+            self.propagate_event(Event::SupplyAlert(event)).await;
+        }
+    }
+
     // Check if the wallet is in online mode
     pub async fn is_online(&self) -> bool {
         if let Some(network_handler) = self.network_handler.lock().await.as_ref() {
@@ -899,8 +2198,21 @@ impl Wallet {
     }
 
     // Create a signature of the given data
-    pub fn sign_data(&self, data: &[u8]) -> Signature {
-        self.keypair.sign(data)
+    pub fn sign_data(&self, data: &[u8]) -> Result<Signature, WalletError> {
+        Ok(self.get_keypair()?.sign(data))
+    }
+
+    // Sign a message to prove the ownership of our address
+    pub fn sign_message(&self, message: &[u8]) -> Result<Signature, WalletError> {
+        Ok(self.get_keypair()?.sign(&signed_message_bytes(message)))
+    }
+
+    // Verify a message signed by the owner of the address using sign_message
+    pub fn verify_message(address: &Address, message: &[u8], signature: &Signature) -> bool {
+        match address.get_public_key().decompress() {
+            Ok(key) => signature.verify(&signed_message_bytes(message), &key),
+            Err(_) => false
+        }
     }
 
     // Get the public key of the wallet
@@ -910,34 +2222,305 @@ impl Wallet {
 
     // Get the address of the wallet using its network used
     pub fn get_address(&self) -> Address {
-        self.keypair.get_public_key().to_address(self.get_network().is_mainnet())
+        self.decompressed_public_key.to_address(self.get_network().is_mainnet())
     }
 
     // Get the address with integrated data and using its network used
     pub fn get_address_with(&self, data: DataElement) -> Address {
-        self.keypair.get_public_key().to_address_with(self.get_network().is_mainnet(), data)
+        self.decompressed_public_key.to_address_with(self.get_network().is_mainnet(), data)
     }
 
-    // Returns the seed using the language index provided
-    pub fn get_seed(&self, language_index: usize) -> Result<String, Error> {
-        let words = mnemonics::key_to_words(self.keypair.get_private_key(), language_index)?;
-        Ok(words.join(" "))
+    // Compute a digest of the full wallet state
+    // It can be used to verify that a restored backup is identical to the original wallet
+    pub async fn state_digest(&self) -> Result<Hash, Error> {
+        trace!("state digest");
+        let storage = self.storage.read().await;
+        storage.get_state_digest(self.get_public_key())
     }
 
-    // Current account nonce for transactions
-    // Nonce is used against replay attacks on-chain
-    pub async fn get_nonce(&self) -> u64 {
+    // Get a single transaction of the history by its hash, None if it's not stored
+    // Entries are saved decrypted, so amounts and extra data of the transfers are readable directly
+    pub async fn get_transaction(&self, hash: &Hash) -> Result<Option<entry::TransactionEntry>, Error> {
+        trace!("get transaction {}", hash);
         let storage = self.storage.read().await;
-        storage.get_nonce().unwrap_or(0)
+        if !storage.has_transaction(hash)? {
+            return Ok(None)
+        }
+
+        storage.get_transaction(hash).map(Some)
     }
 
-    // Encrypted storage of the wallet
-    pub fn get_storage(&self) -> &RwLock<EncryptedStorage> {
-        &self.storage
+    // Get the transactions history matching the filter
+    pub async fn history(&self, filter: entry::HistoryFilter) -> Result<Vec<entry::TransactionEntry>, WalletError> {
+        trace!("history");
+        let storage = self.storage.read().await;
+        Ok(storage.get_history(&filter)?)
     }
 
-    // Network that the wallet is using
-    pub fn get_network(&self) -> &Network {
+    // Estimate the yield of an asset over the last window of topoheights
+    // Returns None if there is not enough history to compute it
+    pub async fn estimate_yield(&self, asset: &Hash, window_topoheights: u64) -> Result<Option<f64>, WalletError> {
+        trace!("estimate yield for {}", asset);
+        let storage = self.storage.read().await;
+        if !storage.has_balance_for(asset).await? {
+            return Ok(None)
+        }
+
+        let balance = storage.get_plaintext_balance_for(asset).await?;
+        let topoheight = storage.get_synced_topoheight()?;
+        let entries = storage.get_transactions()?;
+
+        Ok(entry::estimate_yield(&entries, asset, balance, topoheight, window_topoheights))
+    }
+
+    // Suggest a fresh receive address that never received any funds
+    // Each suggested address integrate a different index, so reusing addresses can be avoided
+    pub async fn suggest_next_receive_address(&self) -> Result<Address, WalletError> {
+        trace!("suggest next receive address");
+        let index = {
+            let mut storage = self.storage.write().await;
+            storage.next_unused_receive_address_index()?
+        };
+
+        Ok(self.get_address_with(receive_address_data(index)))
+    }
+
+    // Check if the address already received funds
+    // A normal address is considered used as soon as we have any incoming transaction
+    pub async fn address_was_used(&self, address: &Address) -> Result<bool, WalletError> {
+        trace!("address was used");
+        if address.is_mainnet() != self.get_network().is_mainnet() || address.get_public_key() != self.get_public_key() {
+            return Ok(false)
+        }
+
+        let storage = self.storage.read().await;
+        let used = match address.get_type() {
+            AddressType::Normal => storage.has_incoming_with_extra_data(None)?,
+            AddressType::Data(data) => storage.has_incoming_with_extra_data(Some(data))?
+        };
+
+        Ok(used)
+    }
+
+    // Returns the seed using the language index provided
+    pub fn get_seed(&self, language_index: usize) -> Result<String, Error> {
+        let words = mnemonics::key_to_words(self.get_keypair()?.get_private_key(), language_index)?;
+        Ok(words.join(" "))
+    }
+
+    // Returns the seed in the language provided
+    // Use mnemonics::find_language to retrieve it from its name
+    pub fn get_seed_for_language(&self, language: &Language) -> Result<String, Error> {
+        let words = mnemonics::key_to_words_with_language(self.get_keypair()?.get_private_key(), language)?;
+        Ok(words.join(" "))
+    }
+
+    // Detect the language of an imported seed
+    pub fn detect_seed_language(words: &[String]) -> Option<&'static Language<'static>> {
+        mnemonics::detect_language(words)
+    }
+
+    // Derive the keypair of the account at this index from the wallet seed
+    // Index 0 is the wallet keypair, so the default address never changes
+    // The index is saved in the storage to know which accounts are in use
+    pub async fn derive_keypair(&self, account_index: u32) -> Result<KeyPair, Error> {
+        let keypair = derive_account_keypair(&self.get_keypair()?, account_index)?;
+        let mut storage = self.storage.write().await;
+        storage.add_derived_account(account_index)?;
+
+        Ok(keypair)
+    }
+
+    // Get all the account indexes derived from the wallet seed
+    pub async fn get_derived_accounts(&self) -> Result<Vec<u32>, Error> {
+        let storage = self.storage.read().await;
+        storage.get_derived_accounts()
+    }
+
+    // Save a new contact in the address book
+    // Labels are unique, an existing contact is never overwritten
+    pub async fn add_contact(&self, label: String, address: Address) -> Result<(), WalletError> {
+        trace!("add contact {}", label);
+        let mut storage = self.storage.write().await;
+        if storage.has_contact(&label)? {
+            return Err(WalletError::ContactAlreadyExists(label))
+        }
+
+        storage.set_contact(&label, &Contact::new(address))?;
+        Ok(())
+    }
+
+    // Remove a contact from the address book
+    pub async fn remove_contact(&self, label: &String) -> Result<(), WalletError> {
+        trace!("remove contact {}", label);
+        let mut storage = self.storage.write().await;
+        if !storage.has_contact(label)? {
+            return Err(WalletError::ContactNotFound(label.clone()))
+        }
+
+        storage.delete_contact(label)?;
+        Ok(())
+    }
+
+    // Get all contacts of the address book with their labels
+    pub async fn list_contacts(&self) -> Result<Vec<(String, Address)>, WalletError> {
+        trace!("list contacts");
+        let storage = self.storage.read().await;
+        let contacts = storage.get_contacts()?
+            .into_iter()
+            .map(|(label, contact)| (label, contact.get_address().clone()))
+            .collect();
+        Ok(contacts)
+    }
+
+    // Add a tag to a contact of the address book
+    // Returns false if the contact already had this tag
+    pub async fn add_contact_tag(&self, label: &String, tag: &str) -> Result<bool, WalletError> {
+        let mut storage = self.storage.write().await;
+        if !storage.has_contact(label)? {
+            return Err(WalletError::ContactNotFound(label.clone()))
+        }
+
+        let mut contact = storage.get_contact(label)?;
+        let added = contact.add_tag(tag);
+        if added {
+            storage.set_contact(label, &contact)?;
+        }
+        Ok(added)
+    }
+
+    // Remove a tag from a contact of the address book
+    // Returns false if the contact didn't have this tag
+    pub async fn remove_contact_tag(&self, label: &String, tag: &str) -> Result<bool, WalletError> {
+        let mut storage = self.storage.write().await;
+        if !storage.has_contact(label)? {
+            return Err(WalletError::ContactNotFound(label.clone()))
+        }
+
+        let mut contact = storage.get_contact(label)?;
+        let removed = contact.remove_tag(tag);
+        if removed {
+            storage.set_contact(label, &contact)?;
+        }
+        Ok(removed)
+    }
+
+    // Get all contacts having this tag, tag is case-insensitive
+    pub async fn get_contacts_by_tag(&self, tag: &str) -> Result<Vec<(String, Contact)>, WalletError> {
+        let storage = self.storage.read().await;
+        let contacts = storage.get_contacts_by_tag(tag)?;
+        Ok(contacts)
+    }
+
+    // Oldest topoheight needed to keep the wallet functional
+    // History below it can be pruned without affecting balances or the sync with the daemon
+    pub async fn prunable_topoheight(&self) -> Result<u64, WalletError> {
+        trace!("prunable topoheight");
+        let storage = self.storage.read().await;
+        if !storage.has_top_block_hash()? {
+            return Ok(0)
+        }
+
+        let stable_topoheight = storage.get_synced_topoheight()?.saturating_sub(PRUNE_SAFETY_MARGIN);
+        let topoheight = storage.get_prunable_topoheight(stable_topoheight)?;
+        Ok(topoheight)
+    }
+
+    // Delete the transactions history below the topoheight
+    // Balances, nonce and assets are kept
+    // Returns the number of deleted transactions
+    pub async fn prune_history(&self, below_topoheight: u64) -> Result<usize, WalletError> {
+        trace!("prune history below {}", below_topoheight);
+        let prunable_topoheight = self.prunable_topoheight().await?;
+        if below_topoheight > prunable_topoheight {
+            return Err(WalletError::PruneTopoheightTooHigh(below_topoheight, prunable_topoheight))
+        }
+
+        let mut storage = self.storage.write().await;
+        let deleted = storage.prune_below_topoheight(below_topoheight)?;
+        Ok(deleted)
+    }
+
+    // Export a proof of our balance for this asset at the topoheight
+    // The daemon gives the path of our encrypted balance in the balances merkle tree of the chain,
+    // the proof binds the amount to this ciphertext and leads to the root announced by any node at this topoheight
+    pub async fn export_balance_proof(self: &Arc<Self>, asset: Hash, topoheight: u64) -> Result<BalanceProof, WalletError> {
+        trace!("export balance proof for {} at topoheight {}", asset, topoheight);
+        let keypair = self.get_keypair()?;
+        let address = self.get_address();
+        let result = {
+            let network_handler = self.network_handler.lock().await;
+            let api = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)?.get_api();
+            api.get_balance_merkle_proof(&address, &asset, topoheight).await?
+        };
+        let Some(result) = result else {
+            return Err(WalletError::BalanceNotFound(asset))
+        };
+
+        let mut ciphertext = result.version.take_balance();
+        let known_amount = {
+            let storage = self.storage.read().await;
+            if storage.has_balance_for(&asset).await? {
+                let mut balance = storage.get_balance_for(&asset).await?;
+                (balance.ciphertext.compressed() == ciphertext.compressed()).then_some(balance.amount)
+            } else {
+                None
+            }
+        };
+
+        let amount = match known_amount {
+            Some(amount) => amount,
+            None => {
+                let decompressed = ciphertext.decompressed().map_err(|_| WalletError::InconsistentBalanceCiphertexts)?.clone();
+                Arc::clone(self).decrypt_ciphertext(decompressed).await?
+            }
+        };
+
+        let proof = BalanceProof::new(&keypair, asset, topoheight, amount, ciphertext.compressed().clone(), result.path, result.merkle_hash)
+            .ok_or(WalletError::InconsistentBalanceCiphertexts)?;
+        // Path must lead to the root announced by the daemon
+        if !verify_balance_proof(&proof, &proof.merkle_hash) {
+            return Err(WalletError::InvalidBalanceProof)
+        }
+
+        Ok(proof)
+    }
+
+    // Current account nonce for transactions
+    // Nonce is used against replay attacks on-chain
+    pub async fn get_nonce(&self) -> u64 {
+        let storage = self.storage.read().await;
+        storage.get_nonce().unwrap_or(0)
+    }
+
+    // Capture the in-memory state of the wallet (nonce, cached balances and pending balances)
+    #[cfg(any(test, feature = "test_utils"))]
+    pub async fn capture_state(&self) -> Result<WalletStateSnapshot, WalletError> {
+        let storage = self.storage.read().await;
+        let snapshot = storage.capture_state().await?;
+        Ok(snapshot)
+    }
+
+    // Restore a state previously captured to simulate a crash and recover
+    #[cfg(any(test, feature = "test_utils"))]
+    pub async fn restore_state(&self, snapshot: WalletStateSnapshot) -> Result<(), WalletError> {
+        let mut storage = self.storage.write().await;
+        storage.restore_state(snapshot).await?;
+        Ok(())
+    }
+
+    // Wait until the rescan in progress, if any, is done
+    pub async fn wait_for_rescan(&self) {
+        self.rescan_state.wait().await
+    }
+
+    // Encrypted storage of the wallet
+    pub fn get_storage(&self) -> &RwLock<EncryptedStorage> {
+        &self.storage
+    }
+
+    // Network that the wallet is using
+    pub fn get_network(&self) -> &Network {
         &self.network
     }
 }
@@ -990,7 +2573,7 @@ impl XSWDPermissionHandler for Arc<Wallet> {
     }
 
     async fn get_public_key(&self) -> Result<&DecompressedPublicKey, Error> {
-        Ok(self.keypair.get_public_key())
+        Ok(&self.decompressed_public_key)
     }
 }
 
@@ -1015,4 +2598,1312 @@ impl XSWDNodeMethodHandler for Arc<Wallet> {
 
         Err(RpcResponseError::new(id, InternalRpcError::CustomStr("Wallet is not in online mode")))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use serde_json::json;
+    use tokio::time::{sleep, timeout};
+    use xelis_common::{
+        account::{balance_leaf_hash, build_merkle_path, VersionedBalance, VersionedNonce},
+        api::{daemon::{GetBalanceMerkleProofResult, GetNonceResult, NotifyEvent as DaemonNotifyEvent}, DataValue},
+        config::COIN_VALUE,
+        crypto::{elgamal::CompressedCiphertext, KeyPair},
+        transaction::builder::AccountState
+    };
+    use crate::{
+        config::WALLET_BACKUP_VERSION,
+        entry::{EntryData, TransferIn},
+        kv_store::MemoryStore,
+        mock_daemon::{block_response, info_response, MockDaemon},
+        test_utils::{create_test_wallet, create_test_wallet_with_storage, create_test_watch_only_wallet}
+    };
+    use super::*;
+
+    // Daemon whose chain is at the topoheight, the accounts synced aren't registered
+    async fn start_chain_daemon(topoheight: Arc<AtomicU64>) -> MockDaemon {
+        let miner = KeyPair::new().get_public_key().to_address(true);
+        MockDaemon::start(move |method, params| match method {
+            "get_version" => Ok(json!("1.9.0")),
+            "get_info" => Ok(serde_json::to_value(info_response(topoheight.load(Ordering::SeqCst))).unwrap()),
+            "get_block_at_topoheight" => Ok(serde_json::to_value(block_response(params["topoheight"].as_u64().unwrap(), &miner, None)).unwrap()),
+            "get_nonce" => Err("Account not found".to_owned()),
+            _ => Err(format!("unexpected method {}", method))
+        }).await
+    }
+
+    // Wait until the wallet is synced up to this topoheight
+    async fn wait_synced_topoheight(wallet: &Wallet, topoheight: u64) {
+        timeout(Duration::from_secs(5), async {
+            while wallet.get_storage().read().await.get_synced_topoheight().ok() != Some(topoheight) {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.expect("wallet is not synced");
+    }
+
+    // Wait until the wallet listens to the new blocks of the daemon
+    async fn wait_subscribed(daemon: &MockDaemon) {
+        timeout(Duration::from_secs(5), async {
+            while daemon.requests("subscribe").is_empty() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.expect("wallet is not subscribed");
+    }
+
+    #[test]
+    fn test_verify_balance_ciphertexts() {
+        let keypair = KeyPair::new();
+        let balance = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+        let output = CiphertextCache::Compressed(keypair.get_public_key().encrypt(40u64).compress());
+
+        // consistent pairs
+        assert!(verify_balance_ciphertexts(&keypair, &balance, 100, None, None, BalanceType::Input).is_ok());
+        assert!(verify_balance_ciphertexts(&keypair, &balance, 100, None, None, BalanceType::Output).is_ok());
+        assert!(verify_balance_ciphertexts(&keypair, &balance, 100, Some(&output), Some(40), BalanceType::Both).is_ok());
+
+        // output balance without both types
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 100, Some(&output), Some(40), BalanceType::Input), Err(WalletError::InconsistentBalanceCiphertexts)));
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 100, Some(&output), Some(40), BalanceType::Output), Err(WalletError::InconsistentBalanceCiphertexts)));
+        // missing output balance
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 100, None, None, BalanceType::Both), Err(WalletError::InconsistentBalanceCiphertexts)));
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 100, Some(&output), None, BalanceType::Both), Err(WalletError::InconsistentBalanceCiphertexts)));
+
+        // garbage output ciphertext
+        let garbage = CiphertextCache::Compressed(CompressedCiphertext::from_bytes(&[0xFF; 64]).unwrap());
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 100, Some(&garbage), Some(40), BalanceType::Both), Err(WalletError::InconsistentBalanceCiphertexts)));
+
+        // plaintext not matching the ciphertext
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 101, None, None, BalanceType::Input), Err(WalletError::BalanceCiphertextMismatch)));
+        assert!(matches!(verify_balance_ciphertexts(&keypair, &balance, 100, Some(&output), Some(0), BalanceType::Both), Err(WalletError::BalanceCiphertextMismatch)));
+        // encrypted to another key
+        assert!(matches!(verify_balance_ciphertexts(&KeyPair::new(), &balance, 100, None, None, BalanceType::Input), Err(WalletError::BalanceCiphertextMismatch)));
+    }
+
+    #[test]
+    fn test_build_sweep_transaction() {
+        let source = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 3);
+        let ciphertext = CiphertextCache::Decompressed(source.get_public_key().encrypt(1000u64));
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+
+        let transaction = build_sweep_transaction(&mut state, &source, destination.clone(), XELIS_ASSET).unwrap();
+        assert_eq!(*transaction.get_source(), source.get_public_key().compress());
+        assert_eq!(transaction.get_nonce(), 3);
+
+        // Everything is sent minus the fees
+        let fee = transaction.get_fee();
+        let TransactionType::Transfers(transfers) = transaction.get_data() else {
+            panic!("expected transfers");
+        };
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].get_destination(), destination.get_public_key());
+        assert_eq!(state.get_account_balance(&XELIS_ASSET).unwrap(), 0);
+        assert!(fee > 0);
+
+        // Not enough to pay the fees
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+        let ciphertext = CiphertextCache::Decompressed(source.get_public_key().encrypt(1u64));
+        state.add_balance(XELIS_ASSET, Balance::new(1, ciphertext));
+        assert!(matches!(build_sweep_transaction(&mut state, &source, destination, XELIS_ASSET), Err(WalletError::NotEnoughFundsForFee(_, 1))));
+    }
+
+    #[tokio::test]
+    async fn test_create_sweep_transfer() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let asset = Hash::new([1u8; 32]);
+        let wallet = create_test_wallet(keypair);
+
+        let mut storage = wallet.get_storage().write().await;
+        storage.set_synced_topoheight(10).unwrap();
+        storage.set_top_block_hash(&Hash::zero()).unwrap();
+        storage.set_nonce(2).unwrap();
+        for (asset, amount) in [(&XELIS_ASSET, COIN_VALUE), (&asset, 500)] {
+            let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(amount));
+            storage.set_balance_for(asset, Balance::new(amount, ciphertext)).await.unwrap();
+        }
+
+        // Can't sweep to ourself
+        assert!(matches!(wallet.create_sweep_transfer(&mut storage, XELIS_ASSET, wallet.get_address()).await, Err(WalletError::TxOwnerIsReceiver)));
+
+        // The whole asset balance is sent, XELIS pays the fee
+        let transaction = wallet.create_sweep_transfer(&mut storage, asset.clone(), destination.clone()).await.unwrap();
+        let fee = transaction.get_fee();
+        assert_eq!(transaction.get_nonce(), 2);
+        assert_eq!(storage.get_unconfirmed_balance_for(&asset).await.unwrap().spendable_amount(), 0);
+        assert_eq!(storage.get_unconfirmed_balance_for(&XELIS_ASSET).await.unwrap().spendable_amount(), COIN_VALUE - fee);
+
+        // XELIS is swept including its own fee
+        let transaction = wallet.create_sweep_transfer(&mut storage, XELIS_ASSET, destination).await.unwrap();
+        assert_eq!(transaction.get_nonce(), 3);
+        assert_eq!(storage.get_unconfirmed_balance_for(&XELIS_ASSET).await.unwrap().spendable_amount(), 0);
+        assert_eq!(storage.get_nonce().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_dust_assets() {
+        let asset = Hash::new([1u8; 32]);
+
+        // XELIS balance below the fee, the other asset can't be moved either
+        let dust = dust_assets(&[(XELIS_ASSET, 99), (asset.clone(), 1000), (Hash::new([2u8; 32]), 0)], 100);
+        assert_eq!(dust, vec![(XELIS_ASSET, 99), (asset.clone(), 1000)]);
+
+        // Enough XELIS to pay the fees
+        assert!(dust_assets(&[(XELIS_ASSET, 100), (asset.clone(), 1)], 100).is_empty());
+
+        // No XELIS at all
+        assert_eq!(dust_assets(&[(asset.clone(), 1)], 100), vec![(asset, 1)]);
+    }
+
+    #[test]
+    fn test_verify_transaction_size() {
+        let source = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+        let ciphertext = CiphertextCache::Decompressed(source.get_public_key().encrypt(1000u64));
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+        let transaction = build_sweep_transaction(&mut state, &source, destination, XELIS_ASSET).unwrap();
+
+        let size = verify_transaction_size(&transaction, MAX_TRANSACTION_SIZE).unwrap();
+        assert_eq!(size, transaction.to_bytes().len());
+        // source key, signature and proofs are at least included
+        assert!(size > 32 + 64 && size < MAX_TRANSACTION_SIZE);
+
+        assert!(matches!(verify_transaction_size(&transaction, size - 1), Err(WalletError::TransactionTooLarge(s, m)) if s == size && m == size - 1));
+    }
+
+    #[test]
+    fn test_verify_transaction_funds() {
+        let source = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let asset = Hash::new([1u8; 32]);
+        let transfers = TransactionTypeBuilder::Transfers(vec![
+            TransferBuilder {
+                asset: XELIS_ASSET,
+                amount: 1000,
+                destination: destination.clone(),
+                extra_data: None
+            },
+            TransferBuilder {
+                asset: asset.clone(),
+                amount: 500,
+                destination,
+                extra_data: None
+            }
+        ]);
+        let used_assets = transfers.used_assets();
+        let builder = TransactionBuilder::new(0, source.get_public_key().compress(), transfers, FeeBuilder::Value(10));
+
+        let state_with = |xelis: u64, other: u64| {
+            let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+            state.add_balance(XELIS_ASSET, Balance::new(xelis, CiphertextCache::Decompressed(source.get_public_key().encrypt(xelis))));
+            state.add_balance(asset.clone(), Balance::new(other, CiphertextCache::Decompressed(source.get_public_key().encrypt(other))));
+            state
+        };
+
+        // Both assets are covered, fee included
+        assert_eq!(verify_transaction_funds(&builder, &used_assets, &mut state_with(1010, 500)).unwrap(), 10);
+
+        // Both shortfalls are reported, XELIS one includes the fee
+        match verify_transaction_funds(&builder, &used_assets, &mut state_with(1000, 100)) {
+            Err(WalletError::InsufficientFundsMulti(shortfalls)) => {
+                assert_eq!(shortfalls, vec![(XELIS_ASSET, 1010, 1000), (asset.clone(), 500, 100)]);
+            },
+            _ => panic!("expected shortfalls for both assets")
+        }
+
+        // Only the fee is missing
+        assert!(matches!(
+            verify_transaction_funds(&builder, &used_assets, &mut state_with(1005, 500)),
+            Err(WalletError::InsufficientFundsMulti(shortfalls)) if shortfalls == vec![(XELIS_ASSET, 1010, 1005)]
+        ));
+    }
+
+    #[test]
+    fn test_spendable_balance_funds() {
+        let source = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let transfers = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 1000,
+            destination: destination.clone(),
+            extra_data: None
+        }]);
+        let used_assets = transfers.used_assets();
+        let builder = TransactionBuilder::new(0, source.get_public_key().compress(), transfers, FeeBuilder::Value(10));
+
+        // 2000 in total, but only 800 are left from our last outgoing transaction
+        let state_with = |balance_type: BalanceType| {
+            let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+            let ciphertext = CiphertextCache::Decompressed(source.get_public_key().encrypt(2000u64));
+            state.add_balance(XELIS_ASSET, Balance::with_type(2000, ciphertext, balance_type, Some(800)));
+            state
+        };
+
+        assert!(matches!(
+            verify_transaction_funds(&builder, &used_assets, &mut state_with(BalanceType::Both)),
+            Err(WalletError::InsufficientFundsMulti(shortfalls)) if shortfalls == vec![(XELIS_ASSET, 1010, 800)]
+        ));
+        // No incoming funds in the same version, the full balance is spendable
+        assert_eq!(verify_transaction_funds(&builder, &used_assets, &mut state_with(BalanceType::Output)).unwrap(), 10);
+
+        // Transfers are checked against the spendable amount too
+        let balances = HashMap::from([(XELIS_ASSET, state_with(BalanceType::Both).get_spendable_balance(&XELIS_ASSET).unwrap())]);
+        let outputs = vec![(XELIS_ASSET, destination.clone(), None, 1000)];
+        assert!(matches!(build_transfers(outputs, &source.get_public_key().compress(), true, &balances), Err(WalletError::InsufficientFundsMulti(_))));
+
+        // Sweep only sends the spendable part
+        let mut state = state_with(BalanceType::Both);
+        let transaction = build_sweep_transaction(&mut state, &source, destination, XELIS_ASSET).unwrap();
+        let fee = transaction.get_fee();
+        assert_eq!(state.get_account_balance(&XELIS_ASSET).unwrap(), 2000 - 800);
+        assert_eq!(state.get_spendable_balance(&XELIS_ASSET).unwrap(), 0);
+        assert!(fee < 800);
+    }
+
+    #[test]
+    fn test_derive_account_keypair() {
+        let keypair = KeyPair::new();
+        let words = mnemonics::key_to_words(keypair.get_private_key(), 0).unwrap();
+
+        // Default account keeps the wallet address
+        let default = derive_account_keypair(&keypair, 0).unwrap();
+        assert_eq!(default.get_public_key().compress(), keypair.get_public_key().compress());
+
+        // Recovering the wallet from its seed derives the same accounts
+        let recovered = KeyPair::from_private_key(mnemonics::words_to_key(&words).unwrap());
+        for index in [1, 2, u32::MAX] {
+            let account = derive_account_keypair(&keypair, index).unwrap();
+            assert_eq!(account.get_public_key().compress(), derive_account_keypair(&recovered, index).unwrap().get_public_key().compress());
+            assert_ne!(account.get_public_key().compress(), keypair.get_public_key().compress());
+        }
+    }
+
+    #[test]
+    fn test_preview_address_from_seed() {
+        let key = PrivateKey::from_bytes(&[1u8; 32]).unwrap();
+        let expected = KeyPair::from_private_key(key.clone()).get_public_key().to_address(true);
+        let seed = mnemonics::key_to_words(&key, 0).unwrap().join(" ");
+
+        let address = Wallet::preview_address_from_seed(&seed, Network::Mainnet).unwrap();
+        assert_eq!(address, expected);
+        assert!(address.is_mainnet());
+        assert!(!Wallet::preview_address_from_seed(&seed, Network::Testnet).unwrap().is_mainnet());
+
+        // Misspelled word
+        let mut words: Vec<&str> = seed.split_whitespace().collect();
+        words[3] = "notaword";
+        let err = Wallet::preview_address_from_seed(&words.join(" "), Network::Mainnet).unwrap_err();
+        assert!(matches!(err.downcast_ref::<WalletError>(), Some(WalletError::InvalidSeedWord(_))));
+
+        // Missing word
+        let words: Vec<&str> = seed.split_whitespace().skip(1).collect();
+        let err = Wallet::preview_address_from_seed(&words.join(" "), Network::Mainnet).unwrap_err();
+        assert!(matches!(err.downcast_ref::<WalletError>(), Some(WalletError::InvalidSeedLength(_, _))));
+    }
+
+    #[tokio::test]
+    async fn test_watch_only() {
+        let keypair = KeyPair::new();
+        let wallet = create_test_watch_only_wallet(keypair.get_public_key().clone());
+        assert!(wallet.is_watch_only());
+        assert!(!wallet.is_locked());
+        assert_eq!(wallet.get_address(), keypair.get_public_key().to_address(true));
+
+        // No private key is held, nothing can be signed
+        let transfers = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 10,
+            destination: KeyPair::new().get_public_key().to_address(true),
+            extra_data: None
+        }]);
+        assert!(matches!(wallet.create_transaction(transfers, FeeBuilder::default()).await, Err(WalletError::WatchOnly)));
+        assert!(matches!(wallet.sign_data(b"data"), Err(WalletError::WatchOnly)));
+        assert!(matches!(wallet.get_seed(0).unwrap_err().downcast_ref::<WalletError>(), Some(WalletError::WatchOnly)));
+
+        // Nor decrypted
+        let ciphertext = keypair.get_public_key().encrypt(100u64);
+        assert!(matches!(Arc::clone(&wallet).decrypt_ciphertext(ciphertext).await, Err(WalletError::WatchOnly)));
+        assert!(matches!(wallet.get_balance(&XELIS_ASSET).await, Err(WalletError::WatchOnly)));
+        assert!(wallet.list_balances().await.is_err());
+
+        // Address must be on the network of the wallet
+        let address = keypair.get_public_key().to_address(false);
+        let err = Wallet::open_watch_only("watch".to_owned(), "password".to_owned(), address, Network::Mainnet, Arc::new(PrecomputedTables::new(8))).unwrap_err();
+        assert!(matches!(err.downcast_ref::<WalletError>(), Some(WalletError::InvalidAddressParams)));
+    }
+
+    #[test]
+    fn test_fee_multiplier() {
+        let source = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let transfers = || TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 1000,
+            destination: destination.clone(),
+            extra_data: None
+        }]);
+        let used_assets = transfers().used_assets();
+
+        let estimate = |multiplier: f64| {
+            let builder = TransactionBuilder::new(0, source.get_public_key().compress(), transfers(), FeeBuilder::Multiplier(multiplier));
+            let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+            state.add_balance(XELIS_ASSET, Balance::new(COIN_VALUE, CiphertextCache::Decompressed(source.get_public_key().encrypt(COIN_VALUE))));
+            verify_transaction_funds(&builder, &used_assets, &mut state).unwrap()
+        };
+
+        // Funds check is done against the multiplied fee
+        let fee = estimate(1f64);
+        assert_eq!(estimate(2f64), fee * 2);
+
+        assert!(verify_fee_multiplier(&FeeBuilder::Multiplier(1f64)).is_ok());
+        assert!(verify_fee_multiplier(&FeeBuilder::Multiplier(2f64)).is_ok());
+        assert!(verify_fee_multiplier(&FeeBuilder::Value(0)).is_ok());
+        assert!(matches!(verify_fee_multiplier(&FeeBuilder::Multiplier(0.5f64)), Err(WalletError::FeeTooLow(m)) if m == 0.5f64));
+        assert!(matches!(verify_fee_multiplier(&FeeBuilder::Multiplier(f64::NAN)), Err(WalletError::FeeTooLow(_))));
+    }
+
+    #[test]
+    fn test_build_transfers() {
+        let source = KeyPair::new().get_public_key().compress();
+        let asset = Hash::new([1u8; 32]);
+        let alice = KeyPair::new().get_public_key().to_address(true);
+        let bob = KeyPair::new().get_public_key().to_address(true);
+        let balances = HashMap::from([(XELIS_ASSET, 100), (asset.clone(), 50)]);
+
+        // Each output is covered, but not their total
+        let outputs = vec![
+            (XELIS_ASSET, alice.clone(), None, 60),
+            (XELIS_ASSET, bob.clone(), None, 60),
+            (asset.clone(), bob.clone(), None, 50)
+        ];
+        assert!(matches!(
+            build_transfers(outputs, &source, true, &balances),
+            Err(WalletError::InsufficientFundsMulti(shortfalls)) if shortfalls == vec![(XELIS_ASSET, 120, 100)]
+        ));
+
+        // Unknown asset has no balance
+        let outputs = vec![(Hash::new([2u8; 32]), alice.clone(), None, 1)];
+        assert!(build_transfers(outputs, &source, true, &balances).is_err());
+
+        let outputs = vec![
+            (XELIS_ASSET, alice.clone(), None, 40),
+            (XELIS_ASSET, bob.clone(), Some(DataElement::Value(xelis_common::api::DataValue::U64(1))), 60)
+        ];
+        let transfers = build_transfers(outputs, &source, true, &balances).unwrap();
+        assert_eq!(transfers.len(), 2);
+        assert_eq!(transfers[0].destination, alice);
+        assert_eq!(transfers[1].amount, 60);
+        assert!(transfers[1].extra_data.is_some());
+    }
+
+    #[test]
+    fn test_build_transfers_integrated_address() {
+        let source = KeyPair::new().get_public_key().compress();
+        let key = KeyPair::new().get_public_key().compress();
+        let balances = HashMap::from([(XELIS_ASSET, 100)]);
+        let field = |name: &str, value: u64| (DataValue::String(name.to_owned()), DataElement::Value(DataValue::U64(value)));
+        let payment_id = DataElement::Fields(HashMap::from([field("payment_id", 1)]));
+        let integrated = Address::new(true, AddressType::Data(payment_id.clone()), key.clone());
+        let normal = Address::new(true, AddressType::Normal, key);
+
+        // Integrated data is sent as extra data to the normal address
+        let transfers = build_transfers(vec![(XELIS_ASSET, integrated.clone(), None, 10)], &source, true, &balances).unwrap();
+        assert_eq!(transfers[0].destination, normal);
+        assert_eq!(transfers[0].extra_data, Some(payment_id.clone()));
+
+        // Plain address keeps the extra data provided
+        let memo = DataElement::Value(DataValue::String("memo".to_owned()));
+        let transfers = build_transfers(vec![(XELIS_ASSET, normal.clone(), Some(memo.clone()), 10)], &source, true, &balances).unwrap();
+        assert_eq!(transfers[0].destination, normal);
+        assert_eq!(transfers[0].extra_data, Some(memo.clone()));
+        let transfers = build_transfers(vec![(XELIS_ASSET, normal.clone(), None, 10)], &source, true, &balances).unwrap();
+        assert!(transfers[0].extra_data.is_none());
+
+        // Both are set
+        let transfers = build_transfers(vec![(XELIS_ASSET, integrated.clone(), Some(memo.clone()), 10)], &source, true, &balances).unwrap();
+        assert_eq!(transfers[0].extra_data, Some(DataElement::Array(vec![payment_id.clone(), memo])));
+
+        // Fields are merged, the address ones take precedence
+        let extra = DataElement::Fields(HashMap::from([field("payment_id", 2), field("invoice", 3)]));
+        let merged = DataElement::Fields(HashMap::from([field("payment_id", 1), field("invoice", 3)]));
+        let transfers = build_transfers(vec![(XELIS_ASSET, integrated, Some(extra), 10)], &source, true, &balances).unwrap();
+        assert_eq!(transfers[0].extra_data, Some(merged));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_transaction_fee() {
+        let keypair = KeyPair::new();
+        let wallet = create_test_wallet(keypair.clone());
+
+        let tx_type = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 1000,
+            destination: KeyPair::new().get_public_key().to_address(true),
+            extra_data: None
+        }]);
+
+        for multiplier in [1f64, 2f64] {
+            // No funds needed for the estimation
+            let estimated = wallet.estimate_transaction_fee(&tx_type, multiplier).await.unwrap();
+
+            let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+            state.add_balance(XELIS_ASSET, Balance::new(COIN_VALUE, CiphertextCache::Decompressed(keypair.get_public_key().encrypt(COIN_VALUE))));
+            let builder = TransactionBuilder::new(0, keypair.get_public_key().compress(), tx_type.clone(), FeeBuilder::Multiplier(multiplier));
+            let transaction = builder.build(&mut state, &keypair).unwrap();
+            assert_eq!(estimated, transaction.get_fee());
+        }
+
+        assert!(matches!(wallet.estimate_transaction_fee(&tx_type, 0.5f64).await, Err(WalletError::FeeTooLow(_))));
+    }
+
+    #[test]
+    fn test_verify_transaction_nonce() {
+        let source = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let ciphertext = CiphertextCache::Decompressed(source.get_public_key().encrypt(1000u64));
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 5);
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+        let transaction = build_sweep_transaction(&mut state, &source, destination, XELIS_ASSET).unwrap();
+        assert_eq!(transaction.get_nonce(), 5);
+
+        // Same nonce as on chain, or queued after transactions in mempool
+        assert!(verify_transaction_nonce(&transaction, 5).is_ok());
+        assert!(verify_transaction_nonce(&transaction, 3).is_ok());
+
+        // Chain already used this nonce
+        assert!(matches!(verify_transaction_nonce(&transaction, 6), Err(WalletError::NonceMismatch(6, 5))));
+    }
+
+    #[tokio::test]
+    async fn test_export_signed_transaction() {
+        let keypair = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+
+        // Signed on the offline wallet
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(1000u64));
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 3);
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+        let transaction = build_sweep_transaction(&mut state, &keypair, destination, XELIS_ASSET).unwrap();
+
+        let wallet = create_test_wallet(keypair);
+        let bytes = wallet.export_signed_transaction(&transaction);
+        assert_eq!(Transaction::from_bytes(&bytes).unwrap().hash(), transaction.hash());
+
+        wallet.get_storage().write().await.set_nonce(3).unwrap();
+
+        // Invalid bytes are rejected before reaching the network
+        assert!(matches!(wallet.submit_raw_transaction(&bytes[..bytes.len() - 1]).await, Err(WalletError::InvalidRawTransaction(_))));
+
+        // Not submitted, nonce is unchanged
+        assert!(matches!(wallet.submit_raw_transaction(&bytes).await, Err(WalletError::NotOnlineMode)));
+        assert_eq!(wallet.get_storage().read().await.get_nonce().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_submit_raw_transaction() {
+        let keypair = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+
+        // Signed on the offline wallet
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(1000u64));
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 3);
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+        let transaction = build_sweep_transaction(&mut state, &keypair, destination, XELIS_ASSET).unwrap();
+
+        let daemon = MockDaemon::start(|method, _| match method {
+            "get_version" => Ok(json!("1.9.0")),
+            "get_transaction" => Err("Transaction not found".to_owned()),
+            "get_nonce" => Ok(serde_json::to_value(GetNonceResult { topoheight: 10, version: VersionedNonce::new(3, None) }).unwrap()),
+            "submit_transaction" => Ok(json!(true)),
+            _ => Err(format!("unexpected method {}", method))
+        }).await;
+
+        // Submitted from a watch-only wallet, it never signs anything
+        let wallet = create_test_watch_only_wallet(keypair.get_public_key().clone());
+        *wallet.network_handler.lock().await = Some(NetworkHandler::with_api(Arc::clone(&wallet), daemon.connect().await).await.unwrap());
+        wallet.get_storage().write().await.set_nonce(3).unwrap();
+
+        let bytes = wallet.export_signed_transaction(&transaction);
+        let submitted = wallet.submit_raw_transaction(&bytes).await.unwrap();
+        assert_eq!(submitted.hash(), transaction.hash());
+        assert_eq!(daemon.requests("submit_transaction").len(), 1);
+
+        // Nonce is increased once accepted
+        assert_eq!(wallet.get_storage().read().await.get_nonce().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_submit_transaction_retry() {
+        let keypair = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(1000u64));
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 3);
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+        let transaction = build_sweep_transaction(&mut state, &keypair, destination, XELIS_ASSET).unwrap();
+
+        // Daemon knows the transaction once it was submitted, like a mempool
+        let in_mempool = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let known = serde_json::to_value(&transaction).unwrap();
+        let daemon = {
+            let in_mempool = Arc::clone(&in_mempool);
+            MockDaemon::start(move |method, _| match method {
+                "get_version" => Ok(json!("1.9.0")),
+                "get_transaction" if in_mempool.load(std::sync::atomic::Ordering::SeqCst) => Ok(known.clone()),
+                "get_transaction" => Err("Transaction not found".to_owned()),
+                "get_nonce" => Ok(serde_json::to_value(GetNonceResult { topoheight: 10, version: VersionedNonce::new(3, None) }).unwrap()),
+                "submit_transaction" => {
+                    in_mempool.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Ok(json!(true))
+                },
+                _ => Err(format!("unexpected method {}", method))
+            }).await
+        };
+
+        let wallet = create_test_wallet(keypair);
+        *wallet.network_handler.lock().await = Some(NetworkHandler::with_api(Arc::clone(&wallet), daemon.connect().await).await.unwrap());
+        wallet.get_storage().write().await.set_nonce(3).unwrap();
+
+        let bytes = wallet.export_signed_transaction(&transaction);
+        wallet.submit_raw_transaction(&bytes).await.unwrap();
+        assert_eq!(daemon.requests("submit_transaction").len(), 1);
+        assert_eq!(wallet.get_storage().read().await.get_nonce().unwrap(), 4);
+
+        // Retried after a timeout while the daemon already accepted it: success without submitting it again
+        wallet.submit_raw_transaction(&bytes).await.unwrap();
+        wallet.submit_transaction(&transaction).await.unwrap();
+        assert_eq!(daemon.requests("get_transaction").len(), 3);
+        assert_eq!(daemon.requests("submit_transaction").len(), 1);
+        // Stale nonce check isn't done again either
+        assert_eq!(daemon.requests("get_nonce").len(), 1);
+        // Nonce isn't increased a second time
+        assert_eq!(wallet.get_storage().read().await.get_nonce().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_during_rescan() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+
+        let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(1000u64));
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+        state.add_balance(XELIS_ASSET, Balance::new(1000, ciphertext));
+        let transaction = build_sweep_transaction(&mut state, &keypair, destination.clone(), XELIS_ASSET).unwrap();
+
+        let wallet = create_test_wallet(keypair);
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_synced_topoheight(10).unwrap();
+            storage.set_top_block_hash(&Hash::zero()).unwrap();
+            let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(COIN_VALUE));
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(COIN_VALUE, ciphertext)).await.unwrap();
+        }
+
+        let transaction_type = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: XELIS_ASSET,
+            amount: 100,
+            destination,
+            extra_data: None
+        }]);
+
+        // Storage is being rewritten by the rescan
+        let guard = wallet.rescan_state.start().unwrap();
+        assert!(matches!(wallet.create_transaction(transaction_type.clone(), FeeBuilder::default()).await, Err(WalletError::RescanInProgress)));
+        assert!(matches!(wallet.submit_transaction(&transaction).await, Err(WalletError::RescanInProgress)));
+        // Nothing was applied
+        assert_eq!(wallet.get_storage().read().await.get_nonce().unwrap_or(0), 0);
+
+        drop(guard);
+        wallet.create_transaction(transaction_type, FeeBuilder::default()).await.unwrap();
+        assert_eq!(wallet.get_storage().read().await.get_nonce().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_kdf() {
+        let store = MemoryStore::new();
+        let password = "xelis".to_owned();
+        let storage = Wallet::init_storage(Storage::with_store(store.clone()), password.clone(), Network::Mainnet).unwrap();
+        assert_eq!(storage.get_public_storage().get_kdf_params().unwrap(), KdfParams::default());
+
+        let wallet = create_test_wallet_with_storage(storage, KeyPair::new());
+        let stronger = KdfParams::new(DEFAULT_KDF_MEMORY * 2, DEFAULT_KDF_ITERATIONS + 1, DEFAULT_KDF_PARALLELISM);
+
+        {
+            // Weaker parameters are rejected
+            let weaker = KdfParams::new(DEFAULT_KDF_MEMORY, 1, DEFAULT_KDF_PARALLELISM);
+            assert!(wallet.upgrade_kdf(password.clone(), weaker).await.is_err());
+
+            wallet.upgrade_kdf(password.clone(), stronger).await.unwrap();
+            wallet.is_valid_password(password.clone()).await.unwrap();
+        }
+
+        // Storage can be unlocked again using the stored parameters
+        let storage = Wallet::unlock_storage(Storage::with_store(store.clone()), password, Network::Mainnet).unwrap();
+        assert_eq!(storage.get_public_storage().get_kdf_params().unwrap(), stronger);
+        assert!(Wallet::unlock_storage(Storage::with_store(store), "invalid".to_owned(), Network::Mainnet).is_err());
+
+        assert_eq!(KdfParams::from_bytes(&stronger.to_bytes()).unwrap(), stronger);
+    }
+
+    #[tokio::test]
+    async fn test_address_book() {
+        let store = MemoryStore::new();
+        let keypair = KeyPair::new();
+        let alice = KeyPair::new().get_public_key().to_address(true);
+        let bob = KeyPair::new().get_public_key().to_address(true);
+
+        {
+            let storage = EncryptedStorage::new(Storage::with_store(store.clone()), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+            let wallet = create_test_wallet_with_storage(storage, keypair.clone());
+            wallet.add_contact("alice".to_owned(), alice.clone()).await.unwrap();
+            wallet.add_contact("bob".to_owned(), bob.clone()).await.unwrap();
+            assert!(matches!(wallet.add_contact("alice".to_owned(), bob.clone()).await, Err(WalletError::ContactAlreadyExists(_))));
+
+            let mut contacts = wallet.list_contacts().await.unwrap();
+            contacts.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(contacts, vec![("alice".to_owned(), alice.clone()), ("bob".to_owned(), bob.clone())]);
+
+            wallet.remove_contact(&"bob".to_owned()).await.unwrap();
+            assert!(matches!(wallet.remove_contact(&"bob".to_owned()).await, Err(WalletError::ContactNotFound(_))));
+            wallet.get_storage().write().await.flush().unwrap();
+        }
+
+        // Still present after reopening the wallet
+        let storage = EncryptedStorage::new(Storage::with_store(store), &[1u8; 32], [2u8; SALT_SIZE], Network::Mainnet).unwrap();
+        let wallet = create_test_wallet_with_storage(storage, keypair);
+        assert_eq!(wallet.list_contacts().await.unwrap(), vec![("alice".to_owned(), alice)]);
+    }
+
+    #[test]
+    fn test_build_transfers_destination() {
+        let source = KeyPair::new().get_public_key().compress();
+        let balances = HashMap::from([(XELIS_ASSET, 100)]);
+
+        // Testnet address from a mainnet wallet
+        let testnet = KeyPair::new().get_public_key().to_address(false);
+        let outputs = vec![(XELIS_ASSET, testnet.clone(), None, 10)];
+        assert!(matches!(build_transfers(outputs, &source, true, &balances), Err(WalletError::NetworkMismatch(true, false))));
+        let outputs = vec![(XELIS_ASSET, testnet, None, 10)];
+        assert!(build_transfers(outputs, &source, false, &balances).is_ok());
+
+        // Sending to ourself
+        let outputs = vec![(XELIS_ASSET, source.clone().to_address(true), None, 10)];
+        assert!(matches!(build_transfers(outputs, &source, true, &balances), Err(WalletError::TxOwnerIsReceiver)));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_events() {
+        let keypair = KeyPair::new();
+        let wallet = create_test_wallet(keypair);
+
+        let mut receiver = wallet.subscribe_events().await;
+        let mut second = wallet.subscribe_events().await;
+
+        // Events published by the network handler when a block is synced
+        wallet.propagate_event(Event::BalanceChanged(BalanceChanged {
+            asset: XELIS_ASSET,
+            balance: 100
+        })).await;
+        wallet.propagate_event(Event::SyncProgress { topoheight: 42 }).await;
+
+        for receiver in [&mut receiver, &mut second] {
+            assert!(matches!(receiver.recv().await.unwrap(), Event::BalanceChanged(BalanceChanged { balance: 100, .. })));
+            assert!(matches!(receiver.recv().await.unwrap(), Event::SyncProgress { topoheight: 42 }));
+        }
+
+        assert!(wallet.close_events_channel().await);
+        assert!(receiver.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resume_online_mode() {
+        let wallet = create_test_wallet(KeyPair::new());
+
+        assert!(matches!(wallet.resume_online_mode().await, Err(WalletError::NoSavedDaemon)));
+        assert!(matches!(wallet.set_offline_mode(true).await, Err(WalletError::NotOnlineMode)));
+
+        // The saved daemon is used, even if it can't be reached
+        wallet.get_storage().write().await.set_daemon_address(&"127.0.0.1:1".to_owned()).unwrap();
+        assert!(!matches!(wallet.resume_online_mode().await, Err(WalletError::NoSavedDaemon)));
+        assert!(!wallet.is_online().await);
+        assert_eq!(wallet.get_storage().read().await.get_daemon_address().unwrap(), Some("127.0.0.1:1".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_switch_daemon_offline() {
+        let wallet = create_test_wallet(KeyPair::new());
+
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_synced_topoheight(42).unwrap();
+            storage.set_daemon_address(&"127.0.0.1:1".to_owned()).unwrap();
+        }
+
+        // There is no daemon to switch from
+        assert!(matches!(wallet.switch_daemon(&"127.0.0.1:2".to_owned()).await, Err(WalletError::NotOnlineMode)));
+
+        // Sync state and saved daemon are untouched
+        let storage = wallet.get_storage().read().await;
+        assert_eq!(storage.get_synced_topoheight().unwrap(), 42);
+        assert_eq!(storage.get_daemon_address().unwrap(), Some("127.0.0.1:1".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn test_balance_cache() {
+        let keypair = KeyPair::new();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let mut state = TransactionBuilderState::new(true, Reference { topoheight: 0, hash: Hash::zero() }, 0);
+        state.add_balance(XELIS_ASSET, Balance::new(1000, CiphertextCache::Decompressed(keypair.get_public_key().encrypt(1000u64))));
+        let transaction = build_sweep_transaction(&mut state, &keypair, destination, XELIS_ASSET).unwrap();
+
+        let wallet = create_test_wallet(keypair);
+        let other = Hash::new([1u8; 32]);
+        async fn set_balances(wallet: &Wallet, assets: [&Hash; 2], amount: u64) {
+            let mut storage = wallet.get_storage().write().await;
+            for asset in assets {
+                let ciphertext = CiphertextCache::Decompressed(KeyPair::new().get_public_key().encrypt(amount));
+                storage.set_balance_for(asset, Balance::new(amount, ciphertext)).await.unwrap();
+            }
+        }
+
+        wallet.set_balance_cache_ttl(Duration::from_secs(60)).await;
+        set_balances(&wallet, [&XELIS_ASSET, &other], 100).await;
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 100);
+        assert_eq!(wallet.get_balance(&other).await.unwrap(), 100);
+
+        // Storage is updated without invalidating the cache, reads don't reach it
+        set_balances(&wallet, [&XELIS_ASSET, &other], 50).await;
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 100);
+
+        // Only the assets spent by the transaction are invalidated
+        wallet.invalidate_transaction_balances(&transaction).await;
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 50);
+        assert_eq!(wallet.get_balance(&other).await.unwrap(), 100);
+
+        wallet.invalidate_cached_balance(&other).await;
+        assert_eq!(wallet.get_balance(&other).await.unwrap(), 50);
+
+        assert_eq!(transaction_assets(&transaction), HashSet::from([XELIS_ASSET]));
+    }
+
+    #[tokio::test]
+    async fn test_balance_cache_hit() {
+        let keypair = KeyPair::new();
+        let ciphertext = CiphertextCache::Decompressed(keypair.get_public_key().encrypt(100u64));
+        let wallet = create_test_wallet(keypair);
+        wallet.set_balance_cache_ttl(Duration::from_secs(60)).await;
+        wallet.get_storage().write().await.set_balance_for(&XELIS_ASSET, Balance::new(100, ciphertext)).await.unwrap();
+
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 100);
+        assert_eq!(wallet.get_storage().read().await.get_plaintext_balance_reads(), 1);
+        // Served from memory, the storage isn't called again
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 100);
+        assert_eq!(wallet.get_storage().read().await.get_plaintext_balance_reads(), 1);
+
+        wallet.invalidate_cached_balance(&XELIS_ASSET).await;
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 100);
+        assert_eq!(wallet.get_storage().read().await.get_plaintext_balance_reads(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_prune_history() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let from = KeyPair::new().get_public_key().compress();
+        let wallet = create_test_wallet(keypair);
+
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_synced_topoheight(200).unwrap();
+            storage.set_top_block_hash(&Hash::zero()).unwrap();
+            storage.set_nonce(4).unwrap();
+            let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(400u64));
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(400, ciphertext)).await.unwrap();
+            for topoheight in [10u64, 20, 30, 190] {
+                let entry = entry::TransactionEntry::new(Hash::new([topoheight as u8; 32]), topoheight, EntryData::Incoming {
+                    from: from.clone(),
+                    transfers: vec![TransferIn::new(XELIS_ASSET, 100, None)]
+                });
+                storage.save_transaction(entry.get_hash(), &entry).unwrap();
+                storage.add_topoheight_to_changes(topoheight, &Hash::new([topoheight as u8; 32])).unwrap();
+            }
+        }
+
+        // 30 is the last change below the safety margin, 190 could still be reorged
+        assert_eq!(wallet.prunable_topoheight().await.unwrap(), 30);
+        assert!(matches!(wallet.prune_history(190).await, Err(WalletError::PruneTopoheightTooHigh(190, 30))));
+
+        assert_eq!(wallet.prune_history(30).await.unwrap(), 2);
+        assert_eq!(wallet.prune_history(30).await.unwrap(), 0);
+
+        let storage = wallet.get_storage().read().await;
+        let mut topoheights: Vec<u64> = storage.get_transactions().unwrap().iter().map(|e| e.get_topoheight()).collect();
+        topoheights.sort();
+        assert_eq!(topoheights, vec![30, 190]);
+        assert_eq!(storage.get_transaction(&Hash::new([30u8; 32])).unwrap().get_topoheight(), 30);
+        assert!(storage.get_transaction(&Hash::new([10u8; 32])).is_err());
+
+        // Balances and nonce are kept
+        assert_eq!(storage.get_plaintext_balance_for(&XELIS_ASSET).await.unwrap(), 400);
+        assert_eq!(storage.get_nonce().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_rotate_master_key() {
+        let store = MemoryStore::new();
+        let password = "xelis".to_owned();
+        let asset = Hash::new([1u8; 32]);
+        let key = DataValue::String("key".to_owned());
+        let entry = entry::TransactionEntry::new(Hash::new([3u8; 32]), 10, EntryData::Incoming {
+            from: KeyPair::new().get_public_key().compress(),
+            transfers: vec![TransferIn::new(XELIS_ASSET, 100, None)]
+        });
+
+        // Check that every kind of entry can still be decrypted
+        fn assert_entries(storage: &EncryptedStorage, asset: &Hash, key: &DataValue, entry: &entry::TransactionEntry) {
+            assert_eq!(storage.get_nonce().unwrap(), 3);
+            assert_eq!(storage.get_asset_decimals(asset).unwrap(), 8);
+            assert!(matches!(storage.get_custom_data("tree", key).unwrap(), DataElement::Value(DataValue::U64(42))));
+            assert_eq!(storage.get_transaction(entry.get_hash()).unwrap().get_topoheight(), entry.get_topoheight());
+            assert!(storage.get_public_storage().get_pending_master_key().unwrap().is_none());
+        }
+
+        let mut storage = Wallet::init_storage(Storage::with_store(store.clone()), password.clone(), Network::Mainnet).unwrap();
+        storage.set_nonce(3).unwrap();
+        storage.add_asset(&asset, 8).await.unwrap();
+        storage.set_custom_data("tree", &key, &DataElement::Value(DataValue::U64(42))).unwrap();
+        storage.save_transaction(entry.get_hash(), &entry).unwrap();
+        let encrypted_master_key = storage.get_public_storage().get_encrypted_master_key().unwrap();
+
+        let wallet = create_test_wallet_with_storage(storage, KeyPair::new());
+        assert!(wallet.rotate_master_key("invalid".to_owned()).await.is_err());
+        wallet.rotate_master_key(password.clone()).await.unwrap();
+        assert_entries(&*wallet.get_storage().read().await, &asset, &key, &entry);
+
+        // Reopening after the rotation decrypts all the entries with the new master key
+        let storage = Wallet::unlock_storage(Storage::with_store(store.clone()), password.clone(), Network::Mainnet).unwrap();
+        assert_entries(&storage, &asset, &key, &entry);
+        assert_ne!(storage.get_public_storage().get_encrypted_master_key().unwrap(), encrypted_master_key);
+
+        // Interrupted after encrypting the trees but before replacing the master key
+        let mut storage = storage;
+        let old_encrypted_master_key = storage.get_public_storage().get_encrypted_master_key().unwrap();
+        {
+            let inner = storage.get_mutable_public_storage();
+            let hashed_password = hash_password(password.clone(), &inner.get_password_salt().unwrap(), &inner.get_kdf_params().unwrap()).unwrap();
+            let cipher = Cipher::new(hashed_password.as_slice(), None).unwrap();
+            inner.set_pending_master_key(&cipher.encrypt_value(&[5u8; 32]).unwrap()).unwrap();
+        }
+        storage.rotate_master_key(&[5u8; 32]).unwrap();
+        assert_eq!(storage.get_public_storage().get_encrypted_master_key().unwrap(), old_encrypted_master_key);
+
+        // Rotation is completed when the wallet is opened
+        let storage = Wallet::unlock_storage(Storage::with_store(store.clone()), password.clone(), Network::Mainnet).unwrap();
+        assert_entries(&storage, &asset, &key, &entry);
+        assert_ne!(storage.get_public_storage().get_encrypted_master_key().unwrap(), old_encrypted_master_key);
+
+        // Interrupted right after saving the new master key
+        let mut storage = storage;
+        {
+            let inner = storage.get_mutable_public_storage();
+            let hashed_password = hash_password(password.clone(), &inner.get_password_salt().unwrap(), &inner.get_kdf_params().unwrap()).unwrap();
+            let cipher = Cipher::new(hashed_password.as_slice(), None).unwrap();
+            inner.set_pending_master_key(&cipher.encrypt_value(&[6u8; 32]).unwrap()).unwrap();
+        }
+        let storage = Wallet::unlock_storage(Storage::with_store(store), password, Network::Mainnet).unwrap();
+        assert_entries(&storage, &asset, &key, &entry);
+    }
+
+    #[tokio::test]
+    async fn test_create_transaction_with_fee_asset() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let destination = KeyPair::new().get_public_key().to_address(true);
+        let asset = Hash::new([1u8; 32]);
+        let wallet = create_test_wallet(keypair);
+
+        let mut storage = wallet.get_storage().write().await;
+        storage.set_synced_topoheight(10).unwrap();
+        storage.set_top_block_hash(&Hash::zero()).unwrap();
+        for (asset, amount) in [(&XELIS_ASSET, COIN_VALUE), (&asset, 500)] {
+            let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(amount));
+            storage.set_balance_for(asset, Balance::new(amount, ciphertext)).await.unwrap();
+        }
+
+        let transaction_type = TransactionTypeBuilder::Transfers(vec![TransferBuilder {
+            asset: asset.clone(),
+            amount: 100,
+            destination,
+            extra_data: None
+        }]);
+
+        // Protocol can't express fees in another asset
+        let result = wallet.create_transaction_with_fee_asset(&mut storage, transaction_type.clone(), FeeBuilder::default(), asset.clone()).await;
+        assert!(matches!(result, Err(WalletError::FeeAssetUnsupported(fee_asset)) if fee_asset == asset));
+
+        // Fees paid in XELIS like the default path
+        let (_, transaction) = wallet.create_transaction_with_fee_asset(&mut storage, transaction_type.clone(), FeeBuilder::default(), XELIS_ASSET).await.unwrap();
+        assert!(transaction.get_fee() > 0);
+        assert_eq!(transaction.get_nonce(), 0);
+
+        // Requested fee is kept
+        let (_, transaction) = wallet.create_transaction_with_fee_asset(&mut storage, transaction_type, FeeBuilder::Value(5000), XELIS_ASSET).await.unwrap();
+        assert_eq!(transaction.get_fee(), 5000);
+    }
+
+    #[tokio::test]
+    async fn test_export_balance_proof() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let address = public_key.to_address(true);
+        let asset = Hash::new([1u8; 32]);
+        let other = Hash::new([2u8; 32]);
+        let wallet = create_test_wallet(keypair);
+
+        // Not anchored without a daemon
+        assert!(matches!(wallet.export_balance_proof(asset.clone(), 10).await, Err(WalletError::NotOnlineMode)));
+
+        // Our balance is a leaf of the daemon tree next to the balance of another account
+        let ciphertext = public_key.encrypt(500u64).compress();
+        let leaves = vec![balance_leaf_hash(address.get_public_key(), &asset, &ciphertext), Hash::new([42u8; 32])];
+        let (merkle_hash, path) = build_merkle_path(leaves, 0).unwrap();
+        let daemon = {
+            let (asset, other, ciphertext, merkle_hash) = (asset.clone(), other.clone(), ciphertext.clone(), merkle_hash.clone());
+            MockDaemon::start(move |method, params| match method {
+                "get_version" => Ok(json!("1.9.0")),
+                // Our balance of the other asset has the same ciphertext, its leaf doesn't lead to the root
+                "get_balance_merkle_proof" if params["asset"] == json!(asset) || params["asset"] == json!(other) => Ok(serde_json::to_value(GetBalanceMerkleProofResult {
+                    version: VersionedBalance::new(CiphertextCache::Compressed(ciphertext.clone()), Some(8)),
+                    path: path.clone(),
+                    merkle_hash: merkle_hash.clone()
+                }).unwrap()),
+                "get_balance_merkle_proof" => Ok(json!(null)),
+                _ => Err(format!("unexpected method {}", method))
+            }).await
+        };
+        *wallet.network_handler.lock().await = Some(NetworkHandler::with_api(Arc::clone(&wallet), daemon.connect().await).await.unwrap());
+
+        {
+            // Amounts already known, tables used in tests are too small to decrypt them
+            let mut storage = wallet.get_storage().write().await;
+            for asset in [&asset, &other] {
+                storage.set_balance_for(asset, Balance::new(500, CiphertextCache::Compressed(ciphertext.clone()))).await.unwrap();
+            }
+        }
+
+        let proof = wallet.export_balance_proof(asset.clone(), 10).await.unwrap();
+        assert_eq!(proof.amount, 500);
+        assert_eq!(proof.topoheight, 10);
+        assert_eq!(proof.merkle_hash, merkle_hash);
+        assert_eq!(daemon.requests("get_balance_merkle_proof")[0]["topoheight"], json!(10));
+
+        let decoded = BalanceProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert!(verify_balance_proof(&decoded, &merkle_hash));
+
+        // Amount changed while the ciphertext is kept
+        let mut tampered = decoded.clone();
+        tampered.amount = 600;
+        assert!(!verify_balance_proof(&tampered, &merkle_hash));
+
+        let result = wallet.export_balance_proof(other.clone(), 10).await;
+        assert!(matches!(result, Err(WalletError::InvalidBalanceProof)));
+        // No balance for this asset at the topoheight
+        let result = wallet.export_balance_proof(Hash::new([3u8; 32]), 10).await;
+        assert!(matches!(result, Err(WalletError::BalanceNotFound(_))));
+
+        // Amount proof can't be made without the private key
+        let watch_only = create_test_watch_only_wallet(public_key);
+        assert!(matches!(watch_only.export_balance_proof(asset, 10).await, Err(WalletError::WatchOnly)));
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_backup() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let from = KeyPair::new().get_public_key().compress();
+        let contact = Contact::new(KeyPair::new().get_public_key().to_address(true));
+        let asset = Hash::new([1u8; 32]);
+        let wallet = create_test_wallet(keypair);
+
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_private_key(wallet.get_keypair().unwrap().get_private_key()).unwrap();
+            storage.set_synced_topoheight(20).unwrap();
+            storage.set_nonce(3).unwrap();
+            for (asset, amount) in [(&XELIS_ASSET, COIN_VALUE), (&asset, 500)] {
+                let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(amount));
+                storage.add_asset(asset, 8).await.unwrap();
+                storage.set_balance_for(asset, Balance::new(amount, ciphertext)).await.unwrap();
+            }
+            for topoheight in [10u64, 20] {
+                let entry = entry::TransactionEntry::new(Hash::new([topoheight as u8; 32]), topoheight, EntryData::Incoming {
+                    from: from.clone(),
+                    transfers: vec![TransferIn::new(asset.clone(), 250, None)]
+                });
+                storage.save_transaction(entry.get_hash(), &entry).unwrap();
+            }
+            storage.set_contact(&"alice".to_owned(), &contact).unwrap();
+            storage.set_top_block_hash(&Hash::new([20u8; 32])).unwrap();
+            for topoheight in [10u64, 20] {
+                storage.add_topoheight_to_changes(topoheight, &Hash::new([topoheight as u8; 32])).unwrap();
+            }
+        }
+
+        let password = "backup".to_owned();
+        let bytes = wallet.export_encrypted_backup(password.clone()).await.unwrap();
+
+        assert!(Wallet::decrypt_backup(&bytes, "wrong".to_owned(), Network::Mainnet).is_err());
+        let err = Wallet::decrypt_backup(&bytes, password.clone(), Network::Testnet).unwrap_err();
+        assert!(matches!(err.downcast_ref::<WalletError>(), Some(WalletError::BackupNetworkMismatch(Network::Mainnet, Network::Testnet))));
+
+        let mut unknown_version = bytes.clone();
+        unknown_version[0] = WALLET_BACKUP_VERSION + 1;
+        assert!(Wallet::decrypt_backup(&unknown_version, password.clone(), Network::Mainnet).is_err());
+
+        let backup = Wallet::decrypt_backup(&bytes, password.clone(), Network::Mainnet).unwrap();
+        let mut restored = Wallet::init_storage(Storage::with_store(MemoryStore::new()), password.clone(), Network::Mainnet).unwrap();
+        backup.restore_into(&mut restored).await.unwrap();
+
+        let original = wallet.get_storage().read().await;
+        assert_eq!(restored.get_private_key().unwrap().to_bytes(), wallet.get_keypair().unwrap().get_private_key().to_bytes());
+        assert_eq!(restored.get_nonce().unwrap(), 3);
+        assert_eq!(restored.get_synced_topoheight().unwrap(), 20);
+        assert_eq!(restored.get_top_block_hash().unwrap(), Hash::new([20u8; 32]));
+        assert_eq!(restored.get_topoheight_changes().count(), 2);
+        // Sync markers included, the restored wallet is in the same state
+        assert_eq!(restored.get_state_digest(wallet.get_public_key()).unwrap(), original.get_state_digest(wallet.get_public_key()).unwrap());
+        assert_eq!(restored.get_all_balances().await.unwrap(), original.get_all_balances().await.unwrap());
+        assert_eq!(restored.get_all_balances().await.unwrap().len(), 2);
+
+        let history = |storage: &EncryptedStorage| {
+            let mut entries: Vec<Vec<u8>> = storage.get_transactions().unwrap().iter().map(|e| e.to_bytes()).collect();
+            entries.sort();
+            entries
+        };
+        assert_eq!(history(&restored), history(&original));
+        assert_eq!(history(&restored).len(), 2);
+
+        let contacts = restored.get_contacts().unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].0, "alice");
+        assert_eq!(contacts[0].1.to_bytes(), contact.to_bytes());
+        drop(original);
+
+        // Imported on disk, nothing is left in the temporary directory
+        let name = std::env::temp_dir().join(format!("xelis-import-{}", rand::random::<u64>())).to_string_lossy().into_owned();
+        let imported = Wallet::import_encrypted_backup(name.clone(), &bytes, password.clone(), Network::Mainnet, Arc::new(PrecomputedTables::new(8))).await.unwrap();
+        assert_eq!(imported.get_public_key(), wallet.get_public_key());
+        assert!(!Path::new(&format!("{}.import", name)).exists());
+        drop(imported);
+
+        // Never overwrites an existing wallet
+        assert!(Wallet::import_encrypted_backup(name.clone(), &bytes, password, Network::Mainnet, Arc::new(PrecomputedTables::new(8))).await.is_err());
+        remove_dir_all(&name).unwrap();
+    }
+
+    #[test]
+    fn test_sign_message() {
+        let keypair = KeyPair::new();
+        let wallet = create_test_wallet(keypair);
+        let address = wallet.get_address();
+        let message = b"exchange challenge 1234";
+
+        let signature = wallet.sign_message(message).unwrap();
+        assert!(Wallet::verify_message(&address, message, &signature));
+
+        // Signed by another key
+        let other = KeyPair::new().get_public_key().to_address(true);
+        assert!(!Wallet::verify_message(&other, message, &signature));
+
+        // Tampered message
+        assert!(!Wallet::verify_message(&address, b"exchange challenge 1235", &signature));
+
+        // Raw signature of the same data can't be used as a signed message
+        let raw = wallet.sign_data(message).unwrap();
+        assert!(!Wallet::verify_message(&address, message, &raw));
+    }
+
+    #[tokio::test]
+    async fn test_lock_wallet() {
+        let keypair = KeyPair::new();
+        let password = "password".to_owned();
+        let mut storage = Wallet::init_storage(Storage::with_store(MemoryStore::new()), password.clone(), Network::Mainnet).unwrap();
+        storage.set_private_key(keypair.get_private_key()).unwrap();
+        let public_key = keypair.get_public_key().clone();
+        let wallet = create_test_wallet_with_storage(storage, keypair);
+        let address = wallet.get_address();
+
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_synced_topoheight(10).unwrap();
+            let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(100u64));
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(100, ciphertext)).await.unwrap();
+        }
+
+        assert!(wallet.sign_message(b"challenge").is_ok());
+        wallet.lock().unwrap();
+        assert!(wallet.is_locked());
+        assert!(matches!(wallet.sign_message(b"challenge"), Err(WalletError::Locked)));
+        assert!(matches!(wallet.sign_data(b"data"), Err(WalletError::Locked)));
+        assert!(wallet.get_seed(0).is_err());
+
+        // Read-only operations keep working
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), 100);
+        assert!(wallet.history(entry::HistoryFilter::default()).await.unwrap().is_empty());
+        assert_eq!(wallet.get_address().get_public_key(), address.get_public_key());
+
+        assert!(wallet.unlock("wrong".to_owned()).await.is_err());
+        assert!(wallet.is_locked());
+
+        wallet.unlock(password).await.unwrap();
+        assert!(!wallet.is_locked());
+        let signature = wallet.sign_message(b"challenge").unwrap();
+        assert!(Wallet::verify_message(&address, b"challenge", &signature));
+    }
+
+    #[tokio::test]
+    async fn test_hard_reset() {
+        let keypair = KeyPair::new();
+        let public_key = keypair.get_public_key().clone();
+        let contact = Contact::new(KeyPair::new().get_public_key().to_address(true));
+        let wallet = create_test_wallet(keypair);
+        let address = wallet.get_address();
+
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.set_private_key(wallet.get_keypair().unwrap().get_private_key()).unwrap();
+            storage.set_synced_topoheight(20).unwrap();
+            storage.set_top_block_hash(&Hash::new([3u8; 32])).unwrap();
+            storage.set_nonce(3).unwrap();
+            let ciphertext = CiphertextCache::Decompressed(public_key.encrypt(COIN_VALUE));
+            storage.add_asset(&XELIS_ASSET, 8).await.unwrap();
+            storage.set_balance_for(&XELIS_ASSET, Balance::new(COIN_VALUE, ciphertext)).await.unwrap();
+            let entry = entry::TransactionEntry::new(Hash::new([1u8; 32]), 10, EntryData::Coinbase { reward: COIN_VALUE });
+            storage.save_transaction(entry.get_hash(), &entry).unwrap();
+            storage.add_topoheight_to_changes(10, &Hash::new([4u8; 32])).unwrap();
+            storage.set_contact(&"alice".to_owned(), &contact).unwrap();
+        }
+        assert_eq!(wallet.get_balance(&XELIS_ASSET).await.unwrap(), COIN_VALUE);
+
+        wallet.hard_reset().await.unwrap();
+
+        {
+            let storage = wallet.get_storage().read().await;
+            assert!(storage.get_all_balances().await.unwrap().is_empty());
+            assert!(storage.get_transactions().unwrap().is_empty());
+            assert!(!storage.has_topoheight_in_changes(10).unwrap());
+            assert!(storage.get_nonce().is_err());
+            assert!(storage.get_synced_topoheight().is_err());
+            assert!(!storage.has_top_block_hash().unwrap());
+
+            // Keys and user data are kept
+            assert!(storage.get_private_key().is_ok());
+            assert_eq!(storage.get_contacts().unwrap().len(), 1);
+        }
+        // Cached balance is dropped too
+        assert!(wallet.get_balance(&XELIS_ASSET).await.is_err());
+        assert_eq!(wallet.get_address(), address);
+    }
+
+    #[tokio::test]
+    async fn test_get_transaction() {
+        let wallet = create_test_wallet(KeyPair::new());
+        let asset = Hash::new([1u8; 32]);
+        let memo = DataElement::Value(DataValue::String("invoice 42".to_owned()));
+        let coinbase = entry::TransactionEntry::new(Hash::new([4u8; 32]), 10, EntryData::Coinbase { reward: COIN_VALUE });
+        let incoming = entry::TransactionEntry::new(Hash::new([5u8; 32]), 12, EntryData::Incoming {
+            from: KeyPair::new().get_public_key().compress(),
+            transfers: vec![TransferIn::new(asset.clone(), 250, Some(memo.clone()))]
+        });
+
+        {
+            let mut storage = wallet.get_storage().write().await;
+            storage.save_transaction(coinbase.get_hash(), &coinbase).unwrap();
+            storage.save_transaction(incoming.get_hash(), &incoming).unwrap();
+        }
+
+        let found = wallet.get_transaction(coinbase.get_hash()).await.unwrap().unwrap();
+        assert_eq!(found.get_topoheight(), 10);
+        assert!(matches!(found.get_entry(), EntryData::Coinbase { reward: COIN_VALUE }));
+
+        // Transfer with its extra data
+        let found = wallet.get_transaction(incoming.get_hash()).await.unwrap().unwrap();
+        assert_eq!(found.get_topoheight(), 12);
+        let EntryData::Incoming { transfers, .. } = found.get_entry() else {
+            panic!("expected an incoming entry")
+        };
+        assert_eq!(*transfers[0].get_asset(), asset);
+        assert_eq!(transfers[0].get_amount(), 250);
+        assert_eq!(*transfers[0].get_extra_data(), Some(memo));
+
+        assert!(wallet.get_transaction(&Hash::new([6u8; 32])).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_pause_sync() {
+        let wallet = create_test_wallet(KeyPair::new());
+        let miner = KeyPair::new().get_public_key().to_address(true);
+        assert!(matches!(wallet.pause_sync().await, Err(WalletError::NotOnlineMode)));
+        assert!(matches!(wallet.resume_sync().await, Err(WalletError::NotOnlineMode)));
+
+        let topoheight = Arc::new(AtomicU64::new(1));
+        let daemon = start_chain_daemon(Arc::clone(&topoheight)).await;
+        wallet.set_online_mode_with_api(daemon.connect().await).await.unwrap();
+        wait_synced_topoheight(&wallet, 1).await;
+        wait_subscribed(&daemon).await;
+
+        // New blocks are applied while running
+        topoheight.store(2, Ordering::SeqCst);
+        daemon.notify(DaemonNotifyEvent::NewBlock, &block_response(2, &miner, None));
+        wait_synced_topoheight(&wallet, 2).await;
+
+        // Ignored once paused
+        wallet.pause_sync().await.unwrap();
+        assert!(matches!(wallet.pause_sync().await, Err(WalletError::NetworkError(NetworkError::AlreadyPaused))));
+        topoheight.store(3, Ordering::SeqCst);
+        daemon.notify(DaemonNotifyEvent::NewBlock, &block_response(3, &miner, None));
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(wallet.get_storage().read().await.get_synced_topoheight().unwrap(), 2);
+
+        // Resumed from the topoheight synced before the pause
+        topoheight.store(4, Ordering::SeqCst);
+        let verified = daemon.requests("get_block_at_topoheight").len();
+        wallet.resume_sync().await.unwrap();
+        wait_synced_topoheight(&wallet, 4).await;
+        assert_eq!(daemon.requests("get_block_at_topoheight")[verified]["topoheight"], json!(2));
+        assert!(matches!(wallet.resume_sync().await, Err(WalletError::NetworkError(NetworkError::NotPaused))));
+
+        wallet.set_offline_mode(false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_is_top_block_stale() {
+        let wallet = create_test_wallet(KeyPair::new());
+        // Nothing stored yet, nothing can be stale even offline
+        assert!(!wallet.is_top_block_stale().await.unwrap());
+
+        wallet.get_storage().write().await.set_top_block_hash(&Hash::new([5u8; 32])).unwrap();
+        wallet.get_storage().write().await.set_synced_topoheight(5).unwrap();
+        assert!(matches!(wallet.is_top_block_stale().await, Err(WalletError::NotOnlineMode)));
+
+        let topoheight = Arc::new(AtomicU64::new(5));
+        let daemon = start_chain_daemon(Arc::clone(&topoheight)).await;
+        let handler = NetworkHandler::with_api(Arc::clone(&wallet), daemon.connect().await).await.unwrap();
+        *wallet.network_handler.lock().await = Some(handler);
+
+        // Same block at our synced topoheight
+        assert!(!wallet.is_top_block_stale().await.unwrap());
+        let requests = daemon.requests("get_block_at_topoheight");
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["topoheight"], json!(5));
+
+        // Reorged, the daemon has another block at this topoheight
+        wallet.get_storage().write().await.set_top_block_hash(&Hash::new([6u8; 32])).unwrap();
+        assert!(wallet.is_top_block_stale().await.unwrap());
+    }
+}